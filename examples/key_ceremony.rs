@@ -0,0 +1,126 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Generates privileged keys of the service configuration with sharded backups,
+//! and recovers them.
+//!
+//! ```sh
+//! cargo run --example key_ceremony generate <role> <threshold> <shares> <out_dir>
+//! cargo run --example key_ceremony recover <role> <share_file>...
+//! ```
+//!
+//! `generate` creates a key for the role (e.g., `issuer`, `treasury` or `admin`)
+//! and writes a printable recovery sheet `<role>-share-<index>.txt` for every
+//! share to `out_dir`; the secret key itself is not stored. `recover` rebuilds
+//! the key from the shares, given as recovery sheets or files with the share
+//! alone, and prints it. Both print the public key for the configuration and
+//! a wallet creation signed with the key, to be posted to a testnet at
+//! `v1/wallets` to check the key.
+
+extern crate cryptocurrency;
+extern crate exonum;
+extern crate serde_json;
+
+use exonum::crypto::{PublicKey, SecretKey};
+use exonum::encoding::serialize::encode_hex;
+
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process;
+
+use cryptocurrency::ceremony::{self, Share, SHARE_PREFIX};
+
+/// Length of a key seed in bytes.
+const SEED_LENGTH: usize = 32;
+
+/// Print the message and exit with a non-zero status.
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
+
+/// Print the public key and the verification transaction of the key.
+fn print_key(role: &str, pub_key: &PublicKey, secret_key: &SecretKey) {
+    println!("Public key of the {} key: {}", role, pub_key.to_string());
+    let tx = ceremony::verification_tx(role, pub_key, secret_key);
+    println!("Verification transaction for a testnet:");
+    println!("{}", serde_json::to_string_pretty(&tx).unwrap());
+}
+
+/// Generate a key and write the recovery sheets.
+fn generate(role: &str, threshold: u8, count: u8, out_dir: &Path) {
+    let seed = ceremony::random_bytes(SEED_LENGTH);
+    let (pub_key, secret_key) = ceremony::keypair_from_seed(&seed).unwrap();
+    let shares = ceremony::split(&seed, threshold, count)
+        .unwrap_or_else(|e| fail(&e.to_string()));
+    // Check the backup before handing it out.
+    let recovered = ceremony::combine(&shares[..threshold as usize]).unwrap();
+    assert_eq!(recovered, seed);
+
+    for share in &shares {
+        let path = out_dir.join(format!("{}-share-{}.txt", role, share.index));
+        let sheet = ceremony::recovery_sheet(role, &pub_key, share, count);
+        File::create(&path)
+            .and_then(|mut file| file.write_all(sheet.as_bytes()))
+            .unwrap_or_else(|e| fail(&format!("Cannot write {}: {}", path.display(), e)));
+        println!("Wrote {}", path.display());
+    }
+    print_key(role, &pub_key, &secret_key);
+}
+
+/// Read a share from a recovery sheet or a file holding the share alone.
+fn read_share(path: &str) -> Share {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .unwrap_or_else(|e| fail(&format!("Cannot read {}: {}", path, e)));
+    let start = contents.find(SHARE_PREFIX).unwrap_or_else(
+        || fail(&format!("No share in {}", path)),
+    );
+    // The share runs up to the first blank line.
+    let share = contents[start..].split("\n\n").next().unwrap();
+    share.parse().unwrap_or_else(
+        |e| fail(&format!("Invalid share in {}: {}", path, e)),
+    )
+}
+
+/// Recover a key from the shares.
+fn recover(role: &str, paths: &[String]) {
+    let shares: Vec<Share> = paths.iter().map(|path| read_share(path)).collect();
+    let seed = ceremony::combine(&shares).unwrap_or_else(|e| fail(&e.to_string()));
+    let (pub_key, secret_key) = ceremony::keypair_from_seed(&seed).unwrap_or_else(
+        |e| fail(&e.to_string()),
+    );
+    println!("Secret key of the {} key: {}", role, encode_hex(secret_key.as_ref()));
+    print_key(role, &pub_key, &secret_key);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let number = |arg: &str| arg.parse().unwrap_or_else(|_| fail("Invalid number"));
+    match args.first().map(String::as_str) {
+        Some("generate") if args.len() == 5 => {
+            generate(&args[1], number(&args[2]), number(&args[3]), Path::new(&args[4]))
+        }
+        Some("recover") if args.len() >= 3 => recover(&args[1], &args[2..]),
+        _ => {
+            fail(
+                "Usage: key_ceremony generate <role> <threshold> <shares> <out_dir>\n       \
+                 key_ceremony recover <role> <share_file>...",
+            )
+        }
+    }
+}
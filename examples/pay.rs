@@ -0,0 +1,83 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Turns a payment URI, e.g. one scanned from a QR code, into a signed transfer.
+//!
+//! ```sh
+//! cargo run --example pay <uri> <pub_key> <secret_key> <height> [amount] [fee]
+//! ```
+//!
+//! `height` is the current blockchain height, used to check the expiry of the
+//! request. `amount` is required if the request does not fix it. The transfer is
+//! printed in the JSON form accepted by `v1/wallets/transfer`. The memo of the
+//! request, if any, is included into the transfer and printed to the standard
+//! error for the payer to review.
+
+extern crate cryptocurrency;
+extern crate exonum;
+extern crate serde_json;
+
+use exonum::crypto::{PublicKey, SecretKey};
+use exonum::encoding::serialize::FromHex;
+
+use std::env;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use cryptocurrency::payment::PaymentRequest;
+
+/// Print the message and exit with a non-zero status.
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
+
+/// Parse a decimal integer argument.
+fn parse_u64(arg: &str, name: &str) -> u64 {
+    arg.parse()
+        .unwrap_or_else(|_| fail(&format!("Invalid {}: {}", name, arg)))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 4 || args.len() > 6 {
+        fail("Usage: pay <uri> <pub_key> <secret_key> <height> [amount] [fee]");
+    }
+
+    let mut request = PaymentRequest::from_uri(&args[0])
+        .unwrap_or_else(|e| fail(&format!("Invalid payment URI: {}", e)));
+    let from = PublicKey::from_hex(&args[1]).unwrap_or_else(|_| fail("Invalid public key"));
+    let secret_key = SecretKey::from_hex(&args[2]).unwrap_or_else(|_| fail("Invalid secret key"));
+    let height = parse_u64(&args[3], "height");
+    if let Some(amount) = args.get(4) {
+        let amount = parse_u64(amount, "amount");
+        if request.amount.map_or(false, |fixed| fixed != amount) {
+            fail("The amount is fixed by the payment request");
+        }
+        request.amount = Some(amount);
+    }
+    let fee = args.get(5).map_or(0, |fee| parse_u64(fee, "fee"));
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    let tx = request
+        .to_transfer(&from, fee, seed, height, &secret_key)
+        .unwrap_or_else(|e| fail(&e.to_string()));
+    if let Some(ref memo) = request.memo {
+        eprintln!("Memo: {}", memo);
+    }
+    println!("{}", serde_json::to_string_pretty(&tx).unwrap());
+}
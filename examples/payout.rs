@@ -0,0 +1,265 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Watches a folder for payout manifests and pays them out from a wallet.
+//!
+//! ```sh
+//! cargo run --example payout \
+//!     <folder> <node_url> <pub_key> <secret_key> <approver> [fee] [throttle_ms]
+//! ```
+//!
+//! Every `*.csv` or `*.json` manifest is processed once its detached signature
+//! `<manifest>.sig`, the hex-encoded signature of the manifest hash by the
+//! `approver`, appears next to it. The manifest is checked against the wallet
+//! balance, split into batch transfers and submitted to the node at `node_url`,
+//! waiting `throttle_ms` (1000 by default) between submissions. When the batches
+//! are committed or the wait times out, the reconciliation report is written to
+//! `<manifest>.report.json` and the manifest is renamed to `<manifest>.done`.
+//! Manifests failing the checks are renamed to `<manifest>.rejected`.
+
+extern crate cryptocurrency;
+extern crate exonum;
+extern crate hyper;
+extern crate serde;
+extern crate serde_json;
+
+use exonum::crypto::{PublicKey, SecretKey, Signature};
+use exonum::encoding::serialize::FromHex;
+use exonum::messages::Message;
+use hyper::Client;
+use hyper::header::ContentType;
+use serde::de::DeserializeOwned;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use cryptocurrency::{BatchCost, ServiceInfo, TransactionStatus, Wallet};
+use cryptocurrency::payout::{Manifest, ReconciliationEntry, ReconciliationReport};
+
+/// Path of the service API relative to the node URL.
+const SERVICE_API: &str = "api/services/cryptocurrency/v1";
+
+/// Interval between scans of the folder, in seconds.
+const SCAN_INTERVAL_SECS: u64 = 5;
+
+/// Number of status polls of the submitted batches before giving up.
+const STATUS_POLLS: u32 = 30;
+
+/// Payout settings given on the command line.
+struct Payer {
+    node_url: String,
+    pub_key: PublicKey,
+    secret_key: SecretKey,
+    approver: PublicKey,
+    fee: u64,
+    throttle: Duration,
+    client: Client,
+}
+
+impl Payer {
+    /// Get a JSON response from the service API.
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        let url = format!("{}/{}/{}", self.node_url, SERVICE_API, path);
+        let mut response = self.client.get(&url).send().map_err(|e| e.to_string())?;
+        let mut body = String::new();
+        response.read_to_string(&mut body).map_err(|e| e.to_string())?;
+        serde_json::from_str(&body).map_err(|e| format!("{}: {}", e, body))
+    }
+
+    /// Post a JSON request to the service API.
+    fn post(&self, path: &str, body: &str) -> Result<(), String> {
+        let url = format!("{}/{}/{}", self.node_url, SERVICE_API, path);
+        let response = self.client
+            .post(&url)
+            .header(ContentType::json())
+            .body(body)
+            .send()
+            .map_err(|e| e.to_string())?;
+        if !response.status.is_success() {
+            return Err(format!("{} returned {}", url, response.status));
+        }
+        Ok(())
+    }
+
+    /// Check, submit and reconcile a manifest.
+    fn process(&self, manifest: &Manifest) -> Result<ReconciliationReport, String> {
+        let wallet: Wallet = self.get(&format!("wallet/{}", self.pub_key.to_string()))?;
+        let info: ServiceInfo = self.get("info")?;
+        let max_batch = ::std::cmp::max(info.limits.max_batch_payments as usize, 1);
+        let batch_cost = |amounts: &[u64]| {
+            let amounts: Vec<String> = amounts.iter().map(u64::to_string).collect();
+            let path = format!(
+                "wallet/{}/batch-cost?fee={}&amounts={}",
+                self.pub_key.to_string(),
+                self.fee,
+                amounts.join(",")
+            );
+            self.get::<BatchCost>(&path).map(|info| info.cost)
+        };
+        manifest.check(wallet.balance(), max_batch, batch_cost).map_err(
+            |e| e.to_string(),
+        )?;
+
+        let batches = manifest.batches(&self.pub_key, self.fee, max_batch, &self.secret_key);
+        for (index, tx) in batches.iter().enumerate() {
+            if index > 0 {
+                thread::sleep(self.throttle);
+            }
+            let body = serde_json::to_string(tx).unwrap();
+            self.post("wallets/batch-transfer", &body)?;
+            println!("Submitted batch {:?}", tx.hash());
+        }
+
+        let mut statuses = Vec::new();
+        for _ in 0..STATUS_POLLS {
+            thread::sleep(self.throttle);
+            statuses = Vec::new();
+            for tx in &batches {
+                let status = self.get(&format!("transactions/{}", tx.hash().to_string()))?;
+                statuses.push(status);
+            }
+            let settled = statuses.iter().all(|status| match *status {
+                TransactionStatus::Committed { .. } => true,
+                _ => false,
+            });
+            if settled {
+                break;
+            }
+        }
+
+        let entries = manifest
+            .lines
+            .chunks(max_batch)
+            .zip(batches.iter().zip(statuses))
+            .map(|(lines, (tx, status))| {
+                ReconciliationEntry {
+                    tx_hash: tx.hash(),
+                    payments: lines.to_vec(),
+                    status,
+                }
+            })
+            .collect();
+        Ok(ReconciliationReport {
+            manifest: manifest.hash,
+            entries,
+        })
+    }
+}
+
+/// Path with the suffix appended to the file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Read a file into bytes.
+fn read(path: &Path) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+/// Read and verify a manifest with its detached signature.
+fn load(path: &Path, sig_path: &Path, approver: &PublicKey) -> Result<Manifest, String> {
+    let manifest = Manifest::parse(&read(path)?).map_err(|e| e.to_string())?;
+    let signature = String::from_utf8(read(sig_path)?).map_err(|e| e.to_string())?;
+    let signature = Signature::from_hex(signature.trim()).map_err(|e| e.to_string())?;
+    if !manifest.verify(&signature, approver) {
+        return Err("Manifest signature does not match the approver".to_owned());
+    }
+    Ok(manifest)
+}
+
+/// Process the signed manifests in the folder.
+fn scan(folder: &Path, payer: &Payer) -> Result<(), String> {
+    for entry in fs::read_dir(folder).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        let is_manifest = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") | Some("json") => !path.to_string_lossy().ends_with(".report.json"),
+            _ => false,
+        };
+        let sig_path = with_suffix(&path, ".sig");
+        if !is_manifest || !sig_path.exists() {
+            continue;
+        }
+
+        println!("Processing {}", path.display());
+        let report = load(&path, &sig_path, &payer.approver).and_then(
+            |manifest| payer.process(&manifest),
+        );
+        match report {
+            Ok(report) => {
+                let json = serde_json::to_string_pretty(&report).unwrap();
+                File::create(with_suffix(&path, ".report.json"))
+                    .and_then(|mut file| file.write_all(json.as_bytes()))
+                    .map_err(|e| e.to_string())?;
+                if !report.is_complete() {
+                    eprintln!("Not all batches of {} succeeded", path.display());
+                }
+                fs::rename(&path, with_suffix(&path, ".done")).map_err(|e| e.to_string())?;
+            }
+            Err(e) => {
+                eprintln!("Rejected {}: {}", path.display(), e);
+                fs::rename(&path, with_suffix(&path, ".rejected")).map_err(
+                    |e| e.to_string(),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print the message and exit with a non-zero status.
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 5 || args.len() > 7 {
+        fail(
+            "Usage: payout <folder> <node_url> <pub_key> <secret_key> <approver> \
+             [fee] [throttle_ms]",
+        );
+    }
+
+    let folder = PathBuf::from(&args[0]);
+    let key = |arg: &str| PublicKey::from_hex(arg).unwrap_or_else(|_| fail("Invalid public key"));
+    let number = |arg: &String| arg.parse().unwrap_or_else(|_| fail("Invalid number"));
+    let payer = Payer {
+        node_url: args[1].trim_right_matches('/').to_owned(),
+        pub_key: key(&args[2]),
+        secret_key: SecretKey::from_hex(&args[3]).unwrap_or_else(|_| fail("Invalid secret key")),
+        approver: key(&args[4]),
+        fee: args.get(5).map_or(0, &number),
+        throttle: Duration::from_millis(args.get(6).map_or(1000, &number)),
+        client: Client::new(),
+    };
+
+    loop {
+        if let Err(e) = scan(&folder, &payer) {
+            eprintln!("{}", e);
+        }
+        thread::sleep(Duration::from_secs(SCAN_INTERVAL_SECS));
+    }
+}
@@ -118,15 +118,15 @@ launch-server
 
 echo "Creating a wallet for Johnny..."
 create-wallet create-wallet-1.json
-check-transaction 44c6c2c5
+check-transaction f20cfc2a
 
 echo "Creating a wallet for Janie..."
 create-wallet create-wallet-2.json
-check-transaction 8714e906
+check-transaction 05b53dad
 
 echo "Transferring funds from Johnny to Janie"
 transfer transfer-funds.json
-check-transaction e63b28ca
+check-transaction 6a204016
 
 echo "Waiting until transactions are committed..."
 sleep 7
@@ -135,22 +135,22 @@ echo "Retrieving info on all wallets..."
 RESP=`curl http://127.0.0.1:8000/api/services/cryptocurrency/v1/wallets 2>/dev/null`
 # Wallet records in the response are deterministically ordered by increasing
 # public key. As Johnny's pubkey is lexicographically lesser than Janie's, it it possible to
-# determine his wallet as .[0] and hers as .[1].
-check-request "Johnny Doe" 90 "`echo $RESP | jq .[0]`"
-check-request "Janie Roe" 110 "`echo $RESP | jq .[1]`"
+# determine his wallet as .items[0] and hers as .items[1].
+check-request "Johnny Doe" 90 "`echo $RESP | jq .items[0]`"
+check-request "Janie Roe" 110 "`echo $RESP | jq .items[1]`"
 
 echo "Retrieving info on Johnny's wallet..."
-RESP=`curl http://127.0.0.1:8000/api/services/cryptocurrency/v1/wallet/03e657ae71e51be60a45b4bd20bcf79ff52f0c037ae6da0540a0e0066132b472 2>/dev/null`
+RESP=`curl http://127.0.0.1:8000/api/services/cryptocurrency/v1/wallet/088c3d467501a78489740c5d9e1dc3f2388817ac80b804a4eafda66c8220c927 2>/dev/null`
 check-request "Johnny Doe" 90 "$RESP"
 
 echo "Retrieving Johnny's transaction info..."
-TXID=44c6c2c58eaab71f8d627d75ca72f244289bc84586a7fb42186a676b2ec4626b
+TXID=f20cfc2a2d579aeddbb4db859911772dce7aaf9b655d8c1f52f02f6d7481de60
 RESP=`curl http://127.0.0.1:8000/api/system/v1/transactions/$TXID 2>/dev/null`
 EXP=`cat create-wallet-1.json`
 check-create-tx "Johnny Doe" "$EXP" "$RESP"
 
 echo "Retrieving transfer transaction info..."
-TXID=e63b28caa07adffb6e2453390a59509a1469e66698c75b4cfb2f0ae7a6887fdc
+TXID=6a204016dd1b0853879743b54dc562b9e3e679598da901380ef8981c4d1d092d
 RESP=`curl http://127.0.0.1:8000/api/system/v1/transactions/$TXID 2>/dev/null`
 EXP=`cat transfer-funds.json`
 check-transfer-tx "$EXP" "$RESP"
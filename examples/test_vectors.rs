@@ -0,0 +1,366 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Emits canonical test vectors for client libraries: deterministic keypairs and
+//! a signed transaction of every type together with its binary encoding and hash.
+//!
+//! ```sh
+//! cargo run --example test_vectors [output.json]
+//! ```
+//!
+//! The vectors are written to the given file, or to the standard output.
+
+extern crate cryptocurrency;
+extern crate exonum;
+extern crate serde;
+#[macro_use]
+extern crate serde_json;
+
+use exonum::crypto::{self, Hash, PublicKey, SecretKey, Seed};
+use exonum::encoding::serialize::encode_hex;
+use exonum::messages::{Message, MessageBuffer};
+use serde::Serialize;
+use serde_json::Value;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+
+use cryptocurrency::{SERVICE_ID, TxCreateWallet, TxTransfer, TxDeferredTransfer,
+                     TxCancelDeferredTransfer, TxTick, TxGrantPromotion, TxInsurancePayout,
+                     TxApproveInsurancePayout, TxBurnWithTag, TxCreateAndFundWallet,
+                     TxAuthorizeSessionKey, TxRevokeSessionKey, TxSessionTransfer,
+                     TxAttestWallet, TxRevokeAttestation, TxLinkWallets,
+                     TxRegisterPaymentReference, TxReviewHeldTransfer, TxAssignTier,
+                     TxCreateAuction, TxBid, TxOpenStream, TxWithdrawStream, TxCloseStream,
+                     TxLotteryDraw, TxEnterDraw, TxIssueFeeCoupon, TxCouponTransfer,
+                     TxDeposit, TxClaimDeposit, TxClawback, TxApproveClawback, TxBatchTransfer,
+                     Payment, TxSetupMultisig, TxMultisigTransfer, TxApproveMultisigTransfer,
+                     TxCreateInvitation, TxIssue, TxBurn, TxFreezeWallet,
+                     TxUnfreezeWallet, TxUpdateWallet, TxRenameWallet,
+                     TxCloseWallet, TxApprove, TxTransferFrom,
+                     TxSetSweepRule, TxClearSweepRule, TxCreateEscrow, TxReleaseEscrow,
+                     TxRefundEscrow, TxLockedTransfer, TxCreateHtlc, TxClaimHtlc,
+                     TxRefundHtlc, TxCreateAsset, TxAssetTransfer, TIER_VERIFIED,
+                     link_consent};
+
+/// Keypair derived from a seed filled with the given byte.
+fn keypair(byte: u8) -> (PublicKey, SecretKey) {
+    crypto::gen_keypair_from_seed(&Seed::new([byte; 32]))
+}
+
+/// Describe a keypair together with the seed it is derived from.
+fn keypair_vector(name: &str, byte: u8) -> Value {
+    let (public_key, secret_key) = keypair(byte);
+    json!({
+        "name": name,
+        "seed": encode_hex(&[byte; 32][..]),
+        "public_key": public_key,
+        "secret_key": secret_key,
+    })
+}
+
+/// Describe a signed transaction: its JSON form as accepted by the REST API,
+/// its binary encoding and its hash.
+fn tx_vector<T: Message + Serialize>(name: &str, tx: &T) -> Value {
+    let buffer: &MessageBuffer = tx.raw();
+    let bytes: &[u8] = buffer.as_ref();
+    json!({
+        "name": name,
+        "message_id": tx.raw().message_type(),
+        "json": tx,
+        "encoding": encode_hex(bytes),
+        "hash": tx.hash(),
+    })
+}
+
+fn main() {
+    let (alice, alice_key) = keypair(1);
+    let (bob, bob_key) = keypair(2);
+    let (session, session_key) = keypair(3);
+    let (operator, operator_key) = keypair(4);
+    let tag = crypto::hash(b"tag");
+
+    let transfer = TxTransfer::new(
+        &alice,
+        &bob,
+        10, // amount
+        1, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        1, // seed
+        &alice_key,
+    );
+    let deferred = TxDeferredTransfer::new(&alice, &bob, 10, 100, 2, &alice_key);
+    let payout = TxInsurancePayout::new(&operator, &alice, 50, 3, &operator_key);
+    let auction = TxCreateAuction::new(&alice, &tag, 20, 400, 16, &alice_key);
+    let stream = TxOpenStream::new(&alice, &bob, 1, 100, 18, &alice_key);
+    let draw = TxLotteryDraw::new(&alice, 30, 500, 20, &alice_key);
+    let deposit = TxDeposit::new(&alice, &bob, 40, &tag, 700, 23, &alice_key);
+    let multisig_transfer = TxMultisigTransfer::new(&alice, &bob, &operator, 10, 1, 25, &bob_key);
+    let escrow = TxCreateEscrow::new(&alice, &bob, &operator, 40, 38, &alice_key);
+    let asset = TxCreateAsset::new(&alice, "Gold", 1000, 41, &alice_key);
+    let preimage = crypto::hash(b"preimage");
+    let htlc = TxCreateHtlc::new(
+        &alice,
+        &bob,
+        &crypto::hash(preimage.as_ref()),
+        40, // amount
+        600, // timeout
+        40, // seed
+        &alice_key,
+    );
+    let consent = crypto::sign(link_consent(&alice, &bob).as_ref(), &bob_key);
+
+    let transactions = vec![
+        tx_vector(
+            "create_wallet",
+            &TxCreateWallet::new(&alice, "Alice", &Hash::zero(), "", "", &Hash::zero(), &alice_key),
+        ),
+        tx_vector(
+            "create_invited_wallet",
+            &TxCreateWallet::new(&bob, "Bob", &tag, "", "", &Hash::zero(), &bob_key),
+        ),
+        tx_vector(
+            "update_wallet",
+            &TxUpdateWallet::new(
+                &alice,
+                "Alice A.",
+                "mailto:alice@example.com",
+                &crypto::hash(b"avatar"),
+                31, // seed
+                &alice_key,
+            ),
+        ),
+        tx_vector(
+            "rename_wallet",
+            &TxRenameWallet::new(&alice, "Alicia", 32, &alice_key),
+        ),
+        tx_vector(
+            "close_wallet",
+            &TxCloseWallet::new(&bob, &alice, 33, &bob_key),
+        ),
+        tx_vector(
+            "create_invitation",
+            &TxCreateInvitation::new(&alice, &tag, 30, 10, 800, 26, &alice_key),
+        ),
+        tx_vector("transfer", &transfer),
+        tx_vector(
+            "transfer_with_dependency",
+            &TxTransfer::new(
+                &bob,
+                &alice,
+                5, // amount
+                1, // fee
+                &transfer.hash(),
+                &tag,
+                "Invoice 42",
+                4, // seed
+                &bob_key,
+            ),
+        ),
+        tx_vector(
+            "approve",
+            &TxApprove::new(&alice, &bob, 30, 34, &alice_key),
+        ),
+        tx_vector(
+            "transfer_from",
+            &TxTransferFrom::new(&bob, &alice, &operator, 20, 1, 35, &bob_key),
+        ),
+        tx_vector(
+            "set_sweep_rule",
+            &TxSetSweepRule::new(&alice, &bob, 1000, 36, &alice_key),
+        ),
+        tx_vector(
+            "clear_sweep_rule",
+            &TxClearSweepRule::new(&alice, 37, &alice_key),
+        ),
+        tx_vector("deferred_transfer", &deferred),
+        tx_vector(
+            "cancel_deferred_transfer",
+            &TxCancelDeferredTransfer::new(&alice, &deferred.hash(), &alice_key),
+        ),
+        tx_vector(
+            "locked_transfer",
+            &TxLockedTransfer::new(&alice, &bob, 10, 100, 39, &alice_key),
+        ),
+        tx_vector("tick", &TxTick::new(&operator, 100, &operator_key)),
+        tx_vector(
+            "issue",
+            &TxIssue::new(&operator, &alice, 50, 27, &operator_key),
+        ),
+        tx_vector(
+            "grant_promotion",
+            &TxGrantPromotion::new(&operator, &alice, 20, 200, 5, &operator_key),
+        ),
+        tx_vector("insurance_payout", &payout),
+        tx_vector(
+            "approve_insurance_payout",
+            &TxApproveInsurancePayout::new(&operator, &payout.hash(), &operator_key),
+        ),
+        tx_vector(
+            "burn_with_tag",
+            &TxBurnWithTag::new(&alice, &tag, 5, 6, &alice_key),
+        ),
+        tx_vector("burn", &TxBurn::new(&alice, 5, 28, &alice_key)),
+        tx_vector(
+            "create_and_fund_wallet",
+            &TxCreateAndFundWallet::new(&alice, &bob, "Bob", 30, 1, 7, &alice_key),
+        ),
+        tx_vector(
+            "authorize_session_key",
+            &TxAuthorizeSessionKey::new(&alice, &session, 10, 300, 8, &alice_key),
+        ),
+        tx_vector(
+            "revoke_session_key",
+            &TxRevokeSessionKey::new(&alice, &session, 9, &alice_key),
+        ),
+        tx_vector(
+            "session_transfer",
+            &TxSessionTransfer::new(&alice, &session, &bob, 5, 1, 10, &session_key),
+        ),
+        tx_vector(
+            "attest_wallet",
+            &TxAttestWallet::new(&operator, &alice, 11, &operator_key),
+        ),
+        tx_vector(
+            "revoke_attestation",
+            &TxRevokeAttestation::new(&operator, &alice, 12, &operator_key),
+        ),
+        tx_vector(
+            "freeze_wallet",
+            &TxFreezeWallet::new(&operator, &alice, 29, &operator_key),
+        ),
+        tx_vector(
+            "unfreeze_wallet",
+            &TxUnfreezeWallet::new(&operator, &alice, 30, &operator_key),
+        ),
+        tx_vector(
+            "link_wallets",
+            &TxLinkWallets::new(&alice, &bob, &consent, 13, &alice_key),
+        ),
+        tx_vector(
+            "register_payment_reference",
+            &TxRegisterPaymentReference::new(&bob, 14, &bob_key),
+        ),
+        tx_vector(
+            "review_held_transfer",
+            &TxReviewHeldTransfer::new(&operator, &transfer.hash(), true, &operator_key),
+        ),
+        tx_vector(
+            "assign_tier",
+            &TxAssignTier::new(&operator, &alice, TIER_VERIFIED, 15, &operator_key),
+        ),
+        tx_vector("create_auction", &auction),
+        tx_vector("bid", &TxBid::new(&bob, &auction.hash(), 25, 17, &bob_key)),
+        tx_vector("open_stream", &stream),
+        tx_vector(
+            "withdraw_stream",
+            &TxWithdrawStream::new(&bob, &stream.hash(), 19, &bob_key),
+        ),
+        tx_vector(
+            "close_stream",
+            &TxCloseStream::new(&alice, &stream.hash(), &alice_key),
+        ),
+        tx_vector("lottery_draw", &draw),
+        tx_vector(
+            "enter_draw",
+            &TxEnterDraw::new(&bob, &draw.hash(), &bob_key),
+        ),
+        tx_vector(
+            "issue_fee_coupon",
+            &TxIssueFeeCoupon::new(&operator, &tag, 5000, 10, 600, 21, &operator_key),
+        ),
+        tx_vector(
+            "coupon_transfer",
+            &TxCouponTransfer::new(&alice, &bob, 10, 0, &tag, 22, &alice_key),
+        ),
+        tx_vector("deposit", &deposit),
+        tx_vector(
+            "claim_deposit",
+            &TxClaimDeposit::new(&bob, &deposit.hash(), 15, &bob_key),
+        ),
+        tx_vector("create_escrow", &escrow),
+        tx_vector(
+            "release_escrow",
+            &TxReleaseEscrow::new(&operator, &escrow.hash(), &operator_key),
+        ),
+        tx_vector(
+            "refund_escrow",
+            &TxRefundEscrow::new(&operator, &escrow.hash(), &operator_key),
+        ),
+        tx_vector("create_asset", &asset),
+        tx_vector(
+            "asset_transfer",
+            &TxAssetTransfer::new(&alice, &bob, &asset.hash(), 100, 42, &alice_key),
+        ),
+        tx_vector("create_htlc", &htlc),
+        tx_vector(
+            "claim_htlc",
+            &TxClaimHtlc::new(&bob, &htlc.hash(), &preimage, &bob_key),
+        ),
+        tx_vector(
+            "refund_htlc",
+            &TxRefundHtlc::new(&alice, &htlc.hash(), &alice_key),
+        ),
+        tx_vector(
+            "batch_transfer",
+            &TxBatchTransfer::new(
+                &alice,
+                vec![Payment::new(&bob, 10), Payment::new(&operator, 5)],
+                1, // fee
+                24, // seed
+                &alice_key,
+            ),
+        ),
+        tx_vector(
+            "setup_multisig",
+            &TxSetupMultisig::new(&alice, vec![bob, operator], 2, &alice_key),
+        ),
+        tx_vector("multisig_transfer", &multisig_transfer),
+        tx_vector(
+            "approve_multisig_transfer",
+            &TxApproveMultisigTransfer::new(&operator, &multisig_transfer.hash(), &operator_key),
+        ),
+        tx_vector(
+            "clawback",
+            &TxClawback::new(&operator, &transfer.hash(), &tag, &operator_key),
+        ),
+        tx_vector(
+            "approve_clawback",
+            &TxApproveClawback::new(&operator, &transfer.hash(), &operator_key),
+        ),
+    ];
+
+    let vectors = json!({
+        "service_id": SERVICE_ID,
+        "keypairs": [
+            keypair_vector("alice", 1),
+            keypair_vector("bob", 2),
+            keypair_vector("session", 3),
+            keypair_vector("operator", 4),
+        ],
+        "transactions": transactions,
+    });
+
+    let output = serde_json::to_string_pretty(&vectors).unwrap();
+    match env::args().nth(1) {
+        Some(path) => {
+            let mut file = File::create(&path).unwrap();
+            writeln!(file, "{}", output).unwrap();
+        }
+        None => writeln!(io::stdout(), "{}", output).unwrap(),
+    }
+}
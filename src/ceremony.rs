@@ -0,0 +1,290 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Key ceremony for the privileged keys of the service configuration, such as
+//! the issuer, treasury, verifier and admin keys.
+//!
+//! The seed of a key is split with Shamir's secret sharing over GF(256), so that
+//! any `threshold` of the shares recover it and fewer reveal nothing. Each share
+//! is printed on a recovery sheet in the form
+//! `excoin-share:<threshold>-<index>-<data>-<checksum>`, where the checksum
+//! catches transcription errors. A key recovered from the shares is checked by
+//! signing a wallet creation on a testnet with `verification_tx`.
+//!
+//! The `key_ceremony` example drives the ceremony from the command line.
+
+use exonum::crypto::{self, Hash, PublicKey, SecretKey, Seed};
+use exonum::encoding::serialize::{encode_hex, FromHex};
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use TxCreateWallet;
+
+/// Length of a key seed in bytes.
+const SEED_LENGTH: usize = 32;
+
+/// Prefix of the text form of a share.
+pub const SHARE_PREFIX: &str = "excoin-share:";
+
+/// Share of a secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    /// Number of shares needed to recover the secret.
+    pub threshold: u8,
+    /// Index of the share, from 1.
+    pub index: u8,
+    /// Share of every byte of the secret.
+    pub data: Vec<u8>,
+}
+
+impl Share {
+    /// Checksum of the share: the first four bytes of the hash of its contents.
+    fn checksum(&self) -> String {
+        let mut bytes = vec![self.threshold, self.index];
+        bytes.extend_from_slice(&self.data);
+        encode_hex(&crypto::hash(&bytes).as_ref()[..4])
+    }
+}
+
+impl fmt::Display for Share {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}-{}-{}-{}",
+            SHARE_PREFIX,
+            self.threshold,
+            self.index,
+            encode_hex(&self.data),
+            self.checksum()
+        )
+    }
+}
+
+impl FromStr for Share {
+    type Err = CeremonyError;
+
+    /// Parse a share, ignoring whitespace inserted for readability.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        if !s.starts_with(SHARE_PREFIX) {
+            return Err(CeremonyError::Format);
+        }
+        let fields: Vec<&str> = s[SHARE_PREFIX.len()..].split('-').collect();
+        if fields.len() != 4 {
+            return Err(CeremonyError::Format);
+        }
+        let share = Share {
+            threshold: fields[0].parse().map_err(|_| CeremonyError::Format)?,
+            index: fields[1].parse().map_err(|_| CeremonyError::Format)?,
+            data: Vec::<u8>::from_hex(fields[2]).map_err(|_| CeremonyError::Format)?,
+        };
+        if share.checksum() != fields[3].to_lowercase() {
+            return Err(CeremonyError::Checksum);
+        }
+        Ok(share)
+    }
+}
+
+/// Multiplication in GF(256) with the polynomial x^8 + x^4 + x^3 + x + 1.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// Multiplicative inverse in GF(256), as `a^254`.
+fn gf_inv(a: u8) -> u8 {
+    let (mut result, mut base, mut exp) = (1, a, 254u8);
+    while exp != 0 {
+        if exp & 1 != 0 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Random bytes from the system generator, drawn as seeds of fresh keypairs.
+pub fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(len + SEED_LENGTH);
+    while bytes.len() < len {
+        let (_, secret_key) = crypto::gen_keypair();
+        bytes.extend_from_slice(&secret_key.as_ref()[..SEED_LENGTH]);
+    }
+    bytes.truncate(len);
+    bytes
+}
+
+/// Split the secret into `count` shares, any `threshold` of which recover it.
+pub fn split(secret: &[u8], threshold: u8, count: u8) -> Result<Vec<Share>, CeremonyError> {
+    if threshold == 0 || threshold > count {
+        return Err(CeremonyError::Threshold);
+    }
+    // Coefficients of the polynomials of degree `threshold - 1`, one polynomial
+    // per byte of the secret, which is the constant term.
+    let degree = threshold as usize - 1;
+    let coefficients = random_bytes(secret.len() * degree);
+    let shares = (1..count as u16 + 1)
+        .map(|x| {
+            let x = x as u8;
+            let data = secret
+                .iter()
+                .enumerate()
+                .map(|(i, &byte)| {
+                    let poly = &coefficients[i * degree..(i + 1) * degree];
+                    let higher = poly.iter().rev().fold(0, |acc, &c| gf_mul(acc, x) ^ c);
+                    gf_mul(higher, x) ^ byte
+                })
+                .collect();
+            Share {
+                threshold,
+                index: x,
+                data,
+            }
+        })
+        .collect();
+    Ok(shares)
+}
+
+/// Recover the secret from at least `threshold` distinct shares.
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>, CeremonyError> {
+    let first = shares.first().ok_or(CeremonyError::NotEnoughShares)?;
+    let mut indices = BTreeSet::new();
+    for share in shares {
+        if share.threshold != first.threshold || share.data.len() != first.data.len() {
+            return Err(CeremonyError::Mismatch);
+        }
+        if share.index == 0 || !indices.insert(share.index) {
+            return Err(CeremonyError::DuplicateShare(share.index));
+        }
+    }
+    if shares.len() < first.threshold as usize {
+        return Err(CeremonyError::NotEnoughShares);
+    }
+
+    // Lagrange interpolation at zero.
+    let shares = &shares[..first.threshold as usize];
+    let mut secret = vec![0; first.data.len()];
+    for (j, share) in shares.iter().enumerate() {
+        let (mut numerator, mut denominator) = (1, 1);
+        for (m, other) in shares.iter().enumerate() {
+            if m != j {
+                numerator = gf_mul(numerator, other.index);
+                denominator = gf_mul(denominator, other.index ^ share.index);
+            }
+        }
+        let basis = gf_mul(numerator, gf_inv(denominator));
+        for (byte, &y) in secret.iter_mut().zip(&share.data) {
+            *byte ^= gf_mul(y, basis);
+        }
+    }
+    Ok(secret)
+}
+
+/// Keypair derived from a recovered seed.
+pub fn keypair_from_seed(seed: &[u8]) -> Result<(PublicKey, SecretKey), CeremonyError> {
+    let seed = Seed::from_slice(seed).ok_or(CeremonyError::Mismatch)?;
+    Ok(crypto::gen_keypair_from_seed(&seed))
+}
+
+/// Wallet creation signed with the key, to be submitted to a testnet to check
+/// that the key works before its public key goes into the configuration.
+pub fn verification_tx(
+    role: &str,
+    pub_key: &PublicKey,
+    secret_key: &SecretKey,
+) -> TxCreateWallet {
+    let name = format!("{} key check", role);
+    TxCreateWallet::new(pub_key, &name, &Hash::zero(), "", "", &Hash::zero(), secret_key)
+}
+
+/// Printable recovery sheet holding one share of a key.
+pub fn recovery_sheet(role: &str, pub_key: &PublicKey, share: &Share, count: u8) -> String {
+    let text = share.to_string();
+    let (prefix, body) = text.split_at(SHARE_PREFIX.len());
+    let groups: Vec<String> = body.as_bytes()
+        .chunks(8)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect();
+    let lines: Vec<String> = groups.chunks(6).map(|line| line.join(" ")).collect();
+    format!(
+        "EXCOIN KEY RECOVERY SHEET\n\n\
+         Role:        {}\n\
+         Public key:  {}\n\
+         Share:       {} of {}; any {} shares recover the key\n\n\
+         {}\n{}\n\n\
+         Keep this sheet offline. Spaces and line breaks may be omitted when\n\
+         entering the share.\n",
+        role,
+        pub_key.to_string(),
+        share.index,
+        count,
+        share.threshold,
+        prefix,
+        lines.join("\n")
+    )
+}
+
+/// Errors occurring in the key ceremony.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CeremonyError {
+    /// The threshold is zero or exceeds the number of shares.
+    Threshold,
+    /// The share is not in the `excoin-share:` form.
+    Format,
+    /// The checksum of the share does not match; it is mistyped.
+    Checksum,
+    /// Fewer shares than the threshold were given.
+    NotEnoughShares,
+    /// The share index is zero or given more than once.
+    DuplicateShare(u8),
+    /// The shares belong to different secrets, or the secret is not a seed.
+    Mismatch,
+}
+
+impl fmt::Display for CeremonyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CeremonyError::DuplicateShare(index) => write!(f, "Duplicate share {}", index),
+            _ => f.write_str(self.description()),
+        }
+    }
+}
+
+impl Error for CeremonyError {
+    fn description(&self) -> &str {
+        match *self {
+            CeremonyError::Threshold => "Threshold must be between 1 and the number of shares",
+            CeremonyError::Format => "Malformed share",
+            CeremonyError::Checksum => "Share checksum mismatch",
+            CeremonyError::NotEnoughShares => "Not enough shares to recover the key",
+            CeremonyError::DuplicateShare(_) => "Duplicate share",
+            CeremonyError::Mismatch => "Shares do not belong to the same key",
+        }
+    }
+}
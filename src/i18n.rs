@@ -0,0 +1,284 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Catalog of human-readable API messages, `Accept-Language` negotiation and
+//! locale-aware formatting of amounts.
+//!
+//! Only the human-readable text is translated; the shape of responses and the
+//! HTTP status codes do not depend on the negotiated language. Amounts in JSON
+//! responses are always plain integers; `format_amount` is meant for text
+//! presented to people.
+
+/// Languages the API messages are translated to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    /// English, used when no supported language is requested.
+    English,
+    /// Russian.
+    Russian,
+    /// Ukrainian.
+    Ukrainian,
+}
+
+/// All supported languages.
+pub const LANGUAGES: &[Language] = &[Language::English, Language::Russian, Language::Ukrainian];
+
+impl Language {
+    /// Primary language subtag as used in the `Accept-Language` header.
+    pub fn tag(&self) -> &'static str {
+        match *self {
+            Language::English => "en",
+            Language::Russian => "ru",
+            Language::Ukrainian => "uk",
+        }
+    }
+
+    /// Find a supported language by a language tag, e.g. `ru` or `en-US`.
+    /// The comparison is case-insensitive and ignores subtags.
+    pub fn from_tag(tag: &str) -> Option<Language> {
+        let primary = tag.split('-').next().unwrap_or("").trim().to_lowercase();
+        LANGUAGES.iter().cloned().find(|lang| lang.tag() == primary)
+    }
+
+    /// Choose the language for a response given the value of the `Accept-Language`
+    /// header. Languages are tried in the order of decreasing quality; among
+    /// languages with equal quality the first listed wins. Falls back to English.
+    pub fn negotiate(accept_language: &str) -> Language {
+        let mut candidates: Vec<(u32, usize, Language)> = accept_language
+            .split(',')
+            .enumerate()
+            .filter_map(|(position, item)| {
+                let mut parts = item.split(';');
+                let lang = Language::from_tag(parts.next().unwrap_or(""))?;
+                let quality = parts
+                    .filter_map(|param| {
+                        let param = param.trim();
+                        if param.starts_with("q=") {
+                            param[2..].parse::<f32>().ok()
+                        } else {
+                            None
+                        }
+                    })
+                    .next()
+                    .unwrap_or(1.0);
+                if quality > 0.0 {
+                    Some(((quality * 1000.0) as u32, position, lang))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        candidates
+            .first()
+            .map(|&(_, _, lang)| lang)
+            .unwrap_or(Language::English)
+    }
+}
+
+impl Language {
+    /// Separator between the integer and the fractional parts of a number.
+    pub fn decimal_separator(&self) -> char {
+        match *self {
+            Language::English => '.',
+            Language::Russian | Language::Ukrainian => ',',
+        }
+    }
+
+    /// Separator between groups of three digits in the integer part of a number.
+    pub fn group_separator(&self) -> char {
+        match *self {
+            Language::English => ',',
+            // No-break space.
+            Language::Russian | Language::Ukrainian => '\u{a0}',
+        }
+    }
+}
+
+/// Format an amount of the smallest units of a token with the given number of
+/// decimals, e.g. `123456789` with 2 decimals is `1,234,567.89` in English.
+/// All fractional digits are kept, including trailing zeros.
+pub fn format_amount(amount: u64, decimals: u32, lang: Language) -> String {
+    let digits = format!("{:0width$}", amount, width = decimals as usize + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - decimals as usize);
+
+    let mut formatted = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            formatted.push(lang.group_separator());
+        }
+        formatted.push(digit);
+    }
+    if !fraction.is_empty() {
+        formatted.push(lang.decimal_separator());
+        formatted.push_str(fraction);
+    }
+    formatted
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Language::English
+    }
+}
+
+/// Human-readable messages returned by the service API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiMessage {
+    /// The requested wallet does not exist.
+    WalletNotFound,
+    /// The wallet has no promotional balance.
+    PromotionNotFound,
+    /// A transaction endpoint received an empty body.
+    EmptyRequestBody,
+    /// A payment request cannot be encoded as a QR code.
+    QrEncoding,
+    /// The wallet is not attested.
+    AttestationNotFound,
+    /// The group of linked wallets does not exist.
+    GroupNotFound,
+    /// The payment stream does not exist.
+    StreamNotFound,
+    /// The block at the requested height is not committed yet.
+    BlockNotFound,
+    /// The lottery draw does not exist.
+    DrawNotFound,
+    /// The fee coupon does not exist.
+    CouponNotFound,
+    /// The deposit does not exist or has been claimed or returned.
+    DepositNotFound,
+    /// The statement window ends before it starts or covers too many transactions.
+    InvalidStatementWindow,
+    /// The wallet is not a multisignature wallet.
+    MultisigNotFound,
+    /// The transfer from a multisignature wallet does not exist.
+    MultisigTransferNotFound,
+    /// The invitation does not exist, is used up or has expired.
+    InvitationNotFound,
+    /// The wallet has no sweep rule.
+    SweepRuleNotFound,
+    /// The escrow does not exist or is already settled.
+    EscrowNotFound,
+    /// The hashed time-lock contract does not exist or is already settled.
+    HtlcNotFound,
+    /// No preimage has been revealed for the hash lock.
+    PreimageNotFound,
+    /// The asset does not exist.
+    AssetNotFound,
+}
+
+impl ApiMessage {
+    /// Text of the message in the given language.
+    pub fn text(&self, lang: Language) -> &'static str {
+        match (*self, lang) {
+            (ApiMessage::WalletNotFound, Language::English) => "Wallet not found",
+            (ApiMessage::WalletNotFound, Language::Russian) => "Кошелек не найден",
+            (ApiMessage::WalletNotFound, Language::Ukrainian) => "Гаманець не знайдено",
+
+            (ApiMessage::PromotionNotFound, Language::English) => "Promotional balance not found",
+            (ApiMessage::PromotionNotFound, Language::Russian) => "Промо-баланс не найден",
+            (ApiMessage::PromotionNotFound, Language::Ukrainian) => "Промо-баланс не знайдено",
+
+            (ApiMessage::EmptyRequestBody, Language::English) => "Empty request body",
+            (ApiMessage::EmptyRequestBody, Language::Russian) => "Пустое тело запроса",
+            (ApiMessage::EmptyRequestBody, Language::Ukrainian) => "Порожнє тіло запиту",
+
+            (ApiMessage::QrEncoding, Language::English) => "Cannot encode QR code",
+            (ApiMessage::QrEncoding, Language::Russian) => "Невозможно закодировать QR-код",
+            (ApiMessage::QrEncoding, Language::Ukrainian) => "Неможливо закодувати QR-код",
+
+            (ApiMessage::AttestationNotFound, Language::English) => "Attestation not found",
+            (ApiMessage::AttestationNotFound, Language::Russian) => "Аттестация не найдена",
+            (ApiMessage::AttestationNotFound, Language::Ukrainian) => "Атестацію не знайдено",
+
+            (ApiMessage::GroupNotFound, Language::English) => "Group not found",
+            (ApiMessage::GroupNotFound, Language::Russian) => "Группа не найдена",
+            (ApiMessage::GroupNotFound, Language::Ukrainian) => "Групу не знайдено",
+
+            (ApiMessage::StreamNotFound, Language::English) => "Payment stream not found",
+            (ApiMessage::StreamNotFound, Language::Russian) => "Платежный поток не найден",
+            (ApiMessage::StreamNotFound, Language::Ukrainian) => "Платіжний потік не знайдено",
+
+            (ApiMessage::BlockNotFound, Language::English) => "Block not found",
+            (ApiMessage::BlockNotFound, Language::Russian) => "Блок не найден",
+            (ApiMessage::BlockNotFound, Language::Ukrainian) => "Блок не знайдено",
+
+            (ApiMessage::DrawNotFound, Language::English) => "Lottery draw not found",
+            (ApiMessage::DrawNotFound, Language::Russian) => "Розыгрыш не найден",
+            (ApiMessage::DrawNotFound, Language::Ukrainian) => "Розіграш не знайдено",
+
+            (ApiMessage::CouponNotFound, Language::English) => "Fee coupon not found",
+            (ApiMessage::CouponNotFound, Language::Russian) => "Купон на комиссию не найден",
+            (ApiMessage::CouponNotFound, Language::Ukrainian) => "Купон на комісію не знайдено",
+
+            (ApiMessage::DepositNotFound, Language::English) => "Deposit not found",
+            (ApiMessage::DepositNotFound, Language::Russian) => "Депозит не найден",
+            (ApiMessage::DepositNotFound, Language::Ukrainian) => "Депозит не знайдено",
+
+            (ApiMessage::InvalidStatementWindow, Language::English) => {
+                "Invalid or too large statement period"
+            }
+            (ApiMessage::InvalidStatementWindow, Language::Russian) => {
+                "Недопустимый или слишком большой период выписки"
+            }
+            (ApiMessage::InvalidStatementWindow, Language::Ukrainian) => {
+                "Неприпустимий або занадто великий період виписки"
+            }
+
+            (ApiMessage::MultisigNotFound, Language::English) => "Multisignature wallet not found",
+            (ApiMessage::MultisigNotFound, Language::Russian) => {
+                "Кошелек с мультиподписью не найден"
+            }
+            (ApiMessage::MultisigNotFound, Language::Ukrainian) => {
+                "Гаманець з мультипідписом не знайдено"
+            }
+
+            (ApiMessage::MultisigTransferNotFound, Language::English) => {
+                "Multisignature transfer not found"
+            }
+            (ApiMessage::MultisigTransferNotFound, Language::Russian) => {
+                "Перевод с мультиподписью не найден"
+            }
+            (ApiMessage::MultisigTransferNotFound, Language::Ukrainian) => {
+                "Переказ з мультипідписом не знайдено"
+            }
+
+            (ApiMessage::InvitationNotFound, Language::English) => "Invitation not found",
+            (ApiMessage::InvitationNotFound, Language::Russian) => "Приглашение не найдено",
+            (ApiMessage::InvitationNotFound, Language::Ukrainian) => "Запрошення не знайдено",
+
+            (ApiMessage::SweepRuleNotFound, Language::English) => "Sweep rule not found",
+            (ApiMessage::SweepRuleNotFound, Language::Russian) => "Правило автоперевода не найдено",
+            (ApiMessage::SweepRuleNotFound, Language::Ukrainian) => {
+                "Правило автопереказу не знайдено"
+            }
+
+            (ApiMessage::EscrowNotFound, Language::English) => "Escrow not found",
+            (ApiMessage::EscrowNotFound, Language::Russian) => "Эскроу не найден",
+            (ApiMessage::EscrowNotFound, Language::Ukrainian) => "Ескроу не знайдено",
+
+            (ApiMessage::HtlcNotFound, Language::English) => "HTLC not found",
+            (ApiMessage::HtlcNotFound, Language::Russian) => "HTLC не найден",
+            (ApiMessage::HtlcNotFound, Language::Ukrainian) => "HTLC не знайдено",
+
+            (ApiMessage::PreimageNotFound, Language::English) => "Preimage not revealed",
+            (ApiMessage::PreimageNotFound, Language::Russian) => "Прообраз не раскрыт",
+            (ApiMessage::PreimageNotFound, Language::Ukrainian) => "Прообраз не розкрито",
+
+            (ApiMessage::AssetNotFound, Language::English) => "Asset not found",
+            (ApiMessage::AssetNotFound, Language::Russian) => "Актив не найден",
+            (ApiMessage::AssetNotFound, Language::Ukrainian) => "Актив не знайдено",
+        }
+    }
+}
@@ -22,40 +22,645 @@ extern crate serde_derive;
 extern crate exonum;
 extern crate router;
 extern crate bodyparser;
+extern crate hyper;
 extern crate iron;
+extern crate qrcode;
+extern crate unicode_normalization;
+
+pub mod ceremony;
+pub mod i18n;
+pub mod pagination;
+pub mod payment;
+pub mod payout;
+pub mod risk;
+pub mod sla;
+pub mod text;
 
 // Import necessary types from crates.
 
 use exonum::blockchain::{Blockchain, Service, GenesisConfig, ValidatorKeys, Transaction,
-                         ApiContext};
+                         ApiContext, ServiceContext, Schema as CoreSchema, BlockProof};
 use exonum::encoding::serialize::FromHex;
 use exonum::node::{NodeConfig, NodeApiConfig, TransactionSend, ApiSender};
 use exonum::messages::{RawTransaction, Message};
-use exonum::storage::{Fork, MapIndex};
-use exonum::crypto::{PublicKey, Hash};
+use exonum::storage::{Entry, Fork, KeySetIndex, MapProof, ProofListIndex, ProofMapIndex,
+                      Snapshot, StorageKey};
+use exonum::helpers::Height;
+use exonum::crypto::{self, PublicKey, SecretKey, Hash, Signature};
 use exonum::encoding;
 use exonum::api::{Api, ApiError};
 use iron::prelude::*;
 use iron::Handler;
 use router::Router;
 use serde::Deserialize;
+use qrcode::QrCode;
+use qrcode::render::svg;
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use i18n::{ApiMessage, Language};
+use pagination::{Page, PageRequest, PageRequestError, QueryBudget};
+use payment::PaymentRequest;
+use risk::{RiskModel, RiskMonitor, DEFAULT_RISK_WINDOW};
+use sla::{AlertSink, SlaTracker};
+use text::TextError;
 
 // // // // // // // // // // CONSTANTS // // // // // // // // // //
 
 // Define service ID for the service trait.
 
-const SERVICE_ID: u16 = 1;
+/// Service identifier. Transactions registered by embedders must use it
+/// as their `TYPE` so that the node routes them to this service.
+pub const SERVICE_ID: u16 = 1;
+
+/// Service name, also used as the key of the service configuration.
+pub const SERVICE_NAME: &str = "cryptocurrency";
 
 // Define constants for transaction types within the service.
 
-const TX_CREATE_WALLET_ID: u16 = 1;
+/// Message type of `TxCreateWallet`.
+pub const TX_CREATE_WALLET_ID: u16 = 1;
+
+/// Message type of `TxTransfer`.
+pub const TX_TRANSFER_ID: u16 = 2;
+
+/// Message type of `TxDeferredTransfer`.
+pub const TX_DEFERRED_TRANSFER_ID: u16 = 3;
+
+/// Message type of `TxCancelDeferredTransfer`.
+pub const TX_CANCEL_DEFERRED_TRANSFER_ID: u16 = 4;
+
+/// Message type of `TxTick`.
+pub const TX_TICK_ID: u16 = 5;
+
+/// Message type of `TxGrantPromotion`.
+pub const TX_GRANT_PROMOTION_ID: u16 = 6;
+
+/// Message type of `TxInsurancePayout`.
+pub const TX_INSURANCE_PAYOUT_ID: u16 = 7;
+
+/// Message type of `TxApproveInsurancePayout`.
+pub const TX_APPROVE_INSURANCE_PAYOUT_ID: u16 = 8;
+
+/// Message type of `TxBurnWithTag`.
+pub const TX_BURN_WITH_TAG_ID: u16 = 9;
+
+/// Message type of `TxCreateAndFundWallet`.
+pub const TX_CREATE_AND_FUND_WALLET_ID: u16 = 10;
+
+/// Message type of `TxAuthorizeSessionKey`.
+pub const TX_AUTHORIZE_SESSION_KEY_ID: u16 = 11;
+
+/// Message type of `TxRevokeSessionKey`.
+pub const TX_REVOKE_SESSION_KEY_ID: u16 = 12;
+
+/// Message type of `TxSessionTransfer`.
+pub const TX_SESSION_TRANSFER_ID: u16 = 13;
+
+/// Message type of `TxAttestWallet`.
+pub const TX_ATTEST_WALLET_ID: u16 = 14;
+
+/// Message type of `TxRevokeAttestation`.
+pub const TX_REVOKE_ATTESTATION_ID: u16 = 15;
+
+/// Message type of `TxLinkWallets`.
+pub const TX_LINK_WALLETS_ID: u16 = 16;
+
+/// Message type of `TxRegisterPaymentReference`.
+pub const TX_REGISTER_PAYMENT_REFERENCE_ID: u16 = 17;
+
+/// Message type of `TxReviewHeldTransfer`.
+pub const TX_REVIEW_HELD_TRANSFER_ID: u16 = 18;
+
+/// Message type of `TxAssignTier`.
+pub const TX_ASSIGN_TIER_ID: u16 = 19;
+
+/// Message type of `TxCreateAuction`.
+pub const TX_CREATE_AUCTION_ID: u16 = 20;
+
+/// Message type of `TxBid`.
+pub const TX_BID_ID: u16 = 21;
+
+/// Message type of `TxOpenStream`.
+pub const TX_OPEN_STREAM_ID: u16 = 22;
+
+/// Message type of `TxWithdrawStream`.
+pub const TX_WITHDRAW_STREAM_ID: u16 = 23;
+
+/// Message type of `TxCloseStream`.
+pub const TX_CLOSE_STREAM_ID: u16 = 24;
+
+/// Message type of `TxLotteryDraw`.
+pub const TX_LOTTERY_DRAW_ID: u16 = 25;
+
+/// Message type of `TxEnterDraw`.
+pub const TX_ENTER_DRAW_ID: u16 = 26;
+
+/// Message type of `TxIssueFeeCoupon`.
+pub const TX_ISSUE_FEE_COUPON_ID: u16 = 27;
+
+/// Message type of `TxCouponTransfer`.
+pub const TX_COUPON_TRANSFER_ID: u16 = 28;
+
+/// Message type of `TxDeposit`.
+pub const TX_DEPOSIT_ID: u16 = 29;
+
+/// Message type of `TxClaimDeposit`.
+pub const TX_CLAIM_DEPOSIT_ID: u16 = 30;
+
+/// Message type of `TxClawback`.
+pub const TX_CLAWBACK_ID: u16 = 31;
+
+/// Message type of `TxApproveClawback`.
+pub const TX_APPROVE_CLAWBACK_ID: u16 = 32;
+
+/// Message type of `TxBatchTransfer`.
+pub const TX_BATCH_TRANSFER_ID: u16 = 33;
+
+/// Message type of `TxSetupMultisig`.
+pub const TX_SETUP_MULTISIG_ID: u16 = 34;
+
+/// Message type of `TxMultisigTransfer`.
+pub const TX_MULTISIG_TRANSFER_ID: u16 = 35;
+
+/// Message type of `TxApproveMultisigTransfer`.
+pub const TX_APPROVE_MULTISIG_TRANSFER_ID: u16 = 36;
+
+/// Message type of `TxCreateInvitation`.
+pub const TX_CREATE_INVITATION_ID: u16 = 37;
+
+/// Message type of `TxIssue`.
+pub const TX_ISSUE_ID: u16 = 38;
+
+/// Message type of `TxBurn`.
+pub const TX_BURN_ID: u16 = 39;
+
+/// Message type of `TxFreezeWallet`.
+pub const TX_FREEZE_WALLET_ID: u16 = 40;
+
+/// Message type of `TxUnfreezeWallet`.
+pub const TX_UNFREEZE_WALLET_ID: u16 = 41;
+
+/// Message type of `TxUpdateWallet`.
+pub const TX_UPDATE_WALLET_ID: u16 = 42;
+
+/// Message type of `TxRenameWallet`.
+pub const TX_RENAME_WALLET_ID: u16 = 43;
+
+/// Message type of `TxCloseWallet`.
+pub const TX_CLOSE_WALLET_ID: u16 = 44;
+
+/// Message type of `TxApprove`.
+pub const TX_APPROVE_ID: u16 = 45;
+
+/// Message type of `TxTransferFrom`.
+pub const TX_TRANSFER_FROM_ID: u16 = 46;
+
+/// Message type of `TxSetSweepRule`.
+pub const TX_SET_SWEEP_RULE_ID: u16 = 47;
+
+/// Message type of `TxClearSweepRule`.
+pub const TX_CLEAR_SWEEP_RULE_ID: u16 = 48;
+
+/// Message type of `TxCreateEscrow`.
+pub const TX_CREATE_ESCROW_ID: u16 = 49;
+
+/// Message type of `TxReleaseEscrow`.
+pub const TX_RELEASE_ESCROW_ID: u16 = 50;
+
+/// Message type of `TxRefundEscrow`.
+pub const TX_REFUND_ESCROW_ID: u16 = 51;
+
+/// Message type of `TxLockedTransfer`.
+pub const TX_LOCKED_TRANSFER_ID: u16 = 52;
+
+/// Message type of `TxCreateHtlc`.
+pub const TX_CREATE_HTLC_ID: u16 = 53;
+
+/// Message type of `TxClaimHtlc`.
+pub const TX_CLAIM_HTLC_ID: u16 = 54;
+
+/// Message type of `TxRefundHtlc`.
+pub const TX_REFUND_HTLC_ID: u16 = 55;
+
+/// Message type of `TxCreateAsset`.
+pub const TX_CREATE_ASSET_ID: u16 = 56;
+
+/// Message type of `TxAssetTransfer`.
+pub const TX_ASSET_TRANSFER_ID: u16 = 57;
+
+/// Tier of a newly created wallet.
+pub const TIER_BASIC: u8 = 0;
+
+/// Tier of a wallet whose owner has passed identity verification.
+pub const TIER_VERIFIED: u8 = 1;
+
+/// Tier of a wallet owned by an institution.
+pub const TIER_INSTITUTIONAL: u8 = 2;
+
+// Define kinds of actions scheduled at a height.
 
-const TX_TRANSFER_ID: u16 = 2;
+const SCHEDULED_DEFERRED_TRANSFER: u8 = 0;
+const SCHEDULED_UNLOCK: u8 = 1;
+const SCHEDULED_PROMOTION_EXPIRY: u8 = 2;
+const SCHEDULED_AUCTION: u8 = 3;
+const SCHEDULED_DRAW: u8 = 4;
+const SCHEDULED_DEPOSIT_RETURN: u8 = 5;
+const SCHEDULED_CLAWBACK: u8 = 6;
+const SCHEDULED_INVITATION_EXPIRY: u8 = 7;
 
 // Define initial balance of a newly created wallet.
 
 const INIT_BALANCE: u64 = 100;
 
+// Define default length of a wallet activity period in blocks.
+
+const DEFAULT_ACTIVITY_PERIOD: u64 = 1000;
+
+// Define how many recent blocks are taken into account when computing the base fee.
+
+const BASE_FEE_LOOKBACK: u64 = 256;
+
+// Define default maximum length of a wallet name in characters.
+
+const DEFAULT_MAX_NAME_LENGTH: u64 = 64;
+
+// Define maximum edit distance between names reported as similar by `GET v1/names/check`.
+
+const NAME_SIMILARITY_DISTANCE: u64 = 2;
+
+// Define default maximum number of payments in a batch transfer.
+
+const DEFAULT_MAX_BATCH_PAYMENTS: u64 = 100;
+
+// Define default maximum length of a contact URI in wallet metadata in characters.
+
+const DEFAULT_MAX_METADATA_LENGTH: u64 = 256;
+
+// Define default maximum length of a transfer memo in characters.
+
+const DEFAULT_MAX_MEMO_LENGTH: u64 = 140;
+
+// Define default maximum number of transfers queued until their dependencies are executed.
+
+const DEFAULT_MAX_QUEUED_TRANSFERS: u64 = 100;
+
+// Define number of blocks after which a transaction submitted through the API
+// and not committed is no longer reported as pending.
+
+const SUBMITTED_TX_LIFETIME: u64 = 1000;
+
+// Define default number of recent blocks covered by `GET v1/admin/overview`.
+
+const DEFAULT_OVERVIEW_WINDOW: u64 = 100;
+
+// Define maximum number of hops from the wallet covered by `GET v1/wallet/:pub_key/graph`.
+
+const MAX_GRAPH_DEPTH: u64 = 3;
+
+// Define maximum number of wallets in a counterparty graph.
+
+const MAX_GRAPH_NODES: usize = 100;
+
+// // // // // // // // // // CONFIGURATION // // // // // // // // // //
+
+/// Service configuration. It is written to the genesis block by `Service::initialize`
+/// and may later be changed like any other part of the blockchain configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrencyConfig {
+    /// Length of a period for wallet activity counters, in blocks.
+    #[serde(default = "default_activity_period")]
+    pub activity_period: u64,
+    /// Rules for awarding loyalty points. No points are awarded if not set.
+    #[serde(default)]
+    pub loyalty: Option<LoyaltyRules>,
+    /// Key authorized to sign issuer transactions.
+    #[serde(default)]
+    pub issuer: Option<PublicKey>,
+    /// Wallet funding promotional balances and receiving their expired remainders.
+    #[serde(default)]
+    pub treasury: Option<PublicKey>,
+    /// Insurance fund settings. No levy is charged if not set.
+    #[serde(default)]
+    pub insurance: Option<InsuranceConfig>,
+    /// Dynamic minimum fee for transfers. Any fee is accepted if not set.
+    #[serde(default)]
+    pub fee_market: Option<FeeMarketConfig>,
+    /// Key authorized to attest wallets as verified. No wallets can be attested if not set.
+    #[serde(default)]
+    pub verifier: Option<PublicKey>,
+    /// Rules putting transfers on hold. No transfers are held if not set.
+    #[serde(default)]
+    pub velocity: Option<VelocityRules>,
+    /// Limits and fee rates of account tiers. Tiers are not enforced if not set.
+    #[serde(default)]
+    pub tiers: Option<TierConfig>,
+    /// Key authorized to issue fee coupons. No coupons can be issued if not set.
+    #[serde(default)]
+    pub coupon_issuer: Option<PublicKey>,
+    /// Key authorized to freeze and unfreeze wallets. No wallets can be frozen if not set.
+    #[serde(default)]
+    pub admin: Option<PublicKey>,
+    /// Size limits of transaction fields.
+    #[serde(default)]
+    pub limits: TransactionLimits,
+    /// Heights from which transactions of the given message types are accepted.
+    /// Transactions of types not listed here are accepted at any height.
+    #[serde(default)]
+    pub activation_heights: BTreeMap<u16, u64>,
+    /// Emergency clawback settings. No transfers can be clawed back if not set.
+    #[serde(default)]
+    pub clawback: Option<ClawbackConfig>,
+    /// Per-epoch caps on issuer actions. No caps are enforced if not set.
+    #[serde(default)]
+    pub caps: Option<EpochCaps>,
+    /// Collect transfer fees in a pool shared among the validators instead of
+    /// burning them.
+    #[serde(default)]
+    pub validator_rewards: bool,
+}
+
+fn default_activity_period() -> u64 {
+    DEFAULT_ACTIVITY_PERIOD
+}
+
+impl Default for CurrencyConfig {
+    fn default() -> Self {
+        CurrencyConfig {
+            activity_period: DEFAULT_ACTIVITY_PERIOD,
+            loyalty: None,
+            issuer: None,
+            treasury: None,
+            insurance: None,
+            fee_market: None,
+            verifier: None,
+            velocity: None,
+            tiers: None,
+            coupon_issuer: None,
+            admin: None,
+            limits: TransactionLimits::default(),
+            activation_heights: BTreeMap::new(),
+            clawback: None,
+            caps: None,
+            validator_rewards: false,
+        }
+    }
+}
+
+/// Size limits of transaction fields, published by `GET v1/info`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionLimits {
+    /// Maximum length of a wallet name in characters.
+    #[serde(default = "default_max_name_length")]
+    pub max_name_length: u64,
+    /// Maximum number of payments in a batch transfer.
+    #[serde(default = "default_max_batch_payments")]
+    pub max_batch_payments: u64,
+    /// Maximum length of a contact URI in wallet metadata, in characters.
+    #[serde(default = "default_max_metadata_length")]
+    pub max_metadata_length: u64,
+    /// Maximum length of a transfer memo in characters.
+    #[serde(default = "default_max_memo_length")]
+    pub max_memo_length: u64,
+    /// Maximum number of transfers in a block queued until their dependencies
+    /// are executed.
+    #[serde(default = "default_max_queued_transfers")]
+    pub max_queued_transfers: u64,
+}
+
+fn default_max_name_length() -> u64 {
+    DEFAULT_MAX_NAME_LENGTH
+}
+
+fn default_max_batch_payments() -> u64 {
+    DEFAULT_MAX_BATCH_PAYMENTS
+}
+
+fn default_max_metadata_length() -> u64 {
+    DEFAULT_MAX_METADATA_LENGTH
+}
+
+fn default_max_memo_length() -> u64 {
+    DEFAULT_MAX_MEMO_LENGTH
+}
+
+fn default_max_queued_transfers() -> u64 {
+    DEFAULT_MAX_QUEUED_TRANSFERS
+}
+
+impl Default for TransactionLimits {
+    fn default() -> Self {
+        TransactionLimits {
+            max_name_length: DEFAULT_MAX_NAME_LENGTH,
+            max_batch_payments: DEFAULT_MAX_BATCH_PAYMENTS,
+            max_metadata_length: DEFAULT_MAX_METADATA_LENGTH,
+            max_memo_length: DEFAULT_MAX_MEMO_LENGTH,
+            max_queued_transfers: DEFAULT_MAX_QUEUED_TRANSFERS,
+        }
+    }
+}
+
+/// Rules for awarding loyalty points to senders of transfers.
+///
+/// A sender receives `points_per_transfer` for every outgoing transfer, plus
+/// `streak_bonus` for every consecutive activity period (beyond the current one)
+/// in which the sender made transfers, counting at most `max_streak` periods.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoyaltyRules {
+    pub points_per_transfer: u64,
+    pub streak_bonus: u64,
+    pub max_streak: u64,
+}
+
+impl LoyaltyRules {
+    /// Points awarded for a transfer made with the given activity streak.
+    pub fn points(&self, streak: u64) -> u64 {
+        let streak = ::std::cmp::min(streak, self.max_streak);
+        self.points_per_transfer + self.streak_bonus * streak.saturating_sub(1)
+    }
+}
+
+/// Insurance fund settings.
+///
+/// Every transfer is charged a levy of `levy_bps` basis points of the transferred
+/// amount on top of the amount itself; the levy is credited to the `fund` wallet.
+/// Payouts from the fund require approvals of `threshold` out of `governors`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InsuranceConfig {
+    pub fund: PublicKey,
+    pub levy_bps: u64,
+    pub governors: Vec<PublicKey>,
+    pub threshold: u64,
+}
+
+impl InsuranceConfig {
+    /// Levy charged for transferring the given amount, rounded down.
+    pub fn levy(&self, amount: u64) -> u64 {
+        amount / 10_000 * self.levy_bps + amount % 10_000 * self.levy_bps / 10_000
+    }
+
+    /// Position of a governor in the configuration.
+    pub fn governor_index(&self, pub_key: &PublicKey) -> Option<usize> {
+        self.governors.iter().position(|governor| governor == pub_key)
+    }
+}
+
+/// Emergency clawback settings.
+///
+/// A clawback reverses a committed transfer, e.g. of stolen funds. It is proposed
+/// by one of the `governors` and takes effect `delay` blocks after more than two
+/// thirds of them have approved it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClawbackConfig {
+    pub governors: Vec<PublicKey>,
+    pub delay: u64,
+}
+
+impl ClawbackConfig {
+    /// Position of a governor in the configuration.
+    pub fn governor_index(&self, pub_key: &PublicKey) -> Option<usize> {
+        self.governors.iter().position(|governor| governor == pub_key)
+    }
+
+    /// Number of approvals making a supermajority of the governors.
+    pub fn supermajority(&self) -> u64 {
+        self.governors.len() as u64 * 2 / 3 + 1
+    }
+}
+
+/// Caps limiting the damage a compromised issuer key can do.
+///
+/// Coins granted from the treasury within an epoch of `epoch_length` blocks may
+/// not exceed `max_treasury_outflow` in total, and coins issued within an epoch
+/// may not exceed `max_issuance`, if set. Actions exceeding a cap fail; lifting
+/// a cap takes a configuration change approved by the validators.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpochCaps {
+    pub epoch_length: u64,
+    pub max_treasury_outflow: u64,
+    #[serde(default)]
+    pub max_issuance: Option<u64>,
+}
+
+impl EpochCaps {
+    /// Epoch the given height belongs to.
+    pub fn epoch(&self, height: u64) -> u64 {
+        height / ::std::cmp::max(self.epoch_length, 1)
+    }
+}
+
+/// Velocity rules for transfers.
+///
+/// A transfer is put on hold if its amount exceeds `max_amount`, if the sender
+/// has already made `max_transfers` transfers in the current activity period,
+/// or if the receiver is a new counterparty and the sender has already sent
+/// transfers to `max_counterparties` distinct wallets in the current activity
+/// period. Held transfers are released or rejected by the `compliance` key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VelocityRules {
+    pub compliance: PublicKey,
+    #[serde(default)]
+    pub max_amount: Option<u64>,
+    #[serde(default)]
+    pub max_transfers: Option<u64>,
+    #[serde(default)]
+    pub max_counterparties: Option<u64>,
+}
+
+impl VelocityRules {
+    /// Check whether a transfer of `amount` trips any of the rules, given
+    /// the number of transfers the sender has made and the number of distinct
+    /// receivers of these transfers in the current period.
+    pub fn is_tripped(
+        &self,
+        amount: u64,
+        transfers: u64,
+        counterparties: u64,
+        new_counterparty: bool,
+    ) -> bool {
+        self.max_amount.map_or(false, |max| amount > max) ||
+            self.max_transfers.map_or(false, |max| transfers >= max) ||
+            (new_counterparty && self.max_counterparties.map_or(false, |max| counterparties >= max))
+    }
+}
+
+/// Account tier settings.
+///
+/// Tiers are assigned to wallets by the `admin` key; new wallets have the basic tier.
+/// A transfer must not exceed the `max_amount` of the sender's tier, and its fee
+/// must be at least `fee_bps` basis points of the amount, as well as the base fee.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TierConfig {
+    pub admin: PublicKey,
+    pub basic: TierRules,
+    pub verified: TierRules,
+    pub institutional: TierRules,
+}
+
+impl TierConfig {
+    /// Rules of the given tier. Unknown tiers follow the basic rules.
+    pub fn rules(&self, tier: u8) -> &TierRules {
+        match tier {
+            TIER_VERIFIED => &self.verified,
+            TIER_INSTITUTIONAL => &self.institutional,
+            _ => &self.basic,
+        }
+    }
+}
+
+/// Transfer limit and fee rate of an account tier.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TierRules {
+    #[serde(default)]
+    pub max_amount: Option<u64>,
+    #[serde(default)]
+    pub fee_bps: u64,
+}
+
+impl TierRules {
+    /// Minimum fee for transferring the given amount, rounded down.
+    pub fn min_fee(&self, amount: u64) -> u64 {
+        amount / 10_000 * self.fee_bps + amount % 10_000 * self.fee_bps / 10_000
+    }
+}
+
+/// Dynamic minimum fee settings.
+///
+/// The base fee of a block is derived from the base fee of the previous block:
+/// it grows if the previous block contains more than `target_block_txs`
+/// transactions and decreases otherwise, by at most `1 / max_change_denominator`
+/// of its value per block, never falling below `min_base_fee`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeMarketConfig {
+    pub min_base_fee: u64,
+    pub target_block_txs: u64,
+    #[serde(default = "default_max_change_denominator")]
+    pub max_change_denominator: u64,
+}
+
+fn default_max_change_denominator() -> u64 {
+    8
+}
+
+impl FeeMarketConfig {
+    /// Base fee following a block with `block_txs` transactions and the given base fee.
+    pub fn next_base_fee(&self, base_fee: u64, block_txs: u64) -> u64 {
+        let target = ::std::cmp::max(self.target_block_txs, 1);
+        let denominator = ::std::cmp::max(self.max_change_denominator, 1);
+        let next = if block_txs > target {
+            let delta = base_fee.saturating_mul(block_txs - target) / target / denominator;
+            base_fee.saturating_add(::std::cmp::max(delta, 1))
+        } else {
+            let delta = base_fee.saturating_mul(target - block_txs) / target / denominator;
+            base_fee - delta
+        };
+        ::std::cmp::max(next, self.min_base_fee)
+    }
+}
+
 // // // // // // // // // // PERSISTENT DATA // // // // // // // // // //
 
 // Declare the data to be stored in the blockchain. In the present case,
@@ -64,207 +669,8550 @@ const INIT_BALANCE: u64 = 100;
 /// Declare a [serializable][1] struct and determine bounds of its fields
 /// with `encoding_struct!` macro.
 ///
+/// `history_len` and `history_hash` are the length and the root hash of the
+/// wallet's transaction history, so that the history is covered by the proof
+/// of the wallet. A `frozen` wallet cannot send coins. `display_name`,
+/// `contact_uri` and `avatar` (the hash of an avatar image) are optional
+/// metadata set by the owner; empty strings and the zero hash mean not set.
+///
 /// [1]: https://exonum.com/doc/architecture/serialization
 encoding_struct! {
     struct Wallet {
-        const SIZE = 48;
+        const SIZE = 140;
 
         field pub_key:            &PublicKey  [00 => 32]
         field name:               &str        [32 => 40]
         field balance:            u64         [40 => 48]
+        field verified:           bool        [48 => 49]
+        field tier:               u8          [49 => 50]
+        field history_len:        u64         [50 => 58]
+        field history_hash:       &Hash       [58 => 90]
+        field frozen:             bool        [90 => 91]
+        field display_name:       &str        [91 => 99]
+        field contact_uri:        &str        [99 => 107]
+        field avatar:             &Hash       [107 => 139]
+        field closed:             bool        [139 => 140]
     }
 }
 
 /// Add methods to the `Wallet` type for changing balance.
+///
+/// Balance changes never wrap around: `increase` returns `None` if the balance
+/// would overflow, and transactions reject such credits with
+/// `Error::BalanceOverflow` before changing any balance.
 impl Wallet {
-    pub fn increase(self, amount: u64) -> Self {
-        let balance = self.balance() + amount;
-        Self::new(self.pub_key(), self.name(), balance)
+    pub fn increase(self, amount: u64) -> Option<Self> {
+        let balance = self.balance().checked_add(amount)?;
+        Some(Self::new(
+            self.pub_key(),
+            self.name(),
+            balance,
+            self.verified(),
+            self.tier(),
+            self.history_len(),
+            self.history_hash(),
+            self.frozen(),
+            self.display_name(),
+            self.contact_uri(),
+            self.avatar(),
+            self.closed(),
+        ))
     }
 
     pub fn decrease(self, amount: u64) -> Self {
-        let balance = self.balance() - amount;
-        Self::new(self.pub_key(), self.name(), balance)
+        let balance = self.balance().checked_sub(amount).expect("Balance underflow");
+        Self::new(
+            self.pub_key(),
+            self.name(),
+            balance,
+            self.verified(),
+            self.tier(),
+            self.history_len(),
+            self.history_hash(),
+            self.frozen(),
+            self.display_name(),
+            self.contact_uri(),
+            self.avatar(),
+            self.closed(),
+        )
     }
-}
 
-// // // // // // // // // // DATA LAYOUT // // // // // // // // // //
+    /// Set the name of the wallet.
+    pub fn set_name(self, name: &str) -> Self {
+        Self::new(
+            self.pub_key(),
+            name,
+            self.balance(),
+            self.verified(),
+            self.tier(),
+            self.history_len(),
+            self.history_hash(),
+            self.frozen(),
+            self.display_name(),
+            self.contact_uri(),
+            self.avatar(),
+            self.closed(),
+        )
+    }
 
-/// Create schema of the key-value storage implemented by `MemoryDB`. In the
-/// present case a `Fork` of the database is used.
-pub struct CurrencySchema<'a> {
-    view: &'a mut Fork,
-}
+    /// Set the verification badge of the wallet.
+    pub fn set_verified(self, verified: bool) -> Self {
+        Self::new(
+            self.pub_key(),
+            self.name(),
+            self.balance(),
+            verified,
+            self.tier(),
+            self.history_len(),
+            self.history_hash(),
+            self.frozen(),
+            self.display_name(),
+            self.contact_uri(),
+            self.avatar(),
+            self.closed(),
+        )
+    }
 
-/// Declare layout of the data. Use an instance of [`MapIndex`]
-/// to keep wallets in storage. Index values are serialized `Wallet` structs.
-///
-/// Isolate the wallets map into a separate entity by adding a unique prefix,
-/// i.e. the first argument to the `MapIndex::new` call.
-///
-/// [`MapIndex`]: https://exonum.com/doc/architecture/storage#mapindex
-impl<'a> CurrencySchema<'a> {
-    pub fn wallets(&mut self) -> MapIndex<&mut Fork, PublicKey, Wallet> {
-        MapIndex::new("cryptocurrency.wallets", self.view)
+    /// Set the account tier of the wallet.
+    pub fn set_tier(self, tier: u8) -> Self {
+        Self::new(
+            self.pub_key(),
+            self.name(),
+            self.balance(),
+            self.verified(),
+            tier,
+            self.history_len(),
+            self.history_hash(),
+            self.frozen(),
+            self.display_name(),
+            self.contact_uri(),
+            self.avatar(),
+            self.closed(),
+        )
     }
 
-    /// Get a separate wallet from the storage.
-    pub fn wallet(&mut self, pub_key: &PublicKey) -> Option<Wallet> {
-        self.wallets().get(pub_key)
+    /// Set the length and the root hash of the transaction history of the wallet.
+    pub fn set_history(self, history_len: u64, history_hash: &Hash) -> Self {
+        Self::new(
+            self.pub_key(),
+            self.name(),
+            self.balance(),
+            self.verified(),
+            self.tier(),
+            history_len,
+            history_hash,
+            self.frozen(),
+            self.display_name(),
+            self.contact_uri(),
+            self.avatar(),
+            self.closed(),
+        )
     }
-}
 
-// // // // // // // // // // TRANSACTIONS // // // // // // // // // //
+    /// Set the frozen status of the wallet.
+    pub fn set_frozen(self, frozen: bool) -> Self {
+        Self::new(
+            self.pub_key(),
+            self.name(),
+            self.balance(),
+            self.verified(),
+            self.tier(),
+            self.history_len(),
+            self.history_hash(),
+            frozen,
+            self.display_name(),
+            self.contact_uri(),
+            self.avatar(),
+            self.closed(),
+        )
+    }
 
-/// Create a new wallet.
-message! {
-    struct TxCreateWallet {
-        const TYPE = SERVICE_ID;
-        const ID = TX_CREATE_WALLET_ID;
-        const SIZE = 40;
+    /// Set the metadata of the wallet.
+    pub fn set_metadata(self, display_name: &str, contact_uri: &str, avatar: &Hash) -> Self {
+        Self::new(
+            self.pub_key(),
+            self.name(),
+            self.balance(),
+            self.verified(),
+            self.tier(),
+            self.history_len(),
+            self.history_hash(),
+            self.frozen(),
+            display_name,
+            contact_uri,
+            avatar,
+            self.closed(),
+        )
+    }
 
-        field pub_key:     &PublicKey  [00 => 32]
-        field name:        &str        [32 => 40]
+    /// Mark the wallet as closed.
+    pub fn set_closed(self) -> Self {
+        Self::new(
+            self.pub_key(),
+            self.name(),
+            self.balance(),
+            self.verified(),
+            self.tier(),
+            self.history_len(),
+            self.history_hash(),
+            self.frozen(),
+            self.display_name(),
+            self.contact_uri(),
+            self.avatar(),
+            true,
+        )
     }
 }
 
-/// Transfer coins between the wallets.
-message! {
-    struct TxTransfer {
-        const TYPE = SERVICE_ID;
-        const ID = TX_TRANSFER_ID;
+/// Funds locked by `TxDeferredTransfer` until the execution height.
+encoding_struct! {
+    struct DeferredTransfer {
         const SIZE = 80;
 
-        field from:        &PublicKey  [00 => 32]
-        field to:          &PublicKey  [32 => 64]
-        field amount:      u64         [64 => 72]
-        field seed:        u64         [72 => 80]
+        field from:               &PublicKey  [00 => 32]
+        field to:                 &PublicKey  [32 => 64]
+        field amount:             u64         [64 => 72]
+        field height:             u64         [72 => 80]
     }
 }
 
-// // // // // // // // // // CONTRACTS // // // // // // // // // //
+/// Coins transferred by `TxLockedTransfer` that the receiver may not spend
+/// before the unlock height.
+encoding_struct! {
+    struct LockedTransfer {
+        const SIZE = 80;
 
-/// Execute a transaction.
-impl Transaction for TxCreateWallet {
-    /// Verify integrity of the transaction by checking the transaction
-    /// signature.
-    fn verify(&self) -> bool {
-        self.verify_signature(self.pub_key())
+        field from:               &PublicKey  [00 => 32]
+        field to:                 &PublicKey  [32 => 64]
+        field amount:             u64         [64 => 72]
+        field unlock_height:      u64         [72 => 80]
     }
+}
 
-    /// Apply logic to the storage when executing the transaction.
-    fn execute(&self, view: &mut Fork) {
-        let mut schema = CurrencySchema { view };
-        if schema.wallet(self.pub_key()).is_none() {
-            let wallet = Wallet::new(self.pub_key(), self.name(), INIT_BALANCE);
-            println!("Create the wallet: {:?}", wallet);
-            schema.wallets().put(self.pub_key(), wallet)
-        }
-    }
+/// Activity of a wallet. Counters of sent and received transfers are reset at
+/// the start of each activity period; `streak` is the number of consecutive
+/// periods, ending with `last_active_period`, in which the wallet sent transfers.
+encoding_struct! {
+    struct WalletActivity {
+        const SIZE = 40;
 
-    /// Provide information about the transaction to be used in the blockchain explorer.
-    fn info(&self) -> serde_json::Value {
-        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+        field period:             u64         [00 => 08]
+        field sent:               u64         [08 => 16]
+        field received:           u64         [16 => 24]
+        field last_active_period: u64         [24 => 32]
+        field streak:             u64         [32 => 40]
     }
 }
 
-impl Transaction for TxTransfer {
-    /// Check if the sender is not the receiver. Check correctness of the
-    /// sender's signature.
-    fn verify(&self) -> bool {
-        (*self.from() != *self.to()) && self.verify_signature(self.from())
+impl WalletActivity {
+    /// Activity record of a wallet that has not made any transfers yet.
+    pub fn empty() -> Self {
+        Self::new(0, 0, 0, 0, 0)
     }
 
-    /// Retrieve two wallets to apply the transfer. Check the sender's
-    /// balance and apply changes to the balances of the wallets.
-    fn execute(&self, view: &mut Fork) {
-        let mut schema = CurrencySchema { view };
-        let sender = schema.wallet(self.from());
-        let receiver = schema.wallet(self.to());
-        if let (Some(sender), Some(receiver)) = (sender, receiver) {
-            let amount = self.amount();
-            if sender.balance() >= amount {
-                let sender = sender.decrease(amount);
-                let receiver = receiver.increase(amount);
-                println!("Transfer between wallets: {:?} => {:?}", sender, receiver);
-                let mut wallets = schema.wallets();
-                wallets.put(self.from(), sender);
-                wallets.put(self.to(), receiver);
-            }
+    /// Reset the counters if the record belongs to an earlier period.
+    fn in_period(self, period: u64) -> Self {
+        if self.period() == period {
+            self
+        } else {
+            Self::new(period, 0, 0, self.last_active_period(), self.streak())
         }
     }
 
-    /// Provide information about the transaction to be used in the blockchain explorer.
-    fn info(&self) -> serde_json::Value {
-        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    /// Record an outgoing transfer made in the given period.
+    pub fn record_sent(self, period: u64) -> Self {
+        let activity = self.in_period(period);
+        let streak = if activity.streak() > 0 && activity.last_active_period() == period {
+            activity.streak()
+        } else if activity.streak() > 0 && activity.last_active_period() + 1 == period {
+            activity.streak() + 1
+        } else {
+            1
+        };
+        Self::new(period, activity.sent() + 1, activity.received(), period, streak)
+    }
+
+    /// Record an incoming transfer received in the given period.
+    pub fn record_received(self, period: u64) -> Self {
+        let activity = self.in_period(period);
+        Self::new(
+            period,
+            activity.sent(),
+            activity.received() + 1,
+            activity.last_active_period(),
+            activity.streak(),
+        )
     }
 }
 
-// // // // // // // // // // REST API // // // // // // // // // //
+/// Rule moving the balance of a wallet in excess of `threshold` to the cold
+/// wallet. Sweeps are made by `TxTick` in the block after the balance exceeds
+/// the threshold.
+encoding_struct! {
+    struct SweepRule {
+        const SIZE = 40;
 
-/// Implement the node API.
-#[derive(Clone)]
-struct CryptocurrencyApi {
-    channel: ApiSender,
-    blockchain: Blockchain,
+        field threshold:          u64         [00 => 08]
+        field cold_wallet:        &PublicKey  [08 => 40]
+    }
 }
 
-/// The structure returned by the REST API.
-#[derive(Serialize, Deserialize)]
-pub struct TransactionResponse {
-    pub tx_hash: Hash,
+/// Promotional coins of a wallet. They are spent before the main balance and
+/// return to the treasury at the expiry height.
+encoding_struct! {
+    struct PromotionalBalance {
+        const SIZE = 16;
+
+        field amount:             u64         [00 => 08]
+        field expires_at:         u64         [08 => 16]
+    }
 }
 
-/// Shortcut to get data on wallets.
-impl CryptocurrencyApi {
-    /// Endpoint for getting a single wallet.
+/// Payout from the insurance fund. `approvals` is a bit mask of approving
+/// governors by their position in the configuration; `executed_at` is the height
+/// the payout was made at, or zero while the payout is pending.
+encoding_struct! {
+    struct InsurancePayout {
+        const SIZE = 56;
+
+        field to:                 &PublicKey  [00 => 32]
+        field amount:             u64         [32 => 40]
+        field approvals:          u64         [40 => 48]
+        field executed_at:        u64         [48 => 56]
+    }
+}
+
+impl InsurancePayout {
+    /// Number of governors approved the payout.
+    pub fn approval_count(&self) -> u64 {
+        u64::from(self.approvals().count_ones())
+    }
+}
+
+/// Signers of a multisignature wallet. Spending from the wallet requires
+/// approvals of `threshold` out of `signers`.
+encoding_struct! {
+    struct Multisig {
+        const SIZE = 16;
+
+        field signers:            Vec<PublicKey>  [00 => 08]
+        field threshold:          u64             [08 => 16]
+    }
+}
+
+impl Multisig {
+    /// Position of a signer in the wallet's list of signers.
+    pub fn signer_index(&self, pub_key: &PublicKey) -> Option<usize> {
+        self.signers().iter().position(|signer| signer == pub_key)
+    }
+}
+
+/// Transfer from a multisignature wallet. `approvals` is a bit mask of approving
+/// signers by their position in the wallet's list of signers; `executed_at` is
+/// the height the transfer was made at, or zero while the transfer is pending.
+encoding_struct! {
+    struct MultisigTransfer {
+        const SIZE = 96;
+
+        field wallet:             &PublicKey  [00 => 32]
+        field to:                 &PublicKey  [32 => 64]
+        field amount:             u64         [64 => 72]
+        field fee:                u64         [72 => 80]
+        field approvals:          u64         [80 => 88]
+        field executed_at:        u64         [88 => 96]
+    }
+}
+
+impl MultisigTransfer {
+    /// Number of signers approved the transfer.
+    pub fn approval_count(&self) -> u64 {
+        u64::from(self.approvals().count_ones())
+    }
+}
+
+/// Clawback of a committed transfer, keyed by the hash of the transfer.
+///
+/// `reason` is the hash of the incident report and `approvals` is a bit mask
+/// of approving governors by their position in the configuration. `execute_at`
+/// is zero until a supermajority of governors approves the clawback;
+/// `executed_at` is the height at which `recovered` coins were returned to
+/// the sender of the transfer, or zero while the clawback is pending.
+/// `error_code` is the code of the error that prevented the recovery, or zero.
+/// Clawbacks are never removed and serve as the audit record.
+encoding_struct! {
+    struct Clawback {
+        const SIZE = 97;
+
+        field proposer:           &PublicKey  [00 => 32]
+        field reason:             &Hash       [32 => 64]
+        field approvals:          u64         [64 => 72]
+        field execute_at:         u64         [72 => 80]
+        field executed_at:        u64         [80 => 88]
+        field recovered:          u64         [88 => 96]
+        field error_code:         u8          [96 => 97]
+    }
+}
+
+impl Clawback {
+    /// Number of governors approved the clawback.
+    pub fn approval_count(&self) -> u64 {
+        u64::from(self.approvals().count_ones())
+    }
+
+    /// Error that prevented the recovery of the coins, if any.
+    pub fn error(&self) -> Option<Error> {
+        Error::from_code(self.error_code())
+    }
+}
+
+/// Coins burned with a tag binding the burn to an external identity or commitment.
+encoding_struct! {
+    struct BurnRecord {
+        const SIZE = 80;
+
+        field owner:              &PublicKey  [00 => 32]
+        field amount:             u64         [32 => 40]
+        field tx_hash:            &Hash       [40 => 72]
+        field height:             u64         [72 => 80]
+    }
+}
+
+/// Running total of an amount within the epoch `epoch`.
+encoding_struct! {
+    struct EpochTotal {
+        const SIZE = 16;
+
+        field epoch:              u64         [00 => 08]
+        field amount:             u64         [08 => 16]
+    }
+}
+
+/// Base fee computed for the block at `height`.
+encoding_struct! {
+    struct BaseFee {
+        const SIZE = 16;
+
+        field height:             u64         [00 => 08]
+        field fee:                u64         [08 => 16]
+    }
+}
+
+/// Scope of a session key: the maximum amount of a single transfer and
+/// the height the key expires at.
+encoding_struct! {
+    struct SessionKey {
+        const SIZE = 16;
+
+        field max_amount:         u64         [00 => 08]
+        field expires_at:         u64         [08 => 16]
+    }
+}
+
+/// Attestation of a wallet by the verifier.
+encoding_struct! {
+    struct Attestation {
+        const SIZE = 40;
+
+        field verifier:           &PublicKey  [00 => 32]
+        field height:             u64         [32 => 40]
+    }
+}
+
+/// Transfer put on hold by the velocity rules. The amount and the levy are
+/// locked until the transfer is reviewed; the fee is charged when the transfer is held.
+/// `recorded` is set if the transfer is already recorded in the history and the
+/// activity of the wallets, as for locked transfers.
+encoding_struct! {
+    struct HeldTransfer {
+        const SIZE = 121;
+
+        field from:               &PublicKey  [00 => 32]
+        field to:                 &PublicKey  [32 => 64]
+        field amount:             u64         [64 => 72]
+        field levy:               u64         [72 => 80]
+        field reference:          &Hash       [80 => 112]
+        field height:             u64         [112 => 120]
+        field recorded:           bool        [120 => 121]
+    }
+}
+
+/// Ascending auction of an item identified by its hash, e.g. the hash of an NFT
+/// or of a premium name. The highest bid is escrowed by the service and
+/// `highest_bidder` is the seller while there are no bids. `settled_at` is the height
+/// the auction was settled at, or zero while it is open.
+encoding_struct! {
+    struct Auction {
+        const SIZE = 128;
+
+        field seller:             &PublicKey  [00 => 32]
+        field item:               &Hash       [32 => 64]
+        field reserve_price:      u64         [64 => 72]
+        field deadline:           u64         [72 => 80]
+        field highest_bidder:     &PublicKey  [80 => 112]
+        field highest_bid:        u64         [112 => 120]
+        field settled_at:         u64         [120 => 128]
+    }
+}
+
+/// Payment stream. The sender's `budget` is locked when the stream is opened;
+/// `rate` coins accrue to the receiver with every block after the `start` height
+/// until the budget is exhausted. `withdrawn` is the part of the budget already
+/// paid out to the receiver.
+encoding_struct! {
+    struct Stream {
+        const SIZE = 96;
+
+        field from:               &PublicKey  [00 => 32]
+        field to:                 &PublicKey  [32 => 64]
+        field rate:               u64         [64 => 72]
+        field budget:             u64         [72 => 80]
+        field start:              u64         [80 => 88]
+        field withdrawn:          u64         [88 => 96]
+    }
+}
+
+impl Stream {
+    /// Coins accrued to the receiver by the given height, including the withdrawn ones.
+    pub fn accrued(&self, height: u64) -> u64 {
+        let blocks = height.saturating_sub(self.start());
+        ::std::cmp::min(self.rate().saturating_mul(blocks), self.budget())
+    }
+
+    /// Coins the receiver can withdraw at the given height.
+    pub fn available(&self, height: u64) -> u64 {
+        self.accrued(height) - self.withdrawn()
+    }
+}
+
+/// Fee coupon. A transfer redeeming the coupon before the `expires_at` height
+/// pays the minimum fee reduced by `discount_bps` basis points; the coupon can
+/// be redeemed `max_uses` times.
+encoding_struct! {
+    struct FeeCoupon {
+        const SIZE = 32;
+
+        field discount_bps:       u64         [00 => 08]
+        field max_uses:           u64         [08 => 16]
+        field uses:               u64         [16 => 24]
+        field expires_at:         u64         [24 => 32]
+    }
+}
+
+impl FeeCoupon {
+    /// Check whether the coupon can be redeemed at the given height.
+    pub fn is_valid(&self, height: u64) -> bool {
+        self.uses() < self.max_uses() && height < self.expires_at()
+    }
+
+    /// Minimum fee after the discount, rounded up.
+    pub fn discounted(&self, min_fee: u64) -> u64 {
+        let bps = self.discount_bps();
+        min_fee - (min_fee / 10_000 * bps + min_fee % 10_000 * bps / 10_000)
+    }
+
+    /// Count a redemption of the coupon.
+    pub fn redeem(self) -> Self {
+        Self::new(
+            self.discount_bps(),
+            self.max_uses(),
+            self.uses() + 1,
+            self.expires_at(),
+        )
+    }
+}
+
+/// Sponsored invitation. Each of `max_uses` wallets created with the invitation
+/// starts with `balance` coins locked by the `sponsor` when the invitation was
+/// created. Coins of unused invitations return to the sponsor at the `expires_at`
+/// height.
+encoding_struct! {
+    struct Invitation {
+        const SIZE = 64;
+
+        field sponsor:            &PublicKey  [00 => 32]
+        field balance:            u64         [32 => 40]
+        field max_uses:           u64         [40 => 48]
+        field uses:               u64         [48 => 56]
+        field expires_at:         u64         [56 => 64]
+    }
+}
+
+impl Invitation {
+    /// Check whether a wallet can be created with the invitation at the given height.
+    pub fn is_valid(&self, height: u64) -> bool {
+        self.uses() < self.max_uses() && height < self.expires_at()
+    }
+
+    /// Record a wallet created with the invitation.
+    pub fn redeem(self) -> Self {
+        Self::new(
+            self.sponsor(),
+            self.balance(),
+            self.max_uses(),
+            self.uses() + 1,
+            self.expires_at(),
+        )
+    }
+}
+
+/// Refundable deposit. The `amount` is locked against an external `reference`
+/// (e.g. a rental agreement) and returned to the payer at the `return_at` height
+/// unless the payee claims it before.
+encoding_struct! {
+    struct Deposit {
+        const SIZE = 112;
+
+        field payer:              &PublicKey  [00 => 32]
+        field payee:              &PublicKey  [32 => 64]
+        field amount:             u64         [64 => 72]
+        field reference:          &Hash       [72 => 104]
+        field return_at:          u64         [104 => 112]
+    }
+}
+
+/// Coins locked by the sender until the arbiter releases them to the recipient
+/// or refunds them to the sender.
+encoding_struct! {
+    struct Escrow {
+        const SIZE = 104;
+
+        field sender:             &PublicKey  [00 => 32]
+        field recipient:          &PublicKey  [32 => 64]
+        field arbiter:            &PublicKey  [64 => 96]
+        field amount:             u64         [96 => 104]
+    }
+}
+
+/// Asset issued by a wallet in addition to the native coins of the service.
+/// The whole `supply` is credited to the issuer when the asset is created.
+encoding_struct! {
+    struct Asset {
+        const SIZE = 48;
+
+        field issuer:             &PublicKey  [00 => 32]
+        field name:               &str        [32 => 40]
+        field supply:             u64         [40 => 48]
+    }
+}
+
+/// Hashed time-lock contract. The coins are locked by the sender and paid to
+/// the recipient who reveals the preimage of the `hash_lock` before the
+/// `timeout` height, or returned to the sender from that height on.
+encoding_struct! {
+    struct Htlc {
+        const SIZE = 112;
+
+        field sender:             &PublicKey  [00 => 32]
+        field recipient:          &PublicKey  [32 => 64]
+        field hash_lock:          &Hash       [64 => 96]
+        field amount:             u64         [96 => 104]
+        field timeout:            u64         [104 => 112]
+    }
+}
+
+/// Lottery draw. The `prize` is locked when the draw is created and paid to
+/// a random entrant at `draw_height`; `winner` is the organizer and `drawn_at`
+/// is zero until the draw is made.
+encoding_struct! {
+    struct Draw {
+        const SIZE = 88;
+
+        field organizer:          &PublicKey  [00 => 32]
+        field prize:              u64         [32 => 40]
+        field draw_height:        u64         [40 => 48]
+        field winner:             &PublicKey  [48 => 80]
+        field drawn_at:           u64         [80 => 88]
+    }
+}
+
+/// Incoming transfer carrying a payment reference registered by the receiver.
+encoding_struct! {
+    struct Receipt {
+        const SIZE = 112;
+
+        field reference:          &Hash       [00 => 32]
+        field tx_hash:            &Hash       [32 => 64]
+        field from:               &PublicKey  [64 => 96]
+        field amount:             u64         [96 => 104]
+        field height:             u64         [104 => 112]
+    }
+}
+
+/// Payment made in a block, as assessed by the risk monitor.
+encoding_struct! {
+    struct BlockPayment {
+        const SIZE = 72;
+
+        field from:               &PublicKey  [00 => 32]
+        field to:                 &PublicKey  [32 => 64]
+        field amount:             u64         [64 => 72]
+    }
+}
+
+/// Action scheduled at a height. The key is the hash of the transaction that
+/// created the action, or the public key of the wallet for the expiry of its
+/// promotional balance.
+encoding_struct! {
+    struct ScheduledAction {
+        const SIZE = 33;
+
+        field kind:               u8          [00 => 01]
+        field key:                &Hash       [01 => 33]
+    }
+}
+
+/// Keys of the scheduled actions of the given kind, sorted and deduplicated.
+fn scheduled_keys(actions: &[ScheduledAction], kind: u8) -> Vec<Hash> {
+    let mut keys: Vec<Hash> = actions
+        .iter()
+        .filter(|action| action.kind() == kind)
+        .map(|action| *action.key())
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// Outcome of a payment checked against the velocity rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentOutcome {
+    /// The coins are credited to the receiver.
+    Made,
+    /// The payment is held until reviewed by the compliance key.
+    Held,
+    /// The coins are not credited to the receiver.
+    Failed,
+}
+
+/// Identifier of a payment of a batch transfer held by the velocity rules: the
+/// hash of the batch transaction and the index of the payment.
+pub fn batch_payment_id(tx_hash: &Hash, index: usize) -> Hash {
+    let mut data = b"cryptocurrency.batch_payment".to_vec();
+    data.extend_from_slice(tx_hash.as_ref());
+    data.extend((0..8).map(|byte| (index as u64 >> (8 * byte)) as u8));
+    crypto::hash(&data)
+}
+
+/// Key of a table kept per key, such as the allowances of a wallet, in
+/// `CurrencySchema::index_roots`: the hash of the table name and the key.
+pub fn index_root_key<K: StorageKey>(name: &str, key: &K) -> Hash {
+    let mut data = name.as_bytes().to_vec();
+    data.push(0);
+    let start = data.len();
+    data.resize(start + key.size(), 0);
+    key.write(&mut data[start..]);
+    crypto::hash(&data)
+}
+
+// // // // // // // // // // DATA LAYOUT // // // // // // // // // //
+
+/// Create schema of the key-value storage implemented by `MemoryDB`. The schema
+/// may be created over a read-only `Snapshot` of the database (e.g., in the REST API
+/// or in the block commit hook), or over a `Fork` when executing transactions.
+pub struct CurrencySchema<T> {
+    view: T,
+}
+
+/// Declare layout of the data. Use an instance of [`ProofMapIndex`]
+/// to keep wallets in storage. Index values are serialized `Wallet` structs.
+/// The root hash of the wallets map is a part of the block state hash, so
+/// validators that disagree on wallet balances fail to reach consensus on the block.
+///
+/// Isolate the wallets map into a separate entity by adding a unique prefix,
+/// i.e. the first argument to the `ProofMapIndex::new` call.
+///
+/// [`ProofMapIndex`]: https://exonum.com/doc/architecture/storage#proofmapindex
+impl<T: AsRef<Snapshot>> CurrencySchema<T> {
+    /// Create a schema over the given view of the storage. Used by transactions
+    /// registered outside of this crate to access wallets.
+    pub fn new(view: T) -> Self {
+        CurrencySchema { view }
+    }
+
+    pub fn wallets(&self) -> ProofMapIndex<&T, PublicKey, Wallet> {
+        ProofMapIndex::new("cryptocurrency.wallets", &self.view)
+    }
+
+    /// Hashes of the transactions that created the wallet or moved coins to
+    /// or from it, in the order of execution.
+    pub fn wallet_history(&self, pub_key: &PublicKey) -> ProofListIndex<&T, Hash> {
+        ProofListIndex::with_prefix("cryptocurrency.wallet_history", pub_key, &self.view)
+    }
+
+    /// Root hashes of the tables and hashes of the entries included into the
+    /// block state hash. The wallets table comes first, as wallet proofs refer
+    /// to it by its position. Tables kept per key, such as the allowances of
+    /// a wallet, are included through `index_roots`; the history of a wallet
+    /// through the history root stored in the wallet itself.
+    pub fn state_hash(&self) -> Vec<Hash> {
+        vec![
+            self.wallets().root_hash(),
+            self.deferred_transfers().root_hash(),
+            self.locked_transfers().root_hash(),
+            self.activity().root_hash(),
+            self.loyalty_points().root_hash(),
+            self.promotional_balances().root_hash(),
+            self.beneficiaries().root_hash(),
+            self.insurance_payouts().root_hash(),
+            self.multisigs().root_hash(),
+            self.multisig_transfers().root_hash(),
+            self.clawbacks().root_hash(),
+            self.attestations().root_hash(),
+            self.streams().root_hash(),
+            self.deposits().root_hash(),
+            self.assets().root_hash(),
+            self.escrows().root_hash(),
+            self.htlcs().root_hash(),
+            self.htlc_preimages().root_hash(),
+            self.fee_coupons().root_hash(),
+            self.invitations().root_hash(),
+            self.auctions().root_hash(),
+            self.held_transfers().root_hash(),
+            self.wallet_groups().root_hash(),
+            self.sweep_rules().root_hash(),
+            self.tx_results().root_hash(),
+            self.queued_transfers().root_hash(),
+            self.draws().root_hash(),
+            self.locked_totals().root_hash(),
+            self.sweep_candidates().root_hash(),
+            self.index_roots().root_hash(),
+            self.block_payments().root_hash(),
+            self.transfer_queue_height().hash(),
+            self.block_payments_height().hash(),
+            self.treasury_outflow_state().hash(),
+            self.issuance_state().hash(),
+            self.burned().hash(),
+            self.minted().hash(),
+            self.fee_pool().hash(),
+            self.base_fee_state().hash(),
+            self.tick_height().hash(),
+        ]
+    }
+
+    /// Root hashes of the non-empty tables kept per key, keyed by the hash of
+    /// the table name and the key as computed by `index_root_key`. The heights
+    /// in `scheduled_heights` are exactly those with scheduled actions, so the
+    /// roots of `scheduled_actions` cover them as well.
+    pub fn index_roots(&self) -> ProofMapIndex<&T, Hash, Hash> {
+        ProofMapIndex::new("cryptocurrency.index_roots", &self.view)
+    }
+
+    /// Proof of the state of a wallet, or of its absence, against the state hash
+    /// of the latest committed block.
+    pub fn wallet_proof(&self, pub_key: &PublicKey) -> WalletProof {
+        WalletProof {
+            to_table: CoreSchema::new(&self.view).get_proof_to_service_table(SERVICE_ID, 0),
+            to_wallet: self.wallets().get_proof(pub_key),
+        }
+    }
+
+    /// Get a separate wallet from the storage.
+    pub fn wallet(&self, pub_key: &PublicKey) -> Option<Wallet> {
+        self.wallets().get(pub_key)
+    }
+
+    /// Heights of the blocks in which the state of the wallet changed.
+    pub fn wallet_checkpoint_heights(&self, pub_key: &PublicKey) -> ProofListIndex<&T, u64> {
+        ProofListIndex::with_prefix("cryptocurrency.wallet_checkpoint_heights", pub_key, &self.view)
+    }
+
+    /// States of the wallet after the blocks listed by `wallet_checkpoint_heights`.
+    pub fn wallet_checkpoints(&self, pub_key: &PublicKey) -> ProofListIndex<&T, Wallet> {
+        ProofListIndex::with_prefix("cryptocurrency.wallet_checkpoints", pub_key, &self.view)
+    }
+
+    /// State of the wallet after the block at the given height, or `None` if the
+    /// wallet did not exist then.
+    pub fn wallet_at(&self, pub_key: &PublicKey, height: u64) -> Option<Wallet> {
+        let heights = self.wallet_checkpoint_heights(pub_key);
+        // Find the number of checkpoints made at or before the height.
+        let (mut low, mut high) = (0, heights.len());
+        while low < high {
+            let middle = (low + high) / 2;
+            if heights.get(middle).unwrap() <= height {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        if low == 0 {
+            None
+        } else {
+            self.wallet_checkpoints(pub_key).get(low - 1)
+        }
+    }
+
+    /// Transfers with locked funds awaiting their execution height, keyed by
+    /// the hash of the `TxDeferredTransfer` transaction.
+    pub fn deferred_transfers(&self) -> ProofMapIndex<&T, Hash, DeferredTransfer> {
+        ProofMapIndex::new("cryptocurrency.deferred_transfers", &self.view)
+    }
+
+    /// Transfers whose coins are not yet spendable by the receiver, keyed by
+    /// the hash of the `TxLockedTransfer` transaction.
+    pub fn locked_transfers(&self) -> ProofMapIndex<&T, Hash, LockedTransfer> {
+        ProofMapIndex::new("cryptocurrency.locked_transfers", &self.view)
+    }
+
+    /// Totals of the coins locked for the receivers of locked transfers, keyed
+    /// by the receiver.
+    pub fn locked_totals(&self) -> ProofMapIndex<&T, PublicKey, u64> {
+        ProofMapIndex::new("cryptocurrency.locked_totals", &self.view)
+    }
+
+    /// Coins transferred to a wallet that are still locked.
+    pub fn locked_balance(&self, pub_key: &PublicKey) -> u64 {
+        self.locked_totals().get(pub_key).unwrap_or(0)
+    }
+
+    /// Activity counters of wallets.
+    pub fn activity(&self) -> ProofMapIndex<&T, PublicKey, WalletActivity> {
+        ProofMapIndex::new("cryptocurrency.activity", &self.view)
+    }
+
+    /// Loyalty points of wallets.
+    pub fn loyalty_points(&self) -> ProofMapIndex<&T, PublicKey, u64> {
+        ProofMapIndex::new("cryptocurrency.loyalty_points", &self.view)
+    }
+
+    /// Promotional balances of wallets.
+    pub fn promotional_balances(&self) -> ProofMapIndex<&T, PublicKey, PromotionalBalance> {
+        ProofMapIndex::new("cryptocurrency.promotional_balances", &self.view)
+    }
+
+    /// Promotional coins of a wallet that can be spent at the current height.
+    pub fn promotional_balance(&self, pub_key: &PublicKey) -> u64 {
+        match self.promotional_balances().get(pub_key) {
+            Some(ref promo) if promo.expires_at() > self.height() => promo.amount(),
+            _ => 0,
+        }
+    }
+
+    /// Coins a wallet can spend at the current height: the main balance and
+    /// the unexpired promotional balance.
+    pub fn spendable_balance(&self, pub_key: &PublicKey) -> u64 {
+        self.wallet(pub_key).map_or(0, |wallet| {
+            wallet.balance().saturating_add(self.promotional_balance(pub_key))
+        })
+    }
+
+    /// Check whether a wallet exists, is not closed and can be credited `amount`
+    /// coins without overflowing its balance.
+    pub fn can_credit(&self, pub_key: &PublicKey, amount: u64) -> bool {
+        self.wallet(pub_key).map_or(false, |wallet| {
+            !wallet.closed() && wallet.balance().checked_add(amount).is_some()
+        })
+    }
+
+    /// Beneficiaries of closed wallets.
+    pub fn beneficiaries(&self) -> ProofMapIndex<&T, PublicKey, PublicKey> {
+        ProofMapIndex::new("cryptocurrency.beneficiaries", &self.view)
+    }
+
+    /// Wallet receiving the coins credited to the given one: the wallet itself,
+    /// or the beneficiary of a closed wallet. A beneficiary is open when the
+    /// wallet is closed, so the chain of beneficiaries ends at an open wallet.
+    pub fn payee(&self, pub_key: &PublicKey) -> PublicKey {
+        let beneficiaries = self.beneficiaries();
+        let mut payee = *pub_key;
+        while let Some(beneficiary) = beneficiaries.get(&payee) {
+            payee = beneficiary;
+        }
+        payee
+    }
+
+    /// Insurance fund payouts keyed by the hash of the proposing transaction.
+    pub fn insurance_payouts(&self) -> ProofMapIndex<&T, Hash, InsurancePayout> {
+        ProofMapIndex::new("cryptocurrency.insurance_payouts", &self.view)
+    }
+
+    /// Signers of multisignature wallets.
+    pub fn multisigs(&self) -> ProofMapIndex<&T, PublicKey, Multisig> {
+        ProofMapIndex::new("cryptocurrency.multisigs", &self.view)
+    }
+
+    /// Transfers from multisignature wallets keyed by the hash of the proposing
+    /// transaction.
+    pub fn multisig_transfers(&self) -> ProofMapIndex<&T, Hash, MultisigTransfer> {
+        ProofMapIndex::new("cryptocurrency.multisig_transfers", &self.view)
+    }
+
+    /// Clawbacks keyed by the hash of the clawed back transfer.
+    pub fn clawbacks(&self) -> ProofMapIndex<&T, Hash, Clawback> {
+        ProofMapIndex::new("cryptocurrency.clawbacks", &self.view)
+    }
+
+    /// Successfully executed transfer with the given hash.
+    pub fn committed_transfer(&self, tx_hash: &Hash) -> Option<TxTransfer> {
+        if self.tx_result(tx_hash) != Some(Ok(())) {
+            return None;
+        }
+        let raw = match CoreSchema::new(&self.view).transactions().get(tx_hash) {
+            Some(raw) => raw,
+            None => return None,
+        };
+        if raw.service_id() != SERVICE_ID || raw.message_type() != TX_TRANSFER_ID {
+            return None;
+        }
+        TxTransfer::from_raw(raw).ok()
+    }
+
+    /// Payments of a successfully executed transfer or batch transfer with
+    /// the given hash, as `(from, to, amount)`.
+    pub fn committed_payments(&self, tx_hash: &Hash) -> Vec<(PublicKey, PublicKey, u64)> {
+        if self.tx_result(tx_hash) != Some(Ok(())) {
+            return Vec::new();
+        }
+        let raw = match CoreSchema::new(&self.view).transactions().get(tx_hash) {
+            Some(raw) => raw,
+            None => return Vec::new(),
+        };
+        if raw.service_id() != SERVICE_ID {
+            return Vec::new();
+        }
+        match raw.message_type() {
+            TX_TRANSFER_ID => {
+                TxTransfer::from_raw(raw)
+                    .map(|tx| vec![(*tx.from(), *tx.to(), tx.amount())])
+                    .unwrap_or_default()
+            }
+            TX_BATCH_TRANSFER_ID => {
+                TxBatchTransfer::from_raw(raw)
+                    .map(|tx| {
+                        tx.payments()
+                            .iter()
+                            .map(|payment| (*tx.from(), *payment.to(), payment.amount()))
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Burns made with the given tag, in the order of execution.
+    pub fn burns_by_tag(&self, tag: &Hash) -> ProofListIndex<&T, BurnRecord> {
+        ProofListIndex::with_prefix("cryptocurrency.burns_by_tag", tag, &self.view)
+    }
+
+    /// Attestations of verified wallets.
+    pub fn attestations(&self) -> ProofMapIndex<&T, PublicKey, Attestation> {
+        ProofMapIndex::new("cryptocurrency.attestations", &self.view)
+    }
+
+    /// Wallets whose names contain the given trigram, as produced by `text::trigrams`.
+    pub fn name_trigrams(&self, trigram: &str) -> ProofMapIndex<&T, PublicKey, ()> {
+        let prefix = crypto::hash(trigram.as_bytes());
+        ProofMapIndex::with_prefix("cryptocurrency.name_trigrams", &prefix, &self.view)
+    }
+
+    /// Payment references registered by a wallet, with the registration heights.
+    pub fn payment_references(&self, payee: &PublicKey) -> ProofMapIndex<&T, Hash, u64> {
+        ProofMapIndex::with_prefix("cryptocurrency.payment_references", payee, &self.view)
+    }
+
+    /// Receipts of a wallet, in the order of execution.
+    pub fn receipts(&self, payee: &PublicKey) -> ProofListIndex<&T, Receipt> {
+        ProofListIndex::with_prefix("cryptocurrency.receipts", payee, &self.view)
+    }
+
+    /// Open payment streams keyed by the hashes of the transactions that opened them.
+    pub fn streams(&self) -> ProofMapIndex<&T, Hash, Stream> {
+        ProofMapIndex::new("cryptocurrency.streams", &self.view)
+    }
+
+    /// Refundable deposits keyed by the hashes of the transactions that made them.
+    pub fn deposits(&self) -> ProofMapIndex<&T, Hash, Deposit> {
+        ProofMapIndex::new("cryptocurrency.deposits", &self.view)
+    }
+
+    /// Issued assets keyed by the hashes of the transactions that created them.
+    pub fn assets(&self) -> ProofMapIndex<&T, Hash, Asset> {
+        ProofMapIndex::new("cryptocurrency.assets", &self.view)
+    }
+
+    /// Non-zero asset balances of a wallet, keyed by the asset.
+    pub fn asset_balances(&self, pub_key: &PublicKey) -> ProofMapIndex<&T, Hash, u64> {
+        ProofMapIndex::with_prefix("cryptocurrency.asset_balances", pub_key, &self.view)
+    }
+
+    /// Balance of a wallet in the given asset.
+    pub fn asset_balance(&self, pub_key: &PublicKey, asset: &Hash) -> u64 {
+        self.asset_balances(pub_key).get(asset).unwrap_or(0)
+    }
+
+    /// Open escrows keyed by the hashes of the transactions that made them.
+    pub fn escrows(&self) -> ProofMapIndex<&T, Hash, Escrow> {
+        ProofMapIndex::new("cryptocurrency.escrows", &self.view)
+    }
+
+    /// Open hashed time-lock contracts keyed by the hashes of the transactions
+    /// that made them.
+    pub fn htlcs(&self) -> ProofMapIndex<&T, Hash, Htlc> {
+        ProofMapIndex::new("cryptocurrency.htlcs", &self.view)
+    }
+
+    /// Preimages revealed by claiming hashed time-lock contracts, keyed by
+    /// the hash lock. The other party of a swap learns the preimage here.
+    pub fn htlc_preimages(&self) -> ProofMapIndex<&T, Hash, Hash> {
+        ProofMapIndex::new("cryptocurrency.htlc_preimages", &self.view)
+    }
+
+    /// Fee coupons keyed by the hashes of their codes.
+    pub fn fee_coupons(&self) -> ProofMapIndex<&T, Hash, FeeCoupon> {
+        ProofMapIndex::new("cryptocurrency.fee_coupons", &self.view)
+    }
+
+    /// Sponsored invitations keyed by the hashes of their codes.
+    pub fn invitations(&self) -> ProofMapIndex<&T, Hash, Invitation> {
+        ProofMapIndex::new("cryptocurrency.invitations", &self.view)
+    }
+
+    /// Auctions keyed by the hashes of the transactions that created them.
+    pub fn auctions(&self) -> ProofMapIndex<&T, Hash, Auction> {
+        ProofMapIndex::new("cryptocurrency.auctions", &self.view)
+    }
+
+    /// Transfers put on hold by the velocity rules, keyed by the hashes of the
+    /// transactions making them or by `batch_payment_id` for batch transfers.
+    pub fn held_transfers(&self) -> ProofMapIndex<&T, Hash, HeldTransfer> {
+        ProofMapIndex::new("cryptocurrency.held_transfers", &self.view)
+    }
+
+    /// Receivers of transfers made by a wallet, with the last activity period
+    /// in which the wallet sent them a transfer.
+    pub fn counterparties(&self, from: &PublicKey) -> ProofMapIndex<&T, PublicKey, u64> {
+        ProofMapIndex::with_prefix("cryptocurrency.counterparties", from, &self.view)
+    }
+
+    /// Current activity period.
+    pub fn activity_period(&self) -> u64 {
+        self.height() / ::std::cmp::max(self.config().activity_period, 1)
+    }
+
+    /// Check whether a transfer trips the configured velocity rules.
+    pub fn is_held(&self, from: &PublicKey, to: &PublicKey, amount: u64) -> bool {
+        let rules = match self.config().velocity {
+            Some(rules) => rules,
+            None => return false,
+        };
+        let period = self.activity_period();
+        let transfers = match self.activity().get(from) {
+            Some(ref activity) if activity.period() == period => activity.sent(),
+            _ => 0,
+        };
+        let counterparties = self.counterparties(from);
+        let new_counterparty = counterparties.get(to) != Some(period);
+        let count = counterparties
+            .values()
+            .filter(|last_period| *last_period == period)
+            .count() as u64;
+        rules.is_tripped(amount, transfers, count, new_counterparty)
+    }
+
+    /// Identifiers of the groups linked wallets belong to.
+    pub fn wallet_groups(&self) -> ProofMapIndex<&T, PublicKey, Hash> {
+        ProofMapIndex::new("cryptocurrency.wallet_groups", &self.view)
+    }
+
+    /// Wallets of a group, in the order of linking.
+    pub fn group_members(&self, group: &Hash) -> ProofListIndex<&T, PublicKey> {
+        ProofListIndex::with_prefix("cryptocurrency.group_members", group, &self.view)
+    }
+
+    /// Session keys authorized by the owner of a wallet.
+    pub fn session_keys(&self, owner: &PublicKey) -> ProofMapIndex<&T, PublicKey, SessionKey> {
+        ProofMapIndex::with_prefix("cryptocurrency.session_keys", owner, &self.view)
+    }
+
+    /// Sweep rules keyed by the wallet they apply to.
+    pub fn sweep_rules(&self) -> ProofMapIndex<&T, PublicKey, SweepRule> {
+        ProofMapIndex::new("cryptocurrency.sweep_rules", &self.view)
+    }
+
+    /// Amount to sweep from the wallet under its rule, or `None` if the balance
+    /// does not exceed the threshold or the coins cannot be moved.
+    pub fn sweep_excess(&self, pub_key: &PublicKey, rule: &SweepRule) -> Option<u64> {
+        let wallet = self.wallet(pub_key)?;
+        if wallet.frozen() || wallet.closed() || wallet.balance() <= rule.threshold() {
+            return None;
+        }
+        let excess = wallet.balance() - rule.threshold();
+        match self.wallet(rule.cold_wallet()) {
+            Some(ref cold) if !cold.closed() && cold.balance().checked_add(excess).is_some() => {
+                Some(excess)
+            }
+            _ => None,
+        }
+    }
+
+    /// Allowances granted by the owner of a wallet, keyed by the spender.
+    pub fn allowances(&self, owner: &PublicKey) -> ProofMapIndex<&T, PublicKey, u64> {
+        ProofMapIndex::with_prefix("cryptocurrency.allowances", owner, &self.view)
+    }
+
+    /// Results of executed service transactions: `0` for success, otherwise
+    /// the code of the error.
+    pub fn tx_results(&self) -> ProofMapIndex<&T, Hash, u8> {
+        ProofMapIndex::new("cryptocurrency.tx_results", &self.view)
+    }
+
+    /// Result of the executed service transaction with the given hash, or `None`
+    /// if the transaction has not been executed.
+    pub fn tx_result(&self, tx_hash: &Hash) -> Option<Result<(), Error>> {
+        self.tx_results().get(tx_hash).map(|code| match Error::from_code(code) {
+            Some(err) => Err(err),
+            None => Ok(()),
+        })
+    }
+
+    /// Transfers waiting for the execution of the given transaction.
+    pub fn dependent_transfers(&self, tx_hash: &Hash) -> ProofListIndex<&T, TxTransfer> {
+        ProofListIndex::with_prefix("cryptocurrency.dependent_transfers", tx_hash, &self.view)
+    }
+
+    /// Hashes of the queued transfers mapped to the hashes of the transactions
+    /// they wait for.
+    pub fn queued_transfers(&self) -> ProofMapIndex<&T, Hash, Hash> {
+        ProofMapIndex::new("cryptocurrency.queued_transfers", &self.view)
+    }
+
+    /// Height of the block the queued transfers belong to.
+    pub fn transfer_queue_height(&self) -> Entry<&T, u64> {
+        Entry::new("cryptocurrency.transfer_queue_height", &self.view)
+    }
+
+    /// Payments made in the block at `block_payments_height`, in the order
+    /// they were made. The list is cleared by the first payment of a later block.
+    pub fn block_payments(&self) -> ProofListIndex<&T, BlockPayment> {
+        ProofListIndex::new("cryptocurrency.block_payments", &self.view)
+    }
+
+    pub fn block_payments_height(&self) -> Entry<&T, u64> {
+        Entry::new("cryptocurrency.block_payments_height", &self.view)
+    }
+
+    /// Total of coins granted from the treasury in the last epoch with grants.
+    pub fn treasury_outflow_state(&self) -> Entry<&T, EpochTotal> {
+        Entry::new("cryptocurrency.treasury_outflow", &self.view)
+    }
+
+    /// Total of coins granted from the treasury in the current epoch, if epoch
+    /// caps are configured.
+    pub fn treasury_outflow(&self) -> u64 {
+        let caps = match self.config().caps {
+            Some(caps) => caps,
+            None => return 0,
+        };
+        match self.treasury_outflow_state().get() {
+            Some(ref total) if total.epoch() == caps.epoch(self.height()) => total.amount(),
+            _ => 0,
+        }
+    }
+
+    /// Total of coins issued in the last epoch with issuance.
+    pub fn issuance_state(&self) -> Entry<&T, EpochTotal> {
+        Entry::new("cryptocurrency.issuance", &self.view)
+    }
+
+    /// Total of coins issued in the current epoch, if epoch caps are configured.
+    pub fn issuance(&self) -> u64 {
+        let caps = match self.config().caps {
+            Some(caps) => caps,
+            None => return 0,
+        };
+        match self.issuance_state().get() {
+            Some(ref total) if total.epoch() == caps.epoch(self.height()) => total.amount(),
+            _ => 0,
+        }
+    }
+
+    /// Total of coins burned by their owners.
+    pub fn burned(&self) -> Entry<&T, u64> {
+        Entry::new("cryptocurrency.burned", &self.view)
+    }
+
+    /// Total of coins ever created: the initial balances of wallets created
+    /// without an invitation and the issued coins. Unlike the issuance of
+    /// an epoch, it never resets. Every other credit moves existing coins, so
+    /// keeping this total within `u64` keeps the sum of all balances within it.
+    pub fn minted(&self) -> Entry<&T, u64> {
+        Entry::new("cryptocurrency.minted", &self.view)
+    }
+
+    /// Transfer fees collected for the validators and not distributed yet.
+    pub fn fee_pool(&self) -> Entry<&T, u64> {
+        Entry::new("cryptocurrency.fee_pool", &self.view)
+    }
+
+    /// Height of the block in which `TxTick` last performed the scheduled actions.
+    pub fn tick_height(&self) -> Entry<&T, u64> {
+        Entry::new("cryptocurrency.tick_height", &self.view)
+    }
+
+    /// Validators receiving shares of the fee pool: those whose service keys
+    /// have wallets.
+    pub fn fee_recipients(&self) -> Vec<PublicKey> {
+        CoreSchema::new(&self.view)
+            .actual_configuration()
+            .validator_keys
+            .iter()
+            .map(|keys| keys.service_key)
+            .filter(|key| self.wallet(key).is_some())
+            .collect()
+    }
+
+    /// Share of the fee pool each of the `fee_recipients` is due.
+    pub fn fee_share(&self) -> u64 {
+        let recipients = self.fee_recipients().len() as u64;
+        if recipients == 0 {
+            return 0;
+        }
+        self.fee_pool().get().unwrap_or(0) / recipients
+    }
+
+    /// The last computed base fee.
+    pub fn base_fee_state(&self) -> Entry<&T, BaseFee> {
+        Entry::new("cryptocurrency.base_fee", &self.view)
+    }
+
+    /// Minimum fee accepted for transfers at the current height. It is computed
+    /// from the last stored base fee and the number of transactions in the blocks
+    /// committed since then, looking back at most `BASE_FEE_LOOKBACK` blocks.
+    pub fn base_fee(&self) -> u64 {
+        let market = match self.config().fee_market {
+            Some(market) => market,
+            None => return 0,
+        };
+        let current = self.height();
+        let oldest = current.saturating_sub(BASE_FEE_LOOKBACK);
+        let (mut height, mut fee) = match self.base_fee_state().get() {
+            Some(ref state) if state.height() >= oldest => (state.height(), state.fee()),
+            _ => (oldest, market.min_base_fee),
+        };
+
+        let core_schema = CoreSchema::new(&self.view);
+        while height < current {
+            let block_txs = core_schema.block_txs(Height(height)).len();
+            fee = market.next_base_fee(fee, block_txs);
+            height += 1;
+        }
+        ::std::cmp::max(fee, market.min_base_fee)
+    }
+
+    /// Insurance fund wallet and the levy charged for transferring the given amount,
+    /// if the levy is configured, non-zero and the fund wallet exists.
+    pub fn insurance_levy(&self, amount: u64) -> Option<(PublicKey, u64)> {
+        self.config().insurance.and_then(|insurance| {
+            let levy = insurance.levy(amount);
+            if levy > 0 && self.wallet(&insurance.fund).is_some() {
+                Some((insurance.fund, levy))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Amount withdrawn from the sender of a transfer: the transferred amount,
+    /// the fee and the insurance levy. Fails with `Error::BalanceOverflow` if
+    /// the sum does not fit into an amount.
+    pub fn transfer_cost(&self, amount: u64, fee: u64) -> Result<u64, Error> {
+        let levy = self.insurance_levy(amount).map_or(0, |(_, levy)| levy);
+        amount
+            .checked_add(fee)
+            .and_then(|cost| cost.checked_add(levy))
+            .ok_or(Error::BalanceOverflow)
+    }
+
+    /// Amount withdrawn from `from` by a batch transfer paying `amounts` with
+    /// the given fee: the amounts, the insurance levy of every payment and the
+    /// fee. The fee and the tier limit are checked as for a single transfer of
+    /// the total amount.
+    pub fn batch_transfer_cost(
+        &self,
+        from: &PublicKey,
+        amounts: &[u64],
+        fee: u64,
+    ) -> Result<u64, Error> {
+        let mut total = 0u64;
+        let mut cost = fee;
+        for &amount in amounts {
+            total = total.checked_add(amount).ok_or(Error::BalanceOverflow)?;
+            cost = cost.checked_add(self.transfer_cost(amount, 0)?).ok_or(
+                Error::BalanceOverflow,
+            )?;
+        }
+        check_transfer_fee(self, from, total, fee)?;
+        Ok(cost)
+    }
+
+    /// Actual configuration of the service.
+    pub fn config(&self) -> CurrencyConfig {
+        CoreSchema::new(&self.view)
+            .actual_configuration()
+            .services
+            .get(SERVICE_NAME)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Pagination parameters from the query string of the request.
+    fn page_request(req: &Request) -> Result<PageRequest, ApiError> {
+        PageRequest::from_query(req.url.query().unwrap_or(""))
+            .map_err(|e| ApiError::IncorrectRequest(Box::new(e)))
+    }
+
+    fn cursor_error() -> ApiError {
+        ApiError::IncorrectRequest(Box::new(PageRequestError::Cursor))
+    }
+
+    /// Height of the next block to be committed. During transaction execution
+    /// this is the height of the block the transaction is included into.
+    pub fn height(&self) -> u64 {
+        CoreSchema::new(&self.view).block_hashes_by_height().len()
+    }
+
+    /// Value of the randomness beacon at the given height, or `None` if the block
+    /// at this height is not committed yet.
+    ///
+    /// The value is derived from the hash of the block, so it is the same on every
+    /// node and cannot be predicted before the block is committed. The proposer of
+    /// the block can influence it by choosing the transactions to include, so it
+    /// must not secure large stakes.
+    pub fn beacon(&self, height: u64) -> Option<Hash> {
+        let hashes = CoreSchema::new(&self.view).block_hashes_by_height();
+        hashes.get(height).map(|block_hash| {
+            let mut data = b"cryptocurrency.beacon".to_vec();
+            data.extend_from_slice(block_hash.as_ref());
+            crypto::hash(&data)
+        })
+    }
+
+    /// Lottery draws keyed by the hashes of the transactions that created them.
+    pub fn draws(&self) -> ProofMapIndex<&T, Hash, Draw> {
+        ProofMapIndex::new("cryptocurrency.draws", &self.view)
+    }
+
+    /// Entrants of a lottery draw, in the order of entering.
+    pub fn draw_entrants(&self, draw: &Hash) -> ProofListIndex<&T, PublicKey> {
+        ProofListIndex::with_prefix("cryptocurrency.draw_entrants", draw, &self.view)
+    }
+
+    /// Heights with scheduled actions not yet performed, in ascending order.
+    pub fn scheduled_heights(&self) -> KeySetIndex<&T, u64> {
+        KeySetIndex::new("cryptocurrency.scheduled_heights", &self.view)
+    }
+
+    /// Actions scheduled at the given height. An action is skipped when it is
+    /// due if it has been cancelled, rescheduled or performed since.
+    pub fn scheduled_actions(&self, height: u64) -> ProofListIndex<&T, ScheduledAction> {
+        ProofListIndex::with_prefix("cryptocurrency.scheduled_actions", &height, &self.view)
+    }
+
+    /// Wallets with sweep rules whose balance has exceeded the threshold since
+    /// the last sweep.
+    pub fn sweep_candidates(&self) -> ProofMapIndex<&T, PublicKey, ()> {
+        ProofMapIndex::new("cryptocurrency.sweep_candidates", &self.view)
+    }
+
+    /// Check whether there are scheduled actions to perform at the given height.
+    pub fn has_scheduled(&self, height: u64) -> bool {
+        self.scheduled_heights().iter().next().map_or(
+            false,
+            |due| due <= height,
+        ) ||
+            self.sweep_candidates().keys().any(|pub_key| {
+                self.sweep_rules().get(&pub_key).map_or(false, |rule| {
+                    self.sweep_excess(&pub_key, &rule).is_some()
+                })
+            }) || self.fee_share() > 0 ||
+            self.queued_transfers().keys().next().is_some()
+    }
+}
+
+/// Mutable access to the data.
+impl<'a> CurrencySchema<&'a mut Fork> {
+    pub fn wallets_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, Wallet> {
+        ProofMapIndex::new("cryptocurrency.wallets", &mut self.view)
+    }
+
+    pub fn index_roots_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Hash> {
+        ProofMapIndex::new("cryptocurrency.index_roots", &mut self.view)
+    }
+
+    /// Store the root hash of a table kept per key in `index_roots`, removing
+    /// the entry once the table is empty.
+    fn update_index_root<K: StorageKey>(&mut self, name: &str, key: &K, root: Hash) {
+        let key = index_root_key(name, key);
+        if root == Hash::zero() {
+            self.index_roots_mut().remove(&key);
+        } else {
+            self.index_roots_mut().put(&key, root);
+        }
+    }
+
+    pub fn wallet_checkpoint_heights_mut(
+        &mut self,
+        pub_key: &PublicKey,
+    ) -> ProofListIndex<&mut Fork, u64> {
+        ProofListIndex::with_prefix(
+            "cryptocurrency.wallet_checkpoint_heights",
+            pub_key,
+            &mut self.view,
+        )
+    }
+
+    pub fn wallet_checkpoints_mut(
+        &mut self,
+        pub_key: &PublicKey,
+    ) -> ProofListIndex<&mut Fork, Wallet> {
+        ProofListIndex::with_prefix("cryptocurrency.wallet_checkpoints", pub_key, &mut self.view)
+    }
+
+    /// Store the state of a wallet and checkpoint it as the state after the
+    /// current block, so that it can be queried with `wallet_at`.
+    pub fn put_wallet(&mut self, pub_key: &PublicKey, wallet: Wallet) {
+        let height = self.height();
+        if self.wallet_checkpoint_heights(pub_key).last() == Some(height) {
+            let last = self.wallet_checkpoints(pub_key).len() - 1;
+            self.wallet_checkpoints_mut(pub_key).set(last, wallet.clone());
+        } else {
+            self.wallet_checkpoint_heights_mut(pub_key).push(height);
+            self.wallet_checkpoints_mut(pub_key).push(wallet.clone());
+            let root = self.wallet_checkpoint_heights(pub_key).root_hash();
+            self.update_index_root("cryptocurrency.wallet_checkpoint_heights", pub_key, root);
+        }
+        let root = self.wallet_checkpoints(pub_key).root_hash();
+        self.update_index_root("cryptocurrency.wallet_checkpoints", pub_key, root);
+        if let Some(rule) = self.sweep_rules().get(pub_key) {
+            if wallet.balance() > rule.threshold() {
+                self.sweep_candidates_mut().put(pub_key, ());
+            }
+        }
+        self.wallets_mut().put(pub_key, wallet);
+    }
+
+    pub fn deferred_transfers_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, DeferredTransfer> {
+        ProofMapIndex::new("cryptocurrency.deferred_transfers", &mut self.view)
+    }
+
+    pub fn locked_transfers_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, LockedTransfer> {
+        ProofMapIndex::new("cryptocurrency.locked_transfers", &mut self.view)
+    }
+
+    pub fn locked_totals_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, u64> {
+        ProofMapIndex::new("cryptocurrency.locked_totals", &mut self.view)
+    }
+
+    pub fn promotional_balances_mut(
+        &mut self,
+    ) -> ProofMapIndex<&mut Fork, PublicKey, PromotionalBalance> {
+        ProofMapIndex::new("cryptocurrency.promotional_balances", &mut self.view)
+    }
+
+    /// Withdraw coins from a wallet, spending its promotional balance first.
+    /// Returns the updated wallet, or `None` if the wallet does not exist
+    /// or does not have enough coins.
+    pub fn debit(&mut self, pub_key: &PublicKey, amount: u64) -> Option<Wallet> {
+        let wallet = self.wallet(pub_key)?;
+        let promo = self.promotional_balance(pub_key);
+        if wallet.balance().saturating_add(promo) < amount {
+            return None;
+        }
+
+        let from_promo = ::std::cmp::min(promo, amount);
+        if from_promo > 0 {
+            let old = self.promotional_balances().get(pub_key).unwrap();
+            let rest = old.amount() - from_promo;
+            if rest > 0 {
+                let promo = PromotionalBalance::new(rest, old.expires_at());
+                self.promotional_balances_mut().put(pub_key, promo);
+            } else {
+                self.promotional_balances_mut().remove(pub_key);
+            }
+        }
+        let wallet = wallet.decrease(amount - from_promo);
+        self.put_wallet(pub_key, wallet.clone());
+        Some(wallet)
+    }
+
+    pub fn beneficiaries_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, PublicKey> {
+        ProofMapIndex::new("cryptocurrency.beneficiaries", &mut self.view)
+    }
+
+    /// Credit coins to a wallet, or to its `payee` if the wallet is closed. Returns
+    /// the updated wallet, or `None` without changing the state if the wallet
+    /// does not exist or its balance would overflow.
+    pub fn credit(&mut self, pub_key: &PublicKey, amount: u64) -> Option<Wallet> {
+        let payee = self.payee(pub_key);
+        let wallet = self.wallet(&payee)?.increase(amount)?;
+        self.put_wallet(&payee, wallet.clone());
+        Some(wallet)
+    }
+
+    /// Credit coins taken from `from` earlier, such as locked or held coins,
+    /// to `to`, or return them to `from` if `to` is closed or cannot receive
+    /// them. Returns the key of the credited wallet, or `None` if neither can
+    /// receive the coins.
+    pub fn credit_or_return(
+        &mut self,
+        from: &PublicKey,
+        to: &PublicKey,
+        amount: u64,
+    ) -> Option<PublicKey> {
+        let receiver = if self.can_credit(to, amount) { to } else { from };
+        self.credit(receiver, amount).map(|wallet| *wallet.pub_key())
+    }
+
+    pub fn insurance_payouts_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, InsurancePayout> {
+        ProofMapIndex::new("cryptocurrency.insurance_payouts", &mut self.view)
+    }
+
+    /// Record an approval of an insurance payout by a governor and make the payout
+    /// if the approval threshold is reached and the fund has enough coins.
+    fn approve_insurance_payout(
+        &mut self,
+        payout_hash: &Hash,
+        payout: InsurancePayout,
+        governor: &PublicKey,
+    ) {
+        let insurance = match self.config().insurance {
+            Some(insurance) => insurance,
+            None => return,
+        };
+        let index = match insurance.governor_index(governor) {
+            Some(index) if index < 64 => index,
+            _ => return,
+        };
+        let approvals = payout.approvals() | (1 << index);
+        let mut payout = InsurancePayout::new(
+            payout.to(),
+            payout.amount(),
+            approvals,
+            payout.executed_at(),
+        );
+
+        if payout.approval_count() >= insurance.threshold &&
+            self.can_credit(payout.to(), payout.amount())
+        {
+            if self.debit(&insurance.fund, payout.amount()).is_some() {
+                let receiver = self.credit_or_return(&insurance.fund, payout.to(), payout.amount());
+                println!("Insurance payout to {:?}", receiver);
+                let height = self.height();
+                payout = InsurancePayout::new(payout.to(), payout.amount(), approvals, height);
+            }
+        }
+        self.insurance_payouts_mut().put(payout_hash, payout);
+    }
+
+    pub fn multisigs_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, Multisig> {
+        ProofMapIndex::new("cryptocurrency.multisigs", &mut self.view)
+    }
+
+    pub fn multisig_transfers_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, MultisigTransfer> {
+        ProofMapIndex::new("cryptocurrency.multisig_transfers", &mut self.view)
+    }
+
+    /// Record an approval of a multisignature transfer by a signer and make
+    /// the transfer if the approval threshold is reached and the wallet has
+    /// enough coins. A transfer held by the velocity rules counts as executed.
+    fn approve_multisig_transfer(
+        &mut self,
+        tx_hash: &Hash,
+        transfer: MultisigTransfer,
+        signer: &PublicKey,
+    ) {
+        let multisig = match self.multisigs().get(transfer.wallet()) {
+            Some(multisig) => multisig,
+            None => return,
+        };
+        let index = match multisig.signer_index(signer) {
+            Some(index) if index < 64 => index,
+            _ => return,
+        };
+        let approvals = transfer.approvals() | (1 << index);
+        let mut executed_at = transfer.executed_at();
+        let frozen = self.wallet(transfer.wallet()).map_or(true, |wallet| wallet.frozen());
+        if u64::from(approvals.count_ones()) >= multisig.threshold() && !frozen &&
+            self.wallet(transfer.to()).is_some()
+        {
+            let outcome = self.pay(
+                tx_hash,
+                transfer.wallet(),
+                transfer.to(),
+                transfer.amount(),
+                transfer.fee(),
+                &Hash::zero(),
+            );
+            if outcome == PaymentOutcome::Made {
+                self.append_history(transfer.wallet(), tx_hash);
+                self.append_history(transfer.to(), tx_hash);
+            }
+            if outcome != PaymentOutcome::Failed {
+                executed_at = self.height();
+            }
+        }
+        let transfer = MultisigTransfer::new(
+            transfer.wallet(),
+            transfer.to(),
+            transfer.amount(),
+            transfer.fee(),
+            approvals,
+            executed_at,
+        );
+        self.multisig_transfers_mut().put(tx_hash, transfer);
+    }
+
+    pub fn clawbacks_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Clawback> {
+        ProofMapIndex::new("cryptocurrency.clawbacks", &mut self.view)
+    }
+
+    /// Record an approval of a clawback by a governor and schedule the clawback
+    /// once a supermajority of governors has approved it.
+    fn approve_clawback(&mut self, transfer_hash: &Hash, clawback: Clawback, governor: &PublicKey) {
+        let config = match self.config().clawback {
+            Some(config) => config,
+            None => return,
+        };
+        let index = match config.governor_index(governor) {
+            Some(index) if index < 64 => index,
+            _ => return,
+        };
+        let approvals = clawback.approvals() | (1 << index);
+        let mut execute_at = clawback.execute_at();
+        if execute_at == 0 && u64::from(approvals.count_ones()) >= config.supermajority() {
+            execute_at = self.height() + config.delay;
+            self.schedule(execute_at, SCHEDULED_CLAWBACK, transfer_hash);
+        }
+        let clawback = Clawback::new(
+            clawback.proposer(),
+            clawback.reason(),
+            approvals,
+            execute_at,
+            clawback.executed_at(),
+            clawback.recovered(),
+            clawback.error_code(),
+        );
+        println!("Approve clawback of {:?}: {:?}", transfer_hash, clawback);
+        self.clawbacks_mut().put(transfer_hash, clawback);
+    }
+
+    /// Return what is left of a clawed back transfer to its sender. The receiver
+    /// may have spent a part of the transfer. The coins are taken from the
+    /// receiver as by `debit`, so the receiver must be able to pay them and the
+    /// sender, or its beneficiary, to receive them. Returns the recovered amount.
+    fn claw_back(&mut self, transfer_hash: &Hash) -> Result<u64, Error> {
+        let transfer = self.committed_transfer(transfer_hash).ok_or(
+            Error::TransferNotFound,
+        )?;
+        let recovered = ::std::cmp::min(transfer.amount(), self.spendable_balance(transfer.to()));
+        check_payer(&*self, transfer.to(), recovered, Error::ReceiverNotFound)?;
+        check_receiver(&*self, &self.payee(transfer.from()), recovered)?;
+        self.debit(transfer.to(), recovered);
+        self.credit(transfer.from(), recovered);
+        Ok(recovered)
+    }
+
+    pub fn burns_by_tag_mut(&mut self, tag: &Hash) -> ProofListIndex<&mut Fork, BurnRecord> {
+        ProofListIndex::with_prefix("cryptocurrency.burns_by_tag", tag, &mut self.view)
+    }
+
+    pub fn attestations_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, Attestation> {
+        ProofMapIndex::new("cryptocurrency.attestations", &mut self.view)
+    }
+
+    /// Set the verification badge of an existing wallet.
+    fn set_verified(&mut self, pub_key: &PublicKey, verified: bool) {
+        let wallet = self.wallet(pub_key).unwrap().set_verified(verified);
+        self.put_wallet(pub_key, wallet);
+    }
+
+    pub fn wallet_history_mut(&mut self, pub_key: &PublicKey) -> ProofListIndex<&mut Fork, Hash> {
+        ProofListIndex::with_prefix("cryptocurrency.wallet_history", pub_key, &mut self.view)
+    }
+
+    /// Append a transaction to the history of an existing wallet.
+    pub fn append_history(&mut self, pub_key: &PublicKey, tx_hash: &Hash) {
+        let (len, root) = {
+            let mut history = self.wallet_history_mut(pub_key);
+            history.push(*tx_hash);
+            (history.len(), history.root_hash())
+        };
+        let wallet = self.wallet(pub_key).unwrap().set_history(len, &root);
+        self.put_wallet(pub_key, wallet);
+    }
+
+    pub fn name_trigrams_mut(
+        &mut self,
+        trigram: &str,
+    ) -> ProofMapIndex<&mut Fork, PublicKey, ()> {
+        let prefix = crypto::hash(trigram.as_bytes());
+        ProofMapIndex::with_prefix("cryptocurrency.name_trigrams", &prefix, &mut self.view)
+    }
+
+    /// Add a wallet name to the trigram index used to find similar names.
+    pub fn index_name(&mut self, pub_key: &PublicKey, name: &str) {
+        for trigram in text::trigrams(name) {
+            self.name_trigrams_mut(&trigram).put(pub_key, ());
+            self.update_trigram_root(&trigram);
+        }
+    }
+
+    /// Remove a former wallet name from the trigram index.
+    pub fn unindex_name(&mut self, pub_key: &PublicKey, name: &str) {
+        for trigram in text::trigrams(name) {
+            self.name_trigrams_mut(&trigram).remove(pub_key);
+            self.update_trigram_root(&trigram);
+        }
+    }
+
+    fn update_trigram_root(&mut self, trigram: &str) {
+        let root = self.name_trigrams(trigram).root_hash();
+        let prefix = crypto::hash(trigram.as_bytes());
+        self.update_index_root("cryptocurrency.name_trigrams", &prefix, root);
+    }
+
+    pub fn payment_references_mut(
+        &mut self,
+        payee: &PublicKey,
+    ) -> ProofMapIndex<&mut Fork, Hash, u64> {
+        ProofMapIndex::with_prefix("cryptocurrency.payment_references", payee, &mut self.view)
+    }
+
+    pub fn receipts_mut(&mut self, payee: &PublicKey) -> ProofListIndex<&mut Fork, Receipt> {
+        ProofListIndex::with_prefix("cryptocurrency.receipts", payee, &mut self.view)
+    }
+
+    /// Issue a receipt for a transfer if its reference is registered by the receiver.
+    pub fn issue_receipt(
+        &mut self,
+        tx_hash: &Hash,
+        from: &PublicKey,
+        to: &PublicKey,
+        amount: u64,
+        reference: &Hash,
+    ) {
+        if self.payment_references(to).contains(reference) {
+            let height = self.height();
+            let receipt = Receipt::new(reference, tx_hash, from, amount, height);
+            println!("Receipt of {:?}: {:?}", to, receipt);
+            self.receipts_mut(to).push(receipt);
+            let root = self.receipts(to).root_hash();
+            self.update_index_root("cryptocurrency.receipts", to, root);
+        }
+    }
+
+    pub fn deposits_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Deposit> {
+        ProofMapIndex::new("cryptocurrency.deposits", &mut self.view)
+    }
+
+    pub fn escrows_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Escrow> {
+        ProofMapIndex::new("cryptocurrency.escrows", &mut self.view)
+    }
+
+    /// Pay the coins of an escrow to the recipient if `release` is set, or
+    /// back to the sender otherwise, and close the escrow. The settling
+    /// transaction is recorded in the history of both wallets, unless the
+    /// payment is held by the velocity rules under the hash of the transaction.
+    pub fn settle_escrow(
+        &mut self,
+        escrow_hash: &Hash,
+        release: bool,
+        tx_hash: &Hash,
+    ) -> PaymentOutcome {
+        let escrow = self.escrows().get(escrow_hash).unwrap();
+        let outcome = if release {
+            self.pay_out(tx_hash, escrow.sender(), escrow.recipient(), escrow.amount(), false)
+        } else if self.credit(escrow.sender(), escrow.amount()).is_some() {
+            PaymentOutcome::Made
+        } else {
+            PaymentOutcome::Failed
+        };
+        println!("Settle escrow {:?}: {:?}", escrow_hash, outcome);
+        if outcome != PaymentOutcome::Held {
+            self.append_history(escrow.sender(), tx_hash);
+            self.append_history(escrow.recipient(), tx_hash);
+        }
+        self.escrows_mut().remove(escrow_hash);
+        outcome
+    }
+
+    pub fn assets_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Asset> {
+        ProofMapIndex::new("cryptocurrency.assets", &mut self.view)
+    }
+
+    pub fn asset_balances_mut(
+        &mut self,
+        pub_key: &PublicKey,
+    ) -> ProofMapIndex<&mut Fork, Hash, u64> {
+        ProofMapIndex::with_prefix("cryptocurrency.asset_balances", pub_key, &mut self.view)
+    }
+
+    /// Move `amount` of the asset between wallets. The sender is expected to
+    /// have enough of the asset; zero balances are removed.
+    pub fn transfer_asset(
+        &mut self,
+        from: &PublicKey,
+        to: &PublicKey,
+        asset: &Hash,
+        amount: u64,
+    ) {
+        let sender = self.asset_balance(from, asset) - amount;
+        let receiver = self.asset_balance(to, asset) + amount;
+        {
+            let mut balances = self.asset_balances_mut(from);
+            if sender == 0 {
+                balances.remove(asset);
+            } else {
+                balances.put(asset, sender);
+            }
+        }
+        self.asset_balances_mut(to).put(asset, receiver);
+        self.update_asset_balances_root(from);
+        self.update_asset_balances_root(to);
+    }
+
+    fn update_asset_balances_root(&mut self, pub_key: &PublicKey) {
+        let root = self.asset_balances(pub_key).root_hash();
+        self.update_index_root("cryptocurrency.asset_balances", pub_key, root);
+    }
+
+    pub fn htlcs_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Htlc> {
+        ProofMapIndex::new("cryptocurrency.htlcs", &mut self.view)
+    }
+
+    pub fn htlc_preimages_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Hash> {
+        ProofMapIndex::new("cryptocurrency.htlc_preimages", &mut self.view)
+    }
+
+    /// Pay the coins of a hashed time-lock contract to the recipient if `claim`
+    /// is set, or back to the sender otherwise, and close the contract. The
+    /// settling transaction is recorded in the history of both wallets, unless
+    /// the payment is held by the velocity rules under the hash of the transaction.
+    pub fn settle_htlc(&mut self, htlc_hash: &Hash, claim: bool, tx_hash: &Hash) -> PaymentOutcome {
+        let htlc = self.htlcs().get(htlc_hash).unwrap();
+        let outcome = if claim {
+            self.pay_out(tx_hash, htlc.sender(), htlc.recipient(), htlc.amount(), false)
+        } else if self.credit(htlc.sender(), htlc.amount()).is_some() {
+            PaymentOutcome::Made
+        } else {
+            PaymentOutcome::Failed
+        };
+        println!("Settle HTLC {:?}: {:?}", htlc_hash, outcome);
+        if outcome != PaymentOutcome::Held {
+            self.append_history(htlc.sender(), tx_hash);
+            self.append_history(htlc.recipient(), tx_hash);
+        }
+        self.htlcs_mut().remove(htlc_hash);
+        outcome
+    }
+
+    pub fn invitations_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Invitation> {
+        ProofMapIndex::new("cryptocurrency.invitations", &mut self.view)
+    }
+
+    pub fn fee_coupons_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, FeeCoupon> {
+        ProofMapIndex::new("cryptocurrency.fee_coupons", &mut self.view)
+    }
+
+    pub fn draws_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Draw> {
+        ProofMapIndex::new("cryptocurrency.draws", &mut self.view)
+    }
+
+    pub fn draw_entrants_mut(&mut self, draw: &Hash) -> ProofListIndex<&mut Fork, PublicKey> {
+        ProofListIndex::with_prefix("cryptocurrency.draw_entrants", draw, &mut self.view)
+    }
+
+    pub fn streams_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Stream> {
+        ProofMapIndex::new("cryptocurrency.streams", &mut self.view)
+    }
+
+    /// Pay out the coins accrued by a stream to its receiver. The stream is
+    /// removed once its budget is paid out in full. The coins stay in the stream
+    /// if the receiver cannot receive them. A payment tripping the velocity rules
+    /// is held under the hash of the withdrawing transaction.
+    pub fn withdraw_stream(&mut self, stream_hash: &Hash, tx_hash: &Hash) -> PaymentOutcome {
+        let stream = self.streams().get(stream_hash).unwrap();
+        let mut amount = stream.available(self.height());
+        let mut outcome = PaymentOutcome::Failed;
+        if amount > 0 && self.can_credit(stream.to(), amount) {
+            outcome = self.pay_out(tx_hash, stream.from(), stream.to(), amount, false);
+            println!("Withdraw from stream {:?}: {:?}", stream_hash, outcome);
+        } else {
+            amount = 0;
+        }
+
+        let withdrawn = stream.withdrawn() + amount;
+        if withdrawn == stream.budget() {
+            self.streams_mut().remove(stream_hash);
+        } else {
+            let stream = Stream::new(
+                stream.from(),
+                stream.to(),
+                stream.rate(),
+                stream.budget(),
+                stream.start(),
+                withdrawn,
+            );
+            self.streams_mut().put(stream_hash, stream);
+        }
+        outcome
+    }
+
+    pub fn auctions_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Auction> {
+        ProofMapIndex::new("cryptocurrency.auctions", &mut self.view)
+    }
+
+    pub fn held_transfers_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, HeldTransfer> {
+        ProofMapIndex::new("cryptocurrency.held_transfers", &mut self.view)
+    }
+
+    pub fn counterparties_mut(
+        &mut self,
+        from: &PublicKey,
+    ) -> ProofMapIndex<&mut Fork, PublicKey, u64> {
+        ProofMapIndex::with_prefix("cryptocurrency.counterparties", from, &mut self.view)
+    }
+
+    /// Transfer coins between wallets as `transfer` does, or hold the payment
+    /// under `hold_id` if it trips the velocity rules: the fee is collected and
+    /// the amount and the levy are locked until the payment is reviewed.
+    pub fn pay(
+        &mut self,
+        hold_id: &Hash,
+        from: &PublicKey,
+        to: &PublicKey,
+        amount: u64,
+        fee: u64,
+        reference: &Hash,
+    ) -> PaymentOutcome {
+        if !self.is_held(from, to, amount) {
+            return if self.transfer(from, to, amount, fee) {
+                PaymentOutcome::Made
+            } else {
+                PaymentOutcome::Failed
+            };
+        }
+        let levy = self.insurance_levy(amount).map_or(0, |(_, levy)| levy);
+        let cost = match self.transfer_cost(amount, fee) {
+            Ok(cost) => cost,
+            Err(_) => return PaymentOutcome::Failed,
+        };
+        if self.debit(from, cost).is_none() {
+            return PaymentOutcome::Failed;
+        }
+        self.collect_fee(fee);
+        let height = self.height();
+        let held = HeldTransfer::new(from, to, amount, levy, reference, height, false);
+        println!("Hold transfer: {:?}", held);
+        self.held_transfers_mut().put(hold_id, held);
+        PaymentOutcome::Held
+    }
+
+    /// Credit coins taken from the sender earlier, e.g. locked in an escrow, to
+    /// the receiver, or hold the payment under `hold_id` if it trips the velocity
+    /// rules. The coins are returned to the sender if the receiver cannot receive
+    /// them. A payment made is recorded in the activity of the wallets unless
+    /// `recorded` is set.
+    pub fn pay_out(
+        &mut self,
+        hold_id: &Hash,
+        from: &PublicKey,
+        to: &PublicKey,
+        amount: u64,
+        recorded: bool,
+    ) -> PaymentOutcome {
+        if self.is_held(from, to, amount) {
+            let height = self.height();
+            let held = HeldTransfer::new(from, to, amount, 0, &Hash::zero(), height, recorded);
+            println!("Hold transfer: {:?}", held);
+            self.held_transfers_mut().put(hold_id, held);
+            return PaymentOutcome::Held;
+        }
+        let credited = self.credit_or_return(from, to, amount);
+        if credited.as_ref() != Some(to) {
+            return PaymentOutcome::Failed;
+        }
+        if !recorded {
+            self.record_transfer(from, to, amount);
+        }
+        PaymentOutcome::Made
+    }
+
+    /// Complete or refund a held transfer and record the result of the transfer
+    /// transaction. The levy is refunded to the sender if the insurance fund is
+    /// no longer configured. A released transfer is refunded as well if the
+    /// receiver cannot receive the coins.
+    pub fn review_held_transfer(&mut self, tx_hash: &Hash, release: bool) {
+        let held = match self.held_transfers().get(tx_hash) {
+            Some(held) => held,
+            None => return,
+        };
+        self.held_transfers_mut().remove(tx_hash);
+        let receiver = if release {
+            self.credit_or_return(held.from(), held.to(), held.amount())
+        } else {
+            self.credit(held.from(), held.amount()).map(|_| *held.from())
+        };
+        if receiver.as_ref() != Some(held.to()) {
+            println!("Reject held transfer {:?}", tx_hash);
+            self.credit(held.from(), held.levy());
+            self.record_result(tx_hash, Err(Error::TransferRejected));
+            return;
+        }
+
+        println!("Release held transfer {:?}", tx_hash);
+        let fund = self.config().insurance.map(|insurance| insurance.fund);
+        let levy_to = fund.unwrap_or(*held.from());
+        if held.levy() > 0 {
+            self.credit_or_return(held.from(), &levy_to, held.levy());
+        }
+        if !held.recorded() {
+            self.record_transfer(held.from(), held.to(), held.amount());
+            self.append_history(held.from(), tx_hash);
+            self.append_history(held.to(), tx_hash);
+        }
+        self.issue_receipt(
+            tx_hash,
+            held.from(),
+            held.to(),
+            held.amount(),
+            held.reference(),
+        );
+        self.record_result(tx_hash, Ok(()));
+    }
+
+    pub fn wallet_groups_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, Hash> {
+        ProofMapIndex::new("cryptocurrency.wallet_groups", &mut self.view)
+    }
+
+    pub fn group_members_mut(&mut self, group: &Hash) -> ProofListIndex<&mut Fork, PublicKey> {
+        ProofListIndex::with_prefix("cryptocurrency.group_members", group, &mut self.view)
+    }
+
+    pub fn session_keys_mut(
+        &mut self,
+        owner: &PublicKey,
+    ) -> ProofMapIndex<&mut Fork, PublicKey, SessionKey> {
+        ProofMapIndex::with_prefix("cryptocurrency.session_keys", owner, &mut self.view)
+    }
+
+    fn update_session_keys_root(&mut self, owner: &PublicKey) {
+        let root = self.session_keys(owner).root_hash();
+        self.update_index_root("cryptocurrency.session_keys", owner, root);
+    }
+
+    pub fn sweep_rules_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, SweepRule> {
+        ProofMapIndex::new("cryptocurrency.sweep_rules", &mut self.view)
+    }
+
+    /// Move the excess balance of wallets with sweep rules to their cold wallets,
+    /// recording the sweeping transaction in the history of both wallets.
+    pub fn sweep(&mut self, tx_hash: &Hash) {
+        let candidates: Vec<PublicKey> = self.sweep_candidates().keys().collect();
+        self.sweep_candidates_mut().clear();
+        let due: Vec<(PublicKey, SweepRule, u64)> = candidates
+            .into_iter()
+            .filter_map(|pub_key| {
+                let rule = self.sweep_rules().get(&pub_key)?;
+                let excess = self.sweep_excess(&pub_key, &rule)?;
+                Some((pub_key, rule, excess))
+            })
+            .collect();
+        for (pub_key, rule, excess) in due {
+            let wallet = self.wallet(&pub_key).unwrap().decrease(excess);
+            println!("Sweep to the cold wallet: {:?}", wallet);
+            self.put_wallet(&pub_key, wallet);
+            self.credit_or_return(&pub_key, rule.cold_wallet(), excess);
+            self.record_transfer(&pub_key, rule.cold_wallet(), excess);
+            self.append_history(&pub_key, tx_hash);
+            self.append_history(rule.cold_wallet(), tx_hash);
+        }
+    }
+
+    pub fn allowances_mut(
+        &mut self,
+        owner: &PublicKey,
+    ) -> ProofMapIndex<&mut Fork, PublicKey, u64> {
+        ProofMapIndex::with_prefix("cryptocurrency.allowances", owner, &mut self.view)
+    }
+
+    fn update_allowances_root(&mut self, owner: &PublicKey) {
+        let root = self.allowances(owner).root_hash();
+        self.update_index_root("cryptocurrency.allowances", owner, root);
+    }
+
+    pub fn tx_results_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, u8> {
+        ProofMapIndex::new("cryptocurrency.tx_results", &mut self.view)
+    }
+
+    pub fn dependent_transfers_mut(
+        &mut self,
+        tx_hash: &Hash,
+    ) -> ProofListIndex<&mut Fork, TxTransfer> {
+        ProofListIndex::with_prefix("cryptocurrency.dependent_transfers", tx_hash, &mut self.view)
+    }
+
+    pub fn queued_transfers_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Hash> {
+        ProofMapIndex::new("cryptocurrency.queued_transfers", &mut self.view)
+    }
+
+    pub fn scheduled_heights_mut(&mut self) -> KeySetIndex<&mut Fork, u64> {
+        KeySetIndex::new("cryptocurrency.scheduled_heights", &mut self.view)
+    }
+
+    pub fn scheduled_actions_mut(
+        &mut self,
+        height: u64,
+    ) -> ProofListIndex<&mut Fork, ScheduledAction> {
+        ProofListIndex::with_prefix("cryptocurrency.scheduled_actions", &height, &mut self.view)
+    }
+
+    pub fn sweep_candidates_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, ()> {
+        ProofMapIndex::new("cryptocurrency.sweep_candidates", &mut self.view)
+    }
+
+    /// Schedule an action at the given height.
+    fn schedule(&mut self, height: u64, kind: u8, key: &Hash) {
+        self.scheduled_heights_mut().insert(height);
+        self.scheduled_actions_mut(height).push(ScheduledAction::new(kind, key));
+        let root = self.scheduled_actions(height).root_hash();
+        self.update_index_root("cryptocurrency.scheduled_actions", &height, root);
+    }
+
+    /// Remove and return the actions scheduled at or before the given height.
+    fn take_scheduled(&mut self, height: u64) -> Vec<ScheduledAction> {
+        let heights: Vec<u64> = self.scheduled_heights()
+            .iter()
+            .take_while(|&due| due <= height)
+            .collect();
+        let mut actions = Vec::new();
+        for due in heights {
+            actions.extend(self.scheduled_actions(due).iter());
+            self.scheduled_actions_mut(due).clear();
+            self.scheduled_heights_mut().remove(&due);
+            self.update_index_root("cryptocurrency.scheduled_actions", &due, Hash::zero());
+        }
+        actions
+    }
+
+    pub fn transfer_queue_height_mut(&mut self) -> Entry<&mut Fork, u64> {
+        Entry::new("cryptocurrency.transfer_queue_height", &mut self.view)
+    }
+
+    pub fn block_payments_mut(&mut self) -> ProofListIndex<&mut Fork, BlockPayment> {
+        ProofListIndex::new("cryptocurrency.block_payments", &mut self.view)
+    }
+
+    pub fn block_payments_height_mut(&mut self) -> Entry<&mut Fork, u64> {
+        Entry::new("cryptocurrency.block_payments_height", &mut self.view)
+    }
+
+    /// Store the result of an executed transaction and execute the transfers
+    /// waiting for it, then the transfers waiting for those, and so on. The
+    /// waiting transfers fail if the transaction they wait for has failed.
+    pub fn record_result(&mut self, tx_hash: &Hash, result: Result<(), Error>) {
+        self.fail_stale_transfers();
+        let mut executed = VecDeque::new();
+        executed.push_back((*tx_hash, result));
+        while let Some((tx_hash, result)) = executed.pop_front() {
+            let code = result.err().map_or(0, |err| err.code());
+            self.tx_results_mut().put(&tx_hash, code);
+
+            let dependents: Vec<TxTransfer> = self.dependent_transfers(&tx_hash).iter().collect();
+            if dependents.is_empty() {
+                continue;
+            }
+            self.dependent_transfers_mut(&tx_hash).clear();
+            self.update_index_root("cryptocurrency.dependent_transfers", &tx_hash, Hash::zero());
+            for tx in dependents {
+                self.queued_transfers_mut().remove(&tx.hash());
+                if let Some(result) = self.execute_queued(&tx) {
+                    executed.push_back((tx.hash(), result));
+                }
+            }
+        }
+    }
+
+    /// Queue a transfer until the transaction it depends on is executed in the
+    /// same block and charge the fee. Fails without changing the state if the
+    /// queue is full or the sender cannot pay the fee.
+    pub fn queue_transfer(&mut self, tx: &TxTransfer) -> Result<(), Error> {
+        self.fail_stale_transfers();
+        let limit = self.config().limits.max_queued_transfers;
+        if self.queued_transfers().keys().count() as u64 >= limit {
+            return Err(Error::DependencyPending);
+        }
+        check_payer(&*self, tx.from(), tx.fee(), Error::SenderNotFound)?;
+        self.debit(tx.from(), tx.fee());
+        self.collect_fee(tx.fee());
+        let height = self.height();
+        println!("Queue transfer until {:?} is executed", tx.depends_on());
+        self.dependent_transfers_mut(tx.depends_on()).push(tx.clone());
+        let root = self.dependent_transfers(tx.depends_on()).root_hash();
+        self.update_index_root("cryptocurrency.dependent_transfers", tx.depends_on(), root);
+        self.queued_transfers_mut().put(&tx.hash(), *tx.depends_on());
+        self.transfer_queue_height_mut().set(height);
+        Ok(())
+    }
+
+    /// Fail the transfers queued in an earlier block: their dependencies have
+    /// not been executed in the same block. The fees are not refunded.
+    pub fn fail_stale_transfers(&mut self) {
+        let height = self.height();
+        if self.transfer_queue_height().get().map_or(true, |queued| queued >= height) {
+            return;
+        }
+        let stale: Vec<(Hash, Hash)> = self.queued_transfers().iter().collect();
+        if stale.is_empty() {
+            return;
+        }
+        for (tx_hash, depends_on) in stale {
+            println!("Dependency {:?} of {:?} has not been executed", depends_on, tx_hash);
+            self.dependent_transfers_mut(&depends_on).clear();
+            self.update_index_root("cryptocurrency.dependent_transfers", &depends_on, Hash::zero());
+            self.tx_results_mut().put(&tx_hash, Error::DependencyPending.code());
+        }
+        self.queued_transfers_mut().clear();
+    }
+
+    /// Execute a queued transfer after the transaction it depends on. The fee
+    /// has been charged when the transfer was queued. Returns the result of
+    /// the transfer, or `None` if the transfer is held.
+    fn execute_queued(&mut self, tx: &TxTransfer) -> Option<Result<(), Error>> {
+        if let Some(Err(_)) = self.tx_result(tx.depends_on()) {
+            return Some(Err(Error::DependencyFailed));
+        }
+        if let Err(err) = check_transfer_funds(&*self, tx, 0) {
+            return Some(Err(err));
+        }
+        self.make_transfer(tx, 0)
+    }
+
+    /// Make a validated transfer charging the given fee, or hold it if it trips
+    /// the velocity rules. Returns the result of the transfer, or `None` if
+    /// the transfer is held.
+    pub fn make_transfer(&mut self, tx: &TxTransfer, fee: u64) -> Option<Result<(), Error>> {
+        let tx_hash = tx.hash();
+        match self.pay(&tx_hash, tx.from(), tx.to(), tx.amount(), fee, tx.reference()) {
+            PaymentOutcome::Made => {}
+            PaymentOutcome::Held => return None,
+            PaymentOutcome::Failed => return Some(Err(Error::InsufficientFunds)),
+        }
+        self.append_history(tx.from(), &tx_hash);
+        self.append_history(tx.to(), &tx_hash);
+        self.issue_receipt(&tx_hash, tx.from(), tx.to(), tx.amount(), tx.reference());
+        Some(Ok(()))
+    }
+
+    pub fn treasury_outflow_state_mut(&mut self) -> Entry<&mut Fork, EpochTotal> {
+        Entry::new("cryptocurrency.treasury_outflow", &mut self.view)
+    }
+
+    /// Add coins granted from the treasury to the total of the current epoch.
+    pub fn record_treasury_outflow(&mut self, amount: u64) {
+        if let Some(caps) = self.config().caps {
+            let epoch = caps.epoch(self.height());
+            let total = EpochTotal::new(epoch, self.treasury_outflow() + amount);
+            self.treasury_outflow_state_mut().set(total);
+        }
+    }
+
+    pub fn issuance_state_mut(&mut self) -> Entry<&mut Fork, EpochTotal> {
+        Entry::new("cryptocurrency.issuance", &mut self.view)
+    }
+
+    /// Add issued coins to the total of the current epoch.
+    pub fn record_issuance(&mut self, amount: u64) {
+        if let Some(caps) = self.config().caps {
+            let epoch = caps.epoch(self.height());
+            let total = EpochTotal::new(epoch, self.issuance() + amount);
+            self.issuance_state_mut().set(total);
+        }
+    }
+
+    pub fn burned_mut(&mut self) -> Entry<&mut Fork, u64> {
+        Entry::new("cryptocurrency.burned", &mut self.view)
+    }
+
+    /// Add burned coins to the burned total.
+    pub fn record_burn(&mut self, amount: u64) {
+        let burned = self.burned().get().unwrap_or(0);
+        self.burned_mut().set(burned + amount);
+    }
+
+    pub fn minted_mut(&mut self) -> Entry<&mut Fork, u64> {
+        Entry::new("cryptocurrency.minted", &mut self.view)
+    }
+
+    /// Add created coins to the total of minted coins. The total is expected
+    /// to be checked with `check_mint` first.
+    pub fn record_mint(&mut self, amount: u64) {
+        let minted = self.minted().get().unwrap_or(0);
+        self.minted_mut().set(minted.saturating_add(amount));
+    }
+
+    pub fn fee_pool_mut(&mut self) -> Entry<&mut Fork, u64> {
+        Entry::new("cryptocurrency.fee_pool", &mut self.view)
+    }
+
+    pub fn tick_height_mut(&mut self) -> Entry<&mut Fork, u64> {
+        Entry::new("cryptocurrency.tick_height", &mut self.view)
+    }
+
+    /// Add a transfer fee to the fee pool if validator rewards are enabled;
+    /// otherwise the fee is burned.
+    pub fn collect_fee(&mut self, fee: u64) {
+        if fee > 0 && self.config().validator_rewards {
+            let pool = self.fee_pool().get().unwrap_or(0);
+            self.fee_pool_mut().set(pool + fee);
+        }
+    }
+
+    pub fn base_fee_state_mut(&mut self) -> Entry<&mut Fork, BaseFee> {
+        Entry::new("cryptocurrency.base_fee", &mut self.view)
+    }
+
+    /// Compute the base fee for the current height and store it, so that
+    /// subsequent computations start from it.
+    pub fn update_base_fee(&mut self) -> u64 {
+        let fee = self.base_fee();
+        let height = self.height();
+        self.base_fee_state_mut().set(BaseFee::new(height, fee));
+        fee
+    }
+
+    pub fn activity_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, WalletActivity> {
+        ProofMapIndex::new("cryptocurrency.activity", &mut self.view)
+    }
+
+    pub fn loyalty_points_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, u64> {
+        ProofMapIndex::new("cryptocurrency.loyalty_points", &mut self.view)
+    }
+
+    /// Move coins between existing wallets. The fee is collected by `collect_fee`
+    /// and the insurance levy, if any, is credited to the insurance fund. Returns
+    /// `false` without changing the state if the sender cannot pay `transfer_cost`
+    /// or the receiver cannot be credited the amount.
+    pub fn transfer(&mut self, from: &PublicKey, to: &PublicKey, amount: u64, fee: u64) -> bool {
+        let insurance = self.insurance_levy(amount);
+        let cost = match self.transfer_cost(amount, fee) {
+            Ok(cost) => cost,
+            Err(_) => return false,
+        };
+        if !self.can_credit(to, amount) {
+            return false;
+        }
+        let sender = match self.debit(from, cost) {
+            Some(sender) => sender,
+            None => return false,
+        };
+        let receiver = self.credit(to, amount);
+        println!("Transfer between wallets: {:?} => {:?}", sender, receiver);
+        self.collect_fee(fee);
+        if let Some((fund, levy)) = insurance {
+            self.credit_or_return(from, &fund, levy);
+        }
+        self.record_transfer(from, to, amount);
+        true
+    }
+
+    /// Update activity counters of the sender and the receiver of a transfer,
+    /// award loyalty points to the sender according to the configuration and
+    /// add the payment to `block_payments`.
+    pub fn record_transfer(&mut self, from: &PublicKey, to: &PublicKey, amount: u64) {
+        let config = self.config();
+        let period = self.activity_period();
+        let height = self.height();
+        if self.block_payments_height().get() != Some(height) {
+            self.block_payments_mut().clear();
+            self.block_payments_height_mut().set(height);
+        }
+        self.block_payments_mut().push(BlockPayment::new(from, to, amount));
+
+        self.counterparties_mut(from).put(to, period);
+        let root = self.counterparties(from).root_hash();
+        self.update_index_root("cryptocurrency.counterparties", from, root);
+
+        let sender = self.activity().get(from).unwrap_or_else(WalletActivity::empty);
+        let sender = sender.record_sent(period);
+        if let Some(ref rules) = config.loyalty {
+            let points = self.loyalty_points().get(from).unwrap_or(0);
+            self.loyalty_points_mut().put(from, points + rules.points(sender.streak()));
+        }
+        self.activity_mut().put(from, sender);
+
+        let receiver = self.activity().get(to).unwrap_or_else(WalletActivity::empty);
+        self.activity_mut().put(to, receiver.record_received(period));
+    }
+
+    /// Perform the actions scheduled at or before the given height: fail the
+    /// transfers queued in earlier blocks, credit due deferred transfers to
+    /// their recipients, unlock matured locked transfers, return expired
+    /// promotional coins to the treasury, settle auctions past their deadline, make due
+    /// lottery draws, return unclaimed deposits, execute approved clawbacks, return
+    /// coins of expired invitations and distribute the fee pool among the validators.
+    pub fn process_scheduled(&mut self, height: u64) {
+        self.fail_stale_transfers();
+        let actions = self.take_scheduled(height);
+
+        let due: Vec<(Hash, DeferredTransfer)> =
+            scheduled_keys(&actions, SCHEDULED_DEFERRED_TRANSFER)
+                .into_iter()
+                .filter_map(|tx_hash| Some((tx_hash, self.deferred_transfers().get(&tx_hash)?)))
+                .collect();
+
+        for (tx_hash, transfer) in due {
+            // The recipient is checked when funds are locked, but return them
+            // to the sender if the recipient cannot receive them by now.
+            let outcome =
+                self.pay_out(&tx_hash, transfer.from(), transfer.to(), transfer.amount(), false);
+            println!("Execute deferred transfer {:?}: {:?}", tx_hash, outcome);
+            self.deferred_transfers_mut().remove(&tx_hash);
+        }
+
+        let matured: Vec<(Hash, LockedTransfer)> = scheduled_keys(&actions, SCHEDULED_UNLOCK)
+            .into_iter()
+            .filter_map(|tx_hash| Some((tx_hash, self.locked_transfers().get(&tx_hash)?)))
+            .collect();
+        for (tx_hash, transfer) in matured {
+            // Return the coins to the sender if the recipient has closed the
+            // wallet while the coins were locked. The transfer is recorded when
+            // the coins are locked.
+            let outcome =
+                self.pay_out(&tx_hash, transfer.from(), transfer.to(), transfer.amount(), true);
+            println!("Unlock locked transfer {:?}: {:?}", tx_hash, outcome);
+            self.locked_transfers_mut().remove(&tx_hash);
+            let locked = self.locked_balance(transfer.to()) - transfer.amount();
+            if locked > 0 {
+                self.locked_totals_mut().put(transfer.to(), locked);
+            } else {
+                self.locked_totals_mut().remove(transfer.to());
+            }
+        }
+
+        let expired: Vec<(PublicKey, PromotionalBalance)> =
+            scheduled_keys(&actions, SCHEDULED_PROMOTION_EXPIRY)
+                .into_iter()
+                .filter_map(|key| {
+                    let pub_key = PublicKey::from_slice(key.as_ref()).unwrap();
+                    Some((pub_key, self.promotional_balances().get(&pub_key)?))
+                })
+                .filter(|&(_, ref promo)| promo.expires_at() <= height)
+                .collect();
+        let treasury = self.config().treasury;
+        for (pub_key, promo) in expired {
+            if let Some(ref treasury) = treasury {
+                let wallet = self.credit(treasury, promo.amount());
+                println!("Return expired promotional coins of {:?}: {:?}", pub_key, wallet);
+            }
+            self.promotional_balances_mut().remove(&pub_key);
+        }
+
+        let closed: Vec<(Hash, Auction)> = scheduled_keys(&actions, SCHEDULED_AUCTION)
+            .into_iter()
+            .filter_map(|auction_hash| Some((auction_hash, self.auctions().get(&auction_hash)?)))
+            .filter(|&(_, ref auction)| auction.settled_at() == 0)
+            .collect();
+        for (auction_hash, auction) in closed {
+            if auction.highest_bid() > 0 {
+                // Return the bid to the bidder if the seller cannot receive it.
+                let credited = self.credit_or_return(
+                    auction.highest_bidder(),
+                    auction.seller(),
+                    auction.highest_bid(),
+                );
+                println!("Settle auction {:?} to {:?}", auction_hash, credited);
+                if credited.as_ref() == Some(auction.seller()) {
+                    self.record_transfer(
+                        auction.highest_bidder(),
+                        auction.seller(),
+                        auction.highest_bid(),
+                    );
+                }
+            }
+            let auction = Auction::new(
+                auction.seller(),
+                auction.item(),
+                auction.reserve_price(),
+                auction.deadline(),
+                auction.highest_bidder(),
+                auction.highest_bid(),
+                height,
+            );
+            self.auctions_mut().put(&auction_hash, auction);
+        }
+
+        let due: Vec<(Hash, Draw)> = scheduled_keys(&actions, SCHEDULED_DRAW)
+            .into_iter()
+            .filter_map(|draw_hash| Some((draw_hash, self.draws().get(&draw_hash)?)))
+            .filter(|&(_, ref draw)| draw.drawn_at() == 0)
+            .collect();
+        for (draw_hash, draw) in due {
+            // The beacon of the last block in which entering was possible.
+            let beacon = self.beacon(draw.draw_height() - 1).unwrap();
+            let entrants = self.draw_entrants(&draw_hash);
+            let winner = if entrants.is_empty() {
+                *draw.organizer()
+            } else {
+                let random = beacon.as_ref()[..8].iter().rev().fold(0u64, |acc, &byte| {
+                    acc << 8 | u64::from(byte)
+                });
+                entrants.get(random % entrants.len()).unwrap()
+            };
+            // The organizer keeps the prize if the winner cannot receive it.
+            let winner = self.credit_or_return(draw.organizer(), &winner, draw.prize())
+                .unwrap_or(*draw.organizer());
+            println!("Lottery draw {:?}: {:?}", draw_hash, winner);
+            let draw = Draw::new(
+                draw.organizer(),
+                draw.prize(),
+                draw.draw_height(),
+                &winner,
+                height,
+            );
+            self.draws_mut().put(&draw_hash, draw);
+        }
+
+        let returned: Vec<(Hash, Deposit)> = scheduled_keys(&actions, SCHEDULED_DEPOSIT_RETURN)
+            .into_iter()
+            .filter_map(|deposit_hash| Some((deposit_hash, self.deposits().get(&deposit_hash)?)))
+            .collect();
+        for (deposit_hash, deposit) in returned {
+            let wallet = self.credit(deposit.payer(), deposit.amount());
+            println!("Return deposit {:?}: {:?}", deposit_hash, wallet);
+            self.deposits_mut().remove(&deposit_hash);
+        }
+
+        let due: Vec<(Hash, Clawback)> = scheduled_keys(&actions, SCHEDULED_CLAWBACK)
+            .into_iter()
+            .filter_map(|transfer_hash| {
+                Some((transfer_hash, self.clawbacks().get(&transfer_hash)?))
+            })
+            .filter(|&(_, ref clawback)| clawback.executed_at() == 0)
+            .collect();
+        for (transfer_hash, clawback) in due {
+            let (recovered, error_code) = match self.claw_back(&transfer_hash) {
+                Ok(recovered) => (recovered, 0),
+                Err(err) => (0, err.code()),
+            };
+            println!("Execute clawback of {:?}: {:?}", transfer_hash, recovered);
+            let clawback = Clawback::new(
+                clawback.proposer(),
+                clawback.reason(),
+                clawback.approvals(),
+                clawback.execute_at(),
+                height,
+                recovered,
+                error_code,
+            );
+            self.clawbacks_mut().put(&transfer_hash, clawback);
+        }
+
+        let expired: Vec<(Hash, Invitation)> =
+            scheduled_keys(&actions, SCHEDULED_INVITATION_EXPIRY)
+                .into_iter()
+                .filter_map(|code| Some((code, self.invitations().get(&code)?)))
+                .collect();
+        for (code, invitation) in expired {
+            let unused = (invitation.max_uses() - invitation.uses()) * invitation.balance();
+            let wallet = self.credit(invitation.sponsor(), unused);
+            println!("Return coins of expired invitation {:?}: {:?}", code, wallet);
+            self.invitations_mut().remove(&code);
+        }
+
+        // The remainder of the division and the shares of validators whose
+        // wallets cannot receive them stay in the pool.
+        let share = self.fee_share();
+        if share > 0 {
+            let mut paid = 0;
+            for validator in &self.fee_recipients() {
+                if let Some(wallet) = self.credit(validator, share) {
+                    println!("Validator reward: {:?}", wallet);
+                    paid += share;
+                }
+            }
+            let pool = self.fee_pool().get().unwrap();
+            self.fee_pool_mut().set(pool - paid);
+        }
+    }
+}
+
+// // // // // // // // // // TRANSACTIONS // // // // // // // // // //
+
+/// Create a new wallet. A wallet created with the code hash of a sponsored
+/// invitation starts with the balance of the invitation instead of the default
+/// one; the zero hash as `invitation` means no invitation. The metadata fields
+/// are optional, as in `Wallet`.
+message! {
+    struct TxCreateWallet {
+        const TYPE = SERVICE_ID;
+        const ID = TX_CREATE_WALLET_ID;
+        const SIZE = 120;
+
+        field pub_key:      &PublicKey  [00 => 32]
+        field name:         &str        [32 => 40]
+        field invitation:   &Hash       [40 => 72]
+        field display_name: &str        [72 => 80]
+        field contact_uri:  &str        [80 => 88]
+        field avatar:       &Hash       [88 => 120]
+    }
+}
+
+/// Replace the metadata of a wallet. Signed by the owner of the wallet.
+message! {
+    struct TxUpdateWallet {
+        const TYPE = SERVICE_ID;
+        const ID = TX_UPDATE_WALLET_ID;
+        const SIZE = 88;
+
+        field pub_key:      &PublicKey  [00 => 32]
+        field display_name: &str        [32 => 40]
+        field contact_uri:  &str        [40 => 48]
+        field avatar:       &Hash       [48 => 80]
+        field seed:         u64         [80 => 88]
+    }
+}
+
+/// Change the name of a wallet. Signed by the owner of the wallet.
+message! {
+    struct TxRenameWallet {
+        const TYPE = SERVICE_ID;
+        const ID = TX_RENAME_WALLET_ID;
+        const SIZE = 48;
+
+        field pub_key:      &PublicKey  [00 => 32]
+        field name:         &str        [32 => 40]
+        field seed:         u64         [40 => 48]
+    }
+}
+
+/// Close a wallet, moving its balance and asset balances to the beneficiary.
+/// Signed by the owner of the wallet. Transfers to and from a closed wallet
+/// fail with `WalletClosed`; an unspent promotional balance returns to the
+/// treasury at its expiry as usual.
+message! {
+    struct TxCloseWallet {
+        const TYPE = SERVICE_ID;
+        const ID = TX_CLOSE_WALLET_ID;
+        const SIZE = 72;
+
+        field owner:        &PublicKey  [00 => 32]
+        field beneficiary:  &PublicKey  [32 => 64]
+        field seed:         u64         [64 => 72]
+    }
+}
+
+/// Allow the spender to transfer up to `amount` coins from the owner's wallet
+/// with `TxTransferFrom`. Replaces the previous allowance of the spender;
+/// the zero amount revokes it. Signed by the owner of the wallet.
+message! {
+    struct TxApprove {
+        const TYPE = SERVICE_ID;
+        const ID = TX_APPROVE_ID;
+        const SIZE = 80;
+
+        field owner:        &PublicKey  [00 => 32]
+        field spender:      &PublicKey  [32 => 64]
+        field amount:       u64         [64 => 72]
+        field seed:         u64         [72 => 80]
+    }
+}
+
+/// Set the sweep rule of a wallet, replacing the previous one. Signed by the
+/// owner of the wallet.
+message! {
+    struct TxSetSweepRule {
+        const TYPE = SERVICE_ID;
+        const ID = TX_SET_SWEEP_RULE_ID;
+        const SIZE = 80;
+
+        field owner:        &PublicKey  [00 => 32]
+        field cold_wallet:  &PublicKey  [32 => 64]
+        field threshold:    u64         [64 => 72]
+        field seed:         u64         [72 => 80]
+    }
+}
+
+/// Remove the sweep rule of a wallet. Signed by the owner of the wallet.
+message! {
+    struct TxClearSweepRule {
+        const TYPE = SERVICE_ID;
+        const ID = TX_CLEAR_SWEEP_RULE_ID;
+        const SIZE = 40;
+
+        field owner:        &PublicKey  [00 => 32]
+        field seed:         u64         [32 => 40]
+    }
+}
+
+/// Transfer coins from the owner's wallet within the allowance granted to the
+/// spender by `TxApprove`. Signed by the spender; the owner pays the fee, which
+/// does not count against the allowance.
+message! {
+    struct TxTransferFrom {
+        const TYPE = SERVICE_ID;
+        const ID = TX_TRANSFER_FROM_ID;
+        const SIZE = 120;
+
+        field spender:      &PublicKey  [00 => 32]
+        field from:         &PublicKey  [32 => 64]
+        field to:           &PublicKey  [64 => 96]
+        field amount:       u64         [96 => 104]
+        field fee:          u64         [104 => 112]
+        field seed:         u64         [112 => 120]
+    }
+}
+
+/// Transfer coins between the wallets. The fee is burned, or paid to the validators
+/// if validator rewards are enabled; it must be at least the base fee of the block
+/// the transfer is included into.
+///
+/// If `depends_on` is not the zero hash, the transfer is executed only after
+/// the service transaction with this hash is executed successfully, and fails
+/// if that transaction fails.
+///
+/// If `reference` is a payment reference registered by the receiver, the transfer
+/// is recorded as a receipt of the receiver. The zero hash stands for no reference.
+///
+/// `memo` is a free-form note for the receiver, such as an invoice number, kept
+/// with the transfer in the history of both wallets. The empty string stands for
+/// no memo.
+message! {
+    struct TxTransfer {
+        const TYPE = SERVICE_ID;
+        const ID = TX_TRANSFER_ID;
+        const SIZE = 160;
+
+        field from:        &PublicKey  [00 => 32]
+        field to:          &PublicKey  [32 => 64]
+        field amount:      u64         [64 => 72]
+        field fee:         u64         [72 => 80]
+        field depends_on:  &Hash       [80 => 112]
+        field reference:   &Hash       [112 => 144]
+        field memo:        &str        [144 => 152]
+        field seed:        u64         [152 => 160]
+    }
+}
+
+/// Transfer coins at a future height. The coins are locked when the transaction
+/// is executed and credited to the receiver at the specified height, unless the
+/// sender cancels the transfer before that.
+message! {
+    struct TxDeferredTransfer {
+        const TYPE = SERVICE_ID;
+        const ID = TX_DEFERRED_TRANSFER_ID;
+        const SIZE = 88;
+
+        field from:        &PublicKey  [00 => 32]
+        field to:          &PublicKey  [32 => 64]
+        field amount:      u64         [64 => 72]
+        field height:      u64         [72 => 80]
+        field seed:        u64         [80 => 88]
+    }
+}
+
+/// Cancel a pending deferred transfer and return the locked coins to the sender.
+message! {
+    struct TxCancelDeferredTransfer {
+        const TYPE = SERVICE_ID;
+        const ID = TX_CANCEL_DEFERRED_TRANSFER_ID;
+        const SIZE = 64;
+
+        field from:        &PublicKey  [00 => 32]
+        field tx_hash:     &Hash       [32 => 64]
+    }
+}
+
+/// Perform the actions scheduled for the current height. The transaction is
+/// issued by validators from the block commit hook and signed with their
+/// service keys; the height only makes transactions from different blocks distinct.
+/// Only the first such transaction in a block performs the actions.
+message! {
+    struct TxTick {
+        const TYPE = SERVICE_ID;
+        const ID = TX_TICK_ID;
+        const SIZE = 40;
+
+        field validator:   &PublicKey  [00 => 32]
+        field height:      u64         [32 => 40]
+    }
+}
+
+/// Freeze a wallet, so that it cannot send coins. Signed by the admin key from
+/// the service configuration.
+message! {
+    struct TxFreezeWallet {
+        const TYPE = SERVICE_ID;
+        const ID = TX_FREEZE_WALLET_ID;
+        const SIZE = 72;
+
+        field admin:       &PublicKey  [00 => 32]
+        field wallet:      &PublicKey  [32 => 64]
+        field seed:        u64         [64 => 72]
+    }
+}
+
+/// Unfreeze a frozen wallet. Signed by the admin key from the service configuration.
+message! {
+    struct TxUnfreezeWallet {
+        const TYPE = SERVICE_ID;
+        const ID = TX_UNFREEZE_WALLET_ID;
+        const SIZE = 72;
+
+        field admin:       &PublicKey  [00 => 32]
+        field wallet:      &PublicKey  [32 => 64]
+        field seed:        u64         [64 => 72]
+    }
+}
+
+/// Issue new coins to a wallet. Must be signed by the issuer key from
+/// the service configuration.
+message! {
+    struct TxIssue {
+        const TYPE = SERVICE_ID;
+        const ID = TX_ISSUE_ID;
+        const SIZE = 80;
+
+        field issuer:      &PublicKey  [00 => 32]
+        field to:          &PublicKey  [32 => 64]
+        field amount:      u64         [64 => 72]
+        field seed:        u64         [72 => 80]
+    }
+}
+
+/// Grant promotional coins from the treasury to a wallet. Must be signed by
+/// the issuer key from the service configuration.
+message! {
+    struct TxGrantPromotion {
+        const TYPE = SERVICE_ID;
+        const ID = TX_GRANT_PROMOTION_ID;
+        const SIZE = 88;
+
+        field issuer:      &PublicKey  [00 => 32]
+        field to:          &PublicKey  [32 => 64]
+        field amount:      u64         [64 => 72]
+        field expires_at:  u64         [72 => 80]
+        field seed:        u64         [80 => 88]
+    }
+}
+
+/// Propose a payout from the insurance fund. The proposal counts as
+/// an approval of the proposing governor.
+message! {
+    struct TxInsurancePayout {
+        const TYPE = SERVICE_ID;
+        const ID = TX_INSURANCE_PAYOUT_ID;
+        const SIZE = 80;
+
+        field governor:    &PublicKey  [00 => 32]
+        field to:          &PublicKey  [32 => 64]
+        field amount:      u64         [64 => 72]
+        field seed:        u64         [72 => 80]
+    }
+}
+
+/// Approve a pending payout from the insurance fund.
+message! {
+    struct TxApproveInsurancePayout {
+        const TYPE = SERVICE_ID;
+        const ID = TX_APPROVE_INSURANCE_PAYOUT_ID;
+        const SIZE = 64;
+
+        field governor:    &PublicKey  [00 => 32]
+        field payout:      &Hash       [32 => 64]
+    }
+}
+
+/// Irreversibly destroy coins of a wallet, binding the burn to a 32-byte tag.
+message! {
+    struct TxBurnWithTag {
+        const TYPE = SERVICE_ID;
+        const ID = TX_BURN_WITH_TAG_ID;
+        const SIZE = 80;
+
+        field owner:       &PublicKey  [00 => 32]
+        field tag:         &Hash       [32 => 64]
+        field amount:      u64         [64 => 72]
+        field seed:        u64         [72 => 80]
+    }
+}
+
+/// Irreversibly destroy coins of a wallet.
+message! {
+    struct TxBurn {
+        const TYPE = SERVICE_ID;
+        const ID = TX_BURN_ID;
+        const SIZE = 48;
+
+        field owner:       &PublicKey  [00 => 32]
+        field amount:      u64         [32 => 40]
+        field seed:        u64         [40 => 48]
+    }
+}
+
+/// Create a wallet for the given public key and fund it from an existing wallet
+/// in one step. The message is signed by the funder; the new wallet receives
+/// only the funded amount. The fee and the insurance levy are charged as for
+/// `TxTransfer`. Funding that would be held by the velocity rules fails with
+/// `Error::TransferHeld` instead, so that the wallet is never left unfunded.
+message! {
+    struct TxCreateAndFundWallet {
+        const TYPE = SERVICE_ID;
+        const ID = TX_CREATE_AND_FUND_WALLET_ID;
+        const SIZE = 96;
+
+        field funder:      &PublicKey  [00 => 32]
+        field pub_key:     &PublicKey  [32 => 64]
+        field name:        &str        [64 => 72]
+        field amount:      u64         [72 => 80]
+        field fee:         u64         [80 => 88]
+        field seed:        u64         [88 => 96]
+    }
+}
+
+/// Authorize a session key to make transfers from the wallet up to `max_amount`
+/// each, until the `expires_at` height. Authorizing a key again replaces its scope.
+message! {
+    struct TxAuthorizeSessionKey {
+        const TYPE = SERVICE_ID;
+        const ID = TX_AUTHORIZE_SESSION_KEY_ID;
+        const SIZE = 88;
+
+        field owner:       &PublicKey  [00 => 32]
+        field session_key: &PublicKey  [32 => 64]
+        field max_amount:  u64         [64 => 72]
+        field expires_at:  u64         [72 => 80]
+        field seed:        u64         [80 => 88]
+    }
+}
+
+/// Revoke a session key of the wallet.
+message! {
+    struct TxRevokeSessionKey {
+        const TYPE = SERVICE_ID;
+        const ID = TX_REVOKE_SESSION_KEY_ID;
+        const SIZE = 72;
+
+        field owner:       &PublicKey  [00 => 32]
+        field session_key: &PublicKey  [32 => 64]
+        field seed:        u64         [64 => 72]
+    }
+}
+
+/// Transfer coins from the wallet `from`, signed by a session key of the wallet
+/// instead of the wallet key. The fee and the insurance levy are charged as for
+/// `TxTransfer`.
+message! {
+    struct TxSessionTransfer {
+        const TYPE = SERVICE_ID;
+        const ID = TX_SESSION_TRANSFER_ID;
+        const SIZE = 120;
+
+        field from:        &PublicKey  [00 => 32]
+        field session_key: &PublicKey  [32 => 64]
+        field to:          &PublicKey  [64 => 96]
+        field amount:      u64         [96 => 104]
+        field fee:         u64         [104 => 112]
+        field seed:        u64         [112 => 120]
+    }
+}
+
+/// Mark a wallet as verified. Signed by the verifier from the service configuration.
+message! {
+    struct TxAttestWallet {
+        const TYPE = SERVICE_ID;
+        const ID = TX_ATTEST_WALLET_ID;
+        const SIZE = 72;
+
+        field verifier:    &PublicKey  [00 => 32]
+        field wallet:      &PublicKey  [32 => 64]
+        field seed:        u64         [64 => 72]
+    }
+}
+
+/// Remove the verification mark of a wallet. Signed by the verifier from
+/// the service configuration.
+message! {
+    struct TxRevokeAttestation {
+        const TYPE = SERVICE_ID;
+        const ID = TX_REVOKE_ATTESTATION_ID;
+        const SIZE = 72;
+
+        field verifier:    &PublicKey  [00 => 32]
+        field wallet:      &PublicKey  [32 => 64]
+        field seed:        u64         [64 => 72]
+    }
+}
+
+/// Link two wallets of the same owner into a group. The message is signed
+/// with the key of `wallet`; `other_signature` is the signature of `link_consent`
+/// made with the key of `other`.
+///
+/// If one of the wallets already belongs to a group, the other one joins it.
+/// Otherwise a new group is created, identified by the hash of this transaction.
+message! {
+    struct TxLinkWallets {
+        const TYPE = SERVICE_ID;
+        const ID = TX_LINK_WALLETS_ID;
+        const SIZE = 136;
+
+        field wallet:          &PublicKey  [00 => 32]
+        field other:           &PublicKey  [32 => 64]
+        field other_signature: &Signature  [64 => 128]
+        field seed:            u64         [128 => 136]
+    }
+}
+
+/// Data the key of `other` signs to consent to linking with `wallet`.
+pub fn link_consent(wallet: &PublicKey, other: &PublicKey) -> Hash {
+    let mut data = b"cryptocurrency.link".to_vec();
+    data.extend_from_slice(wallet.as_ref());
+    data.extend_from_slice(other.as_ref());
+    crypto::hash(&data)
+}
+
+/// Register a payment reference of the wallet. The reference is the hash of
+/// this transaction; payers put it into the `reference` field of `TxTransfer`.
+message! {
+    struct TxRegisterPaymentReference {
+        const TYPE = SERVICE_ID;
+        const ID = TX_REGISTER_PAYMENT_REFERENCE_ID;
+        const SIZE = 40;
+
+        field payee:       &PublicKey  [00 => 32]
+        field seed:        u64         [32 => 40]
+    }
+}
+
+/// Release or reject a transfer held by the velocity rules.
+message! {
+    struct TxReviewHeldTransfer {
+        const TYPE = SERVICE_ID;
+        const ID = TX_REVIEW_HELD_TRANSFER_ID;
+        const SIZE = 65;
+
+        field compliance:  &PublicKey  [00 => 32]
+        field tx_hash:     &Hash       [32 => 64]
+        field release:     bool        [64 => 65]
+    }
+}
+
+/// Assign an account tier to a wallet. Signed by the tier admin from the service configuration.
+message! {
+    struct TxAssignTier {
+        const TYPE = SERVICE_ID;
+        const ID = TX_ASSIGN_TIER_ID;
+        const SIZE = 73;
+
+        field admin:       &PublicKey  [00 => 32]
+        field wallet:      &PublicKey  [32 => 64]
+        field tier:        u8          [64 => 65]
+        field seed:        u64         [65 => 73]
+    }
+}
+
+/// Put an item up for an ascending auction closing at the `deadline` height.
+message! {
+    struct TxCreateAuction {
+        const TYPE = SERVICE_ID;
+        const ID = TX_CREATE_AUCTION_ID;
+        const SIZE = 88;
+
+        field seller:        &PublicKey  [00 => 32]
+        field item:          &Hash       [32 => 64]
+        field reserve_price: u64         [64 => 72]
+        field deadline:      u64         [72 => 80]
+        field seed:          u64         [80 => 88]
+    }
+}
+
+/// Bid in an auction. The bid is escrowed until it is outbid or the auction is settled.
+message! {
+    struct TxBid {
+        const TYPE = SERVICE_ID;
+        const ID = TX_BID_ID;
+        const SIZE = 80;
+
+        field bidder:      &PublicKey  [00 => 32]
+        field auction:     &Hash       [32 => 64]
+        field amount:      u64         [64 => 72]
+        field seed:        u64         [72 => 80]
+    }
+}
+
+/// Open a payment stream paying `rate` coins per block to the receiver until
+/// `budget` is exhausted.
+message! {
+    struct TxOpenStream {
+        const TYPE = SERVICE_ID;
+        const ID = TX_OPEN_STREAM_ID;
+        const SIZE = 88;
+
+        field from:        &PublicKey  [00 => 32]
+        field to:          &PublicKey  [32 => 64]
+        field rate:        u64         [64 => 72]
+        field budget:      u64         [72 => 80]
+        field seed:        u64         [80 => 88]
+    }
+}
+
+/// Withdraw the coins accrued by a payment stream. Signed by the receiver.
+message! {
+    struct TxWithdrawStream {
+        const TYPE = SERVICE_ID;
+        const ID = TX_WITHDRAW_STREAM_ID;
+        const SIZE = 72;
+
+        field to:          &PublicKey  [00 => 32]
+        field stream:      &Hash       [32 => 64]
+        field seed:        u64         [64 => 72]
+    }
+}
+
+/// Close a payment stream: pay out the accrued coins to the receiver and return
+/// the rest of the budget to the sender. Signed by the sender.
+message! {
+    struct TxCloseStream {
+        const TYPE = SERVICE_ID;
+        const ID = TX_CLOSE_STREAM_ID;
+        const SIZE = 64;
+
+        field from:        &PublicKey  [00 => 32]
+        field stream:      &Hash       [32 => 64]
+    }
+}
+
+/// Create a lottery draw paying `prize` to a random entrant at `draw_height`.
+/// The winner is chosen with the randomness beacon of the block preceding
+/// `draw_height`.
+message! {
+    struct TxLotteryDraw {
+        const TYPE = SERVICE_ID;
+        const ID = TX_LOTTERY_DRAW_ID;
+        const SIZE = 56;
+
+        field organizer:   &PublicKey  [00 => 32]
+        field prize:       u64         [32 => 40]
+        field draw_height: u64         [40 => 48]
+        field seed:        u64         [48 => 56]
+    }
+}
+
+/// Enter a lottery draw.
+message! {
+    struct TxEnterDraw {
+        const TYPE = SERVICE_ID;
+        const ID = TX_ENTER_DRAW_ID;
+        const SIZE = 64;
+
+        field wallet:      &PublicKey  [00 => 32]
+        field draw:        &Hash       [32 => 64]
+    }
+}
+
+/// Issue a fee coupon under the given code, replacing any coupon previously
+/// issued under it. Must be signed by the coupon issuer.
+message! {
+    struct TxIssueFeeCoupon {
+        const TYPE = SERVICE_ID;
+        const ID = TX_ISSUE_FEE_COUPON_ID;
+        const SIZE = 96;
+
+        field issuer:       &PublicKey  [00 => 32]
+        field code:         &Hash       [32 => 64]
+        field discount_bps: u64         [64 => 72]
+        field max_uses:     u64         [72 => 80]
+        field expires_at:   u64         [80 => 88]
+        field seed:         u64         [88 => 96]
+    }
+}
+
+/// Transfer coins redeeming a fee coupon. The fee must be at least the minimum
+/// fee of `TxTransfer` reduced by the discount of the coupon.
+message! {
+    struct TxCouponTransfer {
+        const TYPE = SERVICE_ID;
+        const ID = TX_COUPON_TRANSFER_ID;
+        const SIZE = 120;
+
+        field from:        &PublicKey  [00 => 32]
+        field to:          &PublicKey  [32 => 64]
+        field amount:      u64         [64 => 72]
+        field fee:         u64         [72 => 80]
+        field code:        &Hash       [80 => 112]
+        field seed:        u64         [112 => 120]
+    }
+}
+
+/// Lock coins of the sponsor for `max_uses` wallets created with the invitation
+/// code, each starting with `balance` coins. The invitation is identified by
+/// the hash of its code.
+message! {
+    struct TxCreateInvitation {
+        const TYPE = SERVICE_ID;
+        const ID = TX_CREATE_INVITATION_ID;
+        const SIZE = 96;
+
+        field sponsor:     &PublicKey  [00 => 32]
+        field code:        &Hash       [32 => 64]
+        field balance:     u64         [64 => 72]
+        field max_uses:    u64         [72 => 80]
+        field expires_at:  u64         [80 => 88]
+        field seed:        u64         [88 => 96]
+    }
+}
+
+/// Lock coins as a refundable deposit for the payee. The deposit is returned
+/// to the payer at the `return_at` height unless the payee claims it before.
+message! {
+    struct TxDeposit {
+        const TYPE = SERVICE_ID;
+        const ID = TX_DEPOSIT_ID;
+        const SIZE = 120;
+
+        field payer:       &PublicKey  [00 => 32]
+        field payee:       &PublicKey  [32 => 64]
+        field amount:      u64         [64 => 72]
+        field reference:   &Hash       [72 => 104]
+        field return_at:   u64         [104 => 112]
+        field seed:        u64         [112 => 120]
+    }
+}
+
+/// Lock coins for the recipient under the control of the arbiter, who later
+/// releases them with `TxReleaseEscrow` or refunds them with `TxRefundEscrow`.
+/// The escrow is identified by the hash of this transaction.
+message! {
+    struct TxCreateEscrow {
+        const TYPE = SERVICE_ID;
+        const ID = TX_CREATE_ESCROW_ID;
+        const SIZE = 112;
+
+        field sender:      &PublicKey  [00 => 32]
+        field recipient:   &PublicKey  [32 => 64]
+        field arbiter:     &PublicKey  [64 => 96]
+        field amount:      u64         [96 => 104]
+        field seed:        u64         [104 => 112]
+    }
+}
+
+/// Pay the coins of an escrow to the recipient. Signed by the arbiter.
+message! {
+    struct TxReleaseEscrow {
+        const TYPE = SERVICE_ID;
+        const ID = TX_RELEASE_ESCROW_ID;
+        const SIZE = 64;
+
+        field arbiter:     &PublicKey  [00 => 32]
+        field escrow:      &Hash       [32 => 64]
+    }
+}
+
+/// Return the coins of an escrow to the sender. Signed by the arbiter.
+message! {
+    struct TxRefundEscrow {
+        const TYPE = SERVICE_ID;
+        const ID = TX_REFUND_ESCROW_ID;
+        const SIZE = 64;
+
+        field arbiter:     &PublicKey  [00 => 32]
+        field escrow:      &Hash       [32 => 64]
+    }
+}
+
+/// Transfer coins that the receiver may spend only from the `unlock_height`.
+/// The coins are debited from the sender at once, shown as locked to the
+/// receiver and credited to the receiver at the unlock height. Unlike
+/// `TxDeferredTransfer`, the transfer cannot be cancelled.
+message! {
+    struct TxLockedTransfer {
+        const TYPE = SERVICE_ID;
+        const ID = TX_LOCKED_TRANSFER_ID;
+        const SIZE = 88;
+
+        field from:          &PublicKey  [00 => 32]
+        field to:            &PublicKey  [32 => 64]
+        field amount:        u64         [64 => 72]
+        field unlock_height: u64         [72 => 80]
+        field seed:          u64         [80 => 88]
+    }
+}
+
+/// Issue a new asset with the given name and supply, credited to the issuer.
+/// The asset is identified by the hash of this transaction; names need not
+/// be unique.
+message! {
+    struct TxCreateAsset {
+        const TYPE = SERVICE_ID;
+        const ID = TX_CREATE_ASSET_ID;
+        const SIZE = 56;
+
+        field issuer:      &PublicKey  [00 => 32]
+        field name:        &str        [32 => 40]
+        field supply:      u64         [40 => 48]
+        field seed:        u64         [48 => 56]
+    }
+}
+
+/// Transfer `amount` of an asset to another wallet.
+message! {
+    struct TxAssetTransfer {
+        const TYPE = SERVICE_ID;
+        const ID = TX_ASSET_TRANSFER_ID;
+        const SIZE = 112;
+
+        field from:        &PublicKey  [00 => 32]
+        field to:          &PublicKey  [32 => 64]
+        field asset:       &Hash       [64 => 96]
+        field amount:      u64         [96 => 104]
+        field seed:        u64         [104 => 112]
+    }
+}
+
+/// Lock coins in a hashed time-lock contract for an atomic swap. The recipient
+/// claims the coins with `TxClaimHtlc` by revealing the preimage of `hash_lock`
+/// (its SHA-256 hash) before the `timeout` height; from that height on the
+/// sender may take them back with `TxRefundHtlc`.
+message! {
+    struct TxCreateHtlc {
+        const TYPE = SERVICE_ID;
+        const ID = TX_CREATE_HTLC_ID;
+        const SIZE = 120;
+
+        field sender:      &PublicKey  [00 => 32]
+        field recipient:   &PublicKey  [32 => 64]
+        field hash_lock:   &Hash       [64 => 96]
+        field amount:      u64         [96 => 104]
+        field timeout:     u64         [104 => 112]
+        field seed:        u64         [112 => 120]
+    }
+}
+
+/// Claim the coins of a hashed time-lock contract by revealing the preimage
+/// of its hash lock. Signed by the recipient.
+message! {
+    struct TxClaimHtlc {
+        const TYPE = SERVICE_ID;
+        const ID = TX_CLAIM_HTLC_ID;
+        const SIZE = 96;
+
+        field recipient:   &PublicKey  [00 => 32]
+        field htlc:        &Hash       [32 => 64]
+        field preimage:    &Hash       [64 => 96]
+    }
+}
+
+/// Return the coins of a timed out hashed time-lock contract to the sender.
+message! {
+    struct TxRefundHtlc {
+        const TYPE = SERVICE_ID;
+        const ID = TX_REFUND_HTLC_ID;
+        const SIZE = 64;
+
+        field sender:      &PublicKey  [00 => 32]
+        field htlc:        &Hash       [32 => 64]
+    }
+}
+
+/// Claim a part or all of a deposit. The claimed `amount` is paid to the payee
+/// and the rest is returned to the payer at once.
+message! {
+    struct TxClaimDeposit {
+        const TYPE = SERVICE_ID;
+        const ID = TX_CLAIM_DEPOSIT_ID;
+        const SIZE = 72;
+
+        field payee:       &PublicKey  [00 => 32]
+        field deposit:     &Hash       [32 => 64]
+        field amount:      u64         [64 => 72]
+    }
+}
+
+/// Payment of a batch transfer.
+encoding_struct! {
+    struct Payment {
+        const SIZE = 40;
+
+        field to:                 &PublicKey  [00 => 32]
+        field amount:             u64         [32 => 40]
+    }
+}
+
+/// Transfer coins from one wallet to several wallets at once. Either all
+/// payments are made or none.
+message! {
+    struct TxBatchTransfer {
+        const TYPE = SERVICE_ID;
+        const ID = TX_BATCH_TRANSFER_ID;
+        const SIZE = 56;
+
+        field from:        &PublicKey    [00 => 32]
+        field payments:    Vec<Payment>  [32 => 40]
+        field fee:         u64           [40 => 48]
+        field seed:        u64           [48 => 56]
+    }
+}
+
+/// Turn a wallet into a multisignature wallet. Afterwards its coins can only be
+/// spent by `TxMultisigTransfer` approved by `threshold` out of `signers`.
+message! {
+    struct TxSetupMultisig {
+        const TYPE = SERVICE_ID;
+        const ID = TX_SETUP_MULTISIG_ID;
+        const SIZE = 48;
+
+        field pub_key:     &PublicKey      [00 => 32]
+        field signers:     Vec<PublicKey>  [32 => 40]
+        field threshold:   u64             [40 => 48]
+    }
+}
+
+/// Propose a transfer from a multisignature wallet. The proposal counts as
+/// an approval of the proposing signer.
+message! {
+    struct TxMultisigTransfer {
+        const TYPE = SERVICE_ID;
+        const ID = TX_MULTISIG_TRANSFER_ID;
+        const SIZE = 120;
+
+        field wallet:      &PublicKey  [00 => 32]
+        field signer:      &PublicKey  [32 => 64]
+        field to:          &PublicKey  [64 => 96]
+        field amount:      u64         [96 => 104]
+        field fee:         u64         [104 => 112]
+        field seed:        u64         [112 => 120]
+    }
+}
+
+/// Approve a pending transfer from a multisignature wallet.
+message! {
+    struct TxApproveMultisigTransfer {
+        const TYPE = SERVICE_ID;
+        const ID = TX_APPROVE_MULTISIG_TRANSFER_ID;
+        const SIZE = 64;
+
+        field signer:      &PublicKey  [00 => 32]
+        field transfer:    &Hash       [32 => 64]
+    }
+}
+
+/// Propose a clawback of a committed transfer. `reason` is the hash of
+/// the incident report. The proposal counts as an approval of the proposing
+/// governor.
+message! {
+    struct TxClawback {
+        const TYPE = SERVICE_ID;
+        const ID = TX_CLAWBACK_ID;
+        const SIZE = 96;
+
+        field governor:    &PublicKey  [00 => 32]
+        field transfer:    &Hash       [32 => 64]
+        field reason:      &Hash       [64 => 96]
+    }
+}
+
+/// Approve a proposed clawback of a transfer.
+message! {
+    struct TxApproveClawback {
+        const TYPE = SERVICE_ID;
+        const ID = TX_APPROVE_CLAWBACK_ID;
+        const SIZE = 64;
+
+        field governor:    &PublicKey  [00 => 32]
+        field transfer:    &Hash       [32 => 64]
+    }
+}
+
+// // // // // // // // // // CONTRACTS // // // // // // // // // //
+
+/// Reasons for a transaction to have no effect when executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The transaction is malformed or its signature is incorrect.
+    Verification,
+    /// A wallet with the same public key already exists.
+    WalletExists,
+    /// The wallet affected by the transaction does not exist.
+    WalletNotFound,
+    /// The sender's wallet does not exist.
+    SenderNotFound,
+    /// The receiver's wallet does not exist.
+    ReceiverNotFound,
+    /// The paying wallet does not have enough coins.
+    InsufficientFunds,
+    /// The fee is lower than the base fee of the block.
+    FeeTooLow,
+    /// The height specified in the transaction has already been reached.
+    InvalidHeight,
+    /// The referenced deferred transfer does not exist.
+    DeferredTransferNotFound,
+    /// The referenced insurance payout does not exist.
+    PayoutNotFound,
+    /// The referenced insurance payout has already been made.
+    PayoutExecuted,
+    /// The signer is not allowed to perform the action.
+    Unauthorized,
+    /// The service configuration does not enable the action.
+    NotConfigured,
+    /// The transaction the transfer depends on has failed.
+    DependencyFailed,
+    /// The transaction the transfer depends on has not been executed yet.
+    DependencyPending,
+    /// The session key is not authorized by the wallet.
+    SessionKeyNotFound,
+    /// The session key has expired.
+    SessionKeyExpired,
+    /// The amount exceeds the limit of the session key.
+    SessionLimitExceeded,
+    /// The wallet is not attested.
+    AttestationNotFound,
+    /// Both wallets already belong to groups.
+    AlreadyLinked,
+    /// The referenced held transfer does not exist.
+    HeldTransferNotFound,
+    /// The held transfer has been rejected by the compliance officer.
+    TransferRejected,
+    /// The amount exceeds the transfer limit of the sender's tier.
+    TierLimitExceeded,
+    /// The referenced auction does not exist.
+    AuctionNotFound,
+    /// The bid is below the reserve price or does not exceed the highest bid.
+    BidTooLow,
+    /// The referenced payment stream does not exist.
+    StreamNotFound,
+    /// The referenced lottery draw does not exist.
+    DrawNotFound,
+    /// The wallet has already entered the lottery draw.
+    AlreadyEntered,
+    /// The referenced fee coupon does not exist.
+    CouponNotFound,
+    /// The fee coupon is expired or used up.
+    CouponExhausted,
+    /// The referenced deposit does not exist or has been returned.
+    DepositNotFound,
+    /// The wallet name exceeds the configured limit.
+    NameTooLong,
+    /// Transactions of this type are not activated at the current height.
+    NotActivated,
+    /// The referenced transfer does not exist or has not succeeded.
+    TransferNotFound,
+    /// A clawback of the transfer has already been proposed.
+    ClawbackExists,
+    /// The referenced clawback does not exist.
+    ClawbackNotFound,
+    /// The clawback has already been executed.
+    ClawbackExecuted,
+    /// The batch transfer has more payments than the configured limit.
+    TooManyPayments,
+    /// The wallet is not a multisignature wallet.
+    MultisigNotFound,
+    /// The wallet is already a multisignature wallet.
+    AlreadyMultisig,
+    /// The referenced multisignature transfer does not exist.
+    MultisigTransferNotFound,
+    /// The multisignature transfer has already been made.
+    MultisigTransferExecuted,
+    /// The action exceeds a per-epoch cap of the configuration.
+    EpochCapExceeded,
+    /// The referenced invitation does not exist.
+    InvitationNotFound,
+    /// The invitation is expired or used up.
+    InvitationExhausted,
+    /// An invitation with the same code already exists.
+    InvitationExists,
+    /// The wallet name is not normalized or mixes scripts.
+    InvalidName,
+    /// The wallet is frozen and cannot send coins.
+    WalletFrozen,
+    /// The wallet metadata is too long or not normalized.
+    InvalidMetadata,
+    /// The wallet is closed and can neither send nor receive coins.
+    WalletClosed,
+    /// The amount exceeds the allowance of the spender.
+    AllowanceExceeded,
+    /// The wallet has no sweep rule.
+    SweepRuleNotFound,
+    /// The escrow does not exist or is already settled.
+    EscrowNotFound,
+    /// The hashed time-lock contract does not exist or is already settled.
+    HtlcNotFound,
+    /// The preimage does not match the hash lock.
+    InvalidPreimage,
+    /// The asset does not exist.
+    AssetNotFound,
+    /// The amounts of the transaction overflow a balance or a total.
+    BalanceOverflow,
+    /// The memo of a transfer is too long or not acceptable text.
+    InvalidMemo,
+    /// The transfer trips the velocity rules and would be held until reviewed.
+    TransferHeld,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(StdError::description(self))
+    }
+}
+
+impl Error {
+    /// Numeric code of the error, as stored with the results of transactions.
+    /// Code `0` stands for success.
+    pub fn code(&self) -> u8 {
+        match *self {
+            Error::Verification => 1,
+            Error::WalletExists => 2,
+            Error::WalletNotFound => 3,
+            Error::SenderNotFound => 4,
+            Error::ReceiverNotFound => 5,
+            Error::InsufficientFunds => 6,
+            Error::FeeTooLow => 7,
+            Error::InvalidHeight => 8,
+            Error::DeferredTransferNotFound => 9,
+            Error::PayoutNotFound => 10,
+            Error::PayoutExecuted => 11,
+            Error::Unauthorized => 12,
+            Error::NotConfigured => 13,
+            Error::DependencyFailed => 14,
+            Error::DependencyPending => 15,
+            Error::SessionKeyNotFound => 16,
+            Error::SessionKeyExpired => 17,
+            Error::SessionLimitExceeded => 18,
+            Error::AttestationNotFound => 19,
+            Error::AlreadyLinked => 20,
+            Error::HeldTransferNotFound => 21,
+            Error::TransferRejected => 22,
+            Error::TierLimitExceeded => 23,
+            Error::AuctionNotFound => 24,
+            Error::BidTooLow => 25,
+            Error::StreamNotFound => 26,
+            Error::DrawNotFound => 27,
+            Error::AlreadyEntered => 28,
+            Error::CouponNotFound => 29,
+            Error::CouponExhausted => 30,
+            Error::DepositNotFound => 31,
+            Error::NameTooLong => 32,
+            Error::NotActivated => 33,
+            Error::TransferNotFound => 34,
+            Error::ClawbackExists => 35,
+            Error::ClawbackNotFound => 36,
+            Error::ClawbackExecuted => 37,
+            Error::TooManyPayments => 38,
+            Error::MultisigNotFound => 39,
+            Error::AlreadyMultisig => 40,
+            Error::MultisigTransferNotFound => 41,
+            Error::MultisigTransferExecuted => 42,
+            Error::EpochCapExceeded => 43,
+            Error::InvitationNotFound => 44,
+            Error::InvitationExhausted => 45,
+            Error::InvitationExists => 46,
+            Error::InvalidName => 47,
+            Error::WalletFrozen => 48,
+            Error::InvalidMetadata => 49,
+            Error::WalletClosed => 50,
+            Error::AllowanceExceeded => 51,
+            Error::SweepRuleNotFound => 52,
+            Error::EscrowNotFound => 53,
+            Error::HtlcNotFound => 54,
+            Error::InvalidPreimage => 55,
+            Error::AssetNotFound => 56,
+            Error::BalanceOverflow => 57,
+            Error::InvalidMemo => 58,
+            Error::TransferHeld => 59,
+        }
+    }
+
+    /// Error with the given code, or `None` for success or an unknown code.
+    pub fn from_code(code: u8) -> Option<Error> {
+        ERRORS.iter().cloned().find(|err| err.code() == code)
+    }
+}
+
+/// All execution errors.
+const ERRORS: &[Error] = &[
+    Error::Verification,
+    Error::WalletExists,
+    Error::WalletNotFound,
+    Error::SenderNotFound,
+    Error::ReceiverNotFound,
+    Error::InsufficientFunds,
+    Error::FeeTooLow,
+    Error::InvalidHeight,
+    Error::DeferredTransferNotFound,
+    Error::PayoutNotFound,
+    Error::PayoutExecuted,
+    Error::Unauthorized,
+    Error::NotConfigured,
+    Error::DependencyFailed,
+    Error::DependencyPending,
+    Error::SessionKeyNotFound,
+    Error::SessionKeyExpired,
+    Error::SessionLimitExceeded,
+    Error::AttestationNotFound,
+    Error::AlreadyLinked,
+    Error::HeldTransferNotFound,
+    Error::TransferRejected,
+    Error::TierLimitExceeded,
+    Error::AuctionNotFound,
+    Error::BidTooLow,
+    Error::StreamNotFound,
+    Error::DrawNotFound,
+    Error::AlreadyEntered,
+    Error::CouponNotFound,
+    Error::CouponExhausted,
+    Error::DepositNotFound,
+    Error::NameTooLong,
+    Error::NotActivated,
+    Error::TransferNotFound,
+    Error::ClawbackExists,
+    Error::ClawbackNotFound,
+    Error::ClawbackExecuted,
+    Error::TooManyPayments,
+    Error::MultisigNotFound,
+    Error::AlreadyMultisig,
+    Error::MultisigTransferNotFound,
+    Error::MultisigTransferExecuted,
+    Error::EpochCapExceeded,
+    Error::InvitationNotFound,
+    Error::InvitationExhausted,
+    Error::InvitationExists,
+    Error::InvalidName,
+    Error::WalletFrozen,
+    Error::InvalidMetadata,
+    Error::WalletClosed,
+    Error::AllowanceExceeded,
+    Error::SweepRuleNotFound,
+    Error::EscrowNotFound,
+    Error::HtlcNotFound,
+    Error::InvalidPreimage,
+    Error::AssetNotFound,
+    Error::BalanceOverflow,
+    Error::InvalidMemo,
+    Error::TransferHeld,
+];
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Verification => "Transaction verification failed",
+            Error::WalletExists => "Wallet already exists",
+            Error::WalletNotFound => "Wallet not found",
+            Error::SenderNotFound => "Sender wallet not found",
+            Error::ReceiverNotFound => "Receiver wallet not found",
+            Error::InsufficientFunds => "Insufficient funds",
+            Error::FeeTooLow => "Fee is lower than the base fee",
+            Error::InvalidHeight => "Height has already been reached",
+            Error::DeferredTransferNotFound => "Deferred transfer not found",
+            Error::PayoutNotFound => "Insurance payout not found",
+            Error::PayoutExecuted => "Insurance payout has already been made",
+            Error::Unauthorized => "Signer is not authorized",
+            Error::NotConfigured => "Action is not enabled in the service configuration",
+            Error::DependencyFailed => "Dependency has failed",
+            Error::DependencyPending => "Dependency has not been executed in the same block",
+            Error::SessionKeyNotFound => "Session key not found",
+            Error::SessionKeyExpired => "Session key has expired",
+            Error::SessionLimitExceeded => "Amount exceeds the limit of the session key",
+            Error::AttestationNotFound => "Wallet is not attested",
+            Error::AlreadyLinked => "Both wallets already belong to groups",
+            Error::HeldTransferNotFound => "Held transfer not found",
+            Error::TransferRejected => "Transfer has been rejected by compliance",
+            Error::TierLimitExceeded => "Amount exceeds the limit of the account tier",
+            Error::AuctionNotFound => "Auction not found",
+            Error::BidTooLow => "Bid is too low",
+            Error::StreamNotFound => "Payment stream not found",
+            Error::DrawNotFound => "Lottery draw not found",
+            Error::AlreadyEntered => "Wallet has already entered the draw",
+            Error::CouponNotFound => "Fee coupon not found",
+            Error::CouponExhausted => "Fee coupon is expired or used up",
+            Error::DepositNotFound => "Deposit not found",
+            Error::NameTooLong => "Wallet name is too long",
+            Error::NotActivated => "Transaction type is not activated yet",
+            Error::TransferNotFound => "Transfer not found",
+            Error::ClawbackExists => "Clawback of the transfer has already been proposed",
+            Error::ClawbackNotFound => "Clawback not found",
+            Error::ClawbackExecuted => "Clawback has already been executed",
+            Error::TooManyPayments => "Batch transfer has too many payments",
+            Error::MultisigNotFound => "Wallet is not a multisignature wallet",
+            Error::AlreadyMultisig => "Wallet is already a multisignature wallet",
+            Error::MultisigTransferNotFound => "Multisignature transfer not found",
+            Error::MultisigTransferExecuted => "Multisignature transfer has already been made",
+            Error::EpochCapExceeded => "Action exceeds the cap of the epoch",
+            Error::InvitationNotFound => "Invitation not found",
+            Error::InvitationExhausted => "Invitation is expired or used up",
+            Error::InvitationExists => "Invitation with the same code already exists",
+            Error::InvalidName => "Wallet name is not normalized or mixes scripts",
+            Error::WalletFrozen => "Wallet is frozen",
+            Error::InvalidMetadata => "Wallet metadata is too long or not normalized",
+            Error::WalletClosed => "Wallet is closed",
+            Error::AllowanceExceeded => "Amount exceeds the allowance of the spender",
+            Error::SweepRuleNotFound => "Sweep rule not found",
+            Error::EscrowNotFound => "Escrow not found",
+            Error::HtlcNotFound => "Hashed time-lock contract not found",
+            Error::InvalidPreimage => "Preimage does not match the hash lock",
+            Error::AssetNotFound => "Asset not found",
+            Error::BalanceOverflow => "Amount overflows the balance",
+            Error::InvalidMemo => "Memo is too long or not normalized",
+            Error::TransferHeld => "Transfer would be held for review by compliance",
+        }
+    }
+}
+
+/// Side-effect-free check whether a transaction would change the state if
+/// executed against the given view of the storage.
+///
+/// `Transaction::execute` of every transaction of the service first runs the same
+/// check against the fork it is executed on and changes the state only if
+/// the check succeeds, so a successful validation against a snapshot means the
+/// transaction takes effect if it is executed in the next block.
+pub trait Validate {
+    /// Validate the transaction against the view. The signature is not checked;
+    /// see `Transaction::verify`.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error>;
+}
+
+/// Check a transaction (including its signature) against a view of the storage,
+/// e.g. a snapshot of the blockchain.
+pub fn validate<V, T>(view: T, tx: &V) -> Result<(), Error>
+where
+    V: Validate + Transaction,
+    T: AsRef<Snapshot>,
+{
+    if !tx.verify() {
+        return Err(Error::Verification);
+    }
+    tx.validate(&CurrencySchema::new(view))
+}
+
+/// Check a wallet creation against a view of the storage.
+pub fn validate_create_wallet<T>(view: T, tx: &TxCreateWallet) -> Result<(), Error>
+where
+    T: AsRef<Snapshot>,
+{
+    validate(view, tx)
+}
+
+/// Check a transfer against a view of the storage. A valid transfer tripping
+/// the velocity rules fails with `Error::TransferHeld`: it would be held until
+/// reviewed rather than made.
+pub fn validate_transfer<T: AsRef<Snapshot>>(view: T, tx: &TxTransfer) -> Result<(), Error> {
+    if !tx.verify() {
+        return Err(Error::Verification);
+    }
+    let schema = CurrencySchema::new(view);
+    tx.validate(&schema)?;
+    if schema.is_held(tx.from(), tx.to(), tx.amount()) {
+        return Err(Error::TransferHeld);
+    }
+    Ok(())
+}
+
+/// Minimum fee of a transfer: the base fee and, if account tiers are configured,
+/// the fee required by the rules of the sender's tier. Fails if the amount exceeds
+/// the limit of the tier.
+fn min_transfer_fee<T: AsRef<Snapshot>>(
+    schema: &CurrencySchema<T>,
+    from: &PublicKey,
+    amount: u64,
+) -> Result<u64, Error> {
+    let mut min_fee = schema.base_fee();
+    if let Some(tiers) = schema.config().tiers {
+        let tier = schema.wallet(from).map_or(TIER_BASIC, |wallet| wallet.tier());
+        let rules = tiers.rules(tier);
+        if rules.max_amount.map_or(false, |max| amount > max) {
+            return Err(Error::TierLimitExceeded);
+        }
+        min_fee = ::std::cmp::max(min_fee, rules.min_fee(amount));
+    }
+    Ok(min_fee)
+}
+
+/// Check the fee of a transfer against the base fee and, if account tiers are
+/// configured, the amount and the fee against the rules of the sender's tier.
+fn check_transfer_fee<T: AsRef<Snapshot>>(
+    schema: &CurrencySchema<T>,
+    from: &PublicKey,
+    amount: u64,
+    fee: u64,
+) -> Result<(), Error> {
+    if fee < min_transfer_fee(schema, from, amount)? {
+        return Err(Error::FeeTooLow);
+    }
+    Ok(())
+}
+
+/// Check the existence of both wallets of a transfer and the sender's balance,
+/// including the given fee and the insurance levy.
+fn check_transfer_funds<T: AsRef<Snapshot>>(
+    schema: &CurrencySchema<T>,
+    tx: &TxTransfer,
+    fee: u64,
+) -> Result<(), Error> {
+    check_receiver(schema, tx.to(), tx.amount())?;
+    let cost = schema.transfer_cost(tx.amount(), fee)?;
+    check_payer(schema, tx.from(), cost, Error::SenderNotFound)
+}
+
+/// Check that a wallet exists and can pay the given amount. Coins of
+/// multisignature wallets are only spent by `TxMultisigTransfer`.
+fn check_payer<T: AsRef<Snapshot>>(
+    schema: &CurrencySchema<T>,
+    pub_key: &PublicKey,
+    amount: u64,
+    not_found: Error,
+) -> Result<(), Error> {
+    let wallet = schema.wallet(pub_key).ok_or(not_found)?;
+    if wallet.closed() {
+        return Err(Error::WalletClosed);
+    }
+    if wallet.frozen() {
+        return Err(Error::WalletFrozen);
+    }
+    if schema.multisigs().contains(pub_key) {
+        return Err(Error::Unauthorized);
+    }
+    if schema.spendable_balance(pub_key) < amount {
+        return Err(Error::InsufficientFunds);
+    }
+    Ok(())
+}
+
+/// Check that a wallet exists and can receive `amount` coins.
+fn check_receiver<T: AsRef<Snapshot>>(
+    schema: &CurrencySchema<T>,
+    pub_key: &PublicKey,
+    amount: u64,
+) -> Result<(), Error> {
+    match schema.wallet(pub_key) {
+        Some(ref wallet) if wallet.closed() => Err(Error::WalletClosed),
+        Some(ref wallet) if wallet.balance().checked_add(amount).is_none() => {
+            Err(Error::BalanceOverflow)
+        }
+        Some(_) => Ok(()),
+        None => Err(Error::ReceiverNotFound),
+    }
+}
+
+/// Check that `amount` new coins can be created without the total of minted
+/// coins overflowing `u64`.
+fn check_mint<T: AsRef<Snapshot>>(schema: &CurrencySchema<T>, amount: u64) -> Result<(), Error> {
+    let minted = schema.minted().get().unwrap_or(0);
+    match minted.checked_add(amount) {
+        Some(_) => Ok(()),
+        None => Err(Error::BalanceOverflow),
+    }
+}
+
+/// Check wallet metadata against the configured limits. The display name is
+/// checked like a wallet name, but may be empty.
+fn check_metadata<T: AsRef<Snapshot>>(
+    schema: &CurrencySchema<T>,
+    display_name: &str,
+    contact_uri: &str,
+) -> Result<(), Error> {
+    let limits = schema.config().limits;
+    let display_name = text::check(display_name, limits.max_name_length);
+    let contact_uri = text::check(contact_uri, limits.max_metadata_length);
+    if display_name.is_err() || contact_uri.is_err() {
+        return Err(Error::InvalidMetadata);
+    }
+    Ok(())
+}
+
+/// Check a transfer memo against the configured limits.
+fn check_memo<T: AsRef<Snapshot>>(schema: &CurrencySchema<T>, memo: &str) -> Result<(), Error> {
+    let max_length = schema.config().limits.max_memo_length;
+    text::check(memo, max_length).map_err(|_| Error::InvalidMemo)
+}
+
+/// Check a wallet name against the configured limits.
+fn check_name<T: AsRef<Snapshot>>(schema: &CurrencySchema<T>, name: &str) -> Result<(), Error> {
+    let max_length = schema.config().limits.max_name_length;
+    match text::check(name, max_length) {
+        Ok(()) => Ok(()),
+        Err(TextError::TooLong) => Err(Error::NameTooLong),
+        Err(_) => Err(Error::InvalidName),
+    }
+}
+
+impl Validate for TxCreateWallet {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_name(schema, self.name())?;
+        check_metadata(schema, self.display_name(), self.contact_uri())?;
+        if schema.wallet(self.pub_key()).is_some() {
+            return Err(Error::WalletExists);
+        }
+        if *self.invitation() != Hash::zero() {
+            let invitation = schema.invitations().get(self.invitation()).ok_or(
+                Error::InvitationNotFound,
+            )?;
+            if !invitation.is_valid(schema.height()) {
+                return Err(Error::InvitationExhausted);
+            }
+            Ok(())
+        } else {
+            check_mint(schema, INIT_BALANCE)
+        }
+    }
+}
+
+/// Execute a transaction.
+impl Transaction for TxCreateWallet {
+    /// Verify integrity of the transaction by checking the transaction
+    /// signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.pub_key())
+    }
+
+    /// Apply logic to the storage when executing the transaction.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        let result = self.validate(&schema);
+        if result.is_ok() {
+            let balance = match schema.invitations().get(self.invitation()) {
+                Some(invitation) => {
+                    let balance = invitation.balance();
+                    let invitation = invitation.redeem();
+                    if invitation.uses() == invitation.max_uses() {
+                        schema.invitations_mut().remove(self.invitation());
+                    } else {
+                        schema.invitations_mut().put(self.invitation(), invitation);
+                    }
+                    balance
+                }
+                None => {
+                    schema.record_mint(INIT_BALANCE);
+                    INIT_BALANCE
+                }
+            };
+            let wallet = Wallet::new(
+                self.pub_key(),
+                self.name(),
+                balance,
+                false,
+                TIER_BASIC,
+                0,
+                &Hash::zero(),
+                false,
+                self.display_name(),
+                self.contact_uri(),
+                self.avatar(),
+                false,
+            );
+            println!("Create the wallet: {:?}", wallet);
+            schema.put_wallet(self.pub_key(), wallet);
+            schema.index_name(self.pub_key(), self.name());
+            schema.append_history(self.pub_key(), &self.hash());
+        }
+        schema.record_result(&self.hash(), result);
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxUpdateWallet {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if schema.wallet(self.pub_key()).is_none() {
+            return Err(Error::WalletNotFound);
+        }
+        check_metadata(schema, self.display_name(), self.contact_uri())
+    }
+}
+
+impl Transaction for TxUpdateWallet {
+    /// Check correctness of the owner's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.pub_key())
+    }
+
+    /// Replace the metadata and record the change in the wallet history.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let wallet = schema.wallet(self.pub_key()).unwrap().set_metadata(
+            self.display_name(),
+            self.contact_uri(),
+            self.avatar(),
+        );
+        println!("Update the wallet: {:?}", wallet);
+        schema.put_wallet(self.pub_key(), wallet);
+        schema.append_history(self.pub_key(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxCloseWallet {
+    /// Check that the owner can spend and the beneficiary can receive coins.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_payer(schema, self.owner(), 0, Error::WalletNotFound)?;
+        let balance = schema.wallet(self.owner()).unwrap().balance();
+        check_receiver(schema, self.beneficiary(), balance)
+    }
+}
+
+impl Transaction for TxCloseWallet {
+    /// Check the owner's signature and that the beneficiary is another wallet.
+    fn verify(&self) -> bool {
+        self.owner() != self.beneficiary() && self.verify_signature(self.owner())
+    }
+
+    /// Sweep the balance and the asset balances to the beneficiary and mark
+    /// the wallet as closed. Coins credited to the wallet later, such as
+    /// refunds, go to the beneficiary.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let wallet = schema.wallet(self.owner()).unwrap();
+        let balance = wallet.balance();
+        let wallet = wallet.decrease(balance).set_closed();
+        println!("Close the wallet: {:?}", wallet);
+        schema.put_wallet(self.owner(), wallet);
+        schema.beneficiaries_mut().put(self.owner(), *self.beneficiary());
+        schema.credit(self.beneficiary(), balance);
+        let assets: Vec<(Hash, u64)> = schema.asset_balances(self.owner()).iter().collect();
+        for (asset, amount) in assets {
+            schema.transfer_asset(self.owner(), self.beneficiary(), &asset, amount);
+        }
+        schema.append_history(self.owner(), &self.hash());
+        schema.append_history(self.beneficiary(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxRenameWallet {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if schema.wallet(self.pub_key()).is_none() {
+            return Err(Error::WalletNotFound);
+        }
+        check_name(schema, self.name())
+    }
+}
+
+impl Transaction for TxRenameWallet {
+    /// Check correctness of the owner's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.pub_key())
+    }
+
+    /// Replace the name, reindex it for the similar names check and record
+    /// the change in the wallet history.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let wallet = schema.wallet(self.pub_key()).unwrap();
+        schema.unindex_name(self.pub_key(), wallet.name());
+        let wallet = wallet.set_name(self.name());
+        println!("Rename the wallet: {:?}", wallet);
+        schema.put_wallet(self.pub_key(), wallet);
+        schema.index_name(self.pub_key(), self.name());
+        schema.append_history(self.pub_key(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxTransfer {
+    /// Check the memo, the fee and the tier limit, the dependency, the existence
+    /// of both wallets and the sender's balance, including the insurance levy.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_memo(schema, self.memo())?;
+        check_transfer_fee(schema, self.from(), self.amount(), self.fee())?;
+        if *self.depends_on() != Hash::zero() {
+            match schema.tx_result(self.depends_on()) {
+                Some(Ok(())) => {}
+                Some(Err(_)) => return Err(Error::DependencyFailed),
+                None => return Err(Error::DependencyPending),
+            }
+        }
+        check_transfer_funds(schema, self, self.fee())
+    }
+}
+
+impl Transaction for TxTransfer {
+    /// Check if the sender is not the receiver. Check correctness of the
+    /// sender's signature.
+    fn verify(&self) -> bool {
+        (*self.from() != *self.to()) && self.verify_signature(self.from())
+    }
+
+    /// Retrieve two wallets to apply the transfer. Check the fee and the sender's
+    /// balance and apply changes to the balances of the wallets. If the
+    /// insurance fund is configured, charge the sender the insurance levy.
+    ///
+    /// A transfer depending on a transaction which is not executed yet is queued
+    /// and charged the fee. It is executed right after the dependency if the
+    /// dependency is executed later in the same block, and fails with
+    /// `DependencyPending` otherwise or if the queue is full. A transfer tripping
+    /// the velocity rules is held until reviewed; its result is recorded then.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        schema.update_base_fee();
+        let result = match self.validate(&schema) {
+            Ok(()) => schema.make_transfer(self, self.fee()),
+            Err(Error::DependencyPending) => schema.queue_transfer(self).err().map(Err),
+            Err(err) => Some(Err(err)),
+        };
+        if let Some(result) = result {
+            schema.record_result(&self.hash(), result);
+        }
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxRegisterPaymentReference {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if schema.wallet(self.payee()).is_none() {
+            return Err(Error::WalletNotFound);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxRegisterPaymentReference {
+    /// Check correctness of the payee's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.payee())
+    }
+
+    /// Register the hash of the transaction as a payment reference.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let height = schema.height();
+        println!("Register payment reference {:?} of {:?}", self.hash(), self.payee());
+        schema.payment_references_mut(self.payee()).put(&self.hash(), height);
+        let root = schema.payment_references(self.payee()).root_hash();
+        schema.update_index_root("cryptocurrency.payment_references", self.payee(), root);
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxCreateAndFundWallet {
+    /// Check the name, that the wallet does not exist yet, the fee and the tier
+    /// limit, the funder's balance and that the funding would not be held.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_name(schema, self.name())?;
+        check_transfer_fee(schema, self.funder(), self.amount(), self.fee())?;
+        if schema.wallet(self.pub_key()).is_some() {
+            return Err(Error::WalletExists);
+        }
+        let cost = schema.transfer_cost(self.amount(), self.fee())?;
+        check_payer(schema, self.funder(), cost, Error::SenderNotFound)?;
+        if schema.is_held(self.funder(), self.pub_key(), self.amount()) {
+            return Err(Error::TransferHeld);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxCreateAndFundWallet {
+    /// Check if the funder is not the owner of the new wallet. Check correctness
+    /// of the funder's signature.
+    fn verify(&self) -> bool {
+        (*self.funder() != *self.pub_key()) && self.verify_signature(self.funder())
+    }
+
+    /// Create an empty wallet and transfer the amount to it.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        schema.update_base_fee();
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let wallet = Wallet::new(
+            self.pub_key(),
+            self.name(),
+            0,
+            false,
+            TIER_BASIC,
+            0,
+            &Hash::zero(),
+            false,
+            "",
+            "",
+            &Hash::zero(),
+            false,
+        );
+        println!("Create the wallet: {:?}", wallet);
+        schema.put_wallet(self.pub_key(), wallet);
+        schema.index_name(self.pub_key(), self.name());
+        // The funding is checked not to be held, so it is made at once.
+        schema.pay(
+            &self.hash(),
+            self.funder(),
+            self.pub_key(),
+            self.amount(),
+            self.fee(),
+            &Hash::zero(),
+        );
+        schema.append_history(self.funder(), &self.hash());
+        schema.append_history(self.pub_key(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxAuthorizeSessionKey {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if schema.wallet(self.owner()).is_none() {
+            return Err(Error::WalletNotFound);
+        }
+        if self.expires_at() <= schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxAuthorizeSessionKey {
+    /// Check if the session key differs from the wallet key. Check correctness
+    /// of the owner's signature.
+    fn verify(&self) -> bool {
+        (*self.owner() != *self.session_key()) && self.verify_signature(self.owner())
+    }
+
+    /// Store the scope of the session key.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let scope = SessionKey::new(self.max_amount(), self.expires_at());
+        println!("Authorize session key {:?}: {:?}", self.session_key(), scope);
+        schema.session_keys_mut(self.owner()).put(self.session_key(), scope);
+        schema.update_session_keys_root(self.owner());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxRevokeSessionKey {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if schema.session_keys(self.owner()).get(self.session_key()).is_none() {
+            return Err(Error::SessionKeyNotFound);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxRevokeSessionKey {
+    /// Check correctness of the owner's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.owner())
+    }
+
+    /// Remove the session key.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        println!("Revoke session key {:?}", self.session_key());
+        schema.session_keys_mut(self.owner()).remove(self.session_key());
+        schema.update_session_keys_root(self.owner());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxSessionTransfer {
+    /// Check the scope of the session key, then the same conditions as for
+    /// `TxTransfer`.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let scope = schema.session_keys(self.from()).get(self.session_key()).ok_or(
+            Error::SessionKeyNotFound,
+        )?;
+        if scope.expires_at() <= schema.height() {
+            return Err(Error::SessionKeyExpired);
+        }
+        if self.amount() > scope.max_amount() {
+            return Err(Error::SessionLimitExceeded);
+        }
+        check_transfer_fee(schema, self.from(), self.amount(), self.fee())?;
+        check_receiver(schema, self.to(), self.amount())?;
+        let cost = schema.transfer_cost(self.amount(), self.fee())?;
+        check_payer(schema, self.from(), cost, Error::SenderNotFound)
+    }
+}
+
+impl Transaction for TxSessionTransfer {
+    /// Check if the sender is not the receiver. Check correctness of the
+    /// session key signature.
+    fn verify(&self) -> bool {
+        (*self.from() != *self.to()) && self.verify_signature(self.session_key())
+    }
+
+    /// Transfer the coins if the session key is authorized for the amount.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        schema.update_base_fee();
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let outcome = schema.pay(
+            &self.hash(),
+            self.from(),
+            self.to(),
+            self.amount(),
+            self.fee(),
+            &Hash::zero(),
+        );
+        if outcome == PaymentOutcome::Held {
+            return;
+        }
+        schema.append_history(self.from(), &self.hash());
+        schema.append_history(self.to(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxApprove {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if schema.wallet(self.owner()).is_none() {
+            return Err(Error::WalletNotFound);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxApprove {
+    /// Check if the owner is not the spender. Check correctness of the owner's signature.
+    fn verify(&self) -> bool {
+        (*self.owner() != *self.spender()) && self.verify_signature(self.owner())
+    }
+
+    /// Store or revoke the allowance.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        {
+            let mut allowances = schema.allowances_mut(self.owner());
+            if self.amount() == 0 {
+                allowances.remove(self.spender());
+            } else {
+                allowances.put(self.spender(), self.amount());
+            }
+        }
+        schema.update_allowances_root(self.owner());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxSetSweepRule {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if schema.wallet(self.owner()).is_none() {
+            return Err(Error::WalletNotFound);
+        }
+        check_receiver(schema, self.cold_wallet(), 0)
+    }
+}
+
+impl Transaction for TxSetSweepRule {
+    /// Check if the cold wallet is another wallet. Check correctness of the owner's signature.
+    fn verify(&self) -> bool {
+        (*self.owner() != *self.cold_wallet()) && self.verify_signature(self.owner())
+    }
+
+    /// Store the rule. The excess balance is swept in the next block.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let rule = SweepRule::new(self.threshold(), self.cold_wallet());
+        schema.sweep_rules_mut().put(self.owner(), rule);
+        schema.sweep_candidates_mut().put(self.owner(), ());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxClearSweepRule {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if !schema.sweep_rules().contains(self.owner()) {
+            return Err(Error::SweepRuleNotFound);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxClearSweepRule {
+    /// Check correctness of the owner's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.owner())
+    }
+
+    /// Remove the rule.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        schema.sweep_rules_mut().remove(self.owner());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxTransferFrom {
+    /// Check the allowance of the spender, then the same conditions as for `TxTransfer`.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let allowance = schema.allowances(self.from()).get(self.spender()).unwrap_or(0);
+        if self.amount() > allowance {
+            return Err(Error::AllowanceExceeded);
+        }
+        check_transfer_fee(schema, self.from(), self.amount(), self.fee())?;
+        check_receiver(schema, self.to(), self.amount())?;
+        let cost = schema.transfer_cost(self.amount(), self.fee())?;
+        check_payer(schema, self.from(), cost, Error::SenderNotFound)
+    }
+}
+
+impl Transaction for TxTransferFrom {
+    /// Check if the sender is not the receiver. Check correctness of the
+    /// spender's signature.
+    fn verify(&self) -> bool {
+        (*self.from() != *self.to()) && self.verify_signature(self.spender())
+    }
+
+    /// Transfer the coins and reduce the allowance by the amount.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        schema.update_base_fee();
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let allowance = schema.allowances(self.from()).get(self.spender()).unwrap();
+        {
+            let mut allowances = schema.allowances_mut(self.from());
+            if allowance == self.amount() {
+                allowances.remove(self.spender());
+            } else {
+                allowances.put(self.spender(), allowance - self.amount());
+            }
+        }
+        schema.update_allowances_root(self.from());
+        let outcome = schema.pay(
+            &self.hash(),
+            self.from(),
+            self.to(),
+            self.amount(),
+            self.fee(),
+            &Hash::zero(),
+        );
+        if outcome == PaymentOutcome::Held {
+            return;
+        }
+        schema.append_history(self.from(), &self.hash());
+        schema.append_history(self.to(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxBatchTransfer {
+    /// Check the number of payments and the existence of the receivers. The fee
+    /// and the tier limit are checked as for a single transfer of the total
+    /// amount; the sender pays the insurance levy for every payment.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let payments = self.payments();
+        if payments.len() as u64 > schema.config().limits.max_batch_payments {
+            return Err(Error::TooManyPayments);
+        }
+        for payment in &payments {
+            check_receiver(schema, payment.to(), payment.amount())?;
+        }
+        let amounts: Vec<u64> = payments.iter().map(|payment| payment.amount()).collect();
+        let cost = schema.batch_transfer_cost(self.from(), &amounts, self.fee())?;
+        check_payer(schema, self.from(), cost, Error::SenderNotFound)
+    }
+}
+
+impl Transaction for TxBatchTransfer {
+    /// Check that there are payments and the sender is not among the receivers.
+    /// Check correctness of the sender's signature.
+    fn verify(&self) -> bool {
+        let payments = self.payments();
+        !payments.is_empty() && payments.iter().all(|payment| payment.to() != self.from()) &&
+            self.verify_signature(self.from())
+    }
+
+    /// Make all payments of the batch. The fee is charged once. Payments held
+    /// by the velocity rules are identified by `batch_payment_id`.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        schema.update_base_fee();
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let mut fee = self.fee();
+        for (index, payment) in self.payments().into_iter().enumerate() {
+            let outcome = schema.pay(
+                &batch_payment_id(&self.hash(), index),
+                self.from(),
+                payment.to(),
+                payment.amount(),
+                fee,
+                &Hash::zero(),
+            );
+            if outcome != PaymentOutcome::Held {
+                schema.append_history(payment.to(), &self.hash());
+            }
+            fee = 0;
+        }
+        schema.append_history(self.from(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxSetupMultisig {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if schema.wallet(self.pub_key()).is_none() {
+            return Err(Error::WalletNotFound);
+        }
+        if schema.multisigs().contains(self.pub_key()) {
+            return Err(Error::AlreadyMultisig);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxSetupMultisig {
+    /// Check that the threshold can be met by at most 64 distinct signers.
+    /// Check correctness of the wallet owner's signature.
+    fn verify(&self) -> bool {
+        let signers = self.signers();
+        let distinct = signers.iter().collect::<BTreeSet<_>>().len() == signers.len();
+        distinct && signers.len() <= 64 && self.threshold() >= 1 &&
+            self.threshold() <= signers.len() as u64 &&
+            self.verify_signature(self.pub_key())
+    }
+
+    /// Register the signers of the wallet.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let multisig = Multisig::new(self.signers(), self.threshold());
+        schema.multisigs_mut().put(self.pub_key(), multisig);
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+/// Check that the wallet is a multisignature wallet and the signer is its signer.
+fn check_signer<T: AsRef<Snapshot>>(
+    schema: &CurrencySchema<T>,
+    wallet: &PublicKey,
+    signer: &PublicKey,
+) -> Result<(), Error> {
+    let multisig = schema.multisigs().get(wallet).ok_or(Error::MultisigNotFound)?;
+    match multisig.signer_index(signer) {
+        Some(index) if index < 64 => Ok(()),
+        _ => Err(Error::Unauthorized),
+    }
+}
+
+impl Validate for TxMultisigTransfer {
+    /// Check the signer, the fee and the existence of the receiver. The balance
+    /// is checked when the transfer is approved.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_signer(schema, self.wallet(), self.signer())?;
+        if schema.wallet(self.wallet()).map_or(false, |wallet| wallet.frozen()) {
+            return Err(Error::WalletFrozen);
+        }
+        check_transfer_fee(schema, self.wallet(), self.amount(), self.fee())?;
+        check_receiver(schema, self.to(), self.amount())?;
+        Ok(())
+    }
+}
+
+impl Transaction for TxMultisigTransfer {
+    /// Check if the sender is not the receiver. Check correctness of the
+    /// signer's signature.
+    fn verify(&self) -> bool {
+        (*self.wallet() != *self.to()) && self.verify_signature(self.signer())
+    }
+
+    /// Register the transfer and approve it on behalf of the proposing signer.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        schema.update_base_fee();
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let transfer =
+            MultisigTransfer::new(self.wallet(), self.to(), self.amount(), self.fee(), 0, 0);
+        schema.approve_multisig_transfer(&self.hash(), transfer, self.signer());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxApproveMultisigTransfer {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let transfer = schema.multisig_transfers().get(self.transfer()).ok_or(
+            Error::MultisigTransferNotFound,
+        )?;
+        check_signer(schema, transfer.wallet(), self.signer())?;
+        if transfer.executed_at() != 0 {
+            return Err(Error::MultisigTransferExecuted);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxApproveMultisigTransfer {
+    /// Check correctness of the signer's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.signer())
+    }
+
+    /// Add the approval to a pending transfer.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let transfer = schema.multisig_transfers().get(self.transfer()).unwrap();
+        schema.approve_multisig_transfer(self.transfer(), transfer, self.signer());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+/// Check that the signer is the configured verifier.
+fn check_verifier<T: AsRef<Snapshot>>(
+    schema: &CurrencySchema<T>,
+    verifier: &PublicKey,
+) -> Result<(), Error> {
+    match schema.config().verifier {
+        Some(ref key) if key == verifier => Ok(()),
+        Some(_) => Err(Error::Unauthorized),
+        None => Err(Error::NotConfigured),
+    }
+}
+
+impl Validate for TxAttestWallet {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_verifier(schema, self.verifier())?;
+        if schema.wallet(self.wallet()).is_none() {
+            return Err(Error::WalletNotFound);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxAttestWallet {
+    /// Check correctness of the verifier's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.verifier())
+    }
+
+    /// Record the attestation and set the badge of the wallet.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let attestation = Attestation::new(self.verifier(), schema.height());
+        println!("Attest wallet {:?}: {:?}", self.wallet(), attestation);
+        schema.attestations_mut().put(self.wallet(), attestation);
+        schema.set_verified(self.wallet(), true);
+        schema.append_history(self.wallet(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxRevokeAttestation {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_verifier(schema, self.verifier())?;
+        if schema.attestations().get(self.wallet()).is_none() {
+            return Err(Error::AttestationNotFound);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxRevokeAttestation {
+    /// Check correctness of the verifier's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.verifier())
+    }
+
+    /// Remove the attestation and the badge of the wallet.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        println!("Revoke attestation of wallet {:?}", self.wallet());
+        schema.attestations_mut().remove(self.wallet());
+        schema.set_verified(self.wallet(), false);
+        schema.append_history(self.wallet(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+/// Check that the signer is the configured admin and the wallet exists.
+fn check_admin<T: AsRef<Snapshot>>(
+    schema: &CurrencySchema<T>,
+    admin: &PublicKey,
+    wallet: &PublicKey,
+) -> Result<(), Error> {
+    match schema.config().admin {
+        Some(ref key) if key == admin => {}
+        Some(_) => return Err(Error::Unauthorized),
+        None => return Err(Error::NotConfigured),
+    }
+    if schema.wallet(wallet).is_none() {
+        return Err(Error::WalletNotFound);
+    }
+    Ok(())
+}
+
+impl Validate for TxFreezeWallet {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_admin(schema, self.admin(), self.wallet())
+    }
+}
+
+impl Transaction for TxFreezeWallet {
+    /// Check correctness of the admin's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.admin())
+    }
+
+    /// Mark the wallet as frozen.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let wallet = schema.wallet(self.wallet()).unwrap().set_frozen(true);
+        println!("Freeze wallet: {:?}", wallet);
+        schema.put_wallet(self.wallet(), wallet);
+        schema.append_history(self.wallet(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxUnfreezeWallet {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_admin(schema, self.admin(), self.wallet())
+    }
+}
+
+impl Transaction for TxUnfreezeWallet {
+    /// Check correctness of the admin's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.admin())
+    }
+
+    /// Clear the frozen mark of the wallet.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let wallet = schema.wallet(self.wallet()).unwrap().set_frozen(false);
+        println!("Unfreeze wallet: {:?}", wallet);
+        schema.put_wallet(self.wallet(), wallet);
+        schema.append_history(self.wallet(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxLinkWallets {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if schema.wallet(self.wallet()).is_none() || schema.wallet(self.other()).is_none() {
+            return Err(Error::WalletNotFound);
+        }
+        let groups = schema.wallet_groups();
+        if groups.contains(self.wallet()) && groups.contains(self.other()) {
+            return Err(Error::AlreadyLinked);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxLinkWallets {
+    /// Check correctness of the signatures of both wallets.
+    fn verify(&self) -> bool {
+        let consent = link_consent(self.wallet(), self.other());
+        (*self.wallet() != *self.other()) && self.verify_signature(self.wallet()) &&
+            crypto::verify(self.other_signature(), consent.as_ref(), self.other())
+    }
+
+    /// Add the wallet which is not in a group yet to the group of the other one,
+    /// or create a new group of both wallets.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let group = {
+            let groups = schema.wallet_groups();
+            groups
+                .get(self.wallet())
+                .or_else(|| groups.get(self.other()))
+                .unwrap_or_else(|| self.hash())
+        };
+        for pub_key in &[*self.wallet(), *self.other()] {
+            if !schema.wallet_groups().contains(pub_key) {
+                println!("Link wallet {:?} into group {:?}", pub_key, group);
+                schema.wallet_groups_mut().put(pub_key, group);
+                schema.group_members_mut(&group).push(*pub_key);
+                let root = schema.group_members(&group).root_hash();
+                schema.update_index_root("cryptocurrency.group_members", &group, root);
+            }
+        }
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxReviewHeldTransfer {
+    /// Check that the transaction is signed by the compliance key and the transfer is held.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let rules = schema.config().velocity.ok_or(Error::NotConfigured)?;
+        if rules.compliance != *self.compliance() {
+            return Err(Error::Unauthorized);
+        }
+        if !schema.held_transfers().contains(self.tx_hash()) {
+            return Err(Error::HeldTransferNotFound);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxReviewHeldTransfer {
+    /// Check correctness of the compliance officer's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.compliance())
+    }
+
+    /// Credit the held amount to the receiver or return it to the sender.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        schema.review_held_transfer(self.tx_hash(), self.release());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxAssignTier {
+    /// Check that the transaction is signed by the tier admin and the wallet exists.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let tiers = schema.config().tiers.ok_or(Error::NotConfigured)?;
+        if tiers.admin != *self.admin() {
+            return Err(Error::Unauthorized);
+        }
+        if schema.wallet(self.wallet()).is_none() {
+            return Err(Error::WalletNotFound);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxAssignTier {
+    /// Check that the tier is known and the admin's signature is correct.
+    fn verify(&self) -> bool {
+        self.tier() <= TIER_INSTITUTIONAL && self.verify_signature(self.admin())
+    }
+
+    /// Set the tier of the wallet.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let wallet = schema.wallet(self.wallet()).unwrap().set_tier(self.tier());
+        println!("Assign tier: {:?}", wallet);
+        schema.put_wallet(self.wallet(), wallet);
+        schema.append_history(self.wallet(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxCreateAuction {
+    /// Check that the seller's wallet exists and the deadline is in the future.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if schema.wallet(self.seller()).is_none() {
+            return Err(Error::WalletNotFound);
+        }
+        if self.deadline() <= schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxCreateAuction {
+    /// Check correctness of the seller's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.seller())
+    }
+
+    /// Open the auction.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let auction = Auction::new(
+            self.seller(),
+            self.item(),
+            self.reserve_price(),
+            self.deadline(),
+            self.seller(),
+            0,
+            0,
+        );
+        println!("Open auction: {:?}", auction);
+        schema.auctions_mut().put(&self.hash(), auction);
+        schema.schedule(self.deadline(), SCHEDULED_AUCTION, &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxBid {
+    /// Check that the auction is open, the bid exceeds the highest bid and
+    /// the reserve price, the previous highest bid can be returned and the
+    /// bidder can pay the bid.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let auction = schema.auctions().get(self.auction()).ok_or(
+            Error::AuctionNotFound,
+        )?;
+        if auction.settled_at() != 0 || auction.deadline() <= schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        if auction.seller() == self.bidder() {
+            return Err(Error::Unauthorized);
+        }
+        if self.amount() < auction.reserve_price() || self.amount() <= auction.highest_bid() {
+            return Err(Error::BidTooLow);
+        }
+        if auction.highest_bid() > 0 &&
+            !schema.can_credit(&schema.payee(auction.highest_bidder()), auction.highest_bid())
+        {
+            return Err(Error::BalanceOverflow);
+        }
+        check_payer(schema, self.bidder(), self.amount(), Error::SenderNotFound)
+    }
+}
+
+impl Transaction for TxBid {
+    /// Check correctness of the bidder's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.bidder())
+    }
+
+    /// Escrow the bid and return the previous highest bid to its bidder.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let auction = schema.auctions().get(self.auction()).unwrap();
+        schema.debit(self.bidder(), self.amount());
+        if auction.highest_bid() > 0 {
+            let outbid = schema.credit(auction.highest_bidder(), auction.highest_bid());
+            println!("Return outbid amount: {:?}", outbid);
+        }
+        let auction = Auction::new(
+            auction.seller(),
+            auction.item(),
+            auction.reserve_price(),
+            auction.deadline(),
+            self.bidder(),
+            self.amount(),
+            0,
+        );
+        println!("Bid in auction: {:?}", auction);
+        schema.auctions_mut().put(self.auction(), auction);
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxOpenStream {
+    /// Check that the receiver exists and the sender can lock the budget.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_receiver(schema, self.to(), self.budget())?;
+        check_payer(schema, self.from(), self.budget(), Error::SenderNotFound)
+    }
+}
+
+impl Transaction for TxOpenStream {
+    /// Check if the sender is not the receiver, the stream pays something, and
+    /// correctness of the sender's signature.
+    fn verify(&self) -> bool {
+        (*self.from() != *self.to()) && self.rate() > 0 && self.budget() > 0 &&
+            self.verify_signature(self.from())
+    }
+
+    /// Lock the budget of the stream.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        schema.debit(self.from(), self.budget());
+        let height = schema.height();
+        let stream = Stream::new(
+            self.from(),
+            self.to(),
+            self.rate(),
+            self.budget(),
+            height,
+            0,
+        );
+        println!("Open stream: {:?}", stream);
+        schema.streams_mut().put(&self.hash(), stream);
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxWithdrawStream {
+    /// Check that the stream exists and pays to the signer, and that the
+    /// signer's wallet is not closed.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let stream = schema.streams().get(self.stream()).ok_or(
+            Error::StreamNotFound,
+        )?;
+        if stream.to() != self.to() {
+            return Err(Error::Unauthorized);
+        }
+        check_receiver(schema, self.to(), 0)
+    }
+}
+
+impl Transaction for TxWithdrawStream {
+    /// Check correctness of the receiver's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.to())
+    }
+
+    /// Pay out the accrued coins.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        if schema.withdraw_stream(self.stream(), &self.hash()) != PaymentOutcome::Held {
+            schema.record_result(&self.hash(), Ok(()));
+        }
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxCloseStream {
+    /// Check that the stream exists, was opened by the signer and the signer
+    /// can be refunded the rest of the budget.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let stream = schema.streams().get(self.stream()).ok_or(
+            Error::StreamNotFound,
+        )?;
+        if stream.from() != self.from() {
+            return Err(Error::Unauthorized);
+        }
+        let refund = stream.budget() - stream.withdrawn();
+        if !schema.can_credit(&schema.payee(self.from()), refund) {
+            return Err(Error::BalanceOverflow);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxCloseStream {
+    /// Check correctness of the sender's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.from())
+    }
+
+    /// Settle the stream with the receiver and refund the rest of the budget.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let outcome = schema.withdraw_stream(self.stream(), &self.hash());
+        if let Some(stream) = schema.streams().get(self.stream()) {
+            let refund = stream.budget() - stream.withdrawn();
+            let sender = schema.credit(self.from(), refund);
+            println!("Close stream {:?}: {:?}", self.stream(), sender);
+            schema.streams_mut().remove(self.stream());
+        }
+        if outcome != PaymentOutcome::Held {
+            schema.record_result(&self.hash(), Ok(()));
+        }
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxLotteryDraw {
+    /// Check that the draw height is in the future and the organizer can pay the prize.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if self.draw_height() <= schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        check_payer(schema, self.organizer(), self.prize(), Error::WalletNotFound)
+    }
+}
+
+impl Transaction for TxLotteryDraw {
+    /// Check correctness of the organizer's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.organizer())
+    }
+
+    /// Lock the prize until the draw.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        schema.debit(self.organizer(), self.prize());
+        let draw = Draw::new(
+            self.organizer(),
+            self.prize(),
+            self.draw_height(),
+            self.organizer(),
+            0,
+        );
+        println!("Create lottery draw: {:?}", draw);
+        schema.draws_mut().put(&self.hash(), draw);
+        schema.schedule(self.draw_height(), SCHEDULED_DRAW, &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxEnterDraw {
+    /// Check that the draw is not made yet and the wallet has not entered it.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let draw = schema.draws().get(self.draw()).ok_or(Error::DrawNotFound)?;
+        if draw.draw_height() <= schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        if schema.wallet(self.wallet()).is_none() {
+            return Err(Error::WalletNotFound);
+        }
+        if schema.draw_entrants(self.draw()).iter().any(
+            |entrant| entrant == *self.wallet(),
+        )
+        {
+            return Err(Error::AlreadyEntered);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxEnterDraw {
+    /// Check correctness of the entrant's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.wallet())
+    }
+
+    /// Add the wallet to the entrants.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        schema.draw_entrants_mut(self.draw()).push(*self.wallet());
+        let root = schema.draw_entrants(self.draw()).root_hash();
+        schema.update_index_root("cryptocurrency.draw_entrants", self.draw(), root);
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxIssueFeeCoupon {
+    /// Check that the transaction is signed by the coupon issuer.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        match schema.config().coupon_issuer {
+            Some(ref key) if key == self.issuer() => Ok(()),
+            Some(_) => Err(Error::Unauthorized),
+            None => Err(Error::NotConfigured),
+        }
+    }
+}
+
+impl Transaction for TxIssueFeeCoupon {
+    /// Check that the discount is at most 100% and the issuer's signature is correct.
+    fn verify(&self) -> bool {
+        self.discount_bps() <= 10_000 && self.max_uses() > 0 &&
+            self.verify_signature(self.issuer())
+    }
+
+    /// Store the coupon.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let coupon = FeeCoupon::new(self.discount_bps(), self.max_uses(), 0, self.expires_at());
+        println!("Issue fee coupon: {:?}", coupon);
+        schema.fee_coupons_mut().put(self.code(), coupon);
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxCouponTransfer {
+    /// Check that the coupon can be redeemed, the discounted fee and the same
+    /// conditions as for `TxTransfer`.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let coupon = schema.fee_coupons().get(self.code()).ok_or(
+            Error::CouponNotFound,
+        )?;
+        if !coupon.is_valid(schema.height()) {
+            return Err(Error::CouponExhausted);
+        }
+        let min_fee = min_transfer_fee(schema, self.from(), self.amount())?;
+        if self.fee() < coupon.discounted(min_fee) {
+            return Err(Error::FeeTooLow);
+        }
+        check_receiver(schema, self.to(), self.amount())?;
+        let cost = schema.transfer_cost(self.amount(), self.fee())?;
+        check_payer(schema, self.from(), cost, Error::SenderNotFound)
+    }
+}
+
+impl Transaction for TxCouponTransfer {
+    /// Check if the sender is not the receiver. Check correctness of the
+    /// sender's signature.
+    fn verify(&self) -> bool {
+        (*self.from() != *self.to()) && self.verify_signature(self.from())
+    }
+
+    /// Redeem the coupon and transfer the coins.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        schema.update_base_fee();
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let coupon = schema.fee_coupons().get(self.code()).unwrap().redeem();
+        schema.fee_coupons_mut().put(self.code(), coupon);
+        let outcome = schema.pay(
+            &self.hash(),
+            self.from(),
+            self.to(),
+            self.amount(),
+            self.fee(),
+            &Hash::zero(),
+        );
+        if outcome == PaymentOutcome::Held {
+            return;
+        }
+        schema.append_history(self.from(), &self.hash());
+        schema.append_history(self.to(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxCreateInvitation {
+    /// Check that the code is not taken and the sponsor can fund all uses of
+    /// the invitation.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if schema.invitations().contains(self.code()) {
+            return Err(Error::InvitationExists);
+        }
+        if self.expires_at() <= schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        let total = self.balance().checked_mul(self.max_uses()).ok_or(
+            Error::InsufficientFunds,
+        )?;
+        check_payer(schema, self.sponsor(), total, Error::WalletNotFound)
+    }
+}
+
+impl Transaction for TxCreateInvitation {
+    /// Check that the invitation can be used. Check correctness of the sponsor's
+    /// signature.
+    fn verify(&self) -> bool {
+        self.max_uses() > 0 && *self.code() != Hash::zero() &&
+            self.verify_signature(self.sponsor())
+    }
+
+    /// Lock the coins for all uses of the invitation.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        schema.debit(self.sponsor(), self.balance() * self.max_uses());
+        let invitation = Invitation::new(
+            self.sponsor(),
+            self.balance(),
+            self.max_uses(),
+            0,
+            self.expires_at(),
+        );
+        println!("Create invitation {:?}: {:?}", self.code(), invitation);
+        schema.invitations_mut().put(self.code(), invitation);
+        schema.schedule(self.expires_at(), SCHEDULED_INVITATION_EXPIRY, self.code());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxDeposit {
+    /// Check that the payee exists, the return height is in the future and the
+    /// payer can lock the amount.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_receiver(schema, self.payee(), self.amount())?;
+        if self.return_at() <= schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        check_payer(schema, self.payer(), self.amount(), Error::SenderNotFound)
+    }
+}
+
+impl Transaction for TxDeposit {
+    /// Check if the payer is not the payee, the deposit is not empty, and
+    /// correctness of the payer's signature.
+    fn verify(&self) -> bool {
+        (*self.payer() != *self.payee()) && self.amount() > 0 &&
+            self.verify_signature(self.payer())
+    }
+
+    /// Lock the deposit.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        schema.debit(self.payer(), self.amount());
+        let deposit = Deposit::new(
+            self.payer(),
+            self.payee(),
+            self.amount(),
+            self.reference(),
+            self.return_at(),
+        );
+        println!("Lock deposit: {:?}", deposit);
+        schema.deposits_mut().put(&self.hash(), deposit);
+        schema.schedule(self.return_at(), SCHEDULED_DEPOSIT_RETURN, &self.hash());
+        schema.append_history(self.payer(), &self.hash());
+        schema.append_history(self.payee(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxClaimDeposit {
+    /// Check that the deposit is made to the signer, is not due for return and
+    /// covers the claimed amount, and that the signer can receive it.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let deposit = schema.deposits().get(self.deposit()).ok_or(
+            Error::DepositNotFound,
+        )?;
+        if deposit.payee() != self.payee() {
+            return Err(Error::Unauthorized);
+        }
+        if deposit.return_at() <= schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        if self.amount() > deposit.amount() {
+            return Err(Error::InsufficientFunds);
+        }
+        check_receiver(schema, self.payee(), self.amount())
+    }
+}
+
+impl Transaction for TxClaimDeposit {
+    /// Check correctness of the payee's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.payee())
+    }
+
+    /// Pay the claimed amount to the payee and return the rest to the payer.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let deposit = schema.deposits().get(self.deposit()).unwrap();
+        let payee = schema.credit(self.payee(), self.amount());
+        println!("Claim deposit {:?}: {:?}", self.deposit(), payee);
+        schema.append_history(self.payee(), &self.hash());
+        let refund = deposit.amount() - self.amount();
+        if schema.credit(deposit.payer(), refund).is_some() {
+            schema.append_history(deposit.payer(), &self.hash());
+        }
+        schema.deposits_mut().remove(self.deposit());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxCreateEscrow {
+    /// Check that the recipient exists and the sender can lock the amount.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_receiver(schema, self.recipient(), self.amount())?;
+        check_payer(schema, self.sender(), self.amount(), Error::SenderNotFound)
+    }
+}
+
+impl Transaction for TxCreateEscrow {
+    /// Check if the sender, the recipient and the arbiter are distinct, the escrow
+    /// is not empty, and correctness of the sender's signature.
+    fn verify(&self) -> bool {
+        (*self.sender() != *self.recipient()) && (*self.arbiter() != *self.sender()) &&
+            (*self.arbiter() != *self.recipient()) && self.amount() > 0 &&
+            self.verify_signature(self.sender())
+    }
+
+    /// Lock the coins.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        schema.debit(self.sender(), self.amount());
+        let escrow = Escrow::new(self.sender(), self.recipient(), self.arbiter(), self.amount());
+        println!("Lock escrow: {:?}", escrow);
+        schema.escrows_mut().put(&self.hash(), escrow);
+        schema.append_history(self.sender(), &self.hash());
+        schema.append_history(self.recipient(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+/// Check that the escrow is open, the signer is its arbiter and the wallet
+/// the coins go to, the recipient's if `release` is set and the sender's
+/// otherwise, can receive them.
+fn check_escrow<T: AsRef<Snapshot>>(
+    schema: &CurrencySchema<T>,
+    escrow: &Hash,
+    arbiter: &PublicKey,
+    release: bool,
+) -> Result<(), Error> {
+    let escrow = schema.escrows().get(escrow).ok_or(Error::EscrowNotFound)?;
+    if escrow.arbiter() != arbiter {
+        return Err(Error::Unauthorized);
+    }
+    let beneficiary = if release {
+        escrow.recipient()
+    } else {
+        escrow.sender()
+    };
+    check_receiver(schema, beneficiary, escrow.amount())
+}
+
+impl Validate for TxReleaseEscrow {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_escrow(schema, self.escrow(), self.arbiter(), true)
+    }
+}
+
+impl Transaction for TxReleaseEscrow {
+    /// Check correctness of the arbiter's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.arbiter())
+    }
+
+    /// Pay the coins to the recipient.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        if schema.settle_escrow(self.escrow(), true, &self.hash()) != PaymentOutcome::Held {
+            schema.record_result(&self.hash(), Ok(()));
+        }
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxRefundEscrow {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_escrow(schema, self.escrow(), self.arbiter(), false)
+    }
+}
+
+impl Transaction for TxRefundEscrow {
+    /// Check correctness of the arbiter's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.arbiter())
+    }
+
+    /// Return the coins to the sender.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        if schema.settle_escrow(self.escrow(), false, &self.hash()) != PaymentOutcome::Held {
+            schema.record_result(&self.hash(), Ok(()));
+        }
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxCreateAsset {
+    /// Check that the issuer can spend and the name is acceptable.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_payer(schema, self.issuer(), 0, Error::WalletNotFound)?;
+        check_name(schema, self.name())
+    }
+}
+
+impl Transaction for TxCreateAsset {
+    /// Check that the supply is not zero and correctness of the issuer's signature.
+    fn verify(&self) -> bool {
+        self.supply() > 0 && self.verify_signature(self.issuer())
+    }
+
+    /// Register the asset and credit the supply to the issuer.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let asset = Asset::new(self.issuer(), self.name(), self.supply());
+        println!("Create asset: {:?}", asset);
+        schema.assets_mut().put(&self.hash(), asset);
+        schema.asset_balances_mut(self.issuer()).put(&self.hash(), self.supply());
+        schema.update_asset_balances_root(self.issuer());
+        schema.append_history(self.issuer(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxAssetTransfer {
+    /// Check that the asset exists, the receiver can receive and the sender
+    /// can spend, and has enough of the asset.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if !schema.assets().contains(self.asset()) {
+            return Err(Error::AssetNotFound);
+        }
+        check_receiver(schema, self.to(), 0)?;
+        check_payer(schema, self.from(), 0, Error::SenderNotFound)?;
+        if schema.asset_balance(self.from(), self.asset()) < self.amount() {
+            return Err(Error::InsufficientFunds);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxAssetTransfer {
+    /// Check if the sender is not the receiver, the amount is not zero, and
+    /// correctness of the sender's signature.
+    fn verify(&self) -> bool {
+        (*self.from() != *self.to()) && self.amount() > 0 && self.verify_signature(self.from())
+    }
+
+    /// Move the asset to the receiver.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        println!("Transfer {} of asset {:?}", self.amount(), self.asset());
+        schema.transfer_asset(self.from(), self.to(), self.asset(), self.amount());
+        schema.append_history(self.from(), &self.hash());
+        schema.append_history(self.to(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxCreateHtlc {
+    /// Check that the timeout is in the future, the recipient exists and
+    /// the sender can lock the amount.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if self.timeout() <= schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        check_receiver(schema, self.recipient(), self.amount())?;
+        check_payer(schema, self.sender(), self.amount(), Error::SenderNotFound)
+    }
+}
+
+impl Transaction for TxCreateHtlc {
+    /// Check if the sender is not the recipient, the contract is not empty and
+    /// has a hash lock, and correctness of the sender's signature.
+    fn verify(&self) -> bool {
+        (*self.sender() != *self.recipient()) && self.amount() > 0 &&
+            (*self.hash_lock() != Hash::zero()) && self.verify_signature(self.sender())
+    }
+
+    /// Lock the coins.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        schema.debit(self.sender(), self.amount());
+        let htlc = Htlc::new(
+            self.sender(),
+            self.recipient(),
+            self.hash_lock(),
+            self.amount(),
+            self.timeout(),
+        );
+        println!("Lock HTLC: {:?}", htlc);
+        schema.htlcs_mut().put(&self.hash(), htlc);
+        schema.append_history(self.sender(), &self.hash());
+        schema.append_history(self.recipient(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxClaimHtlc {
+    /// Check that the contract is open and has not timed out, the signer is its
+    /// recipient, the preimage matches the hash lock and the recipient can
+    /// receive coins.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let htlc = schema.htlcs().get(self.htlc()).ok_or(Error::HtlcNotFound)?;
+        if htlc.recipient() != self.recipient() {
+            return Err(Error::Unauthorized);
+        }
+        if htlc.timeout() <= schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        if crypto::hash(self.preimage().as_ref()) != *htlc.hash_lock() {
+            return Err(Error::InvalidPreimage);
+        }
+        check_receiver(schema, self.recipient(), htlc.amount())
+    }
+}
+
+impl Transaction for TxClaimHtlc {
+    /// Check correctness of the recipient's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.recipient())
+    }
+
+    /// Pay the coins to the recipient and publish the preimage.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let hash_lock = *schema.htlcs().get(self.htlc()).unwrap().hash_lock();
+        schema.htlc_preimages_mut().put(&hash_lock, *self.preimage());
+        if schema.settle_htlc(self.htlc(), true, &self.hash()) != PaymentOutcome::Held {
+            schema.record_result(&self.hash(), Ok(()));
+        }
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxRefundHtlc {
+    /// Check that the contract is open and has timed out, the signer is its
+    /// sender and the sender can receive coins.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let htlc = schema.htlcs().get(self.htlc()).ok_or(Error::HtlcNotFound)?;
+        if htlc.sender() != self.sender() {
+            return Err(Error::Unauthorized);
+        }
+        if htlc.timeout() > schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        check_receiver(schema, self.sender(), htlc.amount())
+    }
+}
+
+impl Transaction for TxRefundHtlc {
+    /// Check correctness of the sender's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.sender())
+    }
+
+    /// Return the coins to the sender.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        if schema.settle_htlc(self.htlc(), false, &self.hash()) != PaymentOutcome::Held {
+            schema.record_result(&self.hash(), Ok(()));
+        }
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxLockedTransfer {
+    /// Check that both wallets exist, the unlock height is in the future
+    /// and the sender has enough coins.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if self.unlock_height() <= schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        check_receiver(schema, self.to(), self.amount())?;
+        check_payer(schema, self.from(), self.amount(), Error::SenderNotFound)
+    }
+}
+
+impl Transaction for TxLockedTransfer {
+    /// Check if the sender is not the receiver. Check correctness of the
+    /// sender's signature.
+    fn verify(&self) -> bool {
+        (*self.from() != *self.to()) && self.verify_signature(self.from())
+    }
+
+    /// Debit the sender and lock the coins for the receiver until the unlock height.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let amount = self.amount();
+        if schema.debit(self.from(), amount).is_some() {
+            let transfer =
+                LockedTransfer::new(self.from(), self.to(), amount, self.unlock_height());
+            println!("Lock coins for the receiver: {:?}", transfer);
+            schema.locked_transfers_mut().put(&self.hash(), transfer);
+            let locked = schema.locked_balance(self.to()) + amount;
+            schema.locked_totals_mut().put(self.to(), locked);
+            schema.schedule(self.unlock_height(), SCHEDULED_UNLOCK, &self.hash());
+            schema.record_transfer(self.from(), self.to(), amount);
+            schema.append_history(self.from(), &self.hash());
+            schema.append_history(self.to(), &self.hash());
+        }
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxDeferredTransfer {
+    /// Check that both wallets exist, the execution height is in the future
+    /// and the sender has enough coins.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        if self.height() <= schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        check_receiver(schema, self.to(), self.amount())?;
+        check_payer(schema, self.from(), self.amount(), Error::SenderNotFound)
+    }
+}
+
+impl Transaction for TxDeferredTransfer {
+    /// Check if the sender is not the receiver. Check correctness of the
+    /// sender's signature.
+    fn verify(&self) -> bool {
+        (*self.from() != *self.to()) && self.verify_signature(self.from())
+    }
+
+    /// Lock the coins until the execution height.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let amount = self.amount();
+        if schema.debit(self.from(), amount).is_some() {
+            let transfer = DeferredTransfer::new(self.from(), self.to(), amount, self.height());
+            println!("Lock coins for deferred transfer: {:?}", transfer);
+            schema.deferred_transfers_mut().put(&self.hash(), transfer);
+            schema.schedule(self.height(), SCHEDULED_DEFERRED_TRANSFER, &self.hash());
+        }
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxCancelDeferredTransfer {
+    /// Check that the transfer is pending and was made by the signer.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let transfer = schema.deferred_transfers().get(self.tx_hash()).ok_or(
+            Error::DeferredTransferNotFound,
+        )?;
+        if transfer.from() != self.from() {
+            return Err(Error::Unauthorized);
+        }
+        if transfer.height() <= schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        if schema.wallet(self.from()).is_none() {
+            return Err(Error::SenderNotFound);
+        }
+        if !schema.can_credit(&schema.payee(self.from()), transfer.amount()) {
+            return Err(Error::BalanceOverflow);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxCancelDeferredTransfer {
+    /// Check correctness of the sender's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.from())
+    }
+
+    /// Return the locked coins to the sender.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let transfer = schema.deferred_transfers().get(self.tx_hash()).unwrap();
+        schema.credit(self.from(), transfer.amount());
+        println!("Cancel deferred transfer: {:?}", transfer);
+        schema.deferred_transfers_mut().remove(self.tx_hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxIssue {
+    /// Check that the transaction is signed by the configured issuer, the amount
+    /// fits the issuance cap and the total of minted coins, and the receiver exists.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let config = schema.config();
+        match config.issuer {
+            Some(ref key) if key == self.issuer() => {}
+            Some(_) => return Err(Error::Unauthorized),
+            None => return Err(Error::NotConfigured),
+        }
+        if let Some(max_issuance) = config.caps.and_then(|caps| caps.max_issuance) {
+            if schema.issuance().saturating_add(self.amount()) > max_issuance {
+                return Err(Error::EpochCapExceeded);
+            }
+        }
+        check_mint(schema, self.amount())?;
+        check_receiver(schema, self.to(), self.amount())
+    }
+}
+
+impl Transaction for TxIssue {
+    /// Check that the amount is positive and the issuer's signature is correct.
+    fn verify(&self) -> bool {
+        self.amount() > 0 && self.verify_signature(self.issuer())
+    }
+
+    /// Credit the new coins to the receiver.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let wallet = schema.credit(self.to(), self.amount());
+        println!("Issue {} coins to {:?}", self.amount(), wallet);
+        schema.append_history(self.to(), &self.hash());
+        schema.record_issuance(self.amount());
+        schema.record_mint(self.amount());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxGrantPromotion {
+    /// Check that the transaction is signed by the configured issuer, the grant
+    /// fits the treasury outflow cap and the treasury has enough coins.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let config = schema.config();
+        if config.issuer.as_ref() != Some(self.issuer()) {
+            return Err(Error::Unauthorized);
+        }
+        let treasury = config.treasury.ok_or(Error::NotConfigured)?;
+        if let Some(caps) = config.caps {
+            let outflow = schema.treasury_outflow().saturating_add(self.amount());
+            if outflow > caps.max_treasury_outflow {
+                return Err(Error::EpochCapExceeded);
+            }
+        }
+        if self.expires_at() <= schema.height() {
+            return Err(Error::InvalidHeight);
+        }
+        check_receiver(schema, self.to(), 0)?;
+        check_payer(schema, &treasury, self.amount(), Error::NotConfigured)
+    }
+}
+
+impl Transaction for TxGrantPromotion {
+    /// Check correctness of the issuer's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.issuer())
+    }
+
+    /// Move the coins from the treasury to the promotional balance of the receiver.
+    /// If the receiver already has unexpired promotional coins, the later of
+    /// the two expiry heights applies.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let treasury = schema.config().treasury.unwrap();
+        if schema.debit(&treasury, self.amount()).is_none() {
+            schema.record_result(&self.hash(), Err(Error::InsufficientFunds));
+            return;
+        }
+        schema.record_treasury_outflow(self.amount());
+
+        let height = schema.height();
+        let promo = match schema.promotional_balances().get(self.to()) {
+            Some(ref old) if old.expires_at() > height => {
+                let expires_at = ::std::cmp::max(old.expires_at(), self.expires_at());
+                PromotionalBalance::new(old.amount() + self.amount(), expires_at)
+            }
+            Some(old) => {
+                // Return the expired coins which were not processed yet.
+                schema.credit(&treasury, old.amount());
+                PromotionalBalance::new(self.amount(), self.expires_at())
+            }
+            None => PromotionalBalance::new(self.amount(), self.expires_at()),
+        };
+        println!("Grant promotional coins to {:?}: {:?}", self.to(), promo);
+        let key = Hash::from_slice(self.to().as_ref()).unwrap();
+        schema.schedule(promo.expires_at(), SCHEDULED_PROMOTION_EXPIRY, &key);
+        schema.promotional_balances_mut().put(self.to(), promo);
+        schema.append_history(self.to(), &self.hash());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+/// Check that the insurance fund is configured and the signer is its governor.
+fn check_governor<T: AsRef<Snapshot>>(
+    schema: &CurrencySchema<T>,
+    governor: &PublicKey,
+) -> Result<(), Error> {
+    let insurance = schema.config().insurance.ok_or(Error::NotConfigured)?;
+    match insurance.governor_index(governor) {
+        Some(index) if index < 64 => Ok(()),
+        _ => Err(Error::Unauthorized),
+    }
+}
+
+impl Validate for TxInsurancePayout {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_governor(schema, self.governor())
+    }
+}
+
+impl Transaction for TxInsurancePayout {
+    /// Check correctness of the governor's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.governor())
+    }
+
+    /// Register the payout and approve it on behalf of the proposing governor.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let payout = InsurancePayout::new(self.to(), self.amount(), 0, 0);
+        schema.approve_insurance_payout(&self.hash(), payout, self.governor());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxApproveInsurancePayout {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_governor(schema, self.governor())?;
+        let payout = schema.insurance_payouts().get(self.payout()).ok_or(
+            Error::PayoutNotFound,
+        )?;
+        if payout.executed_at() != 0 {
+            return Err(Error::PayoutExecuted);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxApproveInsurancePayout {
+    /// Check correctness of the governor's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.governor())
+    }
+
+    /// Add the approval to a pending payout.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let payout = schema.insurance_payouts().get(self.payout()).unwrap();
+        schema.approve_insurance_payout(self.payout(), payout, self.governor());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+/// Check that clawbacks are configured and the signer is their governor.
+fn check_clawback_governor<T: AsRef<Snapshot>>(
+    schema: &CurrencySchema<T>,
+    governor: &PublicKey,
+) -> Result<(), Error> {
+    let config = schema.config().clawback.ok_or(Error::NotConfigured)?;
+    match config.governor_index(governor) {
+        Some(index) if index < 64 => Ok(()),
+        _ => Err(Error::Unauthorized),
+    }
+}
+
+impl Validate for TxClawback {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_clawback_governor(schema, self.governor())?;
+        if schema.committed_transfer(self.transfer()).is_none() {
+            return Err(Error::TransferNotFound);
+        }
+        if schema.clawbacks().contains(self.transfer()) {
+            return Err(Error::ClawbackExists);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxClawback {
+    /// Check correctness of the governor's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.governor())
+    }
+
+    /// Register the clawback and approve it on behalf of the proposing governor.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let clawback = Clawback::new(self.governor(), self.reason(), 0, 0, 0, 0, 0);
+        schema.approve_clawback(self.transfer(), clawback, self.governor());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxApproveClawback {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_clawback_governor(schema, self.governor())?;
+        let clawback = schema.clawbacks().get(self.transfer()).ok_or(
+            Error::ClawbackNotFound,
+        )?;
+        if clawback.executed_at() != 0 {
+            return Err(Error::ClawbackExecuted);
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for TxApproveClawback {
+    /// Check correctness of the governor's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.governor())
+    }
+
+    /// Add the approval to a pending clawback.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        let clawback = schema.clawbacks().get(self.transfer()).unwrap();
+        schema.approve_clawback(self.transfer(), clawback, self.governor());
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxBurnWithTag {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_payer(schema, self.owner(), self.amount(), Error::WalletNotFound)
+    }
+}
+
+impl Transaction for TxBurnWithTag {
+    /// Check correctness of the owner's signature.
+    fn verify(&self) -> bool {
+        self.amount() > 0 && self.verify_signature(self.owner())
+    }
+
+    /// Withdraw the coins from the owner's wallet and record the burn under the tag.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        if schema.debit(self.owner(), self.amount()).is_some() {
+            let height = schema.height();
+            let record = BurnRecord::new(self.owner(), self.amount(), &self.hash(), height);
+            println!("Burn coins with tag {:?}: {:?}", self.tag(), record);
+            schema.burns_by_tag_mut(self.tag()).push(record);
+            let root = schema.burns_by_tag(self.tag()).root_hash();
+            schema.update_index_root("cryptocurrency.burns_by_tag", self.tag(), root);
+            schema.record_burn(self.amount());
+            schema.append_history(self.owner(), &self.hash());
+        }
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxBurn {
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        check_payer(schema, self.owner(), self.amount(), Error::WalletNotFound)
+    }
+}
+
+impl Transaction for TxBurn {
+    /// Check correctness of the owner's signature.
+    fn verify(&self) -> bool {
+        self.amount() > 0 && self.verify_signature(self.owner())
+    }
+
+    /// Withdraw the coins from the owner's wallet and add them to the burned total.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Err(err) = self.validate(&schema) {
+            schema.record_result(&self.hash(), Err(err));
+            return;
+        }
+        if let Some(wallet) = schema.debit(self.owner(), self.amount()) {
+            println!("Burn {} coins: {:?}", self.amount(), wallet);
+            schema.record_burn(self.amount());
+            schema.append_history(self.owner(), &self.hash());
+        }
+        schema.record_result(&self.hash(), Ok(()));
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+impl Validate for TxTick {
+    /// Check that the transaction is issued by a validator.
+    fn validate<T: AsRef<Snapshot>>(&self, schema: &CurrencySchema<T>) -> Result<(), Error> {
+        let is_validator = CoreSchema::new(&schema.view)
+            .actual_configuration()
+            .validator_keys
+            .iter()
+            .any(|keys| keys.service_key == *self.validator());
+        if is_validator {
+            Ok(())
+        } else {
+            Err(Error::Unauthorized)
+        }
+    }
+}
+
+impl Transaction for TxTick {
+    /// Check correctness of the validator's signature.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.validator())
+    }
+
+    /// Perform the actions scheduled up to the current height, then sweep
+    /// the excess balances, unless another validator's tick has done so in
+    /// this block.
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if self.validate(&schema).is_err() {
+            return;
+        }
+        let height = schema.height();
+        if schema.tick_height().get() == Some(height) {
+            return;
+        }
+        schema.tick_height_mut().set(height);
+        schema.process_scheduled(height);
+        schema.sweep(&self.hash());
+    }
+
+    /// Provide information about the transaction to be used in the blockchain explorer.
+    fn info(&self) -> serde_json::Value {
+        serde_json::to_value(&self).expect("Cannot serialize transaction to JSON")
+    }
+}
+
+// // // // // // // // // // REST API // // // // // // // // // //
+
+/// Static explorer page. It is built from the service endpoints and the core
+/// explorer API of the node, so it does not require a separate frontend project.
+#[cfg(feature = "explorer")]
+const EXPLORER_PAGE: &str = include_str!("../static/explorer.html");
+
+/// Implement the node API.
+#[derive(Clone)]
+struct CryptocurrencyApi {
+    channel: ApiSender,
+    blockchain: Blockchain,
+    query_budget: QueryBudget,
+    submitted: Arc<Mutex<BTreeMap<Hash, u64>>>,
+    sla: Arc<Mutex<SlaTracker>>,
+    service_keys: (PublicKey, SecretKey),
+}
+
+/// The structure returned by the REST API.
+#[derive(Serialize, Deserialize)]
+pub struct TransactionResponse {
+    pub tx_hash: Hash,
+}
+
+/// Status of a transaction as seen by the node.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TransactionStatus {
+    /// The transaction is neither committed nor submitted through this node.
+    Unknown,
+    /// The transaction was submitted through this node and is not committed yet.
+    Pending,
+    /// The transaction is committed in the block at `height`. `code` is its
+    /// result code, `0` if it succeeded, or `None` if its execution is postponed,
+    /// as for a transfer waiting for its dependency or held for review.
+    Committed {
+        height: u64,
+        code: Option<u8>,
+        error: Option<String>,
+    },
+}
+
+/// Outcome of a transaction executed by a sandbox endpoint.
+#[cfg(feature = "sandbox")]
+#[derive(Serialize, Deserialize)]
+pub struct SandboxResponse {
+    pub tx_hash: Hash,
+    /// Result code of the transaction, `0` if it succeeded.
+    pub code: u8,
+    /// Description of the error, if the transaction failed.
+    pub error: Option<String>,
+}
+
+/// Pending deferred transfer together with the hash of the transaction that created it.
+#[derive(Serialize, Deserialize)]
+pub struct DeferredTransferInfo {
+    pub tx_hash: Hash,
+    pub transfer: DeferredTransfer,
+}
+
+/// Locked transfer together with the hash of the transaction that created it.
+#[derive(Serialize, Deserialize)]
+pub struct LockedTransferInfo {
+    pub tx_hash: Hash,
+    pub transfer: LockedTransfer,
+}
+
+/// Value of the randomness beacon at a height.
+#[derive(Serialize, Deserialize)]
+pub struct BeaconInfo {
+    pub height: u64,
+    pub value: Hash,
+}
+
+/// Lottery draw and its entrants.
+#[derive(Serialize, Deserialize)]
+pub struct DrawInfo {
+    pub id: Hash,
+    pub draw: Draw,
+    pub entrants: Vec<PublicKey>,
+}
+
+/// Payment stream and the coins its receiver can withdraw in the next block.
+#[derive(Serialize, Deserialize)]
+pub struct StreamInfo {
+    pub id: Hash,
+    pub stream: Stream,
+    pub available: u64,
+}
+
+/// Auction together with the hash of the transaction that created it.
+#[derive(Serialize, Deserialize)]
+pub struct AuctionInfo {
+    pub tx_hash: Hash,
+    pub auction: Auction,
+}
+
+/// Open escrow together with the hash of the transaction that made it.
+#[derive(Serialize, Deserialize)]
+pub struct EscrowInfo {
+    pub tx_hash: Hash,
+    pub escrow: Escrow,
+}
+
+/// Preimage revealed for a hash lock.
+#[derive(Serialize, Deserialize)]
+pub struct HtlcPreimage {
+    pub hash_lock: Hash,
+    pub preimage: Hash,
+}
+
+/// Held transfer together with the hash of the transaction making it, or the
+/// `batch_payment_id` of a payment of a batch transfer.
+#[derive(Serialize, Deserialize)]
+pub struct HeldTransferInfo {
+    pub tx_hash: Hash,
+    pub transfer: HeldTransfer,
+}
+
+/// Activity counters and loyalty points of a wallet.
+#[derive(Serialize, Deserialize)]
+pub struct LoyaltyInfo {
+    pub activity: WalletActivity,
+    pub points: u64,
+}
+
+/// State of the insurance fund.
+#[derive(Serialize, Deserialize)]
+pub struct InsuranceInfo {
+    pub fund: Option<Wallet>,
+    pub payouts: Vec<InsurancePayoutInfo>,
+}
+
+/// Insurance payout together with the hash of the proposing transaction.
+#[derive(Serialize, Deserialize)]
+pub struct InsurancePayoutInfo {
+    pub tx_hash: Hash,
+    pub payout: InsurancePayout,
+}
+
+/// Clawback together with the hash of the clawed back transfer.
+#[derive(Serialize, Deserialize)]
+pub struct ClawbackInfo {
+    pub transfer: Hash,
+    pub clawback: Clawback,
+}
+
+/// Balance of a wallet in an asset.
+#[derive(Serialize, Deserialize)]
+pub struct AssetBalance {
+    pub asset: Hash,
+    pub balance: u64,
+}
+
+/// Allowance granted by the owner of a wallet to a spender.
+#[derive(Serialize, Deserialize)]
+pub struct AllowanceInfo {
+    pub spender: PublicKey,
+    pub amount: u64,
+}
+
+/// Session key of a wallet together with its scope.
+#[derive(Serialize, Deserialize)]
+pub struct SessionKeyInfo {
+    pub session_key: PublicKey,
+    pub scope: SessionKey,
+}
+
+/// Linked wallets and their aggregated balance.
+#[derive(Serialize, Deserialize)]
+pub struct GroupInfo {
+    pub id: Hash,
+    pub wallets: Vec<Wallet>,
+    pub balance: u64,
+}
+
+/// Proof of the state of a wallet against the state hash of a block: `to_table`
+/// links the root hash of the wallets table to the state hash, and `to_wallet`
+/// links the wallet to the root hash of the table.
+#[derive(Debug, Serialize)]
+pub struct WalletProof {
+    pub to_table: MapProof<Hash>,
+    pub to_wallet: MapProof<Wallet>,
+}
+
+/// Reason for a wallet proof to be rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletProofError {
+    /// The proof does not lead from the state hash to the wallets table.
+    InvalidTableProof,
+    /// The proof does not lead from the wallets table to the wallet.
+    InvalidWalletProof,
+}
+
+impl WalletProof {
+    /// Check the proof against the state hash of a trusted block. Returns the
+    /// wallet, or `None` if the proof shows that the wallet does not exist.
+    pub fn verify(
+        &self,
+        state_hash: &Hash,
+        pub_key: &PublicKey,
+    ) -> Result<Option<Wallet>, WalletProofError> {
+        let table_key = CoreSchema::<&Snapshot>::service_table_unique_key(SERVICE_ID, 0);
+        let table_root = match self.to_table.validate(&table_key, *state_hash) {
+            Ok(Some(root)) => *root,
+            _ => return Err(WalletProofError::InvalidTableProof),
+        };
+        match self.to_wallet.validate(pub_key, table_root) {
+            Ok(wallet) => Ok(wallet.cloned()),
+            Err(_) => Err(WalletProofError::InvalidWalletProof),
+        }
+    }
+}
+
+/// Wallet proof together with the block it is checked against and the
+/// precommits of the validators authenticating the block.
+#[derive(Serialize)]
+pub struct WalletProofInfo {
+    pub block_proof: BlockProof,
+    pub wallet_proof: WalletProof,
+}
+
+/// Account statement of a wallet for the blocks from `from_height` to `to_height`
+/// inclusive: the balances before and after these blocks and the transactions
+/// from the wallet history committed in them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Statement {
+    pub pub_key: PublicKey,
+    pub from_height: u64,
+    pub to_height: u64,
+    pub opening_balance: u64,
+    pub closing_balance: u64,
+    pub transactions: Vec<StatementEntry>,
+}
+
+/// Subsystem a transaction in the activity feed of a wallet belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActivityKind {
+    /// Creation of the wallet or a change of its setup.
+    Wallet,
+    /// Payment between wallets.
+    Transfer,
+    /// Review of a transfer put on hold.
+    Hold,
+    /// Deposit locked in escrow or claimed from it.
+    Escrow,
+    /// Action of an operator key, such as an attestation or a freeze.
+    Admin,
+    /// Issuance, promotional grant or burn of coins.
+    Supply,
+    /// Any other transaction.
+    Other,
+}
+
+impl ActivityKind {
+    /// Kind of transactions of the given message type.
+    pub fn of(message_type: u16) -> ActivityKind {
+        match message_type {
+            TX_CREATE_WALLET_ID |
+            TX_UPDATE_WALLET_ID |
+            TX_RENAME_WALLET_ID |
+            TX_CLOSE_WALLET_ID |
+            TX_SETUP_MULTISIG_ID |
+            TX_LINK_WALLETS_ID => ActivityKind::Wallet,
+            TX_TRANSFER_ID |
+            TX_CREATE_AND_FUND_WALLET_ID |
+            TX_SESSION_TRANSFER_ID |
+            TX_TRANSFER_FROM_ID |
+            TX_LOCKED_TRANSFER_ID |
+            TX_ASSET_TRANSFER_ID |
+            TX_COUPON_TRANSFER_ID |
+            TX_BATCH_TRANSFER_ID |
+            TX_MULTISIG_TRANSFER_ID |
+            TX_APPROVE_MULTISIG_TRANSFER_ID => ActivityKind::Transfer,
+            TX_REVIEW_HELD_TRANSFER_ID => ActivityKind::Hold,
+            TX_DEPOSIT_ID |
+            TX_CLAIM_DEPOSIT_ID |
+            TX_CREATE_ESCROW_ID |
+            TX_RELEASE_ESCROW_ID |
+            TX_REFUND_ESCROW_ID |
+            TX_CREATE_HTLC_ID |
+            TX_CLAIM_HTLC_ID |
+            TX_REFUND_HTLC_ID => ActivityKind::Escrow,
+            TX_ATTEST_WALLET_ID |
+            TX_REVOKE_ATTESTATION_ID |
+            TX_ASSIGN_TIER_ID |
+            TX_FREEZE_WALLET_ID |
+            TX_UNFREEZE_WALLET_ID => ActivityKind::Admin,
+            TX_ISSUE_ID |
+            TX_GRANT_PROMOTION_ID |
+            TX_BURN_ID |
+            TX_BURN_WITH_TAG_ID |
+            TX_CREATE_ASSET_ID => ActivityKind::Supply,
+            _ => ActivityKind::Other,
+        }
+    }
+}
+
+/// Wallet in a counterparty graph.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphNode {
+    pub pub_key: PublicKey,
+    /// Number of hops from the wallet the graph is built for.
+    pub distance: u64,
+}
+
+/// Successful transfers from one wallet to another in a counterparty graph.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub from: PublicKey,
+    pub to: PublicKey,
+    /// Total amount transferred.
+    pub volume: u64,
+    /// Number of transfers, counting each payment of a batch transfer.
+    pub transfers: u64,
+}
+
+/// Transfer graph around a wallet, as returned by `GET v1/wallet/:pub_key/graph`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CounterpartyGraph {
+    /// Wallets of the graph, nearest first.
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    /// Whether the graph is cut short by the query budget or `MAX_GRAPH_NODES`.
+    pub truncated: bool,
+}
+
+/// Entry of the activity feed of a wallet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityEntry {
+    pub tx_hash: Hash,
+    /// Height of the block the transaction is committed in.
+    pub height: u64,
+    pub kind: ActivityKind,
+    pub message_type: u16,
+    /// Result code of the transaction, `0` for success.
+    pub code: u8,
+    /// The transaction as shown by the blockchain explorer.
+    pub content: serde_json::Value,
+}
+
+/// Transaction listed in a statement, with the height of the block it is committed in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatementEntry {
+    pub tx_hash: Hash,
+    pub height: u64,
+}
+
+/// Statement signed with the service key of the node that produced it.
+/// The signature covers the JSON serialization of the statement.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedStatement {
+    pub statement: Statement,
+    pub signer: PublicKey,
+    pub signature: Signature,
+}
+
+impl SignedStatement {
+    /// Sign a statement with the given key.
+    pub fn new(statement: Statement, signer: &PublicKey, secret_key: &SecretKey) -> Self {
+        let signature = crypto::sign(&serde_json::to_vec(&statement).unwrap(), secret_key);
+        SignedStatement {
+            statement,
+            signer: *signer,
+            signature,
+        }
+    }
+
+    /// Check the signature of the statement. Whether the signer is trusted is
+    /// up to the caller.
+    pub fn verify(&self) -> bool {
+        let data = serde_json::to_vec(&self.statement).unwrap();
+        crypto::verify(&self.signature, &data, &self.signer)
+    }
+}
+
+/// Root hash of the wallets table and the hashes of all service tables included
+/// into the block state hash at the latest committed block. Monitoring tools
+/// compare roots reported by different nodes at the same height.
+#[derive(Serialize, Deserialize)]
+pub struct StateRoot {
+    pub height: u64,
+    pub wallets: Hash,
+    pub tables: Vec<Hash>,
+}
+
+/// Service parameters clients need to build acceptable transactions.
+#[derive(Serialize, Deserialize)]
+pub struct ServiceInfo {
+    pub height: u64,
+    pub limits: TransactionLimits,
+}
+
+/// Kind of similarity between wallet names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum NameMatchKind {
+    /// The names are equal.
+    Exact,
+    /// The names differ only in case, whitespace or look-alike characters.
+    Confusable,
+    /// The names are within a small edit distance of each other.
+    Similar,
+}
+
+/// Existing wallet with a name similar to the checked one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameMatch {
+    pub pub_key: PublicKey,
+    pub name: String,
+    pub kind: NameMatchKind,
+    /// Edit distance between the skeletons of the names.
+    pub distance: u64,
+}
+
+/// Advisory result of `GET v1/names/check`: existing wallets with names equal or
+/// similar to the checked name, the closest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameCheck {
+    /// Checked name in Unicode normalization form C.
+    pub name: String,
+    pub matches: Vec<NameMatch>,
+}
+
+/// Coins created and removed from circulation, as returned by `GET v1/supply`.
+#[derive(Serialize, Deserialize)]
+pub struct SupplyInfo {
+    pub height: u64,
+    pub minted: u64,
+    pub burned: u64,
+}
+
+/// Minimum fee accepted for transfers in the next block.
+#[derive(Serialize, Deserialize)]
+pub struct FeeEstimate {
+    pub height: u64,
+    pub base_fee: u64,
+}
+
+/// Amount a batch transfer would withdraw from a wallet in the next block.
+#[derive(Serialize, Deserialize)]
+pub struct BatchCost {
+    pub height: u64,
+    pub cost: u64,
+}
+
+/// Aggregate figures for an operator dashboard, as returned by `GET v1/admin/overview`.
+/// Activity figures cover the last `window` committed blocks.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Overview {
+    pub height: u64,
+    pub window: u64,
+    /// Number of wallets.
+    pub wallets: u64,
+    /// Number of wallets with a transaction in their history committed within the window.
+    pub active_wallets: u64,
+    /// Number of transactions committed within the window.
+    pub transactions: u64,
+    /// Number of service transactions failed within the window, by error code.
+    pub errors: BTreeMap<u8, u64>,
+    /// Number of transactions submitted through this node and not committed yet.
+    pub pending: u64,
+    /// Total balance of all wallets.
+    pub supply: u64,
+    /// Total of coins burned by their owners.
+    pub burned: u64,
+    /// Balance of the treasury wallet, if configured.
+    pub treasury_balance: Option<u64>,
+    /// Balance of the insurance fund wallet, if configured.
+    pub insurance_balance: Option<u64>,
+}
+
+/// Shortcut to get data on wallets.
+impl CryptocurrencyApi {
+    /// Language negotiated from the `Accept-Language` header of the request.
+    fn language(req: &Request) -> Language {
+        req.headers
+            .get_raw("Accept-Language")
+            .and_then(|values| values.first())
+            .and_then(|value| ::std::str::from_utf8(value).ok())
+            .map(Language::negotiate)
+            .unwrap_or_default()
+    }
+
+    /// Endpoint for getting a single wallet. With the `height` query parameter,
+    /// the state of the wallet after the block at this height is returned.
     fn get_wallet(&self, req: &mut Request) -> IronResult<Response> {
         let path = req.url.path();
         let wallet_key = path.last().unwrap();
         let public_key = PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?;
+        let params = payment::parse_query(req.url.query().unwrap_or(""))
+            .map_err(|e| ApiError::IncorrectRequest(Box::new(e)))?;
+        let height = match params.get("height") {
+            Some(height) => {
+                Some(height.parse::<u64>().map_err(
+                    |e| ApiError::IncorrectRequest(Box::new(e)),
+                )?)
+            }
+            None => None,
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let wallet = match height {
+            // The block at the current height is not committed yet.
+            Some(height) if height >= schema.height() => {
+                let message = ApiMessage::BlockNotFound.text(Self::language(req));
+                return self.not_found_response(&serde_json::to_value(message).unwrap());
+            }
+            Some(height) => schema.wallet_at(&public_key, height),
+            None => schema.wallet(&public_key),
+        };
+
+        if let Some(wallet) = wallet {
+            self.ok_response(&serde_json::to_value(wallet).unwrap())
+        } else {
+            let message = ApiMessage::WalletNotFound.text(Self::language(req));
+            self.not_found_response(&serde_json::to_value(message).unwrap())
+        }
+    }
+
+    /// Endpoint for getting a proof of the state of a wallet for light clients.
+    fn get_wallet_proof(&self, req: &mut Request) -> IronResult<Response> {
+        let pub_key = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+
+        let snapshot = self.blockchain.snapshot();
+        let schema = CurrencySchema::new(&snapshot);
+        let height = schema.height() - 1;
+        let info = WalletProofInfo {
+            block_proof: CoreSchema::new(&snapshot)
+                .block_and_precommits(Height(height))
+                .unwrap(),
+            wallet_proof: schema.wallet_proof(&pub_key),
+        };
+        self.ok_response(&serde_json::to_value(&info).unwrap())
+    }
+
+    /// Endpoint for listing wallets in the order of their public keys.
+    fn get_wallets(&self, req: &mut Request) -> IronResult<Response> {
+        let page = Self::page_request(req)?;
+        let start = match page.cursor {
+            Some(ref cursor) => {
+                Some(PublicKey::from_hex(cursor).map_err(|_| Self::cursor_error())?)
+            }
+            None => None,
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let idx = schema.wallets();
+        let mut scan = self.query_budget.start();
+        let cursor_of = |wallet: &Wallet| wallet.pub_key().to_string();
+        let mut wallets = match start {
+            Some(ref key) => {
+                let iter = idx.iter_from(key).map(|(_, wallet)| wallet);
+                Page::collect(iter, page.limit, &mut scan, cursor_of)
+            }
+            None => Page::collect(idx.values(), page.limit, &mut scan, cursor_of),
+        };
+        wallets.total_estimate = scan.count(idx.keys());
+
+        self.ok_response(&serde_json::to_value(&wallets).unwrap())
+    }
+
+    /// Endpoint for getting activity counters and loyalty points of a wallet.
+    fn get_loyalty(&self, req: &mut Request) -> IronResult<Response> {
+        let public_key = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        if schema.wallet(&public_key).is_none() {
+            let message = ApiMessage::WalletNotFound.text(Self::language(req));
+            return self.not_found_response(&serde_json::to_value(message).unwrap());
+        }
+        let info = LoyaltyInfo {
+            activity: schema.activity().get(&public_key).unwrap_or_else(
+                WalletActivity::empty,
+            ),
+            points: schema.loyalty_points().get(&public_key).unwrap_or(0),
+        };
+
+        self.ok_response(&serde_json::to_value(&info).unwrap())
+    }
+
+    /// Endpoint for getting the promotional balance of a wallet.
+    fn get_promotion(&self, req: &mut Request) -> IronResult<Response> {
+        let public_key = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.promotional_balances().get(&public_key) {
+            Some(promo) => self.ok_response(&serde_json::to_value(promo).unwrap()),
+            None => {
+                let message = ApiMessage::PromotionNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
+        }
+    }
+
+    /// Endpoint for getting the sweep rule of a wallet.
+    fn get_sweep_rule(&self, req: &mut Request) -> IronResult<Response> {
+        let public_key = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.sweep_rules().get(&public_key) {
+            Some(rule) => self.ok_response(&serde_json::to_value(rule).unwrap()),
+            None => {
+                let message = ApiMessage::SweepRuleNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
+        }
+    }
+
+    /// Endpoint for getting the signers of a multisignature wallet.
+    fn get_multisig(&self, req: &mut Request) -> IronResult<Response> {
+        let public_key = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.multisigs().get(&public_key) {
+            Some(multisig) => self.ok_response(&serde_json::to_value(multisig).unwrap()),
+            None => {
+                let message = ApiMessage::MultisigNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
+        }
+    }
+
+    /// Endpoint for getting a sponsored invitation by the hash of its code.
+    fn get_invitation(&self, req: &mut Request) -> IronResult<Response> {
+        let code = {
+            let code = req.extensions.get::<Router>().unwrap().find("code").unwrap();
+            Hash::from_hex(code).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.invitations().get(&code) {
+            Some(invitation) => self.ok_response(&serde_json::to_value(invitation).unwrap()),
+            None => {
+                let message = ApiMessage::InvitationNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
+        }
+    }
+
+    /// Endpoint for getting a transfer from a multisignature wallet.
+    fn get_multisig_transfer(&self, req: &mut Request) -> IronResult<Response> {
+        let id = {
+            let id = req.extensions.get::<Router>().unwrap().find("id").unwrap();
+            Hash::from_hex(id).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.multisig_transfers().get(&id) {
+            Some(transfer) => self.ok_response(&serde_json::to_value(transfer).unwrap()),
+            None => {
+                let message = ApiMessage::MultisigTransferNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
+        }
+    }
+
+    /// Endpoint for getting a group of linked wallets with the aggregated balance.
+    fn get_group(&self, req: &mut Request) -> IronResult<Response> {
+        let id = {
+            let id = req.extensions.get::<Router>().unwrap().find("id").unwrap();
+            Hash::from_hex(id).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let wallets: Vec<Wallet> = schema
+            .group_members(&id)
+            .iter()
+            .filter_map(|pub_key| schema.wallet(&pub_key))
+            .collect();
+        if wallets.is_empty() {
+            let message = ApiMessage::GroupNotFound.text(Self::language(req));
+            return self.not_found_response(&serde_json::to_value(message).unwrap());
+        }
+        let balance: u64 = wallets.iter().map(Wallet::balance).sum();
+        let group = GroupInfo {
+            id,
+            wallets,
+            balance,
+        };
+
+        self.ok_response(&serde_json::to_value(&group).unwrap())
+    }
+
+    /// Endpoint for listing receipts of a wallet, optionally only those with
+    /// the payment reference given in the `reference` query parameter.
+    fn get_receipts(&self, req: &mut Request) -> IronResult<Response> {
+        let payee = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+        let page = Self::page_request(req)?;
+        let start: u64 = match page.cursor {
+            Some(ref cursor) => cursor.parse().map_err(|_| Self::cursor_error())?,
+            None => 0,
+        };
+        let params = payment::parse_query(req.url.query().unwrap_or(""))
+            .map_err(|e| ApiError::IncorrectRequest(Box::new(e)))?;
+        let reference = match params.get("reference") {
+            Some(reference) => Some(Hash::from_hex(reference).map_err(ApiError::FromHex)?),
+            None => None,
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let idx = schema.receipts(&payee);
+        let mut scan = self.query_budget.start();
+        let mut receipts = Vec::new();
+        let mut next_cursor = None;
+        // Receipts are filtered while scanning, so the cursor is the index
+        // of the first receipt not scanned yet.
+        for (index, receipt) in (start..).zip(idx.iter_from(start)) {
+            if receipts.len() == page.limit || !scan.step() {
+                next_cursor = Some(index.to_string());
+                break;
+            }
+            if reference.map_or(true, |ref reference| receipt.reference() == reference) {
+                receipts.push(receipt);
+            }
+        }
+        let total_estimate = match reference {
+            Some(_) => receipts.len() as u64,
+            None => idx.len(),
+        };
+        let receipts = Page {
+            items: receipts,
+            next_cursor,
+            total_estimate,
+        };
+
+        self.ok_response(&serde_json::to_value(&receipts).unwrap())
+    }
+
+    /// Endpoint for getting the attestation of a verified wallet.
+    fn get_attestation(&self, req: &mut Request) -> IronResult<Response> {
+        let public_key = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.attestations().get(&public_key) {
+            Some(attestation) => self.ok_response(&serde_json::to_value(attestation).unwrap()),
+            None => {
+                let message = ApiMessage::AttestationNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
+        }
+    }
+
+    /// Endpoint for listing session keys of a wallet, including the expired ones.
+    fn get_session_keys(&self, req: &mut Request) -> IronResult<Response> {
+        let owner = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+        let page = Self::page_request(req)?;
+        let start = match page.cursor {
+            Some(ref cursor) => {
+                Some(PublicKey::from_hex(cursor).map_err(|_| Self::cursor_error())?)
+            }
+            None => None,
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let idx = schema.session_keys(&owner);
+        let mut scan = self.query_budget.start();
+        let info = |(session_key, scope): (PublicKey, SessionKey)| {
+            SessionKeyInfo { session_key, scope }
+        };
+        let cursor_of = |info: &SessionKeyInfo| info.session_key.to_string();
+        let mut keys = match start {
+            Some(ref key) => {
+                let iter = idx.iter_from(key).map(info);
+                Page::collect(iter, page.limit, &mut scan, cursor_of)
+            }
+            None => Page::collect(idx.iter().map(info), page.limit, &mut scan, cursor_of),
+        };
+        keys.total_estimate = scan.count(idx.keys());
+
+        self.ok_response(&serde_json::to_value(&keys).unwrap())
+    }
+
+    /// Endpoint for listing allowances granted by the owner of a wallet.
+    fn get_allowances(&self, req: &mut Request) -> IronResult<Response> {
+        let owner = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+        let page = Self::page_request(req)?;
+        let start = match page.cursor {
+            Some(ref cursor) => {
+                Some(PublicKey::from_hex(cursor).map_err(|_| Self::cursor_error())?)
+            }
+            None => None,
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let idx = schema.allowances(&owner);
+        let mut scan = self.query_budget.start();
+        let info = |(spender, amount): (PublicKey, u64)| AllowanceInfo { spender, amount };
+        let cursor_of = |info: &AllowanceInfo| info.spender.to_string();
+        let mut allowances = match start {
+            Some(ref key) => {
+                let iter = idx.iter_from(key).map(info);
+                Page::collect(iter, page.limit, &mut scan, cursor_of)
+            }
+            None => Page::collect(idx.iter().map(info), page.limit, &mut scan, cursor_of),
+        };
+        allowances.total_estimate = scan.count(idx.keys());
+
+        self.ok_response(&serde_json::to_value(&allowances).unwrap())
+    }
+
+    /// Endpoint for listing the asset balances of a wallet.
+    fn get_asset_balances(&self, req: &mut Request) -> IronResult<Response> {
+        let pub_key = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+        let page = Self::page_request(req)?;
+        let start = match page.cursor {
+            Some(ref cursor) => Some(Hash::from_hex(cursor).map_err(|_| Self::cursor_error())?),
+            None => None,
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let idx = schema.asset_balances(&pub_key);
+        let mut scan = self.query_budget.start();
+        let info = |(asset, balance): (Hash, u64)| AssetBalance { asset, balance };
+        let cursor_of = |info: &AssetBalance| info.asset.to_string();
+        let mut balances = match start {
+            Some(ref asset) => {
+                let iter = idx.iter_from(asset).map(info);
+                Page::collect(iter, page.limit, &mut scan, cursor_of)
+            }
+            None => Page::collect(idx.iter().map(info), page.limit, &mut scan, cursor_of),
+        };
+        balances.total_estimate = scan.count(idx.keys());
+
+        self.ok_response(&serde_json::to_value(&balances).unwrap())
+    }
+
+    /// Endpoint for getting an issued asset.
+    fn get_asset(&self, req: &mut Request) -> IronResult<Response> {
+        let id = {
+            let id = req.extensions.get::<Router>().unwrap().find("id").unwrap();
+            Hash::from_hex(id).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.assets().get(&id) {
+            Some(asset) => self.ok_response(&serde_json::to_value(&asset).unwrap()),
+            None => {
+                let message = ApiMessage::AssetNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
+        }
+    }
+
+    /// Endpoint for getting the insurance fund balance and the payout history.
+    fn get_insurance(&self, _: &mut Request) -> IronResult<Response> {
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let fund = schema.config().insurance.and_then(
+            |insurance| schema.wallet(&insurance.fund),
+        );
+        let payouts = schema
+            .insurance_payouts()
+            .iter()
+            .map(|(tx_hash, payout)| InsurancePayoutInfo { tx_hash, payout })
+            .collect();
+        let info = InsuranceInfo { fund, payouts };
+
+        self.ok_response(&serde_json::to_value(&info).unwrap())
+    }
+
+    /// Endpoint for getting the audit record of all clawbacks.
+    fn get_clawbacks(&self, _: &mut Request) -> IronResult<Response> {
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let clawbacks: Vec<_> = schema
+            .clawbacks()
+            .iter()
+            .map(|(transfer, clawback)| ClawbackInfo { transfer, clawback })
+            .collect();
+
+        self.ok_response(&serde_json::to_value(&clawbacks).unwrap())
+    }
+
+    /// Endpoint for getting the root hashes of the service tables.
+    fn get_state_root(&self, _: &mut Request) -> IronResult<Response> {
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let root = StateRoot {
+            height: schema.height(),
+            wallets: schema.wallets().root_hash(),
+            tables: schema.state_hash(),
+        };
+        self.ok_response(&serde_json::to_value(&root).unwrap())
+    }
+
+    /// Endpoint for getting the status of a transaction.
+    fn get_transaction_status(&self, req: &mut Request) -> IronResult<Response> {
+        let tx_hash = {
+            let tx_hash = req.extensions.get::<Router>().unwrap().find("hash").unwrap();
+            Hash::from_hex(tx_hash).map_err(ApiError::FromHex)?
+        };
+
+        let snapshot = self.blockchain.snapshot();
+        let location = CoreSchema::new(&snapshot).tx_location_by_tx_hash().get(&tx_hash);
+        let status = match location {
+            Some(location) => {
+                let result = CurrencySchema::new(&snapshot).tx_result(&tx_hash);
+                TransactionStatus::Committed {
+                    height: location.block_height().0,
+                    code: result.map(|result| result.map(|_| 0).unwrap_or_else(|err| err.code())),
+                    error: result.and_then(|result| result.err()).map(|err| err.to_string()),
+                }
+            }
+            None if self.submitted.lock().unwrap().contains_key(&tx_hash) => {
+                TransactionStatus::Pending
+            }
+            None => TransactionStatus::Unknown,
+        };
+        self.ok_response(&serde_json::to_value(&status).unwrap())
+    }
+
+    /// Endpoint for getting a signed statement of a wallet for the blocks between
+    /// the `from_height` and `to_height` query parameters inclusive.
+    fn get_statement(&self, req: &mut Request) -> IronResult<Response> {
+        let pub_key = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+        let params = payment::parse_query(req.url.query().unwrap_or(""))
+            .map_err(|e| ApiError::IncorrectRequest(Box::new(e)))?;
+        let height_param = |name: &str| -> Result<u64, ApiError> {
+            let value = params.get(name).map_or("", |value| value.as_str());
+            value.parse().map_err(|e| ApiError::IncorrectRequest(Box::new(e)))
+        };
+        let from_height = height_param("from_height")?;
+        let to_height = height_param("to_height")?;
+
+        let snapshot = self.blockchain.snapshot();
+        let schema = CurrencySchema::new(&snapshot);
+        if to_height >= schema.height() {
+            let message = ApiMessage::BlockNotFound.text(Self::language(req));
+            return self.not_found_response(&serde_json::to_value(message).unwrap());
+        }
+        if from_height > to_height {
+            let message = ApiMessage::InvalidStatementWindow.text(Self::language(req));
+            Err(ApiError::IncorrectRequest(message.into()))?
+        }
+
+        let balance_at = |height| schema.wallet_at(&pub_key, height).map_or(0, |w| w.balance());
+        let opening_balance = if from_height == 0 {
+            0
+        } else {
+            balance_at(from_height - 1)
+        };
+        let locations = CoreSchema::new(&snapshot).tx_location_by_tx_hash();
+        let mut scan = self.query_budget.start();
+        let mut transactions = Vec::new();
+        for tx_hash in schema.wallet_history(&pub_key).iter() {
+            if !scan.step() {
+                let message = ApiMessage::InvalidStatementWindow.text(Self::language(req));
+                Err(ApiError::IncorrectRequest(message.into()))?
+            }
+            let height = match locations.get(&tx_hash) {
+                Some(location) => location.block_height().0,
+                None => continue,
+            };
+            if from_height <= height && height <= to_height {
+                transactions.push(StatementEntry { tx_hash, height });
+            }
+        }
+        let statement = Statement {
+            pub_key,
+            from_height,
+            to_height,
+            opening_balance,
+            closing_balance: balance_at(to_height),
+            transactions,
+        };
+
+        let (ref signer, ref secret_key) = self.service_keys;
+        let signed = SignedStatement::new(statement, signer, secret_key);
+        self.ok_response(&serde_json::to_value(&signed).unwrap())
+    }
+
+    /// Endpoint for listing the activity feed of a wallet: the transactions from
+    /// its history together with their kinds, results and contents, oldest first.
+    fn get_wallet_activity(&self, req: &mut Request) -> IronResult<Response> {
+        let pub_key = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+
+        let page = Self::page_request(req)?;
+        let start = match page.cursor {
+            Some(ref cursor) => cursor.parse().map_err(|_| Self::cursor_error())?,
+            None => 0,
+        };
+
+        let snapshot = self.blockchain.snapshot();
+        let schema = CurrencySchema::new(&snapshot);
+        let core = CoreSchema::new(&snapshot);
+        let locations = core.tx_location_by_tx_hash();
+        let transactions = core.transactions();
+        let entry = |tx_hash: Hash| {
+            let height = locations.get(&tx_hash).map_or(0, |location| location.block_height().0);
+            let code = match schema.tx_result(&tx_hash) {
+                Some(Err(err)) => err.code(),
+                _ => 0,
+            };
+            let raw = transactions.get(&tx_hash);
+            let message_type = raw.as_ref().map_or(0, |raw| raw.message_type());
+            let content = match raw.and_then(|raw| self.blockchain.tx_from_raw(raw)) {
+                Some(tx) => tx.info(),
+                None => serde_json::Value::Null,
+            };
+            ActivityEntry {
+                tx_hash,
+                height,
+                kind: ActivityKind::of(message_type),
+                message_type,
+                code,
+                content,
+            }
+        };
+
+        let idx = schema.wallet_history(&pub_key);
+        let mut scan = self.query_budget.start();
+        let items = idx.iter_from(start).map(entry);
+        let mut activity = Page::collect(items, page.limit, &mut scan, |_| String::new());
+        let next = start + activity.items.len() as u64;
+        activity.next_cursor = activity.next_cursor.map(|_| next.to_string());
+        activity.total_estimate = idx.len();
+
+        self.ok_response(&serde_json::to_value(&activity).unwrap())
+    }
+
+    /// Endpoint for getting the graph of successful transfers around a wallet
+    /// up to `depth` hops away (1 by default, at most `MAX_GRAPH_DEPTH`).
+    /// Counterparties are followed only along edges with a volume of at least
+    /// `min_amount`. The histories of the wallets are scanned within the query
+    /// budget, and the graph has at most `MAX_GRAPH_NODES` wallets.
+    fn get_counterparty_graph(&self, req: &mut Request) -> IronResult<Response> {
+        let pub_key = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+        let params = payment::parse_query(req.url.query().unwrap_or(""))
+            .map_err(|e| ApiError::IncorrectRequest(Box::new(e)))?;
+        let number_param = |name: &str, default: u64| -> Result<u64, ApiError> {
+            match params.get(name) {
+                Some(value) => value.parse().map_err(|e| ApiError::IncorrectRequest(Box::new(e))),
+                None => Ok(default),
+            }
+        };
+        let depth = number_param("depth", 1)?;
+        let min_amount = number_param("min_amount", 0)?;
+        if depth == 0 || depth > MAX_GRAPH_DEPTH {
+            let message = format!("Depth must be between 1 and {}", MAX_GRAPH_DEPTH);
+            Err(ApiError::IncorrectRequest(message.into()))?
+        }
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        if schema.wallet(&pub_key).is_none() {
+            let message = ApiMessage::WalletNotFound.text(Self::language(req));
+            return self.not_found_response(&serde_json::to_value(message).unwrap());
+        }
+
+        let mut scan = self.query_budget.start();
+        let mut distances = BTreeMap::new();
+        distances.insert(pub_key, 0);
+        // Aggregates of the edges keyed by `(from, to)`. A transfer is in the
+        // histories of both wallets, so it is counted once by its hash.
+        let mut volumes: BTreeMap<(PublicKey, PublicKey), (u64, u64)> = BTreeMap::new();
+        let mut counted = BTreeSet::new();
+        let mut frontier = vec![pub_key];
+        let mut truncated = false;
+        'graph: for distance in 1..depth + 1 {
+            let mut next = Vec::new();
+            for wallet in frontier {
+                for tx_hash in schema.wallet_history(&wallet).iter() {
+                    if !scan.step() {
+                        truncated = true;
+                        break 'graph;
+                    }
+                    if !counted.insert(tx_hash) {
+                        continue;
+                    }
+                    for (from, to, amount) in schema.committed_payments(&tx_hash) {
+                        let edge = volumes.entry((from, to)).or_insert((0, 0));
+                        *edge = (edge.0.saturating_add(amount), edge.1 + 1);
+                    }
+                }
+
+                // The history of the wallet is scanned, so its edges are complete.
+                let counterparties: Vec<PublicKey> = volumes
+                    .iter()
+                    .filter(|&(_, &(volume, _))| volume >= min_amount)
+                    .filter_map(|(&(from, to), _)| if from == wallet {
+                        Some(to)
+                    } else if to == wallet {
+                        Some(from)
+                    } else {
+                        None
+                    })
+                    .collect();
+                for counterparty in counterparties {
+                    if distances.contains_key(&counterparty) {
+                        continue;
+                    }
+                    if distances.len() >= MAX_GRAPH_NODES {
+                        truncated = true;
+                        break 'graph;
+                    }
+                    distances.insert(counterparty, distance);
+                    next.push(counterparty);
+                }
+            }
+            frontier = next;
+        }
+
+        let mut nodes: Vec<GraphNode> = distances
+            .iter()
+            .map(|(&pub_key, &distance)| GraphNode { pub_key, distance })
+            .collect();
+        nodes.sort_by_key(|node| node.distance);
+        let edges = volumes
+            .into_iter()
+            .filter(|&((ref from, ref to), (volume, _))| {
+                volume >= min_amount && distances.contains_key(from) && distances.contains_key(to)
+            })
+            .map(|((from, to), (volume, transfers))| {
+                GraphEdge {
+                    from,
+                    to,
+                    volume,
+                    transfers,
+                }
+            })
+            .collect();
+        let graph = CounterpartyGraph {
+            nodes,
+            edges,
+            truncated,
+        };
+        self.ok_response(&serde_json::to_value(&graph).unwrap())
+    }
+
+    /// Endpoint for listing the transaction history of a wallet, oldest first.
+    fn get_wallet_history(&self, req: &mut Request) -> IronResult<Response> {
+        let pub_key = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+
+        let page = Self::page_request(req)?;
+        let start = match page.cursor {
+            Some(ref cursor) => cursor.parse().map_err(|_| Self::cursor_error())?,
+            None => 0,
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let idx = schema.wallet_history(&pub_key);
+        let mut scan = self.query_budget.start();
+        let mut history = Page::collect(idx.iter_from(start), page.limit, &mut scan, |_| {
+            String::new()
+        });
+        let next = start + history.items.len() as u64;
+        history.next_cursor = history.next_cursor.map(|_| next.to_string());
+        history.total_estimate = idx.len();
+
+        self.ok_response(&serde_json::to_value(&history).unwrap())
+    }
+
+    /// Endpoint for listing burns made with a particular tag.
+    fn get_burns_by_tag(&self, req: &mut Request) -> IronResult<Response> {
+        let tag = {
+            let tag = req.extensions.get::<Router>().unwrap().find("tag").unwrap();
+            Hash::from_hex(tag).map_err(ApiError::FromHex)?
+        };
+
+        let page = Self::page_request(req)?;
+        let start = match page.cursor {
+            Some(ref cursor) => cursor.parse().map_err(|_| Self::cursor_error())?,
+            None => 0,
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let idx = schema.burns_by_tag(&tag);
+        let mut scan = self.query_budget.start();
+        let mut burns = Page::collect(idx.iter_from(start), page.limit, &mut scan, |_| {
+            String::new()
+        });
+        // The cursor of a list is the index of the first item not on the page.
+        let next = start + burns.items.len() as u64;
+        burns.next_cursor = burns.next_cursor.map(|_| next.to_string());
+        burns.total_estimate = idx.len();
+
+        self.ok_response(&serde_json::to_value(&burns).unwrap())
+    }
+
+    /// Endpoint for getting the service parameters.
+    fn get_info(&self, _: &mut Request) -> IronResult<Response> {
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let info = ServiceInfo {
+            height: schema.height(),
+            limits: schema.config().limits,
+        };
+        self.ok_response(&serde_json::to_value(&info).unwrap())
+    }
+
+    /// Endpoint for getting the total of burned coins.
+    fn get_supply(&self, _: &mut Request) -> IronResult<Response> {
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let supply = SupplyInfo {
+            height: schema.height(),
+            minted: schema.minted().get().unwrap_or(0),
+            burned: schema.burned().get().unwrap_or(0),
+        };
+        self.ok_response(&serde_json::to_value(&supply).unwrap())
+    }
+
+    /// Endpoint for the cost of a batch transfer from a wallet, given the fee
+    /// and the comma-separated amounts of the payments in the `fee` and the
+    /// `amounts` query parameters.
+    fn get_batch_cost(&self, req: &mut Request) -> IronResult<Response> {
+        let from = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+        let params = payment::parse_query(req.url.query().unwrap_or(""))
+            .map_err(|e| ApiError::IncorrectRequest(Box::new(e)))?;
+        let fee: u64 = params.get("fee").map_or("0", |fee| fee.as_str()).parse().map_err(
+            |e| ApiError::IncorrectRequest(Box::new(e)),
+        )?;
+        let amounts = params
+            .get("amounts")
+            .map_or("", |amounts| amounts.as_str())
+            .split(',')
+            .filter(|amount| !amount.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<u64>, _>>()
+            .map_err(|e| ApiError::IncorrectRequest(Box::new(e)))?;
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let cost = schema.batch_transfer_cost(&from, &amounts, fee).map_err(|e| {
+            ApiError::IncorrectRequest(Box::new(e))
+        })?;
+        let info = BatchCost {
+            height: schema.height(),
+            cost,
+        };
+        self.ok_response(&serde_json::to_value(&info).unwrap())
+    }
+
+    /// Endpoint for estimating the fee of a transfer.
+    fn get_fee_estimate(&self, _: &mut Request) -> IronResult<Response> {
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let estimate = FeeEstimate {
+            height: schema.height(),
+            base_fee: schema.base_fee(),
+        };
+
+        self.ok_response(&serde_json::to_value(&estimate).unwrap())
+    }
+
+    /// Endpoint normalizing a wallet name given in the `name` query parameter
+    /// and checking it against the limits of the current configuration.
+    fn get_normalized_name(&self, req: &mut Request) -> IronResult<Response> {
+        let params = payment::parse_query(req.url.query().unwrap_or(""))
+            .map_err(|e| ApiError::IncorrectRequest(Box::new(e)))?;
+        let name = params.get("name").map_or("", |name| name.as_str());
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let max_length = schema.config().limits.max_name_length;
+        let normalized = text::NormalizedText::new(name, max_length);
+        self.ok_response(&serde_json::to_value(&normalized).unwrap())
+    }
+
+    /// Endpoint listing existing wallets with names equal or similar to the name
+    /// in the `name` query parameter. Candidates are found with the trigram index
+    /// of names; the scan stops early if the query budget runs out.
+    fn get_name_check(&self, req: &mut Request) -> IronResult<Response> {
+        let params = payment::parse_query(req.url.query().unwrap_or(""))
+            .map_err(|e| ApiError::IncorrectRequest(Box::new(e)))?;
+        let name = text::normalize(params.get("name").map_or("", |name| name.as_str()));
+        let skeleton = text::skeleton(&name);
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let mut scan = self.query_budget.start();
+        let mut candidates = BTreeSet::new();
+        'scan: for trigram in text::trigrams(&name) {
+            for pub_key in schema.name_trigrams(&trigram).keys() {
+                if !scan.step() {
+                    break 'scan;
+                }
+                candidates.insert(pub_key);
+            }
+        }
+
+        let mut matches: Vec<_> = candidates
+            .into_iter()
+            .filter_map(|pub_key| {
+                let wallet = schema.wallet(&pub_key)?;
+                let other = text::skeleton(wallet.name());
+                let distance = text::edit_distance(&skeleton, &other) as u64;
+                let kind = if wallet.name() == name {
+                    NameMatchKind::Exact
+                } else if distance == 0 {
+                    NameMatchKind::Confusable
+                } else if distance <= NAME_SIMILARITY_DISTANCE {
+                    NameMatchKind::Similar
+                } else {
+                    return None;
+                };
+                Some(NameMatch {
+                    pub_key,
+                    name: wallet.name().to_owned(),
+                    kind,
+                    distance,
+                })
+            })
+            .collect();
+        matches.sort_by_key(|m| (m.distance, m.kind));
+
+        let check = NameCheck { name, matches };
+        self.ok_response(&serde_json::to_value(&check).unwrap())
+    }
+
+    /// Endpoint for listing pending deferred transfers.
+    fn get_deferred_transfers(&self, req: &mut Request) -> IronResult<Response> {
+        let page = Self::page_request(req)?;
+        let start = match page.cursor {
+            Some(ref cursor) => Some(Hash::from_hex(cursor).map_err(|_| Self::cursor_error())?),
+            None => None,
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let idx = schema.deferred_transfers();
+        let mut scan = self.query_budget.start();
+        let info = |(tx_hash, transfer): (Hash, DeferredTransfer)| {
+            DeferredTransferInfo { tx_hash, transfer }
+        };
+        let cursor_of = |info: &DeferredTransferInfo| info.tx_hash.to_string();
+        let mut transfers = match start {
+            Some(ref tx_hash) => {
+                let iter = idx.iter_from(tx_hash).map(info);
+                Page::collect(iter, page.limit, &mut scan, cursor_of)
+            }
+            None => Page::collect(idx.iter().map(info), page.limit, &mut scan, cursor_of),
+        };
+        transfers.total_estimate = scan.count(idx.keys());
+
+        self.ok_response(&serde_json::to_value(&transfers).unwrap())
+    }
+
+    /// Endpoint for listing locked transfers. The optional `to` query parameter
+    /// restricts the list to the transfers locked for the given wallet.
+    fn get_locked_transfers(&self, req: &mut Request) -> IronResult<Response> {
+        let page = Self::page_request(req)?;
+        let start = match page.cursor {
+            Some(ref cursor) => Some(Hash::from_hex(cursor).map_err(|_| Self::cursor_error())?),
+            None => None,
+        };
+        let params = payment::parse_query(req.url.query().unwrap_or(""))
+            .map_err(|e| ApiError::IncorrectRequest(Box::new(e)))?;
+        let to = match params.get("to") {
+            Some(to) => Some(PublicKey::from_hex(to).map_err(ApiError::FromHex)?),
+            None => None,
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let idx = schema.locked_transfers();
+        let iter = match start {
+            Some(ref tx_hash) => idx.iter_from(tx_hash),
+            None => idx.iter(),
+        };
+        let mut scan = self.query_budget.start();
+        let mut items = Vec::new();
+        let mut next_cursor = None;
+        // Transfers are filtered while scanning, so the cursor is the hash
+        // of the first transfer not scanned yet.
+        for (tx_hash, transfer) in iter {
+            if items.len() == page.limit || !scan.step() {
+                next_cursor = Some(tx_hash.to_string());
+                break;
+            }
+            if to.as_ref().map_or(true, |to| transfer.to() == to) {
+                items.push(LockedTransferInfo { tx_hash, transfer });
+            }
+        }
+        let total_estimate = match to {
+            Some(_) => items.len() as u64,
+            None => scan.count(idx.keys()),
+        };
+        let transfers = Page {
+            items,
+            next_cursor,
+            total_estimate,
+        };
+
+        self.ok_response(&serde_json::to_value(&transfers).unwrap())
+    }
+
+    /// Endpoint for getting the value of the randomness beacon at a committed height.
+    fn get_beacon(&self, req: &mut Request) -> IronResult<Response> {
+        let height = {
+            let height = req.extensions.get::<Router>().unwrap().find("height").unwrap();
+            height.parse::<u64>().map_err(
+                |e| ApiError::IncorrectRequest(Box::new(e)),
+            )?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.beacon(height) {
+            Some(value) => {
+                let info = BeaconInfo { height, value };
+                self.ok_response(&serde_json::to_value(&info).unwrap())
+            }
+            None => {
+                let message = ApiMessage::BlockNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
+        }
+    }
+
+    /// Endpoint for getting a lottery draw with its entrants.
+    fn get_draw(&self, req: &mut Request) -> IronResult<Response> {
+        let id = {
+            let id = req.extensions.get::<Router>().unwrap().find("id").unwrap();
+            Hash::from_hex(id).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.draws().get(&id) {
+            Some(draw) => {
+                let entrants = schema.draw_entrants(&id).iter().collect();
+                let info = DrawInfo {
+                    id,
+                    draw,
+                    entrants,
+                };
+                self.ok_response(&serde_json::to_value(&info).unwrap())
+            }
+            None => {
+                let message = ApiMessage::DrawNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
+        }
+    }
+
+    /// Endpoint for getting a fee coupon by the hash of its code.
+    fn get_fee_coupon(&self, req: &mut Request) -> IronResult<Response> {
+        let code = {
+            let code = req.extensions.get::<Router>().unwrap().find("code").unwrap();
+            Hash::from_hex(code).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.fee_coupons().get(&code) {
+            Some(coupon) => self.ok_response(&serde_json::to_value(&coupon).unwrap()),
+            None => {
+                let message = ApiMessage::CouponNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
+        }
+    }
+
+    /// Endpoint for getting a deposit which is not claimed or returned yet.
+    fn get_deposit(&self, req: &mut Request) -> IronResult<Response> {
+        let id = {
+            let id = req.extensions.get::<Router>().unwrap().find("id").unwrap();
+            Hash::from_hex(id).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.deposits().get(&id) {
+            Some(deposit) => self.ok_response(&serde_json::to_value(&deposit).unwrap()),
+            None => {
+                let message = ApiMessage::DepositNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
+        }
+    }
+
+    /// Endpoint for getting an open escrow.
+    fn get_escrow(&self, req: &mut Request) -> IronResult<Response> {
+        let id = {
+            let id = req.extensions.get::<Router>().unwrap().find("id").unwrap();
+            Hash::from_hex(id).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.escrows().get(&id) {
+            Some(escrow) => self.ok_response(&serde_json::to_value(&escrow).unwrap()),
+            None => {
+                let message = ApiMessage::EscrowNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
+        }
+    }
+
+    /// Endpoint for getting an open hashed time-lock contract.
+    fn get_htlc(&self, req: &mut Request) -> IronResult<Response> {
+        let id = {
+            let id = req.extensions.get::<Router>().unwrap().find("id").unwrap();
+            Hash::from_hex(id).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.htlcs().get(&id) {
+            Some(htlc) => self.ok_response(&serde_json::to_value(&htlc).unwrap()),
+            None => {
+                let message = ApiMessage::HtlcNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
+        }
+    }
+
+    /// Endpoint for getting the preimage revealed for a hash lock.
+    fn get_htlc_preimage(&self, req: &mut Request) -> IronResult<Response> {
+        let hash_lock = {
+            let hash_lock = req.extensions.get::<Router>().unwrap().find("hash_lock").unwrap();
+            Hash::from_hex(hash_lock).map_err(ApiError::FromHex)?
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.htlc_preimages().get(&hash_lock) {
+            Some(preimage) => {
+                let info = HtlcPreimage {
+                    hash_lock,
+                    preimage,
+                };
+                self.ok_response(&serde_json::to_value(&info).unwrap())
+            }
+            None => {
+                let message = ApiMessage::PreimageNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
+        }
+    }
+
+    /// Endpoint for listing open escrows.
+    fn get_escrows(&self, req: &mut Request) -> IronResult<Response> {
+        let page = Self::page_request(req)?;
+        let start = match page.cursor {
+            Some(ref cursor) => Some(Hash::from_hex(cursor).map_err(|_| Self::cursor_error())?),
+            None => None,
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let idx = schema.escrows();
+        let mut scan = self.query_budget.start();
+        let info = |(tx_hash, escrow): (Hash, Escrow)| EscrowInfo { tx_hash, escrow };
+        let cursor_of = |info: &EscrowInfo| info.tx_hash.to_string();
+        let mut escrows = match start {
+            Some(ref tx_hash) => {
+                let iter = idx.iter_from(tx_hash).map(info);
+                Page::collect(iter, page.limit, &mut scan, cursor_of)
+            }
+            None => Page::collect(idx.iter().map(info), page.limit, &mut scan, cursor_of),
+        };
+        escrows.total_estimate = scan.count(idx.keys());
+
+        self.ok_response(&serde_json::to_value(&escrows).unwrap())
+    }
 
-        let wallet = {
-            let mut view = self.blockchain.fork();
-            let mut schema = CurrencySchema { view: &mut view };
-            schema.wallet(&public_key)
+    /// Endpoint for getting an open payment stream.
+    fn get_stream(&self, req: &mut Request) -> IronResult<Response> {
+        let id = {
+            let id = req.extensions.get::<Router>().unwrap().find("id").unwrap();
+            Hash::from_hex(id).map_err(ApiError::FromHex)?
         };
 
-        if let Some(wallet) = wallet {
-            self.ok_response(&serde_json::to_value(wallet).unwrap())
-        } else {
-            self.not_found_response(&serde_json::to_value("Wallet not found").unwrap())
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        match schema.streams().get(&id) {
+            Some(stream) => {
+                let available = stream.available(schema.height());
+                let info = StreamInfo {
+                    id,
+                    stream,
+                    available,
+                };
+                self.ok_response(&serde_json::to_value(&info).unwrap())
+            }
+            None => {
+                let message = ApiMessage::StreamNotFound.text(Self::language(req));
+                self.not_found_response(&serde_json::to_value(message).unwrap())
+            }
         }
     }
 
-    /// Endpoint for dumping all wallets from the storage.
-    fn get_wallets(&self, _: &mut Request) -> IronResult<Response> {
-        let mut view = self.blockchain.fork();
-        let mut schema = CurrencySchema { view: &mut view };
-        let idx = schema.wallets();
-        let wallets: Vec<Wallet> = idx.values().collect();
+    /// Endpoint for listing auctions in the order of the hashes of the transactions
+    /// that created them.
+    fn get_auctions(&self, req: &mut Request) -> IronResult<Response> {
+        let page = Self::page_request(req)?;
+        let start = match page.cursor {
+            Some(ref cursor) => Some(Hash::from_hex(cursor).map_err(|_| Self::cursor_error())?),
+            None => None,
+        };
 
-        self.ok_response(&serde_json::to_value(&wallets).unwrap())
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let idx = schema.auctions();
+        let mut scan = self.query_budget.start();
+        let info = |(tx_hash, auction): (Hash, Auction)| AuctionInfo { tx_hash, auction };
+        let cursor_of = |info: &AuctionInfo| info.tx_hash.to_string();
+        let mut auctions = match start {
+            Some(ref tx_hash) => {
+                let iter = idx.iter_from(tx_hash).map(info);
+                Page::collect(iter, page.limit, &mut scan, cursor_of)
+            }
+            None => Page::collect(idx.iter().map(info), page.limit, &mut scan, cursor_of),
+        };
+        auctions.total_estimate = scan.count(idx.keys());
+
+        self.ok_response(&serde_json::to_value(&auctions).unwrap())
+    }
+
+    /// Endpoint for listing transfers held by the velocity rules in the order of
+    /// the transaction hashes.
+    fn get_held_transfers(&self, req: &mut Request) -> IronResult<Response> {
+        let page = Self::page_request(req)?;
+        let start = match page.cursor {
+            Some(ref cursor) => Some(Hash::from_hex(cursor).map_err(|_| Self::cursor_error())?),
+            None => None,
+        };
+
+        let schema = CurrencySchema::new(self.blockchain.snapshot());
+        let idx = schema.held_transfers();
+        let mut scan = self.query_budget.start();
+        let info = |(tx_hash, transfer): (Hash, HeldTransfer)| {
+            HeldTransferInfo { tx_hash, transfer }
+        };
+        let cursor_of = |info: &HeldTransferInfo| info.tx_hash.to_string();
+        let mut transfers = match start {
+            Some(ref tx_hash) => {
+                let iter = idx.iter_from(tx_hash).map(info);
+                Page::collect(iter, page.limit, &mut scan, cursor_of)
+            }
+            None => Page::collect(idx.iter().map(info), page.limit, &mut scan, cursor_of),
+        };
+        transfers.total_estimate = scan.count(idx.keys());
+
+        self.ok_response(&serde_json::to_value(&transfers).unwrap())
+    }
+
+    /// Endpoint rendering a payment request to the wallet as an SVG QR code.
+    /// The optional `amount`, `memo`, `reference` and `expires` query parameters
+    /// are included into the encoded `excoin:` URI.
+    fn get_payment_qr(&self, req: &mut Request) -> IronResult<Response> {
+        use iron::headers::ContentType;
+        use iron::status;
+
+        let to = {
+            let wallet_key = req.extensions.get::<Router>().unwrap().find("pub_key").unwrap();
+            PublicKey::from_hex(wallet_key).map_err(ApiError::FromHex)?
+        };
+        let mut request = PaymentRequest::parse(to, req.url.query().unwrap_or(""))
+            .map_err(|e| ApiError::IncorrectRequest(Box::new(e)))?;
+        request.memo = request.memo.map(|memo| text::normalize(&memo));
+
+        let code = QrCode::new(request.to_uri().as_bytes()).map_err(|e| {
+            let message = ApiMessage::QrEncoding.text(Self::language(req));
+            ApiError::IncorrectRequest(format!("{}: {:?}", message, e).into())
+        })?;
+        let image = code.render::<svg::Color>().min_dimensions(200, 200).build();
+
+        let mut response = Response::with((status::Ok, image));
+        response.headers.set(ContentType("image/svg+xml".parse().unwrap()));
+        Ok(response)
+    }
+
+    /// Endpoint serving the explorer page.
+    #[cfg(feature = "explorer")]
+    fn get_explorer(&self, _: &mut Request) -> IronResult<Response> {
+        use iron::headers::ContentType;
+        use iron::status;
+
+        let mut response = Response::with((status::Ok, EXPLORER_PAGE));
+        response.headers.set(ContentType::html());
+        Ok(response)
     }
 
-    /// Common processing for transaction-accepting endpoints.
+    /// Common processing for sandbox endpoints. The transaction is executed
+    /// against a fork of the latest committed state, which is dropped once the
+    /// response is built; nothing is broadcast to the network.
+    #[cfg(feature = "sandbox")]
+    fn post_sandbox_transaction<T>(&self, req: &mut Request) -> IronResult<Response>
+    where
+        T: Transaction + Clone + for<'de> Deserialize<'de>,
+    {
+        match req.get::<bodyparser::Struct<T>>() {
+            Ok(Some(transaction)) => {
+                let tx_hash = transaction.hash();
+                // The node rejects transactions failing verification before execution.
+                let result = if transaction.verify() {
+                    let mut fork = self.blockchain.fork();
+                    Activated(Box::new(transaction)).execute(&mut fork);
+                    CurrencySchema::new(&fork).tx_result(&tx_hash).unwrap_or(Ok(()))
+                } else {
+                    Err(Error::Verification)
+                };
+                let json = SandboxResponse {
+                    tx_hash,
+                    code: result.map(|_| 0).unwrap_or_else(|err| err.code()),
+                    error: result.err().map(|err| err.to_string()),
+                };
+                self.ok_response(&serde_json::to_value(&json).unwrap())
+            }
+            Ok(None) => {
+                let message = ApiMessage::EmptyRequestBody.text(Self::language(req));
+                Err(ApiError::IncorrectRequest(message.into()))?
+            }
+            Err(e) => Err(ApiError::IncorrectRequest(Box::new(e)))?,
+        }
+    }
+
+    /// Common processing for transaction-accepting endpoints. A transaction
+    /// submitted with the `deadline` query parameter is tracked until it is
+    /// committed and raises an alert if it is not committed within `deadline`
+    /// seconds.
     fn post_transaction<T>(&self, req: &mut Request) -> IronResult<Response>
     where
         T: Transaction + Clone + for<'de> Deserialize<'de>,
     {
+        let params = payment::parse_query(req.url.query().unwrap_or(""))
+            .map_err(|e| ApiError::IncorrectRequest(Box::new(e)))?;
+        let deadline: Option<u64> = match params.get("deadline") {
+            Some(value) => Some(value.parse().map_err(
+                |e| ApiError::IncorrectRequest(Box::new(e)),
+            )?),
+            None => None,
+        };
         match req.get::<bodyparser::Struct<T>>() {
             Ok(Some(transaction)) => {
+                if !transaction.verify() {
+                    let error = ApiError::IncorrectRequest(Box::new(Error::Verification));
+                    return Err(error.into());
+                }
+                if let Some(deadline) = deadline {
+                    self.sla.lock().unwrap().accepts(deadline).map_err(
+                        |e| ApiError::IncorrectRequest(Box::new(e)),
+                    )?;
+                }
                 let transaction: Box<Transaction> = Box::new(transaction);
                 let tx_hash = transaction.hash();
                 self.channel.send(transaction).map_err(ApiError::from)?;
+                let height = CurrencySchema::new(self.blockchain.snapshot()).height();
+                self.submitted.lock().unwrap().insert(tx_hash, height);
+                if let Some(deadline) = deadline {
+                    let mut tracker = self.sla.lock().unwrap();
+                    if let Err(e) = tracker.track(tx_hash, sla::now(), deadline, height) {
+                        println!("Cannot track transaction {:?}: {}", tx_hash, e);
+                    }
+                }
                 let json = TransactionResponse { tx_hash };
                 self.ok_response(&serde_json::to_value(&json).unwrap())
             }
-            Ok(None) => Err(ApiError::IncorrectRequest("Empty request body".into()))?,
+            Ok(None) => {
+                let message = ApiMessage::EmptyRequestBody.text(Self::language(req));
+                Err(ApiError::IncorrectRequest(message.into()))?
+            }
             Err(e) => Err(ApiError::IncorrectRequest(Box::new(e)))?,
         }
     }
 }
 
+/// Implement the private node API, available to the node operator only.
+#[derive(Clone)]
+struct PrivateCryptocurrencyApi {
+    blockchain: Blockchain,
+    risk: Arc<Mutex<RiskMonitor>>,
+    submitted: Arc<Mutex<BTreeMap<Hash, u64>>>,
+    sla: Arc<Mutex<SlaTracker>>,
+}
+
+impl PrivateCryptocurrencyApi {
+    /// Endpoint for listing wallets flagged by the risk model.
+    fn get_risk_flags(&self, _: &mut Request) -> IronResult<Response> {
+        let flagged = self.risk.lock().unwrap().flagged();
+        self.ok_response(&serde_json::to_value(&flagged).unwrap())
+    }
+
+    /// Endpoint for listing recent alerts on transactions not committed
+    /// within their deadlines.
+    fn get_sla_alerts(&self, _: &mut Request) -> IronResult<Response> {
+        let alerts = self.sla.lock().unwrap().alerts();
+        self.ok_response(&serde_json::to_value(&alerts).unwrap())
+    }
+
+    /// Endpoint for getting aggregate figures of the service over the last
+    /// `blocks` committed blocks (`DEFAULT_OVERVIEW_WINDOW` if not given).
+    /// Visits every wallet, so it is served by the private API only.
+    fn get_overview(&self, req: &mut Request) -> IronResult<Response> {
+        let params = payment::parse_query(req.url.query().unwrap_or(""))
+            .map_err(|e| ApiError::IncorrectRequest(Box::new(e)))?;
+        let window = match params.get("blocks") {
+            Some(value) => value.parse().map_err(|e| ApiError::IncorrectRequest(Box::new(e)))?,
+            None => DEFAULT_OVERVIEW_WINDOW,
+        };
+
+        let snapshot = self.blockchain.snapshot();
+        let schema = CurrencySchema::new(&snapshot);
+        let core_schema = CoreSchema::new(&snapshot);
+        let height = schema.height();
+        let from_height = height.saturating_sub(window);
+
+        let mut transactions = 0;
+        let mut errors = BTreeMap::new();
+        for block_height in from_height..height {
+            for tx_hash in core_schema.block_txs(Height(block_height)).iter() {
+                transactions += 1;
+                match schema.tx_results().get(&tx_hash) {
+                    Some(0) | None => {}
+                    Some(code) => *errors.entry(code).or_insert(0) += 1,
+                }
+            }
+        }
+
+        let locations = core_schema.tx_location_by_tx_hash();
+        let is_active = |pub_key: &PublicKey| {
+            schema
+                .wallet_history(pub_key)
+                .last()
+                .and_then(|tx_hash| locations.get(&tx_hash))
+                .map_or(false, |location| location.block_height().0 >= from_height)
+        };
+        let (mut wallets, mut active_wallets, mut supply) = (0, 0, 0u64);
+        for (pub_key, wallet) in schema.wallets().iter() {
+            wallets += 1;
+            if is_active(&pub_key) {
+                active_wallets += 1;
+            }
+            supply = supply.saturating_add(wallet.balance());
+        }
+
+        let config = schema.config();
+        let balance_of = |pub_key: &PublicKey| schema.wallet(pub_key).map(|w| w.balance());
+        let overview = Overview {
+            height,
+            window: height - from_height,
+            wallets,
+            active_wallets,
+            transactions,
+            errors,
+            pending: self.submitted.lock().unwrap().len() as u64,
+            supply,
+            burned: schema.burned().get().unwrap_or(0),
+            treasury_balance: config.treasury.and_then(|key| balance_of(&key)),
+            insurance_balance: config.insurance.and_then(|insurance| balance_of(&insurance.fund)),
+        };
+        self.ok_response(&serde_json::to_value(&overview).unwrap())
+    }
+}
+
+impl Api for PrivateCryptocurrencyApi {
+    fn wire(&self, router: &mut Router) {
+        let self_ = self.clone();
+        let get_risk_flags = move |req: &mut Request| self_.get_risk_flags(req);
+        let self_ = self.clone();
+        let get_overview = move |req: &mut Request| self_.get_overview(req);
+        let self_ = self.clone();
+        let get_sla_alerts = move |req: &mut Request| self_.get_sla_alerts(req);
+        router.get("/v1/risk/flags", get_risk_flags, "get_risk_flags");
+        router.get("/v1/admin/overview", get_overview, "get_overview");
+        router.get("/v1/admin/sla-alerts", get_sla_alerts, "get_sla_alerts");
+    }
+}
 
 /// Implement the `Api` trait.
 /// `Api` facilitates conversion between transactions/read requests and REST
@@ -276,47 +9224,949 @@ impl Api for CryptocurrencyApi {
         let post_create_wallet =
             move |req: &mut Request| self_.post_transaction::<TxCreateWallet>(req);
         let self_ = self.clone();
+        let post_update_wallet =
+            move |req: &mut Request| self_.post_transaction::<TxUpdateWallet>(req);
+        let self_ = self.clone();
+        let post_rename_wallet =
+            move |req: &mut Request| self_.post_transaction::<TxRenameWallet>(req);
+        let self_ = self.clone();
+        let post_close_wallet =
+            move |req: &mut Request| self_.post_transaction::<TxCloseWallet>(req);
+        let self_ = self.clone();
         let post_transfer = move |req: &mut Request| self_.post_transaction::<TxTransfer>(req);
         let self_ = self.clone();
+        let post_create_and_fund_wallet =
+            move |req: &mut Request| self_.post_transaction::<TxCreateAndFundWallet>(req);
+        let self_ = self.clone();
+        let post_authorize_session_key =
+            move |req: &mut Request| self_.post_transaction::<TxAuthorizeSessionKey>(req);
+        let self_ = self.clone();
+        let post_revoke_session_key =
+            move |req: &mut Request| self_.post_transaction::<TxRevokeSessionKey>(req);
+        let self_ = self.clone();
+        let post_batch_transfer =
+            move |req: &mut Request| self_.post_transaction::<TxBatchTransfer>(req);
+        let self_ = self.clone();
+        let post_session_transfer =
+            move |req: &mut Request| self_.post_transaction::<TxSessionTransfer>(req);
+        let self_ = self.clone();
+        let get_session_keys = move |req: &mut Request| self_.get_session_keys(req);
+        let self_ = self.clone();
+        let post_approve = move |req: &mut Request| self_.post_transaction::<TxApprove>(req);
+        let self_ = self.clone();
+        let post_transfer_from =
+            move |req: &mut Request| self_.post_transaction::<TxTransferFrom>(req);
+        let self_ = self.clone();
+        let get_allowances = move |req: &mut Request| self_.get_allowances(req);
+        let self_ = self.clone();
+        let get_asset_balances = move |req: &mut Request| self_.get_asset_balances(req);
+        let self_ = self.clone();
+        let get_asset = move |req: &mut Request| self_.get_asset(req);
+        let self_ = self.clone();
+        let post_create_asset =
+            move |req: &mut Request| self_.post_transaction::<TxCreateAsset>(req);
+        let self_ = self.clone();
+        let post_asset_transfer =
+            move |req: &mut Request| self_.post_transaction::<TxAssetTransfer>(req);
+        let self_ = self.clone();
+        let post_attest_wallet =
+            move |req: &mut Request| self_.post_transaction::<TxAttestWallet>(req);
+        let self_ = self.clone();
+        let post_revoke_attestation =
+            move |req: &mut Request| self_.post_transaction::<TxRevokeAttestation>(req);
+        let self_ = self.clone();
+        let get_attestation = move |req: &mut Request| self_.get_attestation(req);
+        let self_ = self.clone();
+        let post_freeze_wallet =
+            move |req: &mut Request| self_.post_transaction::<TxFreezeWallet>(req);
+        let self_ = self.clone();
+        let post_unfreeze_wallet =
+            move |req: &mut Request| self_.post_transaction::<TxUnfreezeWallet>(req);
+        let self_ = self.clone();
+        let post_link_wallets =
+            move |req: &mut Request| self_.post_transaction::<TxLinkWallets>(req);
+        let self_ = self.clone();
+        let get_group = move |req: &mut Request| self_.get_group(req);
+        let self_ = self.clone();
+        let post_register_payment_reference =
+            move |req: &mut Request| self_.post_transaction::<TxRegisterPaymentReference>(req);
+        let self_ = self.clone();
+        let get_receipts = move |req: &mut Request| self_.get_receipts(req);
+        let self_ = self.clone();
+        let get_held_transfers = move |req: &mut Request| self_.get_held_transfers(req);
+        let self_ = self.clone();
+        let post_assign_tier = move |req: &mut Request| self_.post_transaction::<TxAssignTier>(req);
+        let self_ = self.clone();
+        let get_auctions = move |req: &mut Request| self_.get_auctions(req);
+        let self_ = self.clone();
+        let post_create_auction =
+            move |req: &mut Request| self_.post_transaction::<TxCreateAuction>(req);
+        let self_ = self.clone();
+        let post_bid = move |req: &mut Request| self_.post_transaction::<TxBid>(req);
+        let self_ = self.clone();
+        let get_stream = move |req: &mut Request| self_.get_stream(req);
+        let self_ = self.clone();
+        let get_beacon = move |req: &mut Request| self_.get_beacon(req);
+        let self_ = self.clone();
+        let get_draw = move |req: &mut Request| self_.get_draw(req);
+        let self_ = self.clone();
+        let post_lottery_draw =
+            move |req: &mut Request| self_.post_transaction::<TxLotteryDraw>(req);
+        let self_ = self.clone();
+        let post_enter_draw = move |req: &mut Request| self_.post_transaction::<TxEnterDraw>(req);
+        let self_ = self.clone();
+        let get_fee_coupon = move |req: &mut Request| self_.get_fee_coupon(req);
+        let self_ = self.clone();
+        let post_issue_fee_coupon =
+            move |req: &mut Request| self_.post_transaction::<TxIssueFeeCoupon>(req);
+        let self_ = self.clone();
+        let post_coupon_transfer =
+            move |req: &mut Request| self_.post_transaction::<TxCouponTransfer>(req);
+        let self_ = self.clone();
+        let get_deposit = move |req: &mut Request| self_.get_deposit(req);
+        let self_ = self.clone();
+        let get_escrow = move |req: &mut Request| self_.get_escrow(req);
+        let self_ = self.clone();
+        let get_escrows = move |req: &mut Request| self_.get_escrows(req);
+        let self_ = self.clone();
+        let get_htlc = move |req: &mut Request| self_.get_htlc(req);
+        let self_ = self.clone();
+        let get_htlc_preimage = move |req: &mut Request| self_.get_htlc_preimage(req);
+        let self_ = self.clone();
+        let post_create_htlc = move |req: &mut Request| self_.post_transaction::<TxCreateHtlc>(req);
+        let self_ = self.clone();
+        let post_claim_htlc = move |req: &mut Request| self_.post_transaction::<TxClaimHtlc>(req);
+        let self_ = self.clone();
+        let post_refund_htlc = move |req: &mut Request| self_.post_transaction::<TxRefundHtlc>(req);
+        let self_ = self.clone();
+        let post_create_escrow =
+            move |req: &mut Request| self_.post_transaction::<TxCreateEscrow>(req);
+        let self_ = self.clone();
+        let post_release_escrow =
+            move |req: &mut Request| self_.post_transaction::<TxReleaseEscrow>(req);
+        let self_ = self.clone();
+        let post_refund_escrow =
+            move |req: &mut Request| self_.post_transaction::<TxRefundEscrow>(req);
+        let self_ = self.clone();
+        let post_deposit = move |req: &mut Request| self_.post_transaction::<TxDeposit>(req);
+        let self_ = self.clone();
+        let post_claim_deposit =
+            move |req: &mut Request| self_.post_transaction::<TxClaimDeposit>(req);
+        let self_ = self.clone();
+        let post_open_stream = move |req: &mut Request| self_.post_transaction::<TxOpenStream>(req);
+        let self_ = self.clone();
+        let post_withdraw_stream =
+            move |req: &mut Request| self_.post_transaction::<TxWithdrawStream>(req);
+        let self_ = self.clone();
+        let post_close_stream =
+            move |req: &mut Request| self_.post_transaction::<TxCloseStream>(req);
+        let self_ = self.clone();
+        let post_review_held_transfer =
+            move |req: &mut Request| self_.post_transaction::<TxReviewHeldTransfer>(req);
+        let self_ = self.clone();
+        let post_deferred_transfer =
+            move |req: &mut Request| self_.post_transaction::<TxDeferredTransfer>(req);
+        let self_ = self.clone();
+        let post_locked_transfer =
+            move |req: &mut Request| self_.post_transaction::<TxLockedTransfer>(req);
+        let self_ = self.clone();
+        let post_cancel_deferred_transfer =
+            move |req: &mut Request| self_.post_transaction::<TxCancelDeferredTransfer>(req);
+        let self_ = self.clone();
+        let get_state_root = move |req: &mut Request| self_.get_state_root(req);
+        let self_ = self.clone();
         let get_wallets = move |req: &mut Request| self_.get_wallets(req);
         let self_ = self.clone();
         let get_wallet = move |req: &mut Request| self_.get_wallet(req);
+        let self_ = self.clone();
+        let get_payment_qr = move |req: &mut Request| self_.get_payment_qr(req);
+        let self_ = self.clone();
+        let get_loyalty = move |req: &mut Request| self_.get_loyalty(req);
+        let self_ = self.clone();
+        let get_promotion = move |req: &mut Request| self_.get_promotion(req);
+        let self_ = self.clone();
+        let get_sweep_rule = move |req: &mut Request| self_.get_sweep_rule(req);
+        let self_ = self.clone();
+        let post_set_sweep_rule =
+            move |req: &mut Request| self_.post_transaction::<TxSetSweepRule>(req);
+        let self_ = self.clone();
+        let post_clear_sweep_rule =
+            move |req: &mut Request| self_.post_transaction::<TxClearSweepRule>(req);
+        let self_ = self.clone();
+        let post_grant_promotion =
+            move |req: &mut Request| self_.post_transaction::<TxGrantPromotion>(req);
+        let self_ = self.clone();
+        let post_issue = move |req: &mut Request| self_.post_transaction::<TxIssue>(req);
+        let self_ = self.clone();
+        let post_burn_with_tag =
+            move |req: &mut Request| self_.post_transaction::<TxBurnWithTag>(req);
+        let self_ = self.clone();
+        let get_burns_by_tag = move |req: &mut Request| self_.get_burns_by_tag(req);
+        let self_ = self.clone();
+        let post_burn = move |req: &mut Request| self_.post_transaction::<TxBurn>(req);
+        let self_ = self.clone();
+        let get_supply = move |req: &mut Request| self_.get_supply(req);
+        let self_ = self.clone();
+        let get_wallet_history = move |req: &mut Request| self_.get_wallet_history(req);
+        let self_ = self.clone();
+        let get_wallet_activity = move |req: &mut Request| self_.get_wallet_activity(req);
+        let self_ = self.clone();
+        let get_counterparty_graph = move |req: &mut Request| self_.get_counterparty_graph(req);
+        let self_ = self.clone();
+        let get_transaction_status = move |req: &mut Request| self_.get_transaction_status(req);
+        let self_ = self.clone();
+        let get_wallet_proof = move |req: &mut Request| self_.get_wallet_proof(req);
+        let self_ = self.clone();
+        let get_statement = move |req: &mut Request| self_.get_statement(req);
+        let self_ = self.clone();
+        let get_fee_estimate = move |req: &mut Request| self_.get_fee_estimate(req);
+        let self_ = self.clone();
+        let get_batch_cost = move |req: &mut Request| self_.get_batch_cost(req);
+        let self_ = self.clone();
+        let get_info = move |req: &mut Request| self_.get_info(req);
+        let self_ = self.clone();
+        let get_normalized_name = move |req: &mut Request| self_.get_normalized_name(req);
+        let self_ = self.clone();
+        let get_name_check = move |req: &mut Request| self_.get_name_check(req);
+        let self_ = self.clone();
+        let get_insurance = move |req: &mut Request| self_.get_insurance(req);
+        let self_ = self.clone();
+        let post_insurance_payout =
+            move |req: &mut Request| self_.post_transaction::<TxInsurancePayout>(req);
+        let self_ = self.clone();
+        let post_approve_insurance_payout =
+            move |req: &mut Request| self_.post_transaction::<TxApproveInsurancePayout>(req);
+        let self_ = self.clone();
+        let get_invitation = move |req: &mut Request| self_.get_invitation(req);
+        let self_ = self.clone();
+        let post_create_invitation =
+            move |req: &mut Request| self_.post_transaction::<TxCreateInvitation>(req);
+        let self_ = self.clone();
+        let get_multisig = move |req: &mut Request| self_.get_multisig(req);
+        let self_ = self.clone();
+        let get_multisig_transfer = move |req: &mut Request| self_.get_multisig_transfer(req);
+        let self_ = self.clone();
+        let post_setup_multisig =
+            move |req: &mut Request| self_.post_transaction::<TxSetupMultisig>(req);
+        let self_ = self.clone();
+        let post_multisig_transfer =
+            move |req: &mut Request| self_.post_transaction::<TxMultisigTransfer>(req);
+        let self_ = self.clone();
+        let post_approve_multisig_transfer =
+            move |req: &mut Request| self_.post_transaction::<TxApproveMultisigTransfer>(req);
+        let self_ = self.clone();
+        let get_clawbacks = move |req: &mut Request| self_.get_clawbacks(req);
+        let self_ = self.clone();
+        let post_clawback = move |req: &mut Request| self_.post_transaction::<TxClawback>(req);
+        let self_ = self.clone();
+        let post_approve_clawback =
+            move |req: &mut Request| self_.post_transaction::<TxApproveClawback>(req);
+        let self_ = self.clone();
+        let get_deferred_transfers = move |req: &mut Request| self_.get_deferred_transfers(req);
+        let self_ = self.clone();
+        let get_locked_transfers = move |req: &mut Request| self_.get_locked_transfers(req);
 
         // Bind handlers to specific routes.
         router.post("/v1/wallets", post_create_wallet, "post_create_wallet");
+        router.post("/v1/wallets/update", post_update_wallet, "post_update_wallet");
+        router.post("/v1/wallets/rename", post_rename_wallet, "post_rename_wallet");
+        router.post("/v1/wallets/close", post_close_wallet, "post_close_wallet");
         router.post("/v1/wallets/transfer", post_transfer, "post_transfer");
+        router.post(
+            "/v1/wallets/batch-transfer",
+            post_batch_transfer,
+            "post_batch_transfer",
+        );
+        router.post(
+            "/v1/wallets/create-and-fund",
+            post_create_and_fund_wallet,
+            "post_create_and_fund_wallet",
+        );
+        router.post(
+            "/v1/wallets/deferred-transfer",
+            post_deferred_transfer,
+            "post_deferred_transfer",
+        );
+        router.post(
+            "/v1/wallets/promotion",
+            post_grant_promotion,
+            "post_grant_promotion",
+        );
+        router.post("/v1/wallets/issue", post_issue, "post_issue");
+        router.post(
+            "/v1/wallets/deferred-transfer/cancel",
+            post_cancel_deferred_transfer,
+            "post_cancel_deferred_transfer",
+        );
+        router.post(
+            "/v1/wallets/locked-transfer",
+            post_locked_transfer,
+            "post_locked_transfer",
+        );
         router.get("/v1/wallets", get_wallets, "get_wallets");
         router.get("/v1/wallet/:pub_key", get_wallet, "get_wallet");
+        router.get("/v1/wallet/:pub_key/qr", get_payment_qr, "get_payment_qr");
+        router.get("/v1/wallet/:pub_key/loyalty", get_loyalty, "get_loyalty");
+        router.get("/v1/wallet/:pub_key/promotion", get_promotion, "get_promotion");
+        router.get("/v1/wallet/:pub_key/sweep-rule", get_sweep_rule, "get_sweep_rule");
+        router.post("/v1/sweep-rules", post_set_sweep_rule, "post_set_sweep_rule");
+        router.post(
+            "/v1/sweep-rules/clear",
+            post_clear_sweep_rule,
+            "post_clear_sweep_rule",
+        );
+        router.get(
+            "/v1/wallet/:pub_key/attestation",
+            get_attestation,
+            "get_attestation",
+        );
+        router.get("/v1/wallet/:pub_key/receipts", get_receipts, "get_receipts");
+        router.get("/v1/wallet/:pub_key/proof", get_wallet_proof, "get_wallet_proof");
+        router.get("/v1/wallet/:pub_key/statement", get_statement, "get_statement");
+        router.get(
+            "/v1/wallet/:pub_key/history",
+            get_wallet_history,
+            "get_wallet_history",
+        );
+        router.get(
+            "/v1/wallet/:pub_key/activity",
+            get_wallet_activity,
+            "get_wallet_activity",
+        );
+        router.get(
+            "/v1/wallet/:pub_key/graph",
+            get_counterparty_graph,
+            "get_counterparty_graph",
+        );
+        router.post(
+            "/v1/wallets/payment-references",
+            post_register_payment_reference,
+            "post_register_payment_reference",
+        );
+        router.post("/v1/attestations", post_attest_wallet, "post_attest_wallet");
+        router.post("/v1/groups", post_link_wallets, "post_link_wallets");
+        router.get("/v1/groups/:id", get_group, "get_group");
+        router.post(
+            "/v1/attestations/revoke",
+            post_revoke_attestation,
+            "post_revoke_attestation",
+        );
+        router.post("/v1/wallets/freeze", post_freeze_wallet, "post_freeze_wallet");
+        router.post(
+            "/v1/wallets/unfreeze",
+            post_unfreeze_wallet,
+            "post_unfreeze_wallet",
+        );
+        router.post(
+            "/v1/wallets/session-keys",
+            post_authorize_session_key,
+            "post_authorize_session_key",
+        );
+        router.post(
+            "/v1/wallets/session-keys/revoke",
+            post_revoke_session_key,
+            "post_revoke_session_key",
+        );
+        router.post(
+            "/v1/wallets/session-transfer",
+            post_session_transfer,
+            "post_session_transfer",
+        );
+        router.get(
+            "/v1/wallet/:pub_key/session-keys",
+            get_session_keys,
+            "get_session_keys",
+        );
+        router.post("/v1/allowances", post_approve, "post_approve");
+        router.post(
+            "/v1/wallets/transfer-from",
+            post_transfer_from,
+            "post_transfer_from",
+        );
+        router.get("/v1/wallet/:pub_key/allowances", get_allowances, "get_allowances");
+        router.get(
+            "/v1/wallet/:pub_key/assets",
+            get_asset_balances,
+            "get_asset_balances",
+        );
+        router.get("/v1/assets/:id", get_asset, "get_asset");
+        router.post("/v1/assets", post_create_asset, "post_create_asset");
+        router.post(
+            "/v1/wallets/asset-transfer",
+            post_asset_transfer,
+            "post_asset_transfer",
+        );
+        router.get("/v1/fees", get_fee_estimate, "get_fee_estimate");
+        router.get("/v1/wallet/:pub_key/batch-cost", get_batch_cost, "get_batch_cost");
+        router.get("/v1/info", get_info, "get_info");
+        router.get(
+            "/v1/names/normalize",
+            get_normalized_name,
+            "get_normalized_name",
+        );
+        router.get("/v1/names/check", get_name_check, "get_name_check");
+        router.get(
+            "/v1/transactions/:hash",
+            get_transaction_status,
+            "get_transaction_status",
+        );
+        router.post("/v1/burns", post_burn_with_tag, "post_burn_with_tag");
+        router.get("/v1/burns/:tag", get_burns_by_tag, "get_burns_by_tag");
+        router.post("/v1/wallets/burn", post_burn, "post_burn");
+        router.get("/v1/supply", get_supply, "get_supply");
+        router.get("/v1/insurance", get_insurance, "get_insurance");
+        router.get("/v1/state-root", get_state_root, "get_state_root");
+        router.post(
+            "/v1/insurance/payouts",
+            post_insurance_payout,
+            "post_insurance_payout",
+        );
+        router.post(
+            "/v1/insurance/payouts/approve",
+            post_approve_insurance_payout,
+            "post_approve_insurance_payout",
+        );
+        router.post("/v1/invitations", post_create_invitation, "post_create_invitation");
+        router.get("/v1/invitations/:code", get_invitation, "get_invitation");
+        router.get("/v1/wallet/:pub_key/multisig", get_multisig, "get_multisig");
+        router.post("/v1/multisig", post_setup_multisig, "post_setup_multisig");
+        router.post(
+            "/v1/multisig/transfers",
+            post_multisig_transfer,
+            "post_multisig_transfer",
+        );
+        router.post(
+            "/v1/multisig/transfers/approve",
+            post_approve_multisig_transfer,
+            "post_approve_multisig_transfer",
+        );
+        router.get(
+            "/v1/multisig/transfers/:id",
+            get_multisig_transfer,
+            "get_multisig_transfer",
+        );
+        router.get("/v1/clawbacks", get_clawbacks, "get_clawbacks");
+        router.post("/v1/clawbacks", post_clawback, "post_clawback");
+        router.post(
+            "/v1/clawbacks/approve",
+            post_approve_clawback,
+            "post_approve_clawback",
+        );
+        router.get(
+            "/v1/deferred-transfers",
+            get_deferred_transfers,
+            "get_deferred_transfers",
+        );
+        router.get(
+            "/v1/locked-transfers",
+            get_locked_transfers,
+            "get_locked_transfers",
+        );
+        router.get("/v1/held-transfers", get_held_transfers, "get_held_transfers");
+        router.post("/v1/wallets/tier", post_assign_tier, "post_assign_tier");
+        router.get("/v1/auctions", get_auctions, "get_auctions");
+        router.post("/v1/auctions", post_create_auction, "post_create_auction");
+        router.post("/v1/auctions/bid", post_bid, "post_bid");
+        router.post("/v1/streams", post_open_stream, "post_open_stream");
+        router.post(
+            "/v1/streams/withdraw",
+            post_withdraw_stream,
+            "post_withdraw_stream",
+        );
+        router.post("/v1/streams/close", post_close_stream, "post_close_stream");
+        router.get("/v1/streams/:id", get_stream, "get_stream");
+        router.get("/v1/beacon/:height", get_beacon, "get_beacon");
+        router.post("/v1/draws", post_lottery_draw, "post_lottery_draw");
+        router.post("/v1/draws/enter", post_enter_draw, "post_enter_draw");
+        router.get("/v1/draws/:id", get_draw, "get_draw");
+        router.post("/v1/fee-coupons", post_issue_fee_coupon, "post_issue_fee_coupon");
+        router.get("/v1/fee-coupons/:code", get_fee_coupon, "get_fee_coupon");
+        router.post(
+            "/v1/wallets/coupon-transfer",
+            post_coupon_transfer,
+            "post_coupon_transfer",
+        );
+        router.post("/v1/deposits", post_deposit, "post_deposit");
+        router.post("/v1/deposits/claim", post_claim_deposit, "post_claim_deposit");
+        router.get("/v1/deposits/:id", get_deposit, "get_deposit");
+        router.get("/v1/escrows", get_escrows, "get_escrows");
+        router.get("/v1/escrows/:id", get_escrow, "get_escrow");
+        router.post("/v1/escrows", post_create_escrow, "post_create_escrow");
+        router.post("/v1/escrows/release", post_release_escrow, "post_release_escrow");
+        router.post("/v1/escrows/refund", post_refund_escrow, "post_refund_escrow");
+        router.get("/v1/htlcs/:id", get_htlc, "get_htlc");
+        router.get(
+            "/v1/htlc-preimages/:hash_lock",
+            get_htlc_preimage,
+            "get_htlc_preimage",
+        );
+        router.post("/v1/htlcs", post_create_htlc, "post_create_htlc");
+        router.post("/v1/htlcs/claim", post_claim_htlc, "post_claim_htlc");
+        router.post("/v1/htlcs/refund", post_refund_htlc, "post_refund_htlc");
+        router.post(
+            "/v1/held-transfers/review",
+            post_review_held_transfer,
+            "post_review_held_transfer",
+        );
+
+        #[cfg(feature = "explorer")]
+        {
+            let self_ = self.clone();
+            let get_explorer = move |req: &mut Request| self_.get_explorer(req);
+            router.get("/explorer", get_explorer, "get_explorer");
+        }
+
+        #[cfg(feature = "sandbox")]
+        {
+            let self_ = self.clone();
+            let post_create_wallet =
+                move |req: &mut Request| self_.post_sandbox_transaction::<TxCreateWallet>(req);
+            let self_ = self.clone();
+            let post_transfer =
+                move |req: &mut Request| self_.post_sandbox_transaction::<TxTransfer>(req);
+            let self_ = self.clone();
+            let post_create_and_fund_wallet = move |req: &mut Request| {
+                self_.post_sandbox_transaction::<TxCreateAndFundWallet>(req)
+            };
+            router.post(
+                "/v1/sandbox/wallets",
+                post_create_wallet,
+                "post_sandbox_create_wallet",
+            );
+            router.post(
+                "/v1/sandbox/wallets/transfer",
+                post_transfer,
+                "post_sandbox_transfer",
+            );
+            router.post(
+                "/v1/sandbox/wallets/create-and-fund",
+                post_create_and_fund_wallet,
+                "post_sandbox_create_and_fund_wallet",
+            );
+        }
+    }
+}
+
+// // // // // // // // // // TRANSACTION REGISTRY // // // // // // // // // //
+
+/// Function deserializing a raw message into a transaction of a particular type.
+pub type TxConstructor = fn(RawTransaction) -> Result<Box<Transaction>, encoding::Error>;
+
+/// Mapping from message types to constructors of the corresponding transactions.
+///
+/// The registry is filled with the built-in transactions of the service and can
+/// be extended by embedders, so that additional transaction types (together with
+/// their execution logic) are processed by the service without forking the crate.
+pub struct TransactionRegistry {
+    constructors: BTreeMap<u16, TxConstructor>,
+}
+
+impl TransactionRegistry {
+    /// Create a registry containing the built-in transactions of the service.
+    pub fn new() -> Self {
+        let mut registry = TransactionRegistry { constructors: BTreeMap::new() };
+        registry.register(TX_CREATE_WALLET_ID, |raw| {
+            Ok(Box::new(TxCreateWallet::from_raw(raw)?))
+        });
+        registry.register(TX_TRANSFER_ID, |raw| Ok(Box::new(TxTransfer::from_raw(raw)?)));
+        registry.register(TX_UPDATE_WALLET_ID, |raw| {
+            Ok(Box::new(TxUpdateWallet::from_raw(raw)?))
+        });
+        registry.register(TX_RENAME_WALLET_ID, |raw| {
+            Ok(Box::new(TxRenameWallet::from_raw(raw)?))
+        });
+        registry.register(TX_CLOSE_WALLET_ID, |raw| {
+            Ok(Box::new(TxCloseWallet::from_raw(raw)?))
+        });
+        registry.register(TX_APPROVE_ID, |raw| Ok(Box::new(TxApprove::from_raw(raw)?)));
+        registry.register(TX_TRANSFER_FROM_ID, |raw| {
+            Ok(Box::new(TxTransferFrom::from_raw(raw)?))
+        });
+        registry.register(TX_SET_SWEEP_RULE_ID, |raw| {
+            Ok(Box::new(TxSetSweepRule::from_raw(raw)?))
+        });
+        registry.register(TX_CLEAR_SWEEP_RULE_ID, |raw| {
+            Ok(Box::new(TxClearSweepRule::from_raw(raw)?))
+        });
+        registry.register(TX_CREATE_ESCROW_ID, |raw| {
+            Ok(Box::new(TxCreateEscrow::from_raw(raw)?))
+        });
+        registry.register(TX_RELEASE_ESCROW_ID, |raw| {
+            Ok(Box::new(TxReleaseEscrow::from_raw(raw)?))
+        });
+        registry.register(TX_REFUND_ESCROW_ID, |raw| {
+            Ok(Box::new(TxRefundEscrow::from_raw(raw)?))
+        });
+        registry.register(TX_CREATE_ASSET_ID, |raw| {
+            Ok(Box::new(TxCreateAsset::from_raw(raw)?))
+        });
+        registry.register(TX_ASSET_TRANSFER_ID, |raw| {
+            Ok(Box::new(TxAssetTransfer::from_raw(raw)?))
+        });
+        registry.register(TX_CREATE_HTLC_ID, |raw| {
+            Ok(Box::new(TxCreateHtlc::from_raw(raw)?))
+        });
+        registry.register(TX_CLAIM_HTLC_ID, |raw| {
+            Ok(Box::new(TxClaimHtlc::from_raw(raw)?))
+        });
+        registry.register(TX_REFUND_HTLC_ID, |raw| {
+            Ok(Box::new(TxRefundHtlc::from_raw(raw)?))
+        });
+        registry.register(TX_LOCKED_TRANSFER_ID, |raw| {
+            Ok(Box::new(TxLockedTransfer::from_raw(raw)?))
+        });
+        registry.register(TX_DEFERRED_TRANSFER_ID, |raw| {
+            Ok(Box::new(TxDeferredTransfer::from_raw(raw)?))
+        });
+        registry.register(TX_CANCEL_DEFERRED_TRANSFER_ID, |raw| {
+            Ok(Box::new(TxCancelDeferredTransfer::from_raw(raw)?))
+        });
+        registry.register(TX_CREATE_AND_FUND_WALLET_ID, |raw| {
+            Ok(Box::new(TxCreateAndFundWallet::from_raw(raw)?))
+        });
+        registry.register(TX_AUTHORIZE_SESSION_KEY_ID, |raw| {
+            Ok(Box::new(TxAuthorizeSessionKey::from_raw(raw)?))
+        });
+        registry.register(TX_REVOKE_SESSION_KEY_ID, |raw| {
+            Ok(Box::new(TxRevokeSessionKey::from_raw(raw)?))
+        });
+        registry.register(TX_SESSION_TRANSFER_ID, |raw| {
+            Ok(Box::new(TxSessionTransfer::from_raw(raw)?))
+        });
+        registry.register(TX_ATTEST_WALLET_ID, |raw| {
+            Ok(Box::new(TxAttestWallet::from_raw(raw)?))
+        });
+        registry.register(TX_REVOKE_ATTESTATION_ID, |raw| {
+            Ok(Box::new(TxRevokeAttestation::from_raw(raw)?))
+        });
+        registry.register(TX_FREEZE_WALLET_ID, |raw| {
+            Ok(Box::new(TxFreezeWallet::from_raw(raw)?))
+        });
+        registry.register(TX_UNFREEZE_WALLET_ID, |raw| {
+            Ok(Box::new(TxUnfreezeWallet::from_raw(raw)?))
+        });
+        registry.register(TX_LINK_WALLETS_ID, |raw| {
+            Ok(Box::new(TxLinkWallets::from_raw(raw)?))
+        });
+        registry.register(TX_REGISTER_PAYMENT_REFERENCE_ID, |raw| {
+            Ok(Box::new(TxRegisterPaymentReference::from_raw(raw)?))
+        });
+        registry.register(TX_REVIEW_HELD_TRANSFER_ID, |raw| {
+            Ok(Box::new(TxReviewHeldTransfer::from_raw(raw)?))
+        });
+        registry.register(TX_ASSIGN_TIER_ID, |raw| {
+            Ok(Box::new(TxAssignTier::from_raw(raw)?))
+        });
+        registry.register(TX_CREATE_AUCTION_ID, |raw| {
+            Ok(Box::new(TxCreateAuction::from_raw(raw)?))
+        });
+        registry.register(TX_BID_ID, |raw| Ok(Box::new(TxBid::from_raw(raw)?)));
+        registry.register(TX_OPEN_STREAM_ID, |raw| {
+            Ok(Box::new(TxOpenStream::from_raw(raw)?))
+        });
+        registry.register(TX_WITHDRAW_STREAM_ID, |raw| {
+            Ok(Box::new(TxWithdrawStream::from_raw(raw)?))
+        });
+        registry.register(TX_CLOSE_STREAM_ID, |raw| {
+            Ok(Box::new(TxCloseStream::from_raw(raw)?))
+        });
+        registry.register(TX_LOTTERY_DRAW_ID, |raw| {
+            Ok(Box::new(TxLotteryDraw::from_raw(raw)?))
+        });
+        registry.register(TX_ENTER_DRAW_ID, |raw| {
+            Ok(Box::new(TxEnterDraw::from_raw(raw)?))
+        });
+        registry.register(TX_ISSUE_FEE_COUPON_ID, |raw| {
+            Ok(Box::new(TxIssueFeeCoupon::from_raw(raw)?))
+        });
+        registry.register(TX_COUPON_TRANSFER_ID, |raw| {
+            Ok(Box::new(TxCouponTransfer::from_raw(raw)?))
+        });
+        registry.register(TX_DEPOSIT_ID, |raw| Ok(Box::new(TxDeposit::from_raw(raw)?)));
+        registry.register(TX_CLAIM_DEPOSIT_ID, |raw| {
+            Ok(Box::new(TxClaimDeposit::from_raw(raw)?))
+        });
+        registry.register(TX_CLAWBACK_ID, |raw| {
+            Ok(Box::new(TxClawback::from_raw(raw)?))
+        });
+        registry.register(TX_APPROVE_CLAWBACK_ID, |raw| {
+            Ok(Box::new(TxApproveClawback::from_raw(raw)?))
+        });
+        registry.register(TX_BATCH_TRANSFER_ID, |raw| {
+            Ok(Box::new(TxBatchTransfer::from_raw(raw)?))
+        });
+        registry.register(TX_SETUP_MULTISIG_ID, |raw| {
+            Ok(Box::new(TxSetupMultisig::from_raw(raw)?))
+        });
+        registry.register(TX_MULTISIG_TRANSFER_ID, |raw| {
+            Ok(Box::new(TxMultisigTransfer::from_raw(raw)?))
+        });
+        registry.register(TX_APPROVE_MULTISIG_TRANSFER_ID, |raw| {
+            Ok(Box::new(TxApproveMultisigTransfer::from_raw(raw)?))
+        });
+        registry.register(TX_CREATE_INVITATION_ID, |raw| {
+            Ok(Box::new(TxCreateInvitation::from_raw(raw)?))
+        });
+        registry.register(TX_ISSUE_ID, |raw| Ok(Box::new(TxIssue::from_raw(raw)?)));
+        registry.register(TX_TICK_ID, |raw| Ok(Box::new(TxTick::from_raw(raw)?)));
+        registry.register(TX_GRANT_PROMOTION_ID, |raw| {
+            Ok(Box::new(TxGrantPromotion::from_raw(raw)?))
+        });
+        registry.register(TX_BURN_WITH_TAG_ID, |raw| {
+            Ok(Box::new(TxBurnWithTag::from_raw(raw)?))
+        });
+        registry.register(TX_BURN_ID, |raw| Ok(Box::new(TxBurn::from_raw(raw)?)));
+        registry.register(TX_INSURANCE_PAYOUT_ID, |raw| {
+            Ok(Box::new(TxInsurancePayout::from_raw(raw)?))
+        });
+        registry.register(TX_APPROVE_INSURANCE_PAYOUT_ID, |raw| {
+            Ok(Box::new(TxApproveInsurancePayout::from_raw(raw)?))
+        });
+        registry
+    }
+
+    /// Register a transaction type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a transaction with the same message type is already registered.
+    pub fn register(&mut self, message_type: u16, constructor: TxConstructor) {
+        if self.constructors.insert(message_type, constructor).is_some() {
+            panic!("Transaction with message type {} is already registered", message_type);
+        }
+    }
+
+    /// Check whether a transaction with the given message type is registered.
+    pub fn contains(&self, message_type: u16) -> bool {
+        self.constructors.contains_key(&message_type)
+    }
+
+    /// Deserialize a raw message using the constructor registered for its type.
+    pub fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        match self.constructors.get(&raw.message_type()) {
+            Some(constructor) => constructor(raw),
+            None => Err(encoding::Error::IncorrectMessageType {
+                message_type: raw.message_type(),
+            }),
+        }
+    }
+}
+
+impl Default for TransactionRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Transaction checked against the activation heights in the service configuration
+/// before execution. Until its type is activated, the transaction is rejected with
+/// `Error::NotActivated` on every node, so that nodes already supporting the type
+/// and nodes not supporting it yet agree on the result.
+#[derive(Debug)]
+struct Activated(Box<Transaction>);
+
+impl Message for Activated {
+    fn raw(&self) -> &RawTransaction {
+        self.0.raw()
+    }
+}
+
+impl Transaction for Activated {
+    fn verify(&self) -> bool {
+        self.0.verify()
+    }
+
+    fn execute(&self, view: &mut Fork) {
+        let activated = {
+            let schema = CurrencySchema::new(&mut *view);
+            let message_type = self.raw().message_type();
+            schema.config().activation_heights.get(&message_type).map_or(
+                true,
+                |&height| schema.height() >= height,
+            )
+        };
+        if activated {
+            self.0.execute(view);
+        } else {
+            CurrencySchema::new(view).record_result(&self.hash(), Err(Error::NotActivated));
+        }
+    }
+
+    fn info(&self) -> serde_json::Value {
+        self.0.info()
     }
 }
 
 // // // // // // // // // // SERVICE DECLARATION // // // // // // // // // //
 
 /// Define the service.
-pub struct CurrencyService;
+pub struct CurrencyService {
+    transactions: TransactionRegistry,
+    config: CurrencyConfig,
+    query_budget: QueryBudget,
+    risk: Arc<Mutex<RiskMonitor>>,
+    submitted: Arc<Mutex<BTreeMap<Hash, u64>>>,
+    sla: Arc<Mutex<SlaTracker>>,
+}
+
+impl CurrencyService {
+    /// Create the service processing the built-in transactions only, with
+    /// the default configuration.
+    pub fn new() -> Self {
+        CurrencyService {
+            transactions: TransactionRegistry::new(),
+            config: CurrencyConfig::default(),
+            query_budget: QueryBudget::default(),
+            risk: Arc::new(Mutex::new(RiskMonitor::default())),
+            submitted: Arc::new(Mutex::new(BTreeMap::new())),
+            sla: Arc::new(Mutex::new(SlaTracker::default())),
+        }
+    }
+
+    /// Set the configuration written to the genesis block.
+    pub fn with_config(mut self, config: CurrencyConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Set the limits on the work done by a single request to a listing endpoint.
+    pub fn with_query_budget(mut self, query_budget: QueryBudget) -> Self {
+        self.query_budget = query_budget;
+        self
+    }
+
+    /// Replace the built-in heuristics assessing the senders of committed transfers.
+    pub fn with_risk_model(mut self, model: Box<RiskModel>) -> Self {
+        self.risk = Arc::new(Mutex::new(RiskMonitor::new(model, DEFAULT_RISK_WINDOW)));
+        self
+    }
+
+    /// Pass the alerts on transactions not committed within their deadlines to the sink.
+    pub fn with_alert_sink(mut self, sink: Box<AlertSink>) -> Self {
+        self.sla = Arc::new(Mutex::new(SlaTracker::new(Some(sink))));
+        self
+    }
+
+    /// Pass the payments made in the latest committed block to the risk monitor.
+    fn observe_block(&self, snapshot: &Snapshot) {
+        let schema = CurrencySchema::new(snapshot);
+        let height = schema.height() - 1;
+        let payments: Vec<BlockPayment> = if schema.block_payments_height().get() == Some(height) {
+            schema.block_payments().iter().collect()
+        } else {
+            Vec::new()
+        };
+        let transfers = payments.iter().map(|payment| {
+            (*payment.from(), *payment.to(), payment.amount())
+        });
+        self.risk.lock().unwrap().observe(height, transfers);
+    }
+
+    /// Forget the transactions of the latest committed block submitted through
+    /// the API of this node, as well as the transactions submitted too long ago
+    /// to be still expected to commit.
+    fn forget_committed(&self, snapshot: &Snapshot) {
+        let height = CurrencySchema::new(snapshot).height() - 1;
+        let mut submitted = self.submitted.lock().unwrap();
+        let mut sla = self.sla.lock().unwrap();
+        for tx_hash in CoreSchema::new(snapshot).block_txs(Height(height)).iter() {
+            submitted.remove(&tx_hash);
+            sla.committed(&tx_hash);
+        }
+        let expired: Vec<Hash> = submitted
+            .iter()
+            .filter(|&(_, &submitted_at)| submitted_at + SUBMITTED_TX_LIFETIME <= height)
+            .map(|(tx_hash, _)| *tx_hash)
+            .collect();
+        for tx_hash in expired {
+            submitted.remove(&tx_hash);
+        }
+    }
+
+    /// Raise alerts for the tracked transactions past their deadlines.
+    fn check_deadlines(&self, snapshot: &Snapshot) {
+        let height = CurrencySchema::new(snapshot).height();
+        let submitted = self.submitted.lock().unwrap();
+        let pending = submitted.len() as u64;
+        let alerts = self.sla.lock().unwrap().check(
+            sla::now(),
+            height,
+            pending,
+            |tx_hash| submitted.contains_key(tx_hash),
+        );
+        for alert in alerts {
+            println!("Transaction missed its deadline: {:?}", alert);
+        }
+    }
+
+    /// Register an additional transaction type processed by the service.
+    /// The transaction must use `SERVICE_ID` as its `TYPE`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a transaction with the same message type is already registered.
+    pub fn with_transaction(mut self, message_type: u16, constructor: TxConstructor) -> Self {
+        self.transactions.register(message_type, constructor);
+        self
+    }
+}
+
+impl Default for CurrencyService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 /// Implement a `Service` trait for the service.
 impl Service for CurrencyService {
     fn service_name(&self) -> &'static str {
-        "cryptocurrency"
+        SERVICE_NAME
     }
 
     fn service_id(&self) -> u16 {
         SERVICE_ID
     }
 
+    /// Include the root hashes of the service tables into the block state hash.
+    /// A validator computing a different root after executing the block
+    /// proposes a different block hash and cannot collect a quorum of
+    /// precommits, which stalls the network instead of forking it.
+    fn state_hash(&self, snapshot: &Snapshot) -> Vec<Hash> {
+        CurrencySchema::new(snapshot).state_hash()
+    }
+
+    /// Provide the initial service configuration for the genesis block.
+    fn initialize(&self, _: &mut Fork) -> serde_json::Value {
+        serde_json::to_value(&self.config).unwrap()
+    }
+
     /// Implement a method to deserialize transactions coming to the node.
     fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
-        let trans: Box<Transaction> = match raw.message_type() {
-            TX_TRANSFER_ID => Box::new(TxTransfer::from_raw(raw)?),
-            TX_CREATE_WALLET_ID => Box::new(TxCreateWallet::from_raw(raw)?),
-            _ => {
-                return Err(encoding::Error::IncorrectMessageType {
-                    message_type: raw.message_type(),
-                });
+        let tx = self.transactions.tx_from_raw(raw)?;
+        Ok(Box::new(Activated(tx)))
+    }
+
+    /// Assess the senders of the committed transfers, forget the committed
+    /// transactions submitted through the API and raise alerts for those past
+    /// their deadlines, then issue `TxTick` if the node is a validator and
+    /// there are actions scheduled for the next block.
+    fn handle_commit(&self, context: &mut ServiceContext) {
+        self.observe_block(context.snapshot());
+        self.forget_committed(context.snapshot());
+        self.check_deadlines(context.snapshot());
+        if context.validator_state().is_none() {
+            return;
+        }
+        let height = {
+            let schema = CurrencySchema::new(context.snapshot());
+            let height = schema.height();
+            if !schema.has_scheduled(height) {
+                return;
             }
+            height
         };
-        Ok(trans)
+        let tx = TxTick::new(context.public_key(), height, context.secret_key());
+        context.add_transaction(Box::new(tx));
     }
 
     /// Create a REST `Handler` to process web requests to the node.
@@ -325,6 +10175,23 @@ impl Service for CurrencyService {
         let api = CryptocurrencyApi {
             channel: ctx.node_channel().clone(),
             blockchain: ctx.blockchain().clone(),
+            query_budget: self.query_budget,
+            submitted: self.submitted.clone(),
+            sla: self.sla.clone(),
+            service_keys: (*ctx.public_key(), ctx.secret_key().clone()),
+        };
+        api.wire(&mut router);
+        Some(Box::new(router))
+    }
+
+    /// Create a REST `Handler` to process operator requests to the node.
+    fn private_api_handler(&self, ctx: &ApiContext) -> Option<Box<Handler>> {
+        let mut router = Router::new();
+        let api = PrivateCryptocurrencyApi {
+            blockchain: ctx.blockchain().clone(),
+            risk: self.risk.clone(),
+            submitted: self.submitted.clone(),
+            sla: self.sla.clone(),
         };
         api.wire(&mut router);
         Some(Box::new(router))
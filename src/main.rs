@@ -26,7 +26,7 @@ fn main() {
     println!("Creating in-memory database...");
     let node = Node::new(
         Box::new(MemoryDB::new()),
-        vec![Box::new(CurrencyService)],
+        vec![Box::new(CurrencyService::new())],
         node_config(),
     );
     println!("Starting a single node...");
@@ -0,0 +1,208 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pagination envelope shared by all listing endpoints.
+//!
+//! A listing accepts optional `cursor` and `limit` query parameters and returns
+//! a `Page`. The cursor is opaque to clients: they pass the `next_cursor` of
+//! the previous page to get the next one. The last page has no `next_cursor`.
+//!
+//! The work done by a single request is limited by a `QueryBudget`. If the
+//! budget runs out, the page contains fewer items than requested, but still
+//! has a `next_cursor`, and `total_estimate` is a lower bound.
+
+use payment::{self, PaymentUriError};
+
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Number of items on a page if the `limit` parameter is not specified.
+pub const DEFAULT_PAGE_LIMIT: usize = 100;
+
+/// Maximum number of items on a page.
+pub const MAX_PAGE_LIMIT: usize = 1000;
+
+/// Limits on the work done by a single listing request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryBudget {
+    /// Maximum number of storage entries scanned, including the entries
+    /// counted for `total_estimate`.
+    pub max_scanned: usize,
+    /// Maximum time spent scanning the storage.
+    pub timeout: Duration,
+}
+
+impl QueryBudget {
+    /// Start spending the budget.
+    pub fn start(&self) -> Scan {
+        Scan {
+            remaining: self.max_scanned,
+            deadline: Instant::now() + self.timeout,
+        }
+    }
+}
+
+impl Default for QueryBudget {
+    fn default() -> Self {
+        QueryBudget {
+            max_scanned: 10_000,
+            timeout: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Budget of a request being processed.
+#[derive(Debug)]
+pub struct Scan {
+    remaining: usize,
+    deadline: Instant,
+}
+
+impl Scan {
+    /// Account for scanning one more entry. Returns `false` if the budget has run out.
+    pub fn step(&mut self) -> bool {
+        if self.remaining == 0 || Instant::now() >= self.deadline {
+            self.remaining = 0;
+            return false;
+        }
+        self.remaining -= 1;
+        true
+    }
+
+    /// Count the items within the remaining budget.
+    pub fn count<I: IntoIterator>(&mut self, items: I) -> u64 {
+        let mut count = 0;
+        for _ in items {
+            if !self.step() {
+                break;
+            }
+            count += 1;
+        }
+        count
+    }
+}
+
+/// A page of a listing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Page<T> {
+    /// Items of the page.
+    pub items: Vec<T>,
+    /// Cursor of the next page, if there are more items.
+    pub next_cursor: Option<String>,
+    /// Estimated total number of items in the listing.
+    pub total_estimate: u64,
+}
+
+impl<T> Page<T> {
+    /// Collect a page from items starting at the requested cursor, stopping
+    /// early if the budget runs out. `cursor_of` returns the cursor pointing at
+    /// an item; it is used for the first item that is not included into the page.
+    ///
+    /// `total_estimate` of the page is the number of its items; set it afterwards
+    /// if the listing knows better.
+    pub fn collect<I, F>(items: I, limit: usize, scan: &mut Scan, cursor_of: F) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        F: Fn(&T) -> String,
+    {
+        let mut iter = items.into_iter();
+        let mut page = Vec::new();
+        let mut next = None;
+        loop {
+            if page.len() == limit || !scan.step() {
+                next = iter.next();
+                break;
+            }
+            match iter.next() {
+                Some(item) => page.push(item),
+                None => break,
+            }
+        }
+        Page {
+            total_estimate: page.len() as u64,
+            items: page,
+            next_cursor: next.map(|item| cursor_of(&item)),
+        }
+    }
+}
+
+/// Pagination parameters of a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageRequest {
+    /// Cursor of the requested page; `None` for the first page.
+    pub cursor: Option<String>,
+    /// Maximum number of items on the page.
+    pub limit: usize,
+}
+
+impl PageRequest {
+    /// Parse the `cursor` and `limit` parameters of a query string. Other
+    /// parameters are ignored.
+    pub fn from_query(query: &str) -> Result<Self, PageRequestError> {
+        let mut params = payment::parse_query(query).map_err(PageRequestError::Query)?;
+        let limit = match params.remove("limit") {
+            Some(limit) => {
+                match limit.parse() {
+                    Ok(limit) if limit > 0 && limit <= MAX_PAGE_LIMIT => limit,
+                    _ => return Err(PageRequestError::Limit),
+                }
+            }
+            None => DEFAULT_PAGE_LIMIT,
+        };
+        Ok(PageRequest {
+            cursor: params.remove("cursor"),
+            limit,
+        })
+    }
+}
+
+impl Default for PageRequest {
+    fn default() -> Self {
+        PageRequest {
+            cursor: None,
+            limit: DEFAULT_PAGE_LIMIT,
+        }
+    }
+}
+
+/// Errors occurring when parsing pagination parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageRequestError {
+    /// The query string is malformed.
+    Query(PaymentUriError),
+    /// The limit is not a number between 1 and `MAX_PAGE_LIMIT`.
+    Limit,
+    /// The cursor does not point into the listing.
+    Cursor,
+}
+
+impl fmt::Display for PageRequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PageRequestError::Query(ref e) => write!(f, "Invalid query: {}", e),
+            _ => f.write_str(self.description()),
+        }
+    }
+}
+
+impl Error for PageRequestError {
+    fn description(&self) -> &str {
+        match *self {
+            PageRequestError::Query(_) => "Invalid query",
+            PageRequestError::Limit => "Page limit must be between 1 and 1000",
+            PageRequestError::Cursor => "Invalid page cursor",
+        }
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Payment requests encoded as URIs of the form
+//! `excoin:<pub_key>?amount=<amount>&memo=<memo>&reference=<hash>&expires=<height>`.
+//!
+//! All query parameters are optional. The memo is percent-encoded, the amount
+//! and the expiry height are decimal integers, the reference is a hex-encoded
+//! payment reference registered by the receiver. Unknown parameters are rejected,
+//! so that clients do not silently ignore parts of the request they do not
+//! understand.
+//!
+//! A scanned request is turned into a signed `TxTransfer` with `to_transfer`.
+
+use exonum::crypto::{Hash, PublicKey, SecretKey};
+use exonum::encoding::serialize::FromHex;
+
+use TxTransfer;
+use text;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// URI scheme of payment requests.
+pub const PAYMENT_URI_SCHEME: &str = "excoin";
+
+/// Request to pay to a particular wallet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentRequest {
+    /// Public key of the receiving wallet.
+    pub to: PublicKey,
+    /// Requested amount, if fixed by the receiver.
+    pub amount: Option<u64>,
+    /// Free-form note attached to the payment.
+    pub memo: Option<String>,
+    /// Payment reference registered by the receiver, put into the transfer.
+    pub reference: Option<Hash>,
+    /// Blockchain height from which the request may no longer be paid.
+    pub expires_at: Option<u64>,
+}
+
+impl PaymentRequest {
+    /// Create a payment request without parameters.
+    pub fn new(to: PublicKey) -> Self {
+        PaymentRequest {
+            to,
+            amount: None,
+            memo: None,
+            reference: None,
+            expires_at: None,
+        }
+    }
+
+    /// Set a request parameter from its value in the URI query.
+    pub fn set_param(&mut self, name: &str, value: &str) -> Result<(), PaymentUriError> {
+        match name {
+            "amount" => {
+                let amount = value.parse().map_err(|_| PaymentUriError::Amount)?;
+                self.amount = Some(amount);
+            }
+            "memo" => self.memo = Some(value.to_owned()),
+            "reference" => {
+                let reference = Hash::from_hex(value).map_err(|_| PaymentUriError::Reference)?;
+                self.reference = Some(reference);
+            }
+            "expires" => {
+                let height = value.parse().map_err(|_| PaymentUriError::Expiry)?;
+                self.expires_at = Some(height);
+            }
+            _ => return Err(PaymentUriError::UnknownParameter(name.to_owned())),
+        }
+        Ok(())
+    }
+
+    /// Check whether the request may no longer be paid at the given height.
+    pub fn is_expired(&self, height: u64) -> bool {
+        self.expires_at.map_or(false, |expires_at| height >= expires_at)
+    }
+
+    /// Create a signed transfer paying the request from the wallet `from`.
+    /// `height` is the current blockchain height as known to the client.
+    ///
+    /// Requests without a fixed amount must have `amount` filled in by the payer
+    /// first. The memo of the request, normalized, becomes the memo of the transfer.
+    pub fn to_transfer(
+        &self,
+        from: &PublicKey,
+        fee: u64,
+        seed: u64,
+        height: u64,
+        secret_key: &SecretKey,
+    ) -> Result<TxTransfer, PaymentIntentError> {
+        let amount = self.amount.ok_or(PaymentIntentError::MissingAmount)?;
+        if self.is_expired(height) {
+            return Err(PaymentIntentError::Expired);
+        }
+        let reference = self.reference.unwrap_or_else(Hash::zero);
+        let memo = self.memo.as_ref().map_or_else(String::new, |memo| text::normalize(memo));
+        Ok(TxTransfer::new(
+            from,
+            &self.to,
+            amount,
+            fee,
+            &Hash::zero(),
+            &reference,
+            &memo,
+            seed,
+            secret_key,
+        ))
+    }
+
+    /// Serialize the request into a URI.
+    pub fn to_uri(&self) -> String {
+        let mut uri = format!("{}:{}", PAYMENT_URI_SCHEME, self.to.to_string());
+        let mut params = Vec::new();
+        if let Some(amount) = self.amount {
+            params.push(format!("amount={}", amount));
+        }
+        if let Some(ref memo) = self.memo {
+            params.push(format!("memo={}", percent_encode(memo)));
+        }
+        if let Some(ref reference) = self.reference {
+            params.push(format!("reference={}", reference.to_string()));
+        }
+        if let Some(expires_at) = self.expires_at {
+            params.push(format!("expires={}", expires_at));
+        }
+        if !params.is_empty() {
+            uri.push('?');
+            uri.push_str(&params.join("&"));
+        }
+        uri
+    }
+
+    /// Parse a request from a URI.
+    pub fn from_uri(uri: &str) -> Result<Self, PaymentUriError> {
+        let prefix = format!("{}:", PAYMENT_URI_SCHEME);
+        if !uri.starts_with(&prefix) {
+            return Err(PaymentUriError::Scheme);
+        }
+        let rest = &uri[prefix.len()..];
+        let (key, query) = match rest.find('?') {
+            Some(pos) => (&rest[..pos], Some(&rest[pos + 1..])),
+            None => (rest, None),
+        };
+
+        let to = PublicKey::from_hex(key).map_err(|_| PaymentUriError::PublicKey)?;
+        Self::parse(to, query.unwrap_or(""))
+    }
+
+    /// Parse a request to pay to `to` from the query part of its URI.
+    pub fn parse(to: PublicKey, query: &str) -> Result<Self, PaymentUriError> {
+        let mut request = PaymentRequest::new(to);
+        for (name, value) in parse_query(query)? {
+            request.set_param(&name, &value)?;
+        }
+        Ok(request)
+    }
+}
+
+impl fmt::Display for PaymentRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_uri())
+    }
+}
+
+impl FromStr for PaymentRequest {
+    type Err = PaymentUriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_uri(s)
+    }
+}
+
+/// Errors occurring when parsing a payment URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentUriError {
+    /// The URI does not start with the `excoin:` scheme.
+    Scheme,
+    /// The public key is not a valid hex-encoded key.
+    PublicKey,
+    /// The amount is not a valid unsigned integer.
+    Amount,
+    /// The reference is not a valid hex-encoded hash.
+    Reference,
+    /// The expiry height is not a valid unsigned integer.
+    Expiry,
+    /// A query parameter is malformed or is not valid percent-encoded UTF-8.
+    Encoding,
+    /// A query parameter is repeated.
+    DuplicateParameter(String),
+    /// A query parameter is not recognized.
+    UnknownParameter(String),
+}
+
+impl fmt::Display for PaymentUriError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PaymentUriError::DuplicateParameter(ref name) => {
+                write!(f, "Duplicate parameter: {}", name)
+            }
+            PaymentUriError::UnknownParameter(ref name) => {
+                write!(f, "Unknown parameter: {}", name)
+            }
+            _ => f.write_str(self.description()),
+        }
+    }
+}
+
+impl Error for PaymentUriError {
+    fn description(&self) -> &str {
+        match *self {
+            PaymentUriError::Scheme => "Payment URI must use the excoin scheme",
+            PaymentUriError::PublicKey => "Invalid public key in payment URI",
+            PaymentUriError::Amount => "Invalid amount in payment URI",
+            PaymentUriError::Reference => "Invalid reference in payment URI",
+            PaymentUriError::Expiry => "Invalid expiry height in payment URI",
+            PaymentUriError::Encoding => "Invalid encoding of payment URI parameters",
+            PaymentUriError::DuplicateParameter(_) => "Duplicate payment URI parameter",
+            PaymentUriError::UnknownParameter(_) => "Unknown payment URI parameter",
+        }
+    }
+}
+
+/// Errors occurring when turning a payment request into a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentIntentError {
+    /// The request does not fix the amount, and the payer has not filled it in.
+    MissingAmount,
+    /// The request has expired at the current height.
+    Expired,
+}
+
+impl fmt::Display for PaymentIntentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl Error for PaymentIntentError {
+    fn description(&self) -> &str {
+        match *self {
+            PaymentIntentError::MissingAmount => "Payment request has no amount",
+            PaymentIntentError::Expired => "Payment request has expired",
+        }
+    }
+}
+
+/// Parse a `name=value&...` query string with percent-encoded values.
+pub fn parse_query(query: &str) -> Result<BTreeMap<String, String>, PaymentUriError> {
+    let mut params = BTreeMap::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let mut parts = pair.splitn(2, '=');
+        let name = percent_decode(parts.next().unwrap_or(""))?;
+        let value = percent_decode(parts.next().ok_or(PaymentUriError::Encoding)?)?;
+        if params.contains_key(&name) {
+            return Err(PaymentUriError::DuplicateParameter(name));
+        }
+        params.insert(name, value);
+    }
+    Ok(params)
+}
+
+/// Percent-encode everything except unreserved characters (RFC 3986).
+pub fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Decode a percent-encoded string. `+` is treated as a space, and `%` must be
+/// followed by exactly two hex digits.
+pub fn percent_decode(s: &str) -> Result<String, PaymentUriError> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                if i + 2 >= bytes.len() {
+                    return Err(PaymentUriError::Encoding);
+                }
+                let high = hex_digit(bytes[i + 1]).ok_or(PaymentUriError::Encoding)?;
+                let low = hex_digit(bytes[i + 2]).ok_or(PaymentUriError::Encoding)?;
+                decoded.push(high << 4 | low);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| PaymentUriError::Encoding)
+}
+
+/// Value of an ASCII hex digit.
+fn hex_digit(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'...b'9' => Some(byte - b'0'),
+        b'a'...b'f' => Some(byte - b'a' + 10),
+        b'A'...b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
@@ -0,0 +1,253 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bulk payouts from a manifest of recipients and amounts.
+//!
+//! A manifest is either a CSV file with `<pub_key>,<amount>` lines or a JSON
+//! array of `{ "to": <pub_key>, "amount": <amount> }` objects. CSV lines starting
+//! with `#` and a `to,amount` header are skipped. The manifest is approved by
+//! signing its hash, so that the payouts cannot be altered after the approval.
+//!
+//! The payments of a checked manifest are split into `TxBatchTransfer`s. Seeds
+//! of the batches are derived from the manifest hash, so processing the same
+//! manifest twice yields the same transactions, which the node does not execute
+//! twice. The `payout` example watches a folder for manifests, submits the
+//! batches and writes a `ReconciliationReport` for every manifest.
+
+use exonum::crypto::{self, Hash, PublicKey, SecretKey, Signature};
+use exonum::encoding::serialize::FromHex;
+use serde_json;
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+
+use {Payment, TransactionStatus, TxBatchTransfer};
+
+/// Payment of a manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayoutLine {
+    /// Public key of the receiving wallet.
+    pub to: PublicKey,
+    /// Amount to pay.
+    pub amount: u64,
+}
+
+/// Parsed payout manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    /// Payments in the order of the manifest.
+    pub lines: Vec<PayoutLine>,
+    /// Hash of the manifest contents as read.
+    pub hash: Hash,
+}
+
+impl Manifest {
+    /// Parse a manifest in the CSV or the JSON format. JSON manifests start
+    /// with `[`.
+    pub fn parse(bytes: &[u8]) -> Result<Self, PayoutError> {
+        let hash = crypto::hash(bytes);
+        let contents = ::std::str::from_utf8(bytes).map_err(|_| PayoutError::Parse(0))?;
+        let lines = if contents.trim_left().starts_with('[') {
+            serde_json::from_str(contents).map_err(|e| PayoutError::Parse(e.line()))?
+        } else {
+            parse_csv(contents)?
+        };
+        Ok(Manifest { lines, hash })
+    }
+
+    /// Check the approver's signature of the manifest hash.
+    pub fn verify(&self, signature: &Signature, approver: &PublicKey) -> bool {
+        crypto::verify(signature, self.hash.as_ref(), approver)
+    }
+
+    /// Check that the manifest is not empty, pays every wallet at most once
+    /// and only non-zero amounts, and that `available` coins cover the batches
+    /// of at most `max_batch` payments. `batch_cost` gives the amount a batch
+    /// with the given amounts withdraws from the paying wallet, or the reason
+    /// the service rejects it; it is `CurrencySchema::batch_transfer_cost` for
+    /// the paying wallet and the fee of the batches.
+    pub fn check<F>(
+        &self,
+        available: u64,
+        max_batch: usize,
+        mut batch_cost: F,
+    ) -> Result<(), PayoutError>
+    where
+        F: FnMut(&[u64]) -> Result<u64, String>,
+    {
+        if self.lines.is_empty() {
+            return Err(PayoutError::Empty);
+        }
+        let mut receivers = BTreeSet::new();
+        for line in &self.lines {
+            if line.amount == 0 {
+                return Err(PayoutError::ZeroAmount(line.to));
+            }
+            if !receivers.insert(line.to) {
+                return Err(PayoutError::Duplicate(line.to));
+            }
+        }
+        let mut required = 0u64;
+        for lines in self.lines.chunks(::std::cmp::max(max_batch, 1)) {
+            let amounts: Vec<u64> = lines.iter().map(|line| line.amount).collect();
+            let cost = batch_cost(&amounts).map_err(PayoutError::Rejected)?;
+            required = required.checked_add(cost).ok_or(PayoutError::Overflow)?;
+        }
+        if required > available {
+            return Err(PayoutError::InsufficientFunds {
+                required,
+                available,
+            });
+        }
+        Ok(())
+    }
+
+    /// Sign batch transfers making the payments of the manifest from the wallet
+    /// `from`, at most `max_batch` payments each.
+    pub fn batches(
+        &self,
+        from: &PublicKey,
+        fee: u64,
+        max_batch: usize,
+        secret_key: &SecretKey,
+    ) -> Vec<TxBatchTransfer> {
+        self.lines
+            .chunks(::std::cmp::max(max_batch, 1))
+            .enumerate()
+            .map(|(index, lines)| {
+                let payments = lines
+                    .iter()
+                    .map(|line| Payment::new(&line.to, line.amount))
+                    .collect();
+                let seed = batch_seed(&self.hash, index as u64);
+                TxBatchTransfer::new(from, payments, fee, seed, secret_key)
+            })
+            .collect()
+    }
+}
+
+/// Seed of the batch with the given index: the first eight bytes of the hash
+/// of the manifest hash and the index.
+pub fn batch_seed(manifest: &Hash, index: u64) -> u64 {
+    let mut data = manifest.as_ref().to_vec();
+    data.extend_from_slice(&index.to_string().into_bytes());
+    crypto::hash(&data).as_ref()[..8].iter().fold(
+        0,
+        |seed, &byte| (seed << 8) | u64::from(byte),
+    )
+}
+
+/// Parse `<pub_key>,<amount>` lines.
+fn parse_csv(contents: &str) -> Result<Vec<PayoutLine>, PayoutError> {
+    let mut lines = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line == "to,amount" {
+            continue;
+        }
+        let mut fields = line.split(',').map(str::trim);
+        let parsed = match (fields.next(), fields.next(), fields.next()) {
+            (Some(to), Some(amount), None) => {
+                PublicKey::from_hex(to).ok().and_then(|to| {
+                    amount.parse().ok().map(|amount| PayoutLine { to, amount })
+                })
+            }
+            _ => None,
+        };
+        lines.push(parsed.ok_or(PayoutError::Parse(index + 1))?);
+    }
+    Ok(lines)
+}
+
+/// Errors occurring when reading and checking a payout manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayoutError {
+    /// The manifest is malformed at the given line; `0` if the line is unknown.
+    Parse(usize),
+    /// The manifest has no payments.
+    Empty,
+    /// The manifest pays nothing to the wallet.
+    ZeroAmount(PublicKey),
+    /// The manifest pays the wallet more than once.
+    Duplicate(PublicKey),
+    /// The total of the manifest does not fit into an amount.
+    Overflow,
+    /// The paying wallet has fewer coins than the payments and fees require.
+    InsufficientFunds { required: u64, available: u64 },
+    /// The service rejects a batch of the manifest for the given reason.
+    Rejected(String),
+}
+
+impl fmt::Display for PayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PayoutError::Parse(line) => write!(f, "Malformed manifest at line {}", line),
+            PayoutError::ZeroAmount(ref to) => write!(f, "Zero amount paid to {}", to.to_string()),
+            PayoutError::Duplicate(ref to) => {
+                write!(f, "Wallet {} is paid more than once", to.to_string())
+            }
+            PayoutError::InsufficientFunds {
+                required,
+                available,
+            } => write!(f, "Payouts require {} coins, {} available", required, available),
+            PayoutError::Rejected(ref reason) => write!(f, "Batch is rejected: {}", reason),
+            _ => f.write_str(self.description()),
+        }
+    }
+}
+
+impl Error for PayoutError {
+    fn description(&self) -> &str {
+        match *self {
+            PayoutError::Parse(_) => "Malformed manifest",
+            PayoutError::Empty => "Manifest has no payments",
+            PayoutError::ZeroAmount(_) => "Manifest has a zero amount",
+            PayoutError::Duplicate(_) => "Manifest pays a wallet more than once",
+            PayoutError::Overflow => "Manifest total is too large",
+            PayoutError::InsufficientFunds { .. } => "Insufficient funds for the payouts",
+            PayoutError::Rejected(_) => "Batch is rejected by the service",
+        }
+    }
+}
+
+/// Batch transfer of a manifest together with its final status.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReconciliationEntry {
+    /// Hash of the batch transfer.
+    pub tx_hash: Hash,
+    /// Payments made by the batch.
+    pub payments: Vec<PayoutLine>,
+    /// Status of the batch as reported by the node.
+    pub status: TransactionStatus,
+}
+
+/// Outcome of processing a manifest.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReconciliationReport {
+    /// Hash of the manifest.
+    pub manifest: Hash,
+    /// Batches in the order of submission.
+    pub entries: Vec<ReconciliationEntry>,
+}
+
+impl ReconciliationReport {
+    /// Check whether every batch is committed and succeeded.
+    pub fn is_complete(&self) -> bool {
+        self.entries.iter().all(|entry| match entry.status {
+            TransactionStatus::Committed { code: Some(0), .. } => true,
+            _ => false,
+        })
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Advisory risk scoring of wallet activity.
+//!
+//! Each node assesses the senders of transfers in every committed block and
+//! keeps the results in memory. The assessments are local to the node and do not
+//! affect transaction execution; they are served to the node operator by the
+//! private API. The scoring is done by a `RiskModel`, which can be replaced with
+//! a custom one via `CurrencyService::with_risk_model`.
+
+use exonum::crypto::PublicKey;
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// Number of recent blocks with transfers kept for each wallet by default.
+pub const DEFAULT_RISK_WINDOW: usize = 10;
+
+/// Transfers sent by a wallet in a single block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockActivity {
+    /// Height of the block.
+    pub height: u64,
+    /// Total amount sent.
+    pub volume: u64,
+    /// Number of transfers sent.
+    pub transfers: u64,
+    /// Distinct receivers of the transfers.
+    pub receivers: BTreeSet<PublicKey>,
+}
+
+/// Reason for a wallet to be flagged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RiskFlag {
+    /// Sent volume is much larger than the recent average.
+    VolumeSpike,
+    /// Funds are sent to many distinct receivers at once.
+    FanOut,
+}
+
+/// Assessment of a wallet by a `RiskModel`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RiskAssessment {
+    /// Height of the block after which the wallet was assessed.
+    pub height: u64,
+    /// Score from 0 (no risk) to 100.
+    pub score: u8,
+    pub flags: Vec<RiskFlag>,
+}
+
+/// Scoring of wallet activity.
+pub trait RiskModel: Send + Sync {
+    /// Assess a wallet given its recent activity, oldest block first. The last
+    /// item is the activity in the block just committed.
+    fn assess(&self, recent: &[BlockActivity]) -> (u8, Vec<RiskFlag>);
+}
+
+/// Built-in heuristics flagging volume spikes and fan-out patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeuristicModel {
+    /// A block volume exceeding the average volume of the previous blocks
+    /// by this factor is a spike.
+    pub spike_factor: u64,
+    /// Number of distinct receivers in a block considered a fan-out.
+    pub fan_out: usize,
+}
+
+impl Default for HeuristicModel {
+    fn default() -> Self {
+        HeuristicModel {
+            spike_factor: 10,
+            fan_out: 10,
+        }
+    }
+}
+
+impl RiskModel for HeuristicModel {
+    fn assess(&self, recent: &[BlockActivity]) -> (u8, Vec<RiskFlag>) {
+        let (current, previous) = match recent.split_last() {
+            Some(split) => split,
+            None => return (0, Vec::new()),
+        };
+
+        let mut flags = Vec::new();
+        if !previous.is_empty() {
+            let total = previous.iter().fold(0u64, |sum, block| {
+                sum.saturating_add(block.volume)
+            });
+            let average = total / previous.len() as u64;
+            if current.volume > average.saturating_mul(self.spike_factor) {
+                flags.push(RiskFlag::VolumeSpike);
+            }
+        }
+        if current.receivers.len() >= self.fan_out {
+            flags.push(RiskFlag::FanOut);
+        }
+        (50 * flags.len() as u8, flags)
+    }
+}
+
+/// In-memory state of the risk scoring on a node.
+pub struct RiskMonitor {
+    model: Box<RiskModel>,
+    window: usize,
+    history: BTreeMap<PublicKey, VecDeque<BlockActivity>>,
+    assessments: BTreeMap<PublicKey, RiskAssessment>,
+}
+
+impl RiskMonitor {
+    /// Create a monitor keeping up to `window` recent blocks with transfers for each wallet.
+    pub fn new(model: Box<RiskModel>, window: usize) -> Self {
+        RiskMonitor {
+            model,
+            window,
+            history: BTreeMap::new(),
+            assessments: BTreeMap::new(),
+        }
+    }
+
+    /// Account for the transfers, given as `(from, to, amount)`, committed in
+    /// the block at the given height and reassess their senders.
+    pub fn observe<I>(&mut self, height: u64, transfers: I)
+    where
+        I: IntoIterator<Item = (PublicKey, PublicKey, u64)>,
+    {
+        let mut blocks: BTreeMap<PublicKey, BlockActivity> = BTreeMap::new();
+        for (from, to, amount) in transfers {
+            let block = blocks.entry(from).or_insert_with(|| {
+                BlockActivity {
+                    height,
+                    volume: 0,
+                    transfers: 0,
+                    receivers: BTreeSet::new(),
+                }
+            });
+            block.volume = block.volume.saturating_add(amount);
+            block.transfers += 1;
+            block.receivers.insert(to);
+        }
+
+        for (wallet, block) in blocks {
+            let history = self.history.entry(wallet).or_insert_with(VecDeque::new);
+            history.push_back(block);
+            while history.len() > self.window {
+                history.pop_front();
+            }
+
+            let recent: Vec<_> = history.iter().cloned().collect();
+            let (score, flags) = self.model.assess(&recent);
+            if flags.is_empty() {
+                self.assessments.remove(&wallet);
+            } else {
+                let assessment = RiskAssessment {
+                    height,
+                    score,
+                    flags,
+                };
+                self.assessments.insert(wallet, assessment);
+            }
+        }
+    }
+
+    /// Latest assessment of a wallet, if it is flagged.
+    pub fn assessment(&self, wallet: &PublicKey) -> Option<RiskAssessment> {
+        self.assessments.get(wallet).cloned()
+    }
+
+    /// Latest assessments of all flagged wallets.
+    pub fn flagged(&self) -> Vec<FlaggedWallet> {
+        self.assessments
+            .iter()
+            .map(|(pub_key, assessment)| {
+                FlaggedWallet {
+                    pub_key: *pub_key,
+                    assessment: assessment.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Default for RiskMonitor {
+    fn default() -> Self {
+        RiskMonitor::new(Box::new(HeuristicModel::default()), DEFAULT_RISK_WINDOW)
+    }
+}
+
+/// Flagged wallet as returned by the private API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlaggedWallet {
+    pub pub_key: PublicKey,
+    pub assessment: RiskAssessment,
+}
@@ -0,0 +1,270 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tracking of submitted transactions against commit deadlines.
+//!
+//! A transaction submitted through the API with the `deadline` query parameter,
+//! in seconds, is tracked by the node until it is committed. A transaction which
+//! is not committed within its deadline raises an `SlaAlert` with diagnostics of
+//! the node's queue of submitted transactions. Like risk assessments, deadlines
+//! are local to the node and do not affect transaction execution. Alerts are
+//! served to the node operator by the private API and passed to an `AlertSink`,
+//! such as a `WebhookSink`, set via `CurrencyService::with_alert_sink`.
+//!
+//! To bound the memory used by a node, deadlines longer than `MAX_SLA_DEADLINE`
+//! are refused and at most `MAX_TRACKED_TRANSACTIONS` transactions are tracked
+//! at a time.
+
+use exonum::crypto::Hash;
+use hyper::Client;
+use hyper::header::ContentType;
+use serde_json;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of recent alerts kept by a node.
+pub const MAX_SLA_ALERTS: usize = 1000;
+
+/// Longest deadline a transaction may be tracked against, in seconds.
+pub const MAX_SLA_DEADLINE: u64 = 86_400;
+
+/// Number of transactions a node tracks against deadlines at a time.
+pub const MAX_TRACKED_TRANSACTIONS: usize = 10_000;
+
+/// Current time in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Transaction tracked against a deadline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackedTransaction {
+    /// Time of the submission, in seconds since the Unix epoch.
+    pub submitted_at: u64,
+    /// Time by which the transaction is to be committed.
+    pub deadline: u64,
+    /// Blockchain height at the submission.
+    pub submitted_height: u64,
+}
+
+/// State of the node's queue of submitted transactions when an alert is raised.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolDiagnostics {
+    /// Current blockchain height.
+    pub height: u64,
+    /// Number of blocks committed since the submission.
+    pub blocks_since_submission: u64,
+    /// Number of transactions submitted through this node and not committed yet.
+    pub pending: u64,
+    /// Whether the transaction is still awaiting commitment by this node;
+    /// `false` if the node was restarted or never saw it.
+    pub in_queue: bool,
+}
+
+/// Transaction not committed within its deadline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlaAlert {
+    pub tx_hash: Hash,
+    pub tracked: TrackedTransaction,
+    /// Time the alert was raised.
+    pub raised_at: u64,
+    pub diagnostics: PoolDiagnostics,
+}
+
+/// Receiver of alerts raised by a node.
+pub trait AlertSink: Send + Sync {
+    /// Handle alerts raised after a block is committed. Called from the thread
+    /// committing blocks, so slow work should be moved off it.
+    fn alert(&self, alerts: &[SlaAlert]);
+}
+
+/// Sink posting alerts as a JSON array to a URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookSink {
+    pub url: String,
+}
+
+impl WebhookSink {
+    pub fn new<S: Into<String>>(url: S) -> Self {
+        WebhookSink { url: url.into() }
+    }
+}
+
+impl AlertSink for WebhookSink {
+    /// Post the alerts from a separate thread; failures are logged only.
+    fn alert(&self, alerts: &[SlaAlert]) {
+        let url = self.url.clone();
+        let body = serde_json::to_string(alerts).unwrap();
+        thread::spawn(move || {
+            let response = Client::new()
+                .post(&url)
+                .header(ContentType::json())
+                .body(&body[..])
+                .send();
+            if let Err(e) = response {
+                println!("Cannot post SLA alerts to {}: {}", url, e);
+            }
+        });
+    }
+}
+
+/// In-memory state of the deadline tracking on a node.
+pub struct SlaTracker {
+    tracked: BTreeMap<Hash, TrackedTransaction>,
+    alerts: VecDeque<SlaAlert>,
+    sink: Option<Box<AlertSink>>,
+}
+
+impl SlaTracker {
+    /// Create a tracker passing the alerts to the sink, if any.
+    pub fn new(sink: Option<Box<AlertSink>>) -> Self {
+        SlaTracker {
+            tracked: BTreeMap::new(),
+            alerts: VecDeque::new(),
+            sink,
+        }
+    }
+
+    /// Check whether a transaction with the given deadline, in seconds, can
+    /// be tracked.
+    pub fn accepts(&self, deadline: u64) -> Result<(), SlaError> {
+        if deadline > MAX_SLA_DEADLINE {
+            Err(SlaError::DeadlineTooLong)
+        } else if self.tracked.len() >= MAX_TRACKED_TRANSACTIONS {
+            Err(SlaError::TooManyTracked)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Track a transaction submitted at `now` and the given height, which is
+    /// to be committed within `deadline` seconds.
+    pub fn track(
+        &mut self,
+        tx_hash: Hash,
+        now: u64,
+        deadline: u64,
+        height: u64,
+    ) -> Result<(), SlaError> {
+        self.accepts(deadline)?;
+        let tracked = TrackedTransaction {
+            submitted_at: now,
+            deadline: now.saturating_add(deadline),
+            submitted_height: height,
+        };
+        self.tracked.insert(tx_hash, tracked);
+        Ok(())
+    }
+
+    /// Stop tracking a committed transaction.
+    pub fn committed(&mut self, tx_hash: &Hash) {
+        self.tracked.remove(tx_hash);
+    }
+
+    /// Transaction tracked against a deadline, if any.
+    pub fn tracked(&self, tx_hash: &Hash) -> Option<TrackedTransaction> {
+        self.tracked.get(tx_hash).cloned()
+    }
+
+    /// Raise alerts for the transactions past their deadline at `now` and stop
+    /// tracking them. `is_pending` tells whether a transaction is still in
+    /// the node's queue of `pending` submitted transactions.
+    pub fn check<F>(
+        &mut self,
+        now: u64,
+        height: u64,
+        pending: u64,
+        is_pending: F,
+    ) -> Vec<SlaAlert>
+    where
+        F: Fn(&Hash) -> bool,
+    {
+        let overdue: Vec<Hash> = self.tracked
+            .iter()
+            .filter(|&(_, tracked)| tracked.deadline <= now)
+            .map(|(tx_hash, _)| *tx_hash)
+            .collect();
+        let raised: Vec<SlaAlert> = overdue
+            .into_iter()
+            .map(|tx_hash| {
+                let tracked = self.tracked.remove(&tx_hash).unwrap();
+                let diagnostics = PoolDiagnostics {
+                    height,
+                    blocks_since_submission: height.saturating_sub(tracked.submitted_height),
+                    pending,
+                    in_queue: is_pending(&tx_hash),
+                };
+                SlaAlert {
+                    tx_hash,
+                    tracked,
+                    raised_at: now,
+                    diagnostics,
+                }
+            })
+            .collect();
+
+        if !raised.is_empty() {
+            if let Some(ref sink) = self.sink {
+                sink.alert(&raised);
+            }
+            self.alerts.extend(raised.iter().cloned());
+            while self.alerts.len() > MAX_SLA_ALERTS {
+                self.alerts.pop_front();
+            }
+        }
+        raised
+    }
+
+    /// Recent alerts, oldest first.
+    pub fn alerts(&self) -> Vec<SlaAlert> {
+        self.alerts.iter().cloned().collect()
+    }
+}
+
+impl Default for SlaTracker {
+    fn default() -> Self {
+        SlaTracker::new(None)
+    }
+}
+
+/// Reasons for a transaction not to be tracked against a deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlaError {
+    /// The deadline is longer than `MAX_SLA_DEADLINE`.
+    DeadlineTooLong,
+    /// The node already tracks `MAX_TRACKED_TRANSACTIONS` transactions.
+    TooManyTracked,
+}
+
+impl fmt::Display for SlaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl Error for SlaError {
+    fn description(&self) -> &str {
+        match *self {
+            SlaError::DeadlineTooLong => "Deadline is too long",
+            SlaError::TooManyTracked => "Too many transactions are tracked against deadlines",
+        }
+    }
+}
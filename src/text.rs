@@ -0,0 +1,322 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Validation of user-supplied text, such as wallet names and payment memos.
+//!
+//! Text is accepted only in Unicode normalization form C and without control or
+//! invisible formatting characters, so that equal-looking strings have equal
+//! encodings. Letters of the Latin, Greek and Cyrillic scripts, which share many
+//! look-alike characters, may not be mixed in one string; e.g., a name spelled with
+//! the Cyrillic `а` cannot pass for one spelled with the Latin `a`. Lengths are
+//! counted in characters rather than bytes.
+//!
+//! Clients should bring text to the accepted form with `normalize` before signing
+//! a transaction; the same normalization is served at `/v1/names/normalize`.
+//!
+//! The checks run during transaction execution and the skeletons of names are
+//! indexed in the blockchain state, so all nodes must classify characters alike.
+//! Hence the functions do not rely on the Unicode tables of the standard library,
+//! which change with the compiler version: case mapping, whitespace and control
+//! characters follow the tables in this module, fixed at Unicode 14.0, and
+//! normalization follows `unicode-normalization`, pinned to an exact version in
+//! `Cargo.toml`. Updating either table changes which transactions are valid and
+//! must be rolled out as a new transaction version with an activation height.
+
+use unicode_normalization::UnicodeNormalization;
+
+use std::error::Error;
+use std::fmt;
+
+/// Script of letters commonly confused with each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+}
+
+/// Script of a character, or `None` if the character is not a letter of one of
+/// the confusable scripts.
+pub fn script(c: char) -> Option<Script> {
+    match c as u32 {
+        0x41...0x5A | 0x61...0x7A => Some(Script::Latin),
+        0xD7 | 0xF7 => None,
+        0xC0...0x24F | 0x1E00...0x1EFF => Some(Script::Latin),
+        0x370...0x3FF | 0x1F00...0x1FFF => Some(Script::Greek),
+        0x400...0x52F | 0x2DE0...0x2DFF | 0xA640...0xA69F => Some(Script::Cyrillic),
+        _ => None,
+    }
+}
+
+/// Check whether the text contains letters of more than one confusable script.
+pub fn is_mixed_script(s: &str) -> bool {
+    let mut scripts = s.chars().filter_map(script);
+    match scripts.next() {
+        Some(first) => scripts.any(|script| script != first),
+        None => false,
+    }
+}
+
+/// Check whether a character is a control character or an invisible formatting
+/// character, such as a zero-width space or a bidirectional override.
+pub fn is_control(c: char) -> bool {
+    match c as u32 {
+        0x00...0x1F | 0x7F...0x9F => true,
+        0x200B...0x200F | 0x202A...0x202E | 0x2060...0x2069 | 0xFEFF => true,
+        _ => false,
+    }
+}
+
+/// Check whether a character has the `White_Space` property of Unicode 14.0.
+pub fn is_whitespace(c: char) -> bool {
+    match c as u32 {
+        0x09...0x0D | 0x20 | 0x85 | 0xA0 | 0x1680 | 0x2000...0x200A => true,
+        0x2028 | 0x2029 | 0x202F | 0x205F | 0x3000 => true,
+        _ => false,
+    }
+}
+
+/// Simple lowercase mappings of Unicode 14.0 as `(first, last, delta, stride)`
+/// runs, sorted by `first`: every `stride`-th character from `first` to `last`
+/// maps to the character `delta` code points away.
+const LOWERCASE: &[(u32, u32, i32, u32)] = &[
+    (0x0041, 0x005A, 32, 1), (0x00C0, 0x00D6, 32, 1), (0x00D8, 0x00DE, 32, 1),
+    (0x0100, 0x012E, 1, 2), (0x0130, 0x0130, -199, 1), (0x0132, 0x0136, 1, 2),
+    (0x0139, 0x0147, 1, 2), (0x014A, 0x0176, 1, 2), (0x0178, 0x0178, -121, 1),
+    (0x0179, 0x017D, 1, 2), (0x0181, 0x0181, 210, 1), (0x0182, 0x0184, 1, 2),
+    (0x0186, 0x0186, 206, 1), (0x0187, 0x0187, 1, 1), (0x0189, 0x018A, 205, 1),
+    (0x018B, 0x018B, 1, 1), (0x018E, 0x018E, 79, 1), (0x018F, 0x018F, 202, 1),
+    (0x0190, 0x0190, 203, 1), (0x0191, 0x0191, 1, 1), (0x0193, 0x0193, 205, 1),
+    (0x0194, 0x0194, 207, 1), (0x0196, 0x0196, 211, 1), (0x0197, 0x0197, 209, 1),
+    (0x0198, 0x0198, 1, 1), (0x019C, 0x019C, 211, 1), (0x019D, 0x019D, 213, 1),
+    (0x019F, 0x019F, 214, 1), (0x01A0, 0x01A4, 1, 2), (0x01A6, 0x01A6, 218, 1),
+    (0x01A7, 0x01A7, 1, 1), (0x01A9, 0x01A9, 218, 1), (0x01AC, 0x01AC, 1, 1),
+    (0x01AE, 0x01AE, 218, 1), (0x01AF, 0x01AF, 1, 1), (0x01B1, 0x01B2, 217, 1),
+    (0x01B3, 0x01B5, 1, 2), (0x01B7, 0x01B7, 219, 1), (0x01B8, 0x01B8, 1, 1),
+    (0x01BC, 0x01BC, 1, 1), (0x01C4, 0x01C4, 2, 1), (0x01C5, 0x01C5, 1, 1),
+    (0x01C7, 0x01C7, 2, 1), (0x01C8, 0x01C8, 1, 1), (0x01CA, 0x01CA, 2, 1),
+    (0x01CB, 0x01DB, 1, 2), (0x01DE, 0x01EE, 1, 2), (0x01F1, 0x01F1, 2, 1),
+    (0x01F2, 0x01F4, 1, 2), (0x01F6, 0x01F6, -97, 1), (0x01F7, 0x01F7, -56, 1),
+    (0x01F8, 0x021E, 1, 2), (0x0220, 0x0220, -130, 1), (0x0222, 0x0232, 1, 2),
+    (0x023A, 0x023A, 10795, 1), (0x023B, 0x023B, 1, 1), (0x023D, 0x023D, -163, 1),
+    (0x023E, 0x023E, 10792, 1), (0x0241, 0x0241, 1, 1), (0x0243, 0x0243, -195, 1),
+    (0x0244, 0x0244, 69, 1), (0x0245, 0x0245, 71, 1), (0x0246, 0x024E, 1, 2),
+    (0x0370, 0x0372, 1, 2), (0x0376, 0x0376, 1, 1), (0x037F, 0x037F, 116, 1),
+    (0x0386, 0x0386, 38, 1), (0x0388, 0x038A, 37, 1), (0x038C, 0x038C, 64, 1),
+    (0x038E, 0x038F, 63, 1), (0x0391, 0x03A1, 32, 1), (0x03A3, 0x03AB, 32, 1),
+    (0x03CF, 0x03CF, 8, 1), (0x03D8, 0x03EE, 1, 2), (0x03F4, 0x03F4, -60, 1),
+    (0x03F7, 0x03F7, 1, 1), (0x03F9, 0x03F9, -7, 1), (0x03FA, 0x03FA, 1, 1),
+    (0x03FD, 0x03FF, -130, 1), (0x0400, 0x040F, 80, 1), (0x0410, 0x042F, 32, 1),
+    (0x0460, 0x0480, 1, 2), (0x048A, 0x04BE, 1, 2), (0x04C0, 0x04C0, 15, 1),
+    (0x04C1, 0x04CD, 1, 2), (0x04D0, 0x052E, 1, 2), (0x0531, 0x0556, 48, 1),
+    (0x10A0, 0x10C5, 7264, 1), (0x10C7, 0x10C7, 7264, 1), (0x10CD, 0x10CD, 7264, 1),
+    (0x13A0, 0x13EF, 38864, 1), (0x13F0, 0x13F5, 8, 1), (0x1C90, 0x1CBA, -3008, 1),
+    (0x1CBD, 0x1CBF, -3008, 1), (0x1E00, 0x1E94, 1, 2), (0x1E9E, 0x1E9E, -7615, 1),
+    (0x1EA0, 0x1EFE, 1, 2), (0x1F08, 0x1F0F, -8, 1), (0x1F18, 0x1F1D, -8, 1),
+    (0x1F28, 0x1F2F, -8, 1), (0x1F38, 0x1F3F, -8, 1), (0x1F48, 0x1F4D, -8, 1),
+    (0x1F59, 0x1F5F, -8, 2), (0x1F68, 0x1F6F, -8, 1), (0x1F88, 0x1F8F, -8, 1),
+    (0x1F98, 0x1F9F, -8, 1), (0x1FA8, 0x1FAF, -8, 1), (0x1FB8, 0x1FB9, -8, 1),
+    (0x1FBA, 0x1FBB, -74, 1), (0x1FBC, 0x1FBC, -9, 1), (0x1FC8, 0x1FCB, -86, 1),
+    (0x1FCC, 0x1FCC, -9, 1), (0x1FD8, 0x1FD9, -8, 1), (0x1FDA, 0x1FDB, -100, 1),
+    (0x1FE8, 0x1FE9, -8, 1), (0x1FEA, 0x1FEB, -112, 1), (0x1FEC, 0x1FEC, -7, 1),
+    (0x1FF8, 0x1FF9, -128, 1), (0x1FFA, 0x1FFB, -126, 1), (0x1FFC, 0x1FFC, -9, 1),
+    (0x2126, 0x2126, -7517, 1), (0x212A, 0x212A, -8383, 1), (0x212B, 0x212B, -8262, 1),
+    (0x2132, 0x2132, 28, 1), (0x2160, 0x216F, 16, 1), (0x2183, 0x2183, 1, 1),
+    (0x24B6, 0x24CF, 26, 1), (0x2C00, 0x2C2F, 48, 1), (0x2C60, 0x2C60, 1, 1),
+    (0x2C62, 0x2C62, -10743, 1), (0x2C63, 0x2C63, -3814, 1), (0x2C64, 0x2C64, -10727, 1),
+    (0x2C67, 0x2C6B, 1, 2), (0x2C6D, 0x2C6D, -10780, 1), (0x2C6E, 0x2C6E, -10749, 1),
+    (0x2C6F, 0x2C6F, -10783, 1), (0x2C70, 0x2C70, -10782, 1), (0x2C72, 0x2C72, 1, 1),
+    (0x2C75, 0x2C75, 1, 1), (0x2C7E, 0x2C7F, -10815, 1), (0x2C80, 0x2CE2, 1, 2),
+    (0x2CEB, 0x2CED, 1, 2), (0x2CF2, 0x2CF2, 1, 1), (0xA640, 0xA66C, 1, 2),
+    (0xA680, 0xA69A, 1, 2), (0xA722, 0xA72E, 1, 2), (0xA732, 0xA76E, 1, 2),
+    (0xA779, 0xA77B, 1, 2), (0xA77D, 0xA77D, -35332, 1), (0xA77E, 0xA786, 1, 2),
+    (0xA78B, 0xA78B, 1, 1), (0xA78D, 0xA78D, -42280, 1), (0xA790, 0xA792, 1, 2),
+    (0xA796, 0xA7A8, 1, 2), (0xA7AA, 0xA7AA, -42308, 1), (0xA7AB, 0xA7AB, -42319, 1),
+    (0xA7AC, 0xA7AC, -42315, 1), (0xA7AD, 0xA7AD, -42305, 1), (0xA7AE, 0xA7AE, -42308, 1),
+    (0xA7B0, 0xA7B0, -42258, 1), (0xA7B1, 0xA7B1, -42282, 1), (0xA7B2, 0xA7B2, -42261, 1),
+    (0xA7B3, 0xA7B3, 928, 1), (0xA7B4, 0xA7C2, 1, 2), (0xA7C4, 0xA7C4, -48, 1),
+    (0xA7C5, 0xA7C5, -42307, 1), (0xA7C6, 0xA7C6, -35384, 1), (0xA7C7, 0xA7C9, 1, 2),
+    (0xA7D0, 0xA7D0, 1, 1), (0xA7D6, 0xA7D8, 1, 2), (0xA7F5, 0xA7F5, 1, 1),
+    (0xFF21, 0xFF3A, 32, 1), (0x10400, 0x10427, 40, 1), (0x104B0, 0x104D3, 40, 1),
+    (0x10570, 0x1057A, 39, 1), (0x1057C, 0x1058A, 39, 1), (0x1058C, 0x10592, 39, 1),
+    (0x10594, 0x10595, 39, 1), (0x10C80, 0x10CB2, 64, 1), (0x118A0, 0x118BF, 32, 1),
+    (0x16E40, 0x16E5F, 32, 1), (0x1E900, 0x1E921, 34, 1),
+];
+
+/// Lowercase form of a character according to the simple case mapping of
+/// Unicode 14.0.
+pub fn to_lowercase(c: char) -> char {
+    let code = c as u32;
+    let index = match LOWERCASE.binary_search_by(|&(first, _, _, _)| first.cmp(&code)) {
+        Ok(index) => index,
+        Err(0) => return c,
+        Err(index) => index - 1,
+    };
+    let (first, last, delta, stride) = LOWERCASE[index];
+    if code > last || (code - first) % stride != 0 {
+        return c;
+    }
+    ::std::char::from_u32((code as i32 + delta) as u32).unwrap_or(c)
+}
+
+/// Bring the text to Unicode normalization form C.
+pub fn normalize(s: &str) -> String {
+    s.nfc().collect()
+}
+
+/// Check whether the text is in Unicode normalization form C.
+pub fn is_normalized(s: &str) -> bool {
+    s.nfc().eq(s.chars())
+}
+
+/// Length of the text in characters.
+pub fn char_count(s: &str) -> u64 {
+    s.chars().count() as u64
+}
+
+/// Check that the text is acceptable and at most `max_length` characters long.
+pub fn check(s: &str, max_length: u64) -> Result<(), TextError> {
+    if char_count(s) > max_length {
+        return Err(TextError::TooLong);
+    }
+    if s.chars().any(is_control) {
+        return Err(TextError::ControlCharacter);
+    }
+    if !is_normalized(s) {
+        return Err(TextError::NotNormalized);
+    }
+    if is_mixed_script(s) {
+        return Err(TextError::MixedScripts);
+    }
+    Ok(())
+}
+
+/// Canonical form of the text for comparing names: normalized, lowercase, with
+/// Greek and Cyrillic look-alikes of Latin letters replaced by the Latin letters
+/// and without whitespace. Names with equal skeletons are confusable.
+pub fn skeleton(s: &str) -> String {
+    normalize(s)
+        .chars()
+        .map(to_lowercase)
+        .filter(|&c| !is_whitespace(c))
+        .map(|c| match c {
+            '\u{430}' | '\u{3b1}' => 'a',
+            '\u{432}' | '\u{3b2}' => 'b',
+            '\u{441}' | '\u{3f2}' => 'c',
+            '\u{501}' => 'd',
+            '\u{435}' | '\u{3b5}' => 'e',
+            '\u{4bb}' => 'h',
+            '\u{456}' | '\u{3b9}' => 'i',
+            '\u{458}' => 'j',
+            '\u{3ba}' => 'k',
+            '\u{43c}' => 'm',
+            '\u{43f}' | '\u{3b7}' => 'n',
+            '\u{43e}' | '\u{3bf}' | '0' => 'o',
+            '\u{440}' | '\u{3c1}' => 'p',
+            '\u{455}' => 's',
+            '\u{442}' | '\u{3c4}' => 't',
+            '\u{3c5}' => 'u',
+            '\u{3bd}' => 'v',
+            '\u{448}' | '\u{3c9}' => 'w',
+            '\u{445}' | '\u{3c7}' => 'x',
+            '\u{443}' | '\u{3b3}' => 'y',
+            '1' => 'l',
+            c => c,
+        })
+        .collect()
+}
+
+/// Trigrams of the skeleton of the text, padded so that short names have
+/// trigrams too. Each trigram is listed once.
+pub fn trigrams(s: &str) -> Vec<String> {
+    let chars: Vec<char> = format!("  {} ", skeleton(s)).chars().collect();
+    let mut trigrams: Vec<String> = chars
+        .windows(3)
+        .map(|window| window.iter().cloned().collect())
+        .collect();
+    trigrams.sort();
+    trigrams.dedup();
+    trigrams
+}
+
+/// Levenshtein distance between two strings, in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = if ca == cb { diagonal } else { diagonal + 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = ::std::cmp::min(substitution, ::std::cmp::min(row[j], row[j + 1]) + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Reasons for text to be rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextError {
+    /// The text has too many characters.
+    TooLong,
+    /// The text contains a control or invisible formatting character.
+    ControlCharacter,
+    /// The text is not in Unicode normalization form C.
+    NotNormalized,
+    /// The text mixes letters of confusable scripts.
+    MixedScripts,
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl Error for TextError {
+    fn description(&self) -> &str {
+        match *self {
+            TextError::TooLong => "Text is too long",
+            TextError::ControlCharacter => "Text contains a control character",
+            TextError::NotNormalized => "Text is not in Unicode normalization form C",
+            TextError::MixedScripts => "Text mixes letters of different scripts",
+        }
+    }
+}
+
+/// Text in the normalized form together with the outcome of its check, as
+/// returned by the API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NormalizedText {
+    /// Text in Unicode normalization form C.
+    pub normalized: String,
+    /// Length of the normalized text in characters.
+    pub length: u64,
+    /// Reason why the normalized text would still be rejected, if any.
+    pub error: Option<TextError>,
+}
+
+impl NormalizedText {
+    /// Normalize the text and check it against the given length limit.
+    pub fn new(s: &str, max_length: u64) -> Self {
+        let normalized = normalize(s);
+        let error = check(&normalized, max_length).err();
+        NormalizedText {
+            length: char_count(&normalized),
+            normalized,
+            error,
+        }
+    }
+}
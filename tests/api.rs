@@ -13,210 +13,4714 @@
 // limitations under the License.
 
 extern crate cryptocurrency;
+#[macro_use]
 extern crate exonum;
+#[macro_use]
 extern crate exonum_testkit;
 
-use exonum::crypto::{self, PublicKey, SecretKey};
+use exonum::blockchain::Transaction;
+use exonum::crypto::{self, Hash, PublicKey, SecretKey};
 use exonum::messages::Message;
+use exonum::storage::Fork;
 use exonum_testkit::{ApiKind, TestKit, TestKitApi, TestKitBuilder};
 
+use std::time::Duration;
+
 // Import datatypes used in tests from the crate where the service is defined.
-use cryptocurrency::{TxCreateWallet, TxTransfer, TransactionResponse, Wallet, CurrencyService};
+use cryptocurrency::ceremony::{self, CeremonyError, Share};
+use cryptocurrency::pagination::{Page, QueryBudget};
+use cryptocurrency::payment::{percent_decode, percent_encode, PaymentIntentError, PaymentRequest,
+                              PaymentUriError};
+use cryptocurrency::payout::{Manifest, PayoutError};
+use cryptocurrency::risk::{FlaggedWallet, HeuristicModel, RiskFlag};
+use cryptocurrency::sla::{SlaAlert, SlaError, SlaTracker, MAX_SLA_DEADLINE,
+                         MAX_TRACKED_TRANSACTIONS};
+use cryptocurrency::text::{self, NormalizedText, TextError};
+use cryptocurrency::{TxCreateWallet, TxTransfer, TransactionResponse, Wallet, CurrencyService,
+                     CurrencySchema, SERVICE_ID, TxDeferredTransfer, TxCancelDeferredTransfer,
+                     DeferredTransferInfo, CurrencyConfig, LoyaltyRules, LoyaltyInfo, TxTick,
+                     TxGrantPromotion, PromotionalBalance, InsuranceConfig, InsuranceInfo,
+                     TxInsurancePayout, TxApproveInsurancePayout, TxBurnWithTag, BurnRecord,
+                     FeeMarketConfig, FeeEstimate, BatchCost, Error, validate_create_wallet,
+                     validate_transfer, TxCreateAndFundWallet, TxAuthorizeSessionKey,
+                     TxRevokeSessionKey, TxSessionTransfer, SessionKeyInfo, TxAttestWallet,
+                     TxRevokeAttestation, Attestation, TxLinkWallets, GroupInfo, link_consent,
+                     TxRegisterPaymentReference, Receipt, StateRoot, VelocityRules,
+                     TxReviewHeldTransfer, HeldTransferInfo, TierConfig, TierRules, TxAssignTier,
+                     TIER_BASIC, TIER_VERIFIED, TxCreateAuction, TxBid, AuctionInfo,
+                     TxOpenStream, TxWithdrawStream, TxCloseStream, StreamInfo, BeaconInfo,
+                     TxLotteryDraw, TxEnterDraw, DrawInfo, TxIssueFeeCoupon, TxCouponTransfer,
+                     FeeCoupon, TxDeposit, TxClaimDeposit, Deposit, ServiceInfo,
+                     TransactionLimits, TX_CREATE_AUCTION_ID, TransactionStatus,
+                     WalletProofError, SignedStatement, StatementEntry, ClawbackConfig,
+                     TxClawback, TxApproveClawback, ClawbackInfo, TxBatchTransfer, Payment,
+                     TxSetupMultisig, TxMultisigTransfer, TxApproveMultisigTransfer, TxIssue,
+                     MultisigTransfer, EpochCaps, TxCreateInvitation, Invitation, TxBurn,
+                     SupplyInfo, TxFreezeWallet, TxUnfreezeWallet, NameCheck, NameMatchKind,
+                     ActivityEntry, ActivityKind, TxUpdateWallet, Overview,
+                     TxRenameWallet, TxCloseWallet, TxApprove, TxTransferFrom,
+                     AllowanceInfo, SweepRule, TxSetSweepRule, TxClearSweepRule,
+                     TxCreateEscrow, TxReleaseEscrow, TxRefundEscrow, EscrowInfo,
+                     TxLockedTransfer, LockedTransferInfo, TxCreateHtlc, TxClaimHtlc,
+                     TxRefundHtlc, Htlc, HtlcPreimage, TxCreateAsset, TxAssetTransfer, Asset,
+                     AssetBalance, CounterpartyGraph, batch_payment_id, index_root_key};
+
+/// Wrapper for the cryptocurrency service API allowing to easily use it
+/// (compared to `TestKitApi` calls).
+struct CryptocurrencyApi {
+    inner: TestKitApi,
+}
+
+impl CryptocurrencyApi {
+    /// Generates a wallet creation transaction with a random key pair, sends it over HTTP,
+    /// and checks the synchronous result (i.e., the hash of the transaction returned
+    /// within the `TransactionResponse` struct).
+    /// Note that the transaction is not immediately added to the blockchain, but rather is put
+    /// to the pool of unconfirmed transactions.
+    fn create_wallet(&self, name: &str) -> (TxCreateWallet, SecretKey) {
+        let (pubkey, key) = crypto::gen_keypair();
+        self.create_wallet_with_keypair(name, pubkey, key)
+    }
+
+    /// Same as `create_wallet`, but uses the given key pair.
+    fn create_wallet_with_keypair(
+        &self,
+        name: &str,
+        pubkey: PublicKey,
+        key: SecretKey,
+    ) -> (TxCreateWallet, SecretKey) {
+        // Create a presigned transaction
+        let tx = TxCreateWallet::new(&pubkey, name, &Hash::zero(), "", "", &Hash::zero(), &key);
+        self.create_wallet_tx(&tx);
+        (tx, key)
+    }
+
+    /// Sends a presigned wallet creation transaction over HTTP and checks the synchronous result.
+    fn create_wallet_tx(&self, tx: &TxCreateWallet) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a transfer transaction over HTTP and checks the synchronous result.
+    fn transfer(&self, tx: &TxTransfer) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/transfer",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a create-and-fund transaction over HTTP and checks the synchronous result.
+    fn create_and_fund_wallet(&self, tx: &TxCreateAndFundWallet) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/create-and-fund",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a session key authorization over HTTP and checks the synchronous result.
+    fn authorize_session_key(&self, tx: &TxAuthorizeSessionKey) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/session-keys",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a session key revocation over HTTP and checks the synchronous result.
+    fn revoke_session_key(&self, tx: &TxRevokeSessionKey) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/session-keys/revoke",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a transfer signed by a session key over HTTP and checks the synchronous result.
+    fn session_transfer(&self, tx: &TxSessionTransfer) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/session-transfer",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets session keys of a wallet using an HTTP request.
+    fn get_session_keys(&self, pubkey: &PublicKey) -> Vec<SessionKeyInfo> {
+        let page: Page<SessionKeyInfo> = self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}/session-keys", pubkey.to_string()),
+        );
+        page.items
+    }
+
+    /// Gets allowances granted by the owner of a wallet using an HTTP request.
+    fn get_allowances(&self, pubkey: &PublicKey) -> Vec<AllowanceInfo> {
+        let page: Page<AllowanceInfo> = self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}/allowances", pubkey.to_string()),
+        );
+        page.items
+    }
+
+    /// Sends an asset creation over HTTP and checks the synchronous result.
+    fn create_asset(&self, tx: &TxCreateAsset) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/assets",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends an asset transfer over HTTP and checks the synchronous result.
+    fn asset_transfer(&self, tx: &TxAssetTransfer) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/asset-transfer",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets an asset using an HTTP request.
+    fn get_asset(&self, id: &Hash) -> Asset {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/assets/{}", id.to_string()),
+        )
+    }
+
+    /// Gets the asset balances of a wallet using an HTTP request.
+    fn get_asset_balances(&self, pubkey: &PublicKey) -> Vec<AssetBalance> {
+        let page: Page<AssetBalance> = self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}/assets", pubkey.to_string()),
+        );
+        page.items
+    }
+
+    /// Gets the sweep rule of a wallet using an HTTP request.
+    fn get_sweep_rule(&self, pubkey: &PublicKey) -> SweepRule {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}/sweep-rule", pubkey.to_string()),
+        )
+    }
+
+    /// Sends a sweep rule over HTTP and checks the synchronous result.
+    fn set_sweep_rule(&self, tx: &TxSetSweepRule) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/sweep-rules",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a sweep rule removal over HTTP and checks the synchronous result.
+    fn clear_sweep_rule(&self, tx: &TxClearSweepRule) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/sweep-rules/clear",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends an allowance approval over HTTP and checks the synchronous result.
+    fn approve(&self, tx: &TxApprove) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/allowances",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a delegated transfer over HTTP and checks the synchronous result.
+    fn transfer_from(&self, tx: &TxTransferFrom) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/transfer-from",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a wallet attestation over HTTP and checks the synchronous result.
+    fn attest_wallet(&self, tx: &TxAttestWallet) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/attestations",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a metadata update over HTTP and checks the synchronous result.
+    fn update_wallet(&self, tx: &TxUpdateWallet) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/update",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a wallet rename over HTTP and checks the synchronous result.
+    fn rename_wallet(&self, tx: &TxRenameWallet) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/rename",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a wallet closure over HTTP and checks the synchronous result.
+    fn close_wallet(&self, tx: &TxCloseWallet) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/close",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a wallet freeze over HTTP and checks the synchronous result.
+    fn freeze_wallet(&self, tx: &TxFreezeWallet) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/freeze",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a wallet unfreeze over HTTP and checks the synchronous result.
+    fn unfreeze_wallet(&self, tx: &TxUnfreezeWallet) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/unfreeze",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a tier assignment over HTTP and checks the synchronous result.
+    fn assign_tier(&self, tx: &TxAssignTier) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/tier",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends an auction creation transaction over HTTP and checks the synchronous result.
+    fn create_auction(&self, tx: &TxCreateAuction) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/auctions",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a bid over HTTP and checks the synchronous result.
+    fn bid(&self, tx: &TxBid) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/auctions/bid",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Opens a payment stream over HTTP and checks the synchronous result.
+    fn open_stream(&self, tx: &TxOpenStream) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/streams",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Withdraws from a payment stream over HTTP and checks the synchronous result.
+    fn withdraw_stream(&self, tx: &TxWithdrawStream) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/streams/withdraw",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Closes a payment stream over HTTP and checks the synchronous result.
+    fn close_stream(&self, tx: &TxCloseStream) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/streams/close",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets a payment stream using an HTTP request.
+    fn get_stream(&self, id: &Hash) -> StreamInfo {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/streams/{}", id.to_string()),
+        )
+    }
+
+    /// Gets the value of the randomness beacon using an HTTP request.
+    fn get_beacon(&self, height: u64) -> BeaconInfo {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/beacon/{}", height),
+        )
+    }
+
+    /// Creates a lottery draw over HTTP and checks the synchronous result.
+    fn lottery_draw(&self, tx: &TxLotteryDraw) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/draws",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Enters a lottery draw over HTTP and checks the synchronous result.
+    fn enter_draw(&self, tx: &TxEnterDraw) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/draws/enter",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets a lottery draw using an HTTP request.
+    fn get_draw(&self, id: &Hash) -> DrawInfo {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/draws/{}", id.to_string()),
+        )
+    }
+
+    /// Issues a fee coupon over HTTP and checks the synchronous result.
+    fn issue_fee_coupon(&self, tx: &TxIssueFeeCoupon) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/fee-coupons",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a transfer redeeming a fee coupon over HTTP and checks the synchronous result.
+    fn coupon_transfer(&self, tx: &TxCouponTransfer) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/coupon-transfer",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets a fee coupon using an HTTP request.
+    fn get_fee_coupon(&self, code: &Hash) -> FeeCoupon {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/fee-coupons/{}", code.to_string()),
+        )
+    }
+
+    /// Locks a deposit over HTTP and checks the synchronous result.
+    fn deposit(&self, tx: &TxDeposit) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/deposits",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Claims a deposit over HTTP and checks the synchronous result.
+    fn claim_deposit(&self, tx: &TxClaimDeposit) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/deposits/claim",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets a deposit using an HTTP request.
+    fn get_deposit(&self, id: &Hash) -> Deposit {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/deposits/{}", id.to_string()),
+        )
+    }
+
+    /// Creates an escrow over HTTP and checks the synchronous result.
+    fn create_escrow(&self, tx: &TxCreateEscrow) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/escrows",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Releases an escrow over HTTP and checks the synchronous result.
+    fn release_escrow(&self, tx: &TxReleaseEscrow) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/escrows/release",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Refunds an escrow over HTTP and checks the synchronous result.
+    fn refund_escrow(&self, tx: &TxRefundEscrow) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/escrows/refund",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets open escrows using an HTTP request.
+    fn get_escrows(&self) -> Vec<EscrowInfo> {
+        let page: Page<EscrowInfo> = self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            "v1/escrows",
+        );
+        page.items
+    }
+
+    /// Sends a hashed time-lock contract over HTTP and checks the synchronous result.
+    fn create_htlc(&self, tx: &TxCreateHtlc) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/htlcs",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Claims a hashed time-lock contract over HTTP and checks the synchronous result.
+    fn claim_htlc(&self, tx: &TxClaimHtlc) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/htlcs/claim",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Refunds a hashed time-lock contract over HTTP and checks the synchronous result.
+    fn refund_htlc(&self, tx: &TxRefundHtlc) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/htlcs/refund",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets an open hashed time-lock contract using an HTTP request.
+    fn get_htlc(&self, id: &Hash) -> Htlc {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/htlcs/{}", id.to_string()),
+        )
+    }
+
+    /// Gets the preimage revealed for a hash lock using an HTTP request.
+    fn get_htlc_preimage(&self, hash_lock: &Hash) -> HtlcPreimage {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/htlc-preimages/{}", hash_lock.to_string()),
+        )
+    }
+
+    /// Gets auctions using an HTTP request.
+    fn get_auctions(&self) -> Vec<AuctionInfo> {
+        let page: Page<AuctionInfo> = self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            "v1/auctions",
+        );
+        page.items
+    }
+
+    /// Sends an attestation revocation over HTTP and checks the synchronous result.
+    fn revoke_attestation(&self, tx: &TxRevokeAttestation) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/attestations/revoke",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets the attestation of a wallet using an HTTP request.
+    fn get_attestation(&self, pubkey: &PublicKey) -> Attestation {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}/attestation", pubkey.to_string()),
+        )
+    }
+
+    /// Sends a wallet linking transaction over HTTP and checks the synchronous result.
+    fn link_wallets(&self, tx: &TxLinkWallets) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/groups",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets a group of linked wallets using an HTTP request.
+    fn get_group(&self, id: &Hash) -> GroupInfo {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/groups/{}", id.to_string()),
+        )
+    }
+
+    /// Sends a payment reference registration over HTTP and checks the synchronous result.
+    fn register_payment_reference(&self, tx: &TxRegisterPaymentReference) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/payment-references",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Normalizes a wallet name using an HTTP request.
+    fn get_normalized_name(&self, name: &str) -> NormalizedText {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/names/normalize?name={}", percent_encode(name)),
+        )
+    }
+
+    /// Checks a wallet name for duplicates using an HTTP request.
+    fn get_name_check(&self, name: &str) -> NameCheck {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/names/check?name={}", percent_encode(name)),
+        )
+    }
+
+    /// Gets the operator overview using an HTTP request to the private API.
+    fn get_overview(&self, query: &str) -> Overview {
+        self.inner.get_private(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/admin/overview?{}", query),
+        )
+    }
+
+    /// Gets the counterparty graph of a wallet using an HTTP request.
+    fn get_counterparty_graph(&self, pubkey: &PublicKey, query: &str) -> CounterpartyGraph {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}/graph?{}", pubkey.to_string(), query),
+        )
+    }
+
+    /// Gets the activity feed of a wallet using an HTTP request.
+    fn get_wallet_activity(&self, pubkey: &PublicKey, query: &str) -> Page<ActivityEntry> {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}/activity?{}", pubkey.to_string(), query),
+        )
+    }
+
+    /// Gets receipts of a wallet using an HTTP request.
+    fn get_receipts(&self, pubkey: &PublicKey, query: &str) -> Page<Receipt> {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}/receipts?{}", pubkey.to_string(), query),
+        )
+    }
+
+    /// Gets the state of a wallet after the block at the given height using an HTTP request.
+    fn get_wallet_at(&self, pubkey: &PublicKey, height: u64) -> Wallet {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}?height={}", pubkey.to_string(), height),
+        )
+    }
+
+    /// Gets a signed statement of a wallet using an HTTP request.
+    fn get_statement(
+        &self,
+        pubkey: &PublicKey,
+        from_height: u64,
+        to_height: u64,
+    ) -> SignedStatement {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!(
+                "v1/wallet/{}/statement?from_height={}&to_height={}",
+                pubkey.to_string(),
+                from_height,
+                to_height
+            ),
+        )
+    }
+
+    /// Gets the transaction history of a wallet using an HTTP request.
+    fn get_wallet_history(&self, pubkey: &PublicKey, query: &str) -> Page<Hash> {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}/history?{}", pubkey.to_string(), query),
+        )
+    }
+
+    /// Sends a deferred transfer transaction over HTTP and checks the synchronous result.
+    fn deferred_transfer(&self, tx: &TxDeferredTransfer) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/deferred-transfer",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a deferred transfer cancellation over HTTP and checks the synchronous result.
+    fn cancel_deferred_transfer(&self, tx: &TxCancelDeferredTransfer) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/deferred-transfer/cancel",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets pending deferred transfers using an HTTP request.
+    fn get_deferred_transfers(&self) -> Vec<DeferredTransferInfo> {
+        let page: Page<DeferredTransferInfo> = self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            "v1/deferred-transfers",
+        );
+        page.items
+    }
+
+    /// Sends a locked transfer transaction over HTTP and checks the synchronous result.
+    fn locked_transfer(&self, tx: &TxLockedTransfer) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/locked-transfer",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets locked transfers using an HTTP request.
+    fn get_locked_transfers(&self, query: &str) -> Vec<LockedTransferInfo> {
+        let page: Page<LockedTransferInfo> = self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/locked-transfers?{}", query),
+        );
+        page.items
+    }
+
+    /// Gets activity counters and loyalty points of a wallet using an HTTP request.
+    fn get_loyalty(&self, pubkey: &PublicKey) -> LoyaltyInfo {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}/loyalty", pubkey.to_string()),
+        )
+    }
+
+    /// Sends a promotional grant over HTTP and checks the synchronous result.
+    fn grant_promotion(&self, tx: &TxGrantPromotion) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/promotion",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends an issuance transaction over HTTP and checks the synchronous result.
+    fn issue(&self, tx: &TxIssue) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/issue",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets the promotional balance of a wallet using an HTTP request.
+    fn get_promotion(&self, pubkey: &PublicKey) -> PromotionalBalance {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}/promotion", pubkey.to_string()),
+        )
+    }
+
+    /// Asserts that a wallet has no promotional balance.
+    fn assert_no_promotion(&self, pubkey: &PublicKey) {
+        let err: String = self.inner.get_err(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}/promotion", pubkey.to_string()),
+        );
+        assert_eq!(err, "Promotional balance not found".to_string());
+    }
+
+    /// Sends an insurance payout proposal over HTTP and checks the synchronous result.
+    fn propose_insurance_payout(&self, tx: &TxInsurancePayout) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/insurance/payouts",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends an insurance payout approval over HTTP and checks the synchronous result.
+    fn approve_insurance_payout(&self, tx: &TxApproveInsurancePayout) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/insurance/payouts/approve",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends an invitation over HTTP and checks the synchronous result.
+    fn create_invitation(&self, tx: &TxCreateInvitation) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/invitations",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets an invitation by the hash of its code using an HTTP request.
+    fn get_invitation(&self, code: &Hash) -> Invitation {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/invitations/{}", code.to_string()),
+        )
+    }
+
+    /// Sends a batch transfer over HTTP and checks the synchronous result.
+    fn batch_transfer(&self, tx: &TxBatchTransfer) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/batch-transfer",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Turns a wallet into a multisignature wallet using an HTTP request.
+    fn setup_multisig(&self, tx: &TxSetupMultisig) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/multisig",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a multisignature transfer proposal over HTTP and checks the synchronous result.
+    fn multisig_transfer(&self, tx: &TxMultisigTransfer) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/multisig/transfers",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a multisignature transfer approval over HTTP and checks the synchronous result.
+    fn approve_multisig_transfer(&self, tx: &TxApproveMultisigTransfer) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/multisig/transfers/approve",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets a transfer from a multisignature wallet using an HTTP request.
+    fn get_multisig_transfer(&self, id: &Hash) -> MultisigTransfer {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/multisig/transfers/{}", id.to_string()),
+        )
+    }
+
+    /// Sends a clawback proposal over HTTP and checks the synchronous result.
+    fn propose_clawback(&self, tx: &TxClawback) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/clawbacks",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a clawback approval over HTTP and checks the synchronous result.
+    fn approve_clawback(&self, tx: &TxApproveClawback) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/clawbacks/approve",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets the audit record of clawbacks using an HTTP request.
+    fn get_clawbacks(&self) -> Vec<ClawbackInfo> {
+        self.inner.get(ApiKind::Service("cryptocurrency"), "v1/clawbacks")
+    }
+
+    /// Releases or rejects a held transfer using an HTTP request.
+    fn review_held_transfer(&self, tx: &TxReviewHeldTransfer) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/held-transfers/review",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets transfers held by the velocity rules using an HTTP request.
+    fn get_held_transfers(&self) -> Vec<HeldTransferInfo> {
+        let page: Page<HeldTransferInfo> = self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            "v1/held-transfers",
+        );
+        page.items
+    }
+
+    /// Gets the state of the insurance fund using an HTTP request.
+    fn get_insurance(&self) -> InsuranceInfo {
+        self.inner.get(ApiKind::Service("cryptocurrency"), "v1/insurance")
+    }
+
+    /// Sends a tagged burn over HTTP and checks the synchronous result.
+    fn burn_with_tag(&self, tx: &TxBurnWithTag) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/burns",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Sends a burn transaction over HTTP and checks the synchronous result.
+    fn burn(&self, tx: &TxBurn) {
+        let tx_info: TransactionResponse = self.inner.post(
+            ApiKind::Service("cryptocurrency"),
+            "v1/wallets/burn",
+            tx,
+        );
+        assert_eq!(tx_info.tx_hash, tx.hash());
+    }
+
+    /// Gets the total of burned coins using an HTTP request.
+    fn get_supply(&self) -> SupplyInfo {
+        self.inner.get(ApiKind::Service("cryptocurrency"), "v1/supply")
+    }
+
+    /// Gets burns made with a particular tag using an HTTP request.
+    fn get_burns_by_tag(&self, tag: &Hash) -> Vec<BurnRecord> {
+        let page: Page<BurnRecord> = self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/burns/{}", tag.to_string()),
+        );
+        page.items
+    }
+
+    /// Gets a page of wallets using an HTTP request.
+    fn get_wallets(&self, query: &str) -> Page<Wallet> {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallets?{}", query),
+        )
+    }
+
+    /// Gets the fee estimate for the next block using an HTTP request.
+    fn get_fee_estimate(&self) -> FeeEstimate {
+        self.inner.get(ApiKind::Service("cryptocurrency"), "v1/fees")
+    }
+
+    /// Gets the root hash of the wallets table using an HTTP request.
+    fn get_state_root(&self) -> StateRoot {
+        self.inner.get(ApiKind::Service("cryptocurrency"), "v1/state-root")
+    }
+
+    /// Gets the status of a transaction using an HTTP request.
+    fn get_transaction_status(&self, tx_hash: &Hash) -> TransactionStatus {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/transactions/{}", tx_hash.to_string()),
+        )
+    }
+
+    /// Gets the service parameters using an HTTP request.
+    fn get_info(&self) -> ServiceInfo {
+        self.inner.get(ApiKind::Service("cryptocurrency"), "v1/info")
+    }
+
+    /// Gets the state of a particular wallet using an HTTP request.
+    fn get_wallet(&self, pubkey: &PublicKey) -> Wallet {
+        self.inner.get(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}", pubkey.to_string()),
+        )
+    }
+
+    /// Asserts that a wallet with the specified public key is not known to the blockchain.
+    fn assert_no_wallet(&self, pubkey: &PublicKey) {
+        let err: String = self.inner.get_err(
+            ApiKind::Service("cryptocurrency"),
+            &format!("v1/wallet/{}", pubkey.to_string()),
+        );
+        assert_eq!(err, "Wallet not found".to_string());
+    }
+}
+
+/// Creates a testkit together with the API wrapper defined above.
+fn create_testkit() -> (TestKit, CryptocurrencyApi) {
+    let testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new())
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+    (testkit, api)
+}
+
+/// Check that the wallet creation transaction works when invoked via API.
+#[test]
+fn test_create_wallet() {
+    let (mut testkit, api) = create_testkit();
+    // Create and send a transaction via API
+    let (tx, _) = api.create_wallet("Alice");
+    testkit.create_block();
+
+    // Check that the user indeed is persisted by the service
+    let wallet = api.get_wallet(tx.pub_key());
+    assert_eq!(wallet.pub_key(), tx.pub_key());
+    assert_eq!(wallet.name(), tx.name());
+    assert_eq!(wallet.balance(), 100);
+}
+
+/// Check that the transfer transaction works as intended.
+#[test]
+fn test_transfer() {
+    // Create 2 wallets.
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    // Check that the initial Alice's and Bob's balances persisted by the service.
+    let wallet = api.get_wallet(tx_alice.pub_key());
+    assert_eq!(wallet.balance(), 100);
+    let wallet = api.get_wallet(tx_bob.pub_key());
+    assert_eq!(wallet.balance(), 100);
+
+    // Transfer funds by invoking the corresponding API method.
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        10, // transferred amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_alice,
+    );
+    api.transfer(&tx);
+    testkit.create_block();
+
+    // After the transfer transaction is included into a block, we may check new wallet
+    // balances.
+    let wallet = api.get_wallet(tx_alice.pub_key());
+    assert_eq!(wallet.balance(), 90);
+    let wallet = api.get_wallet(tx_bob.pub_key());
+    assert_eq!(wallet.balance(), 110);
+}
+
+/// Check that a transfer from a non-existing wallet fails as expected.
+#[test]
+fn test_transfer_from_nonexisting_wallet() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    // Do not commit Alice's transaction, so Alice's wallet does not exist
+    // when a transfer occurs.
+    testkit.create_block_with_tx_hashes(&[tx_bob.hash()]);
+
+    api.assert_no_wallet(tx_alice.pub_key());
+    let wallet = api.get_wallet(tx_bob.pub_key());
+    assert_eq!(wallet.balance(), 100);
+
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        10, // transfer amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_alice,
+    );
+    api.transfer(&tx);
+    testkit.create_block_with_tx_hashes(&[tx.hash()]);
+
+    // Check that Bob's balance doesn't change
+    let wallet = api.get_wallet(tx_bob.pub_key());
+    assert_eq!(wallet.balance(), 100);
+}
+
+/// Check that a transfer to a non-existing wallet fails as expected.
+#[test]
+fn test_transfer_to_nonexisting_wallet() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    // Do not commit Bob's transaction, so Bob's wallet does not exist
+    // when a transfer occurs.
+    testkit.create_block_with_tx_hashes(&[tx_alice.hash()]);
+
+    let wallet = api.get_wallet(tx_alice.pub_key());
+    assert_eq!(wallet.balance(), 100);
+    api.assert_no_wallet(tx_bob.pub_key());
+
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        10, // transfer amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_alice,
+    );
+    api.transfer(&tx);
+    testkit.create_block_with_tx_hashes(&[tx.hash()]);
+
+    // Check that Alice's balance doesn't change
+    let wallet = api.get_wallet(tx_alice.pub_key());
+    assert_eq!(wallet.balance(), 100);
+}
+
+/// Check that an overcharge does not lead to changes in sender's and receiver's balances.
+#[test]
+fn test_transfer_overcharge() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    // Transfer funds. The transfer amount (110) is more than Alice has (100).
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        110, // transfer amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_alice,
+    );
+    api.transfer(&tx);
+    testkit.create_block();
+
+    let wallet = api.get_wallet(tx_alice.pub_key());
+    assert_eq!(wallet.balance(), 100);
+    let wallet = api.get_wallet(tx_bob.pub_key());
+    assert_eq!(wallet.balance(), 100);
+}
+
+/// Check that a deferred transfer locks the coins and credits them at the target height.
+#[test]
+fn test_deferred_transfer() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let target_height = testkit.height().0 + 4;
+    let tx = TxDeferredTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        30, // transfer amount
+        target_height,
+        0, // seed
+        &key_alice,
+    );
+    api.deferred_transfer(&tx);
+    testkit.create_block();
+
+    // The coins are locked, but not yet credited.
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 70);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 100);
+    let pending = api.get_deferred_transfers();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].tx_hash, tx.hash());
+
+    // The commit hook issues the tick transaction processed at the target height.
+    while testkit.height().0 < target_height + 1 {
+        testkit.create_block();
+    }
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 70);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 130);
+    assert!(api.get_deferred_transfers().is_empty());
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert!(schema.scheduled_heights().iter().next().is_none());
+    assert!(!schema.has_scheduled(testkit.height().0));
+}
+
+/// Check that the ticks of all validators for a height perform the scheduled
+/// actions once.
+#[test]
+fn test_tick_per_height() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_validators(3)
+        .with_service(CurrencyService::new())
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let target_height = testkit.height().0 + 3;
+    let tx = TxDeferredTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        30, // transfer amount
+        target_height,
+        0, // seed
+        &key_alice,
+    );
+    api.deferred_transfer(&tx);
+    while testkit.height().0 < target_height - 1 {
+        testkit.create_block();
+    }
+
+    let ticks: Vec<Box<Transaction>> = testkit
+        .network()
+        .validators()
+        .iter()
+        .map(|validator| {
+            let (pubkey, key) = validator.service_keypair();
+            Box::new(TxTick::new(pubkey, target_height, key)) as Box<Transaction>
+        })
+        .collect();
+    testkit.create_block_with_transactions(ticks);
+    assert_eq!(testkit.height().0, target_height);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 70);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 130);
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tick_height().get(), Some(target_height));
+    assert!(schema.deferred_transfers().get(&tx.hash()).is_none());
+}
+
+/// Check that the coins of a locked transfer become spendable at the unlock height.
+#[test]
+fn test_locked_transfer() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    let (tx_carol, _) = api.create_wallet("Carol");
+    testkit.create_block();
+
+    let unlock_height = testkit.height().0 + 4;
+    let tx = TxLockedTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        30, // transfer amount
+        unlock_height,
+        0, // seed
+        &key_alice,
+    );
+    api.locked_transfer(&tx);
+    testkit.create_block();
+
+    // The coins are debited from the sender and locked for the receiver.
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 70);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 100);
+    assert_eq!(CurrencySchema::new(testkit.snapshot()).locked_balance(tx_bob.pub_key()), 30);
+    let query = format!("to={}", tx_bob.pub_key().to_string());
+    let locked = api.get_locked_transfers(&query);
+    assert_eq!(locked.len(), 1);
+    assert_eq!(locked[0].tx_hash, tx.hash());
+    let query = format!("to={}", tx_carol.pub_key().to_string());
+    assert!(api.get_locked_transfers(&query).is_empty());
+
+    // Locked coins cannot be spent before the unlock height.
+    let spend = TxTransfer::new(
+        tx_bob.pub_key(),
+        tx_carol.pub_key(),
+        120, // transfer amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_bob,
+    );
+    api.transfer(&spend);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 100);
+
+    // The commit hook issues the tick transaction unlocking the coins.
+    while testkit.height().0 < unlock_height + 1 {
+        testkit.create_block();
+    }
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 130);
+    assert!(api.get_locked_transfers("").is_empty());
+    assert_eq!(CurrencySchema::new(testkit.snapshot()).locked_balance(tx_bob.pub_key()), 0);
+
+    let spend = TxTransfer::new(
+        tx_bob.pub_key(),
+        tx_carol.pub_key(),
+        120, // transfer amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        1, // seed
+        &key_bob,
+    );
+    api.transfer(&spend);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 10);
+    assert_eq!(api.get_wallet(tx_carol.pub_key()).balance(), 220);
+}
+
+/// Check that the sender can cancel a pending deferred transfer.
+#[test]
+fn test_cancel_deferred_transfer() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let tx = TxDeferredTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        30, // transfer amount
+        testkit.height().0 + 100,
+        0, // seed
+        &key_alice,
+    );
+    api.deferred_transfer(&tx);
+    testkit.create_block();
+
+    // Only the sender may cancel the transfer.
+    let cancel = TxCancelDeferredTransfer::new(tx_bob.pub_key(), &tx.hash(), &key_bob);
+    api.cancel_deferred_transfer(&cancel);
+    testkit.create_block();
+    assert_eq!(api.get_deferred_transfers().len(), 1);
+
+    let cancel = TxCancelDeferredTransfer::new(tx_alice.pub_key(), &tx.hash(), &key_alice);
+    api.cancel_deferred_transfer(&cancel);
+    testkit.create_block();
+    assert!(api.get_deferred_transfers().is_empty());
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 100);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 100);
+}
+
+/// Check that transfers update activity counters and award loyalty points.
+#[test]
+fn test_loyalty_points() {
+    let config = CurrencyConfig {
+        activity_period: 2,
+        loyalty: Some(LoyaltyRules {
+            points_per_transfer: 10,
+            streak_bonus: 5,
+            max_streak: 3,
+        }),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    // Make a transfer in each of two consecutive periods.
+    for seed in 0..2 {
+        let tx = TxTransfer::new(
+            tx_alice.pub_key(),
+            tx_bob.pub_key(),
+            1,
+            0,
+            &Hash::zero(),
+            &Hash::zero(),
+            "",
+            seed,
+            &key_alice,
+        );
+        api.transfer(&tx);
+        testkit.create_block();
+        testkit.create_block();
+    }
+
+    let alice = api.get_loyalty(tx_alice.pub_key());
+    assert_eq!(alice.activity.sent(), 1);
+    assert_eq!(alice.activity.streak(), 2);
+    assert_eq!(alice.points, 10 + 15);
+    let bob = api.get_loyalty(tx_bob.pub_key());
+    assert_eq!(bob.activity.received(), 1);
+    assert_eq!(bob.points, 0);
+}
+
+/// Check that promotional coins are spent first and expire back to the treasury.
+#[test]
+fn test_promotional_balance() {
+    let (issuer_pubkey, issuer_key) = crypto::gen_keypair();
+    let (treasury_pubkey, treasury_key) = crypto::gen_keypair();
+    let config = CurrencyConfig {
+        issuer: Some(issuer_pubkey),
+        treasury: Some(treasury_pubkey),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+
+    api.create_wallet_with_keypair("Treasury", treasury_pubkey, treasury_key);
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let expires_at = testkit.height().0 + 5;
+    let tx = TxGrantPromotion::new(
+        &issuer_pubkey,
+        tx_alice.pub_key(),
+        50, // promotional amount
+        expires_at,
+        0, // seed
+        &issuer_key,
+    );
+    api.grant_promotion(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(&treasury_pubkey).balance(), 50);
+
+    // The transfer is covered by the promotional coins first.
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        20,
+        0,
+        &Hash::zero(),
+        &Hash::zero(),
+        "",
+        0,
+        &key_alice,
+    );
+    api.transfer(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 100);
+    assert_eq!(api.get_promotion(tx_alice.pub_key()).amount(), 30);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 120);
+
+    // The rest of the promotional coins returns to the treasury at expiry.
+    while testkit.height().0 < expires_at + 1 {
+        testkit.create_block();
+    }
+    api.assert_no_promotion(tx_alice.pub_key());
+    assert_eq!(api.get_wallet(&treasury_pubkey).balance(), 80);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 100);
+}
+
+/// Check that transfers pay the insurance levy and payouts need enough approvals.
+#[test]
+fn test_insurance_fund() {
+    let (fund_pubkey, fund_key) = crypto::gen_keypair();
+    let governors: Vec<_> = (0..3).map(|_| crypto::gen_keypair()).collect();
+    let config = CurrencyConfig {
+        insurance: Some(InsuranceConfig {
+            fund: fund_pubkey,
+            levy_bps: 500,
+            governors: governors.iter().map(|&(pubkey, _)| pubkey).collect(),
+            threshold: 2,
+        }),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+
+    api.create_wallet_with_keypair("Insurance fund", fund_pubkey, fund_key);
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    // 5% levy on top of the transferred amount.
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        40,
+        0,
+        &Hash::zero(),
+        &Hash::zero(),
+        "",
+        0,
+        &key_alice,
+    );
+    api.transfer(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 58);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 140);
+    assert_eq!(api.get_wallet(&fund_pubkey).balance(), 102);
+
+    let (ref governor, ref governor_key) = governors[0];
+    let proposal = TxInsurancePayout::new(governor, tx_alice.pub_key(), 42, 0, governor_key);
+    api.propose_insurance_payout(&proposal);
+    testkit.create_block();
+    let info = api.get_insurance();
+    assert_eq!(info.payouts.len(), 1);
+    assert_eq!(info.payouts[0].payout.executed_at(), 0);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 58);
+
+    let (ref governor, ref governor_key) = governors[1];
+    let approval = TxApproveInsurancePayout::new(governor, &proposal.hash(), governor_key);
+    api.approve_insurance_payout(&approval);
+    testkit.create_block();
+    let info = api.get_insurance();
+    assert_ne!(info.payouts[0].payout.executed_at(), 0);
+    assert_eq!(info.fund.unwrap().balance(), 60);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 100);
+}
+
+/// Check that tagged burns destroy coins and are indexed by the tag.
+#[test]
+fn test_burn_with_tag() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    testkit.create_block();
+
+    let tag = crypto::hash(b"alice@example.com");
+    let tx = TxBurnWithTag::new(tx_alice.pub_key(), &tag, 30, 0, &key_alice);
+    api.burn_with_tag(&tx);
+    // Overcharge is ignored.
+    let overcharge = TxBurnWithTag::new(tx_alice.pub_key(), &tag, 300, 1, &key_alice);
+    api.burn_with_tag(&overcharge);
+    testkit.create_block();
+
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 70);
+    let burns = api.get_burns_by_tag(&tag);
+    assert_eq!(burns.len(), 1);
+    assert_eq!(burns[0].owner(), tx_alice.pub_key());
+    assert_eq!(burns[0].amount(), 30);
+    assert_eq!(burns[0].tx_hash(), &tx.hash());
+    assert!(api.get_burns_by_tag(&crypto::hash(b"other")).is_empty());
+}
+
+/// Check that untagged and tagged burns add up in the burned total.
+#[test]
+fn test_burn() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    testkit.create_block();
+    assert_eq!(api.get_supply().burned, 0);
+
+    let burn = TxBurn::new(tx_alice.pub_key(), 25, 0, &key_alice);
+    let tagged = TxBurnWithTag::new(tx_alice.pub_key(), &crypto::hash(b"tag"), 5, 1, &key_alice);
+    let overcharge = TxBurn::new(tx_alice.pub_key(), 300, 2, &key_alice);
+    api.burn(&burn);
+    api.burn_with_tag(&tagged);
+    api.burn(&overcharge);
+    testkit.create_block();
+
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&burn.hash()), Some(Ok(())));
+    assert_eq!(schema.tx_result(&overcharge.hash()), Some(Err(Error::InsufficientFunds)));
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 70);
+    assert_eq!(api.get_supply().burned, 30);
+}
+
+/// Check that underpaying transfers are rejected and the base fee follows block fullness.
+#[test]
+fn test_fee_market() {
+    let config = CurrencyConfig {
+        fee_market: Some(FeeMarketConfig {
+            min_base_fee: 8,
+            target_block_txs: 2,
+            max_change_denominator: 8,
+        }),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+    assert_eq!(api.get_fee_estimate().base_fee, 8);
+
+    // The underpaying transfer is rejected; the rest make the block overfull.
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        10,
+        7,
+        &Hash::zero(),
+        &Hash::zero(),
+        "",
+        0,
+        &key_alice,
+    );
+    api.transfer(&tx);
+    for seed in 1..5 {
+        let tx = TxTransfer::new(
+            tx_alice.pub_key(),
+            tx_bob.pub_key(),
+            10,
+            8,
+            &Hash::zero(),
+            &Hash::zero(),
+            "",
+            seed,
+            &key_alice,
+        );
+        api.transfer(&tx);
+    }
+    testkit.create_block();
+
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 100 - 4 * (10 + 8));
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 140);
+    assert_eq!(api.get_fee_estimate().base_fee, 9);
+}
+
+/// Check that transactions are validated against a snapshot with the same
+/// outcome as their execution.
+#[test]
+fn test_validate_against_snapshot() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block_with_tx_hashes(&[tx_alice.hash()]);
+
+    assert_eq!(validate_create_wallet(testkit.snapshot(), &tx_alice), Err(Error::WalletExists));
+    assert_eq!(validate_create_wallet(testkit.snapshot(), &tx_bob), Ok(()));
+
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        10, // transfer amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_alice,
+    );
+    assert_eq!(validate_transfer(testkit.snapshot(), &tx), Err(Error::ReceiverNotFound));
+
+    testkit.create_block_with_tx_hashes(&[tx_bob.hash()]);
+    assert_eq!(validate_transfer(testkit.snapshot(), &tx), Ok(()));
+
+    let overcharge = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        110, // transfer amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_alice,
+    );
+    assert_eq!(
+        validate_transfer(testkit.snapshot(), &overcharge),
+        Err(Error::InsufficientFunds)
+    );
+
+    // A cost overflowing an amount is rejected rather than wrapped around.
+    let overflow = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        10, // transfer amount
+        u64::max_value(), // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_alice,
+    );
+    assert_eq!(
+        validate_transfer(testkit.snapshot(), &overflow),
+        Err(Error::BalanceOverflow)
+    );
+
+    // Validation does not change the state.
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 100);
+
+    api.transfer(&tx);
+    testkit.create_block_with_tx_hashes(&[tx.hash()]);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 90);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 110);
+}
+
+/// Check that a transfer depending on another transaction of the same block is
+/// executed after it, and fails if the dependency fails, is not in the block or
+/// the queue of waiting transfers is full.
+#[test]
+fn test_transfer_dependency() {
+    let config = CurrencyConfig {
+        limits: TransactionLimits {
+            max_queued_transfers: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block_with_tx_hashes(&[tx_alice.hash()]);
+
+    // The transfer depends on Bob's wallet created in the same block.
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        10, // transfer amount
+        0, // fee
+        &tx_bob.hash(), // dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_alice,
+    );
+    api.transfer(&tx);
+    testkit.create_block_with_tx_hashes(&[tx.hash(), tx_bob.hash()]);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 90);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 110);
+    assert_eq!(CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()), Some(Ok(())));
+
+    // The transfers depend on a transaction which is not in the block. The
+    // queue holds one of them until the end of the block and charges the fee;
+    // the other one fails at once.
+    let tx_bob_again = TxCreateWallet::new(
+        tx_bob.pub_key(),
+        "Bob Roe",
+        &Hash::zero(),
+        "",
+        "",
+        &Hash::zero(),
+        &key_bob,
+    );
+    let pending: Vec<TxTransfer> = (1..3)
+        .map(|seed| {
+            TxTransfer::new(
+                tx_alice.pub_key(),
+                tx_bob.pub_key(),
+                10, // transfer amount
+                1, // fee
+                &tx_bob_again.hash(), // dependency
+                &Hash::zero(), // no reference
+                "", // no memo
+                seed,
+                &key_alice,
+            )
+        })
+        .collect();
+    api.transfer(&pending[0]);
+    api.transfer(&pending[1]);
+    testkit.create_block_with_tx_hashes(&[pending[0].hash(), pending[1].hash()]);
+    let results: Vec<_> = {
+        let schema = CurrencySchema::new(testkit.snapshot());
+        pending.iter().map(|tx| schema.tx_result(&tx.hash())).collect()
+    };
+    assert!(results.contains(&None));
+    assert!(results.contains(&Some(Err(Error::DependencyPending))));
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 89);
+
+    // The commit hook issues the tick transaction failing the queued transfer.
+    testkit.create_block();
+    let schema = CurrencySchema::new(testkit.snapshot());
+    for tx in &pending {
+        assert_eq!(schema.tx_result(&tx.hash()), Some(Err(Error::DependencyPending)));
+    }
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 89);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 110);
+
+    // The transfer depends on a wallet creation in the same block which fails,
+    // as the wallet exists.
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        10, // transfer amount
+        0, // fee
+        &tx_bob_again.hash(), // dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        3, // seed
+        &key_alice,
+    );
+    testkit.create_block_with_transactions(txvec![tx.clone(), tx_bob_again]);
+
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()),
+        Some(Err(Error::DependencyFailed))
+    );
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 89);
+}
+
+/// Check that a wallet can be created and funded with a single transaction.
+#[test]
+fn test_create_and_fund_wallet() {
+    let (mut testkit, api) = create_testkit();
+
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    testkit.create_block();
+
+    let (pubkey_bob, _) = crypto::gen_keypair();
+    let tx = TxCreateAndFundWallet::new(
+        tx_alice.pub_key(),
+        &pubkey_bob,
+        "Bob",
+        30, // funded amount
+        0, // fee
+        0, // seed
+        &key_alice,
+    );
+    api.create_and_fund_wallet(&tx);
+    testkit.create_block();
+
+    let wallet = api.get_wallet(&pubkey_bob);
+    assert_eq!(wallet.name(), "Bob");
+    assert_eq!(wallet.balance(), 30);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 70);
+
+    // The wallet cannot be created twice.
+    let tx = TxCreateAndFundWallet::new(
+        tx_alice.pub_key(),
+        &pubkey_bob,
+        "Bob",
+        30, // funded amount
+        0, // fee
+        1, // seed
+        &key_alice,
+    );
+    api.create_and_fund_wallet(&tx);
+    testkit.create_block();
+
+    assert_eq!(api.get_wallet(&pubkey_bob).balance(), 30);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 70);
+}
+
+/// Check paging through the list of wallets.
+#[test]
+fn test_wallets_pagination() {
+    let (mut testkit, api) = create_testkit();
+    for i in 0..5 {
+        api.create_wallet(&format!("User {}", i));
+    }
+    testkit.create_block();
+
+    let first = api.get_wallets("limit=2");
+    assert_eq!(first.items.len(), 2);
+    assert_eq!(first.total_estimate, 5);
+    let cursor = first.next_cursor.clone().expect("No cursor of the second page");
+
+    let second = api.get_wallets(&format!("limit=2&cursor={}", cursor));
+    assert_eq!(second.items.len(), 2);
+    assert_eq!(second.items[0].pub_key().to_string(), cursor);
+    let cursor = second.next_cursor.clone().expect("No cursor of the third page");
+
+    let third = api.get_wallets(&format!("limit=2&cursor={}", cursor));
+    assert_eq!(third.items.len(), 1);
+    assert_eq!(third.next_cursor, None);
+
+    // Pages do not overlap and follow the order of public keys.
+    let keys: Vec<PublicKey> = first
+        .items
+        .iter()
+        .chain(&second.items)
+        .chain(&third.items)
+        .map(|wallet| *wallet.pub_key())
+        .collect();
+    let mut sorted = keys.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(keys, sorted);
+}
+
+/// Check that a listing returns a partial page with a cursor if the query budget
+/// runs out.
+#[test]
+fn test_query_budget() {
+    let budget = QueryBudget {
+        max_scanned: 3,
+        timeout: Duration::from_secs(10),
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_query_budget(budget))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+    for i in 0..5 {
+        api.create_wallet(&format!("User {}", i));
+    }
+    testkit.create_block();
+
+    let first = api.get_wallets("limit=10");
+    assert_eq!(first.items.len(), 3);
+    // No budget is left for counting the wallets.
+    assert_eq!(first.total_estimate, 0);
+    let cursor = first.next_cursor.expect("No cursor after exhausted budget");
+
+    let second = api.get_wallets(&format!("limit=10&cursor={}", cursor));
+    assert_eq!(second.items.len(), 2);
+    assert_eq!(second.next_cursor, None);
+}
+
+/// Check that a session key can make transfers within its scope only.
+#[test]
+fn test_session_key() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let (session_pubkey, session_key) = crypto::gen_keypair();
+    let expires_at = testkit.height().0 + 5;
+    let tx = TxAuthorizeSessionKey::new(
+        tx_alice.pub_key(),
+        &session_pubkey,
+        10, // max amount
+        expires_at,
+        0, // seed
+        &key_alice,
+    );
+    api.authorize_session_key(&tx);
+    testkit.create_block();
+
+    let keys = api.get_session_keys(tx_alice.pub_key());
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0].session_key, session_pubkey);
+    assert_eq!(keys[0].scope.max_amount(), 10);
+
+    let session_transfer = |amount, seed| {
+        TxSessionTransfer::new(
+            tx_alice.pub_key(),
+            &session_pubkey,
+            tx_bob.pub_key(),
+            amount,
+            0, // fee
+            seed,
+            &session_key,
+        )
+    };
+
+    // Transfers within the limit succeed, transfers over it fail.
+    let tx = session_transfer(10, 0);
+    api.session_transfer(&tx);
+    let over_limit = session_transfer(11, 1);
+    api.session_transfer(&over_limit);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 90);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 110);
+
+    // The key cannot be used after revocation.
+    let revoke = TxRevokeSessionKey::new(tx_alice.pub_key(), &session_pubkey, 0, &key_alice);
+    api.revoke_session_key(&revoke);
+    testkit.create_block();
+    assert!(api.get_session_keys(tx_alice.pub_key()).is_empty());
+
+    let tx = session_transfer(5, 2);
+    api.session_transfer(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 90);
+}
+
+/// Check that the verifier can attest wallets and revoke attestations.
+#[test]
+fn test_wallet_attestation() {
+    let (verifier_pubkey, verifier_key) = crypto::gen_keypair();
+    let config = CurrencyConfig {
+        verifier: Some(verifier_pubkey),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    testkit.create_block();
+    assert!(!api.get_wallet(tx_alice.pub_key()).verified());
+
+    // Only the verifier can attest wallets.
+    let tx = TxAttestWallet::new(tx_alice.pub_key(), tx_alice.pub_key(), 0, &key_alice);
+    api.attest_wallet(&tx);
+    testkit.create_block();
+    assert!(!api.get_wallet(tx_alice.pub_key()).verified());
+
+    let tx = TxAttestWallet::new(&verifier_pubkey, tx_alice.pub_key(), 0, &verifier_key);
+    api.attest_wallet(&tx);
+    testkit.create_block();
+    let wallet = api.get_wallet(tx_alice.pub_key());
+    assert!(wallet.verified());
+    assert_eq!(wallet.balance(), 100);
+    assert_eq!(api.get_attestation(tx_alice.pub_key()).verifier(), &verifier_pubkey);
+
+    let tx = TxRevokeAttestation::new(&verifier_pubkey, tx_alice.pub_key(), 0, &verifier_key);
+    api.revoke_attestation(&tx);
+    testkit.create_block();
+    assert!(!api.get_wallet(tx_alice.pub_key()).verified());
+}
+
+/// Check that wallets linked with the consent of both keys report the aggregated balance.
+#[test]
+fn test_link_wallets() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    let (tx_carol, key_carol) = api.create_wallet("Carol");
+    testkit.create_block();
+
+    let consent = link_consent(tx_alice.pub_key(), tx_bob.pub_key());
+    let other_signature = crypto::sign(consent.as_ref(), &key_bob);
+    let tx = TxLinkWallets::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        &other_signature,
+        0, // seed
+        &key_alice,
+    );
+    api.link_wallets(&tx);
+    testkit.create_block();
+    let group_id = tx.hash();
+
+    let group = api.get_group(&group_id);
+    assert_eq!(group.wallets.len(), 2);
+    assert_eq!(group.balance, 200);
+
+    // Carol joins the group of Alice.
+    let consent = link_consent(tx_carol.pub_key(), tx_alice.pub_key());
+    let other_signature = crypto::sign(consent.as_ref(), &key_alice);
+    let tx = TxLinkWallets::new(
+        tx_carol.pub_key(),
+        tx_alice.pub_key(),
+        &other_signature,
+        0, // seed
+        &key_carol,
+    );
+    api.link_wallets(&tx);
+    testkit.create_block();
+
+    let group = api.get_group(&group_id);
+    assert_eq!(group.wallets.len(), 3);
+    assert_eq!(group.balance, 300);
+}
+
+/// Check that transfers carrying registered payment references are recorded as receipts.
+#[test]
+fn test_payment_references() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let invoice_1 = TxRegisterPaymentReference::new(tx_bob.pub_key(), 1, &key_bob);
+    let invoice_2 = TxRegisterPaymentReference::new(tx_bob.pub_key(), 2, &key_bob);
+    api.register_payment_reference(&invoice_1);
+    api.register_payment_reference(&invoice_2);
+    testkit.create_block();
+
+    let unregistered = crypto::hash(b"unregistered");
+    for (seed, reference) in vec![invoice_1.hash(), invoice_2.hash(), unregistered]
+        .into_iter()
+        .enumerate()
+    {
+        let tx = TxTransfer::new(
+            tx_alice.pub_key(),
+            tx_bob.pub_key(),
+            10, // transfer amount
+            0, // fee
+            &Hash::zero(), // no dependency
+            &reference,
+            "",
+            seed as u64,
+            &key_alice,
+        );
+        api.transfer(&tx);
+    }
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 130);
+
+    let receipts = api.get_receipts(tx_bob.pub_key(), "");
+    assert_eq!(receipts.items.len(), 2);
+
+    let query = format!("reference={}", invoice_2.hash().to_string());
+    let receipts = api.get_receipts(tx_bob.pub_key(), &query);
+    assert_eq!(receipts.items.len(), 1);
+    assert_eq!(receipts.items[0].reference(), &invoice_2.hash());
+    assert_eq!(receipts.items[0].from(), tx_alice.pub_key());
+    assert_eq!(receipts.items[0].amount(), 10);
+}
+
+/// Check that a payment URI round-trips and yields a ready-to-sign transfer.
+#[test]
+fn test_payment_intent() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let invoice = TxRegisterPaymentReference::new(tx_bob.pub_key(), 1, &key_bob);
+    api.register_payment_reference(&invoice);
+    testkit.create_block();
+
+    let mut request = PaymentRequest::new(*tx_bob.pub_key());
+    request.memo = Some("Order #1".to_owned());
+    request.reference = Some(invoice.hash());
+    request.expires_at = Some(100);
+    let uri = request.to_uri();
+    assert_eq!(uri.parse::<PaymentRequest>(), Ok(request.clone()));
+
+    let scanned = PaymentRequest::from_uri(&uri).unwrap();
+    assert_eq!(
+        scanned.to_transfer(tx_alice.pub_key(), 0, 0, 2, &key_alice).err(),
+        Some(PaymentIntentError::MissingAmount)
+    );
+    let scanned = PaymentRequest {
+        amount: Some(15),
+        ..scanned
+    };
+    assert_eq!(
+        scanned.to_transfer(tx_alice.pub_key(), 0, 0, 100, &key_alice).err(),
+        Some(PaymentIntentError::Expired)
+    );
+
+    let tx = scanned.to_transfer(tx_alice.pub_key(), 0, 0, 2, &key_alice).unwrap();
+    assert_eq!(tx.to(), tx_bob.pub_key());
+    assert_eq!(tx.amount(), 15);
+    assert_eq!(tx.reference(), &invoice.hash());
+    assert_eq!(tx.memo(), "Order #1");
+    api.transfer(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 115);
+    assert_eq!(api.get_receipts(tx_bob.pub_key(), "").items.len(), 1);
+
+    let uri = format!("excoin:{}?reference=00", tx_bob.pub_key().to_string());
+    assert_eq!(PaymentRequest::from_uri(&uri), Err(PaymentUriError::Reference));
+    let uri = format!("excoin:{}?expires=soon", tx_bob.pub_key().to_string());
+    assert_eq!(PaymentRequest::from_uri(&uri), Err(PaymentUriError::Expiry));
+
+    // Escapes take exactly two hex digits.
+    assert_eq!(percent_decode("Order%20%231"), Ok("Order #1".to_owned()));
+    assert_eq!(percent_decode("%+1"), Err(PaymentUriError::Encoding));
+    assert_eq!(percent_decode("%-1"), Err(PaymentUriError::Encoding));
+    assert_eq!(percent_decode("%4"), Err(PaymentUriError::Encoding));
+    let parsed = PaymentRequest::parse(*tx_bob.pub_key(), "amount=15&memo=%4Fk").unwrap();
+    assert_eq!(parsed.amount, Some(15));
+    assert_eq!(parsed.memo, Some("Ok".to_owned()));
+    assert_eq!(
+        PaymentRequest::parse(*tx_bob.pub_key(), "memo=%+1"),
+        Err(PaymentUriError::Encoding)
+    );
+}
+
+/// Check that the root hashes of the service tables are reported by the API and
+/// follow the tables.
+#[test]
+fn test_state_root() {
+    let (mut testkit, api) = create_testkit();
+    let empty = api.get_state_root();
+    assert_eq!(empty.wallets, CurrencySchema::new(testkit.snapshot()).state_hash()[0]);
+    assert_eq!(empty.tables, CurrencySchema::new(testkit.snapshot()).state_hash());
+
+    let (tx_alice, _) = api.create_wallet("Alice");
+    testkit.create_block();
+
+    let root = api.get_state_root();
+    assert_eq!(root.height, empty.height + 1);
+    assert_ne!(root.wallets, empty.wallets);
+    assert_eq!(root.wallets, CurrencySchema::new(testkit.snapshot()).wallets().root_hash());
+    assert_eq!(root.tables, CurrencySchema::new(testkit.snapshot()).state_hash());
+
+    // Tables other than the wallets change the state hash as well.
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block();
+    let root = api.get_state_root();
+    let tx = TxDeferredTransfer::new(
+        tx_bob.pub_key(),
+        tx_alice.pub_key(),
+        30, // transfer amount
+        root.height + 5,
+        0, // seed
+        &key_bob,
+    );
+    api.deferred_transfer(&tx);
+    testkit.create_block();
+    let deferred = api.get_state_root();
+    assert_ne!(deferred.tables[1], root.tables[1]);
+    assert_eq!(deferred.tables, CurrencySchema::new(testkit.snapshot()).state_hash());
+
+    // So do the tables kept per wallet, such as allowances.
+    api.approve(&TxApprove::new(tx_bob.pub_key(), tx_alice.pub_key(), 10, 0, &key_bob));
+    testkit.create_block();
+    let approved = api.get_state_root();
+    let schema = CurrencySchema::new(testkit.snapshot());
+    let key = index_root_key("cryptocurrency.allowances", tx_bob.pub_key());
+    let allowances = schema.allowances(tx_bob.pub_key()).root_hash();
+    assert_eq!(schema.index_roots().get(&key), Some(allowances));
+    assert!(approved.tables.contains(&schema.index_roots().root_hash()));
+    assert!(!deferred.tables.contains(&schema.index_roots().root_hash()));
+    assert_eq!(approved.tables, schema.state_hash());
+}
+
+/// Check that sandbox endpoints execute transactions without changing the state.
+#[cfg(feature = "sandbox")]
+#[test]
+fn test_sandbox() {
+    use cryptocurrency::SandboxResponse;
+
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        1000, // more than Alice has
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_alice,
+    );
+    let resp: SandboxResponse = api.inner.post(
+        ApiKind::Service("cryptocurrency"),
+        "v1/sandbox/wallets/transfer",
+        &tx,
+    );
+    assert_eq!(resp.tx_hash, tx.hash());
+    assert_eq!(resp.code, Error::InsufficientFunds.code());
+
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        10, // transfer amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        1, // seed
+        &key_alice,
+    );
+    let resp: SandboxResponse = api.inner.post(
+        ApiKind::Service("cryptocurrency"),
+        "v1/sandbox/wallets/transfer",
+        &tx,
+    );
+    assert_eq!(resp.code, 0);
+    assert_eq!(resp.error, None);
+
+    // A transfer signed by another key fails verification as on the node.
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        10, // transfer amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        2, // seed
+        &key_bob,
+    );
+    let resp: SandboxResponse = api.inner.post(
+        ApiKind::Service("cryptocurrency"),
+        "v1/sandbox/wallets/transfer",
+        &tx,
+    );
+    assert_eq!(resp.code, Error::Verification.code());
+    assert!(resp.error.is_some());
+
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 100);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 100);
+}
+
+/// Check that senders of committed payments are assessed and served by the private API.
+#[test]
+fn test_risk_flags() {
+    let model = HeuristicModel {
+        fan_out: 2,
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_risk_model(Box::new(model)))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    let (tx_carol, _) = api.create_wallet("Carol");
+    testkit.create_block();
+
+    for (seed, to) in vec![tx_bob.pub_key(), tx_carol.pub_key()].into_iter().enumerate() {
+        let tx = TxTransfer::new(
+            tx_alice.pub_key(),
+            to,
+            10, // transfer amount
+            0, // fee
+            &Hash::zero(), // no dependency
+            &Hash::zero(), // no reference
+            "", // no memo
+            seed as u64,
+            &key_alice,
+        );
+        api.transfer(&tx);
+    }
+    testkit.create_block();
+
+    let flagged: Vec<FlaggedWallet> = api.inner.get_private(
+        ApiKind::Service("cryptocurrency"),
+        "v1/risk/flags",
+    );
+    assert_eq!(flagged.len(), 1);
+    assert_eq!(&flagged[0].pub_key, tx_alice.pub_key());
+    assert_eq!(flagged[0].assessment.flags, vec![RiskFlag::FanOut]);
+
+    // Payments of other transactions are assessed as well.
+    let payments = vec![
+        Payment::new(tx_alice.pub_key(), 10),
+        Payment::new(tx_carol.pub_key(), 10),
+    ];
+    let tx = TxBatchTransfer::new(tx_bob.pub_key(), payments, 0, 0, &key_bob);
+    api.batch_transfer(&tx);
+    testkit.create_block();
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.block_payments().len(), 2);
+    assert_eq!(schema.block_payments_height().get(), Some(testkit.height().0));
+
+    let flagged: Vec<FlaggedWallet> = api.inner.get_private(
+        ApiKind::Service("cryptocurrency"),
+        "v1/risk/flags",
+    );
+    assert_eq!(flagged.len(), 2);
+    assert!(flagged.iter().any(|wallet| {
+        &wallet.pub_key == tx_bob.pub_key() && wallet.assessment.flags == vec![RiskFlag::FanOut]
+    }));
+}
+
+/// Check that transfers tripping the velocity rules are held until reviewed.
+#[test]
+fn test_velocity_rules() {
+    let (compliance_pubkey, compliance_key) = crypto::gen_keypair();
+    let config = CurrencyConfig {
+        velocity: Some(VelocityRules {
+            compliance: compliance_pubkey,
+            max_amount: Some(50),
+            max_transfers: None,
+            max_counterparties: None,
+        }),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        60, // transfer amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_alice,
+    );
+    assert_eq!(validate_transfer(testkit.snapshot(), &tx), Err(Error::TransferHeld));
+    api.transfer(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 40);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 100);
+    let held = api.get_held_transfers();
+    assert_eq!(held.len(), 1);
+    assert_eq!(held[0].tx_hash, tx.hash());
+    assert_eq!(held[0].transfer.amount(), 60);
+    assert_eq!(CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()), None);
+
+    // Only the compliance key can review held transfers.
+    let review = TxReviewHeldTransfer::new(tx_alice.pub_key(), &tx.hash(), true, &key_alice);
+    api.review_held_transfer(&review);
+    testkit.create_block();
+    assert_eq!(api.get_held_transfers().len(), 1);
+
+    let review = TxReviewHeldTransfer::new(&compliance_pubkey, &tx.hash(), true, &compliance_key);
+    api.review_held_transfer(&review);
+    testkit.create_block();
+    assert!(api.get_held_transfers().is_empty());
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 160);
+    assert_eq!(CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()), Some(Ok(())));
+
+    // A rejected transfer is refunded.
+    let tx = TxTransfer::new(
+        tx_bob.pub_key(),
+        tx_alice.pub_key(),
+        55, // transfer amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_bob,
+    );
+    api.transfer(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 105);
+
+    let review = TxReviewHeldTransfer::new(&compliance_pubkey, &tx.hash(), false, &compliance_key);
+    api.review_held_transfer(&review);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 160);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 40);
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()),
+        Some(Err(Error::TransferRejected))
+    );
+
+    // Payments of other transactions are held as well; a batch transfer holds
+    // only the payments tripping the rules.
+    let payments = vec![
+        Payment::new(tx_alice.pub_key(), 10),
+        Payment::new(tx_alice.pub_key(), 55),
+    ];
+    let tx = TxBatchTransfer::new(tx_bob.pub_key(), payments, 0, 1, &key_bob);
+    api.batch_transfer(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 95);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 50);
+    assert_eq!(CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()), Some(Ok(())));
+    let held = api.get_held_transfers();
+    assert_eq!(held.len(), 1);
+    let hold_id = batch_payment_id(&tx.hash(), 1);
+    assert_eq!(held[0].tx_hash, hold_id);
+
+    let review = TxReviewHeldTransfer::new(&compliance_pubkey, &hold_id, true, &compliance_key);
+    api.review_held_transfer(&review);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 105);
+    assert_eq!(CurrencySchema::new(testkit.snapshot()).tx_result(&hold_id), Some(Ok(())));
+
+    // A wallet is not created with its funding held.
+    let (pubkey_carol, _) = crypto::gen_keypair();
+    let tx = TxCreateAndFundWallet::new(
+        tx_alice.pub_key(),
+        &pubkey_carol,
+        "Carol",
+        60, // funded amount
+        0, // fee
+        0, // seed
+        &key_alice,
+    );
+    api.create_and_fund_wallet(&tx);
+    testkit.create_block();
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&tx.hash()), Some(Err(Error::TransferHeld)));
+    assert!(schema.wallet(&pubkey_carol).is_none());
+    assert!(api.get_held_transfers().is_empty());
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 105);
+}
+
+/// Check that transfer limits and fee rates depend on the tier of the sender.
+#[test]
+fn test_account_tiers() {
+    let (admin_pubkey, admin_key) = crypto::gen_keypair();
+    let config = CurrencyConfig {
+        tiers: Some(TierConfig {
+            admin: admin_pubkey,
+            basic: TierRules {
+                max_amount: Some(50),
+                fee_bps: 0,
+            },
+            verified: TierRules {
+                max_amount: None,
+                fee_bps: 500,
+            },
+            institutional: TierRules::default(),
+        }),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).tier(), TIER_BASIC);
+
+    let transfer = |fee, seed| {
+        TxTransfer::new(
+            tx_alice.pub_key(),
+            tx_bob.pub_key(),
+            60, // transfer amount
+            fee,
+            &Hash::zero(), // no dependency
+            &Hash::zero(), // no reference
+            "", // no memo
+            seed,
+            &key_alice,
+        )
+    };
+    let tx = transfer(0, 0);
+    api.transfer(&tx);
+    testkit.create_block();
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()),
+        Some(Err(Error::TierLimitExceeded))
+    );
+
+    // Funding a new wallet is subject to the same rules.
+    let (pubkey_carol, _) = crypto::gen_keypair();
+    let fund = |fee, seed| {
+        TxCreateAndFundWallet::new(
+            tx_alice.pub_key(),
+            &pubkey_carol,
+            "Carol",
+            60, // funded amount
+            fee,
+            seed,
+            &key_alice,
+        )
+    };
+    let tx = fund(0, 0);
+    api.create_and_fund_wallet(&tx);
+    testkit.create_block();
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()),
+        Some(Err(Error::TierLimitExceeded))
+    );
+
+    let tx = TxAssignTier::new(&admin_pubkey, tx_alice.pub_key(), TIER_VERIFIED, 0, &admin_key);
+    api.assign_tier(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).tier(), TIER_VERIFIED);
+
+    // The verified tier has no limit, but charges 5% of the amount.
+    let tx = transfer(2, 1);
+    api.transfer(&tx);
+    testkit.create_block();
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()),
+        Some(Err(Error::FeeTooLow))
+    );
+
+    let tx = fund(2, 1);
+    api.create_and_fund_wallet(&tx);
+    testkit.create_block();
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()),
+        Some(Err(Error::FeeTooLow))
+    );
+
+    let tx = transfer(3, 2);
+    api.transfer(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 37);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 160);
+}
+
+/// Check that bids are escrowed and the highest bid is paid to the seller at the deadline.
+#[test]
+fn test_auction() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    let (tx_carol, key_carol) = api.create_wallet("Carol");
+    testkit.create_block();
+
+    let deadline = testkit.height().0 + 6;
+    let item = crypto::hash(b"premium name");
+    let auction = TxCreateAuction::new(tx_alice.pub_key(), &item, 20, deadline, 0, &key_alice);
+    api.create_auction(&auction);
+    testkit.create_block();
+
+    // A bid below the reserve price is rejected.
+    let tx = TxBid::new(tx_bob.pub_key(), &auction.hash(), 10, 0, &key_bob);
+    api.bid(&tx);
+    testkit.create_block();
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()),
+        Some(Err(Error::BidTooLow))
+    );
+
+    api.bid(&TxBid::new(tx_bob.pub_key(), &auction.hash(), 25, 1, &key_bob));
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 75);
+
+    // Outbidding returns the previous bid.
+    api.bid(&TxBid::new(tx_carol.pub_key(), &auction.hash(), 30, 0, &key_carol));
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 100);
+    assert_eq!(api.get_wallet(tx_carol.pub_key()).balance(), 70);
+
+    while testkit.height().0 < deadline + 1 {
+        testkit.create_block();
+    }
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 130);
+    let auctions = api.get_auctions();
+    assert_eq!(auctions.len(), 1);
+    assert_eq!(auctions[0].auction.highest_bidder(), tx_carol.pub_key());
+    assert_eq!(auctions[0].auction.settled_at(), deadline);
+}
+
+/// Check that a payment stream accrues coins per block and can be withdrawn and closed.
+#[test]
+fn test_payment_stream() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let open = TxOpenStream::new(tx_alice.pub_key(), tx_bob.pub_key(), 10, 45, 0, &key_alice);
+    api.open_stream(&open);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 55);
+    let start = api.get_stream(&open.hash()).stream.start();
+
+    // The receiver withdraws in the third block after the stream is opened.
+    while testkit.height().0 < start + 2 {
+        testkit.create_block();
+    }
+    api.withdraw_stream(&TxWithdrawStream::new(tx_bob.pub_key(), &open.hash(), 0, &key_bob));
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 130);
+    let info = api.get_stream(&open.hash());
+    assert_eq!(info.stream.withdrawn(), 30);
+    assert_eq!(info.available, 10);
+
+    // Closing pays out the accrued coins and refunds the rest of the budget.
+    let close = TxCloseStream::new(tx_alice.pub_key(), &open.hash(), &key_alice);
+    api.close_stream(&close);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 140);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 60);
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert!(schema.streams().get(&open.hash()).is_none());
+}
+
+/// Check that a lottery draw pays the prize to the entrant picked by the randomness beacon.
+#[test]
+fn test_lottery_draw() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    let (tx_carol, key_carol) = api.create_wallet("Carol");
+    testkit.create_block();
+
+    let draw_height = testkit.height().0 + 4;
+    let draw = TxLotteryDraw::new(tx_alice.pub_key(), 30, draw_height, 0, &key_alice);
+    api.lottery_draw(&draw);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 70);
+
+    api.enter_draw(&TxEnterDraw::new(tx_bob.pub_key(), &draw.hash(), &key_bob));
+    api.enter_draw(&TxEnterDraw::new(tx_carol.pub_key(), &draw.hash(), &key_carol));
+    testkit.create_block();
+
+    // A wallet cannot enter twice.
+    let tx = TxEnterDraw::new(tx_bob.pub_key(), &draw.hash(), &key_bob);
+    api.enter_draw(&tx);
+    testkit.create_block();
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()),
+        Some(Err(Error::AlreadyEntered))
+    );
+
+    while testkit.height().0 < draw_height + 1 {
+        testkit.create_block();
+    }
+    let info = api.get_draw(&draw.hash());
+    assert_eq!(info.entrants, vec![*tx_bob.pub_key(), *tx_carol.pub_key()]);
+    assert_eq!(info.draw.drawn_at(), draw_height);
+
+    let beacon = api.get_beacon(draw_height - 1);
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(Some(beacon.value), schema.beacon(draw_height - 1));
+    let random = beacon.value.as_ref()[..8].iter().rev().fold(0u64, |acc, &byte| {
+        acc << 8 | u64::from(byte)
+    });
+    let winner = info.entrants[(random % 2) as usize];
+    assert_eq!(info.draw.winner(), &winner);
+    assert_eq!(api.get_wallet(&winner).balance(), 130);
+}
+
+/// Check that a fee coupon waives the tier fee a limited number of times.
+#[test]
+fn test_fee_coupon() {
+    let (admin_pubkey, _) = crypto::gen_keypair();
+    let (issuer_pubkey, issuer_key) = crypto::gen_keypair();
+    let config = CurrencyConfig {
+        tiers: Some(TierConfig {
+            admin: admin_pubkey,
+            basic: TierRules {
+                max_amount: None,
+                fee_bps: 1000,
+            },
+            verified: TierRules::default(),
+            institutional: TierRules::default(),
+        }),
+        coupon_issuer: Some(issuer_pubkey),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    // Only the issuer can issue coupons.
+    let code = crypto::hash(b"WELCOME");
+    let tx = TxIssueFeeCoupon::new(tx_alice.pub_key(), &code, 10_000, 1, 1000, 0, &key_alice);
+    api.issue_fee_coupon(&tx);
+    testkit.create_block();
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()),
+        Some(Err(Error::Unauthorized))
+    );
+
+    let tx = TxIssueFeeCoupon::new(&issuer_pubkey, &code, 10_000, 1, 1000, 0, &issuer_key);
+    api.issue_fee_coupon(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_fee_coupon(&code).uses(), 0);
+
+    let transfer = |seed| {
+        TxCouponTransfer::new(
+            tx_alice.pub_key(),
+            tx_bob.pub_key(),
+            30, // transfer amount
+            0, // fee
+            &code,
+            seed,
+            &key_alice,
+        )
+    };
+    api.coupon_transfer(&transfer(0));
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 70);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 130);
+    assert_eq!(api.get_fee_coupon(&code).uses(), 1);
+
+    // The coupon is used up.
+    let tx = transfer(1);
+    api.coupon_transfer(&tx);
+    testkit.create_block();
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()),
+        Some(Err(Error::CouponExhausted))
+    );
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 70);
+}
+
+/// Check that a deposit can be partially claimed by the payee, and is otherwise
+/// returned to the payer at the specified height.
+#[test]
+fn test_refundable_deposit() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let reference = crypto::hash(b"rental #1");
+    let return_at = testkit.height().0 + 4;
+    let claimed = TxDeposit::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        30,
+        &reference,
+        return_at,
+        0,
+        &key_alice,
+    );
+    let returned = TxDeposit::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        20,
+        &reference,
+        return_at,
+        1,
+        &key_alice,
+    );
+    api.deposit(&claimed);
+    api.deposit(&returned);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 50);
+    assert_eq!(api.get_deposit(&claimed.hash()).amount(), 30);
+
+    // Only the payee can claim the deposit.
+    let tx = TxClaimDeposit::new(tx_alice.pub_key(), &claimed.hash(), 30, &key_alice);
+    api.claim_deposit(&tx);
+    testkit.create_block();
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()),
+        Some(Err(Error::Unauthorized))
+    );
+
+    api.claim_deposit(&TxClaimDeposit::new(tx_bob.pub_key(), &claimed.hash(), 10, &key_bob));
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 110);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 70);
+
+    while testkit.height().0 < return_at + 1 {
+        testkit.create_block();
+    }
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 90);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 110);
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert!(schema.deposits().get(&returned.hash()).is_none());
+}
+
+/// Check that the name limit is published and enforced.
+#[test]
+fn test_transaction_limits() {
+    let config = CurrencyConfig {
+        limits: TransactionLimits {
+            max_name_length: 5,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+    assert_eq!(api.get_info().limits.max_name_length, 5);
+
+    let (tx_alice, _) = api.create_wallet("Alice");
+    let (tx_long, _) = api.create_wallet("Alexander");
+    testkit.create_block();
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&tx_alice.hash()), Some(Ok(())));
+    assert_eq!(schema.tx_result(&tx_long.hash()), Some(Err(Error::NameTooLong)));
+    assert!(schema.wallet(tx_long.pub_key()).is_none());
+}
+
+/// Check that wallet names are limited in characters, must be normalized
+/// and may not mix look-alike scripts.
+#[test]
+fn test_name_normalization() {
+    let config = CurrencyConfig {
+        limits: TransactionLimits {
+            max_name_length: 5,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+
+    let normalized = api.get_normalized_name("Cafe\u{301}");
+    assert_eq!(normalized.normalized, "Caf\u{e9}");
+    assert_eq!(normalized.length, 4);
+    assert_eq!(normalized.error, None);
+    let normalized = api.get_normalized_name("\u{410}lice");
+    assert_eq!(normalized.error, Some(TextError::MixedScripts));
+
+    // Five Cyrillic letters take ten bytes, but fit into the limit.
+    let (tx_cyrillic, _) = api.create_wallet("\u{410}\u{43b}\u{438}\u{441}\u{430}");
+    let (tx_decomposed, _) = api.create_wallet("Cafe\u{301}");
+    let (tx_mixed, _) = api.create_wallet("\u{410}lice");
+    let (tx_control, _) = api.create_wallet("Bob\u{202e}");
+    testkit.create_block();
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&tx_cyrillic.hash()), Some(Ok(())));
+    for tx in &[tx_decomposed, tx_mixed, tx_control] {
+        assert_eq!(schema.tx_result(&tx.hash()), Some(Err(Error::InvalidName)));
+    }
+
+    // Character classes come from the tables of the service, not of the compiler.
+    assert_eq!(text::to_lowercase('A'), 'a');
+    assert_eq!(text::to_lowercase('\u{100}'), '\u{101}');
+    assert_eq!(text::to_lowercase('\u{101}'), '\u{101}');
+    assert_eq!(text::to_lowercase('\u{130}'), 'i');
+    assert_eq!(text::to_lowercase('\u{3a3}'), '\u{3c3}');
+    assert_eq!(text::to_lowercase('\u{1e921}'), '\u{1e943}');
+    assert!(text::is_whitespace('\u{3000}') && !text::is_whitespace('\u{200b}'));
+    assert!(text::is_control('\u{85}') && !text::is_control('a'));
+    assert_eq!(text::skeleton("\u{41c}\u{430}r\u{3a5} \u{130}"), "marui");
+}
+
+/// Check that exact, confusable and similar names are reported before wallet creation.
+#[test]
+fn test_name_check() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, _) = api.create_wallet("Alice");
+    let (tx_alise, _) = api.create_wallet("Alise");
+    api.create_wallet("Bob");
+    let (tx_coe, _) = api.create_wallet("\u{421}\u{43e}\u{435}");
+    testkit.create_block();
+
+    let check = api.get_name_check("Alice");
+    assert_eq!(check.name, "Alice");
+    let matches: Vec<_> = check
+        .matches
+        .iter()
+        .map(|m| (m.pub_key, m.kind, m.distance))
+        .collect();
+    assert_eq!(
+        matches,
+        vec![
+            (*tx_alice.pub_key(), NameMatchKind::Exact, 0),
+            (*tx_alise.pub_key(), NameMatchKind::Similar, 1),
+        ]
+    );
+
+    let check = api.get_name_check("Coe");
+    assert_eq!(check.matches.len(), 1);
+    assert_eq!(&check.matches[0].pub_key, tx_coe.pub_key());
+    assert_eq!(check.matches[0].kind, NameMatchKind::Confusable);
+
+    assert!(api.get_name_check("Zed").matches.is_empty());
+}
+
+/// Check that transactions of a type are rejected until its activation height.
+#[test]
+fn test_activation_heights() {
+    let mut config = CurrencyConfig::default();
+    config.activation_heights.insert(TX_CREATE_AUCTION_ID, 4);
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    testkit.create_block();
+
+    let item = crypto::hash(b"premium name");
+    let early = TxCreateAuction::new(tx_alice.pub_key(), &item, 20, 100, 0, &key_alice);
+    api.create_auction(&early);
+    testkit.create_block();
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&early.hash()),
+        Some(Err(Error::NotActivated))
+    );
+
+    while testkit.height().0 < 3 {
+        testkit.create_block();
+    }
+    let tx = TxCreateAuction::new(tx_alice.pub_key(), &item, 20, 100, 1, &key_alice);
+    api.create_auction(&tx);
+    testkit.create_block();
+    assert_eq!(CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()), Some(Ok(())));
+}
+
+/// Check that wallets record the hashes of the transactions affecting them.
+#[test]
+fn test_wallet_history() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let tx_transfer = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        10, // transfer amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_alice,
+    );
+    api.transfer(&tx_transfer);
+    testkit.create_block();
+
+    let history = api.get_wallet_history(tx_alice.pub_key(), "");
+    assert_eq!(history.items, vec![tx_alice.hash(), tx_transfer.hash()]);
+    let history = api.get_wallet_history(tx_bob.pub_key(), "limit=1");
+    assert_eq!(history.items, vec![tx_bob.hash()]);
+    let cursor = history.next_cursor.unwrap();
+    let history = api.get_wallet_history(tx_bob.pub_key(), &format!("cursor={}", cursor));
+    assert_eq!(history.items, vec![tx_transfer.hash()]);
+    assert_eq!(history.next_cursor, None);
+
+    let wallet = api.get_wallet(tx_alice.pub_key());
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(wallet.history_len(), 2);
+    assert_eq!(wallet.history_hash(), &schema.wallet_history(tx_alice.pub_key()).root_hash());
+}
+
+/// Check that the memo of a transfer is kept in the history of both wallets
+/// and that memos exceeding the limit are rejected.
+#[test]
+fn test_transfer_memo() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let transfer = |memo: &str, seed| {
+        let tx = TxTransfer::new(
+            tx_alice.pub_key(),
+            tx_bob.pub_key(),
+            10, // transfer amount
+            0, // fee
+            &Hash::zero(), // no dependency
+            &Hash::zero(), // no reference
+            memo,
+            seed,
+            &key_alice,
+        );
+        api.transfer(&tx);
+        tx
+    };
+    let tx = transfer("Invoice 42", 1);
+    let long_memo: String = ::std::iter::repeat('x').take(141).collect();
+    let too_long = transfer(&long_memo, 2);
+    testkit.create_block();
+
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&tx.hash()), Some(Ok(())));
+    assert_eq!(schema.tx_result(&too_long.hash()), Some(Err(Error::InvalidMemo)));
+    assert_eq!(schema.committed_transfer(&tx.hash()).unwrap().memo(), "Invoice 42");
+    for pub_key in &[tx_alice.pub_key(), tx_bob.pub_key()] {
+        let activity = api.get_wallet_activity(pub_key, "");
+        let entry = activity.items.iter().find(|entry| entry.tx_hash == tx.hash()).unwrap();
+        assert!(entry.content.to_string().contains("\"memo\":\"Invoice 42\""));
+    }
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 110);
+}
+
+/// Check that the activity feed lists transactions of different subsystems in order.
+#[test]
+fn test_wallet_activity() {
+    let (verifier_pubkey, verifier_key) = crypto::gen_keypair();
+    let config = CurrencyConfig {
+        verifier: Some(verifier_pubkey),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let transfer = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        10,
+        0,
+        &Hash::zero(),
+        &Hash::zero(),
+        "",
+        0,
+        &key_alice,
+    );
+    api.transfer(&transfer);
+    testkit.create_block();
+    let attest = TxAttestWallet::new(&verifier_pubkey, tx_alice.pub_key(), 0, &verifier_key);
+    api.attest_wallet(&attest);
+    testkit.create_block();
+    let deposit = TxDeposit::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        20,
+        &Hash::zero(),
+        testkit.height().0 + 100,
+        0,
+        &key_alice,
+    );
+    api.deposit(&deposit);
+    testkit.create_block();
+
+    let activity = api.get_wallet_activity(tx_alice.pub_key(), "limit=3");
+    let entries: Vec<_> = activity
+        .items
+        .iter()
+        .map(|entry| (entry.tx_hash, entry.kind, entry.code))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            (tx_alice.hash(), ActivityKind::Wallet, 0),
+            (transfer.hash(), ActivityKind::Transfer, 0),
+            (attest.hash(), ActivityKind::Admin, 0),
+        ]
+    );
+    assert!(activity.items.iter().all(|entry| !entry.content.is_null()));
+    assert_eq!(activity.total_estimate, 4);
+
+    let cursor = activity.next_cursor.unwrap();
+    let activity = api.get_wallet_activity(tx_alice.pub_key(), &format!("cursor={}", cursor));
+    assert_eq!(activity.items.len(), 1);
+    assert_eq!(activity.items[0].tx_hash, deposit.hash());
+    assert_eq!(activity.items[0].kind, ActivityKind::Escrow);
+    assert_eq!(activity.next_cursor, None);
+}
+
+/// Check that the status of a transaction follows it from submission to commit.
+#[test]
+fn test_transaction_status() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    assert_eq!(api.get_transaction_status(&tx_alice.hash()), TransactionStatus::Pending);
+    testkit.create_block();
+    assert_eq!(
+        api.get_transaction_status(&tx_alice.hash()),
+        TransactionStatus::Committed {
+            height: testkit.height().0,
+            code: Some(0),
+            error: None,
+        }
+    );
+
+    // A transfer to a missing wallet is committed with an error.
+    let (missing, _) = crypto::gen_keypair();
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        &missing,
+        10, // transfer amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_alice,
+    );
+    assert_eq!(api.get_transaction_status(&tx.hash()), TransactionStatus::Unknown);
+    api.transfer(&tx);
+    testkit.create_block();
+    assert_eq!(
+        api.get_transaction_status(&tx.hash()),
+        TransactionStatus::Committed {
+            height: testkit.height().0,
+            code: Some(Error::ReceiverNotFound.code()),
+            error: Some(Error::ReceiverNotFound.to_string()),
+        }
+    );
+}
+
+/// Check that wallet proofs verify against the state hash of the latest block.
+#[test]
+fn test_wallet_proof() {
+    use exonum::blockchain::Schema as CoreSchema;
+
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, _) = api.create_wallet("Alice");
+    testkit.create_block();
+
+    let snapshot = testkit.snapshot();
+    let state_hash = *CoreSchema::new(&snapshot).last_block().unwrap().state_hash();
+    let schema = CurrencySchema::new(&snapshot);
+
+    let proof = schema.wallet_proof(tx_alice.pub_key());
+    let wallet = proof.verify(&state_hash, tx_alice.pub_key()).unwrap().unwrap();
+    assert_eq!(wallet.balance(), 100);
+
+    let (missing, _) = crypto::gen_keypair();
+    let proof = schema.wallet_proof(&missing);
+    assert_eq!(proof.verify(&state_hash, &missing), Ok(None));
+
+    // The proof does not match the state hash of another block.
+    let proof = schema.wallet_proof(tx_alice.pub_key());
+    assert_eq!(
+        proof.verify(&Hash::zero(), tx_alice.pub_key()),
+        Err(WalletProofError::InvalidTableProof)
+    );
+}
+
+/// Check that the past states of a wallet can be queried by height.
+#[test]
+fn test_historical_balance() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+    let created_at = testkit.height().0;
+
+    let transfer = |amount, seed| {
+        TxTransfer::new(
+            tx_alice.pub_key(),
+            tx_bob.pub_key(),
+            amount,
+            0, // fee
+            &Hash::zero(), // no dependency
+            &Hash::zero(), // no reference
+            "", // no memo
+            seed,
+            &key_alice,
+        )
+    };
+    api.transfer(&transfer(10, 0));
+    testkit.create_block();
+    testkit.create_block();
+    api.transfer(&transfer(20, 1));
+    testkit.create_block();
+
+    assert_eq!(api.get_wallet_at(tx_alice.pub_key(), created_at).balance(), 100);
+    assert_eq!(api.get_wallet_at(tx_alice.pub_key(), created_at + 1).balance(), 90);
+    assert_eq!(api.get_wallet_at(tx_alice.pub_key(), created_at + 2).balance(), 90);
+    assert_eq!(api.get_wallet_at(tx_alice.pub_key(), created_at + 3).balance(), 70);
+    assert_eq!(api.get_wallet_at(tx_bob.pub_key(), created_at + 1).balance(), 110);
+
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert!(schema.wallet_at(tx_alice.pub_key(), created_at - 1).is_none());
+}
+
+/// Check that a statement lists the balances and transactions of the window
+/// and is signed by the node.
+#[test]
+fn test_signed_statement() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let transfer = |amount, seed| {
+        TxTransfer::new(
+            tx_alice.pub_key(),
+            tx_bob.pub_key(),
+            amount,
+            0, // fee
+            &Hash::zero(), // no dependency
+            &Hash::zero(), // no reference
+            "", // no memo
+            seed,
+            &key_alice,
+        )
+    };
+    let first = transfer(10, 0);
+    api.transfer(&first);
+    testkit.create_block();
+    let from_height = testkit.height().0;
+    let second = transfer(20, 1);
+    api.transfer(&second);
+    testkit.create_block();
+    let to_height = testkit.height().0;
+    api.transfer(&transfer(30, 2));
+    testkit.create_block();
+
+    let mut signed = api.get_statement(tx_alice.pub_key(), from_height, to_height);
+    assert!(signed.verify());
+    assert_eq!(signed.statement.opening_balance, 100);
+    assert_eq!(signed.statement.closing_balance, 70);
+    assert_eq!(
+        signed.statement.transactions,
+        vec![
+            StatementEntry {
+                tx_hash: first.hash(),
+                height: from_height,
+            },
+            StatementEntry {
+                tx_hash: second.hash(),
+                height: to_height,
+            },
+        ]
+    );
+
+    // Tampering with the statement invalidates the signature.
+    signed.statement.closing_balance = 1000;
+    assert!(!signed.verify());
+}
+
+/// Check that wallets created with an invitation start with the sponsored balance
+/// and that the invitation is limited to the given number of wallets.
+#[test]
+fn test_sponsored_invitation() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_sponsor, key_sponsor) = api.create_wallet("Sponsor");
+    testkit.create_block();
+
+    let code = crypto::hash(b"welcome");
+    let expires_at = testkit.height().0 + 10;
+    let tx =
+        TxCreateInvitation::new(tx_sponsor.pub_key(), &code, 30, 2, expires_at, 0, &key_sponsor);
+    api.create_invitation(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_sponsor.pub_key()).balance(), 40);
+
+    let invited = |name: &str| {
+        let (pubkey, key) = crypto::gen_keypair();
+        let tx = TxCreateWallet::new(&pubkey, name, &code, "", "", &Hash::zero(), &key);
+        api.create_wallet_tx(&tx);
+        tx
+    };
+    let tx_alice = invited("Alice");
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 30);
+    assert_eq!(api.get_invitation(&code).uses(), 1);
+
+    let tx_bob = invited("Bob");
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 30);
+    let tx_carol = invited("Carol");
+    testkit.create_block();
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&tx_carol.hash()), Some(Err(Error::InvitationNotFound)));
+    assert!(schema.wallet(tx_carol.pub_key()).is_none());
+}
+
+/// Check that a batch transfer makes all of its payments or none.
+#[test]
+fn test_batch_transfer() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    let (tx_carol, _) = api.create_wallet("Carol");
+    testkit.create_block();
+
+    let payments = vec![
+        Payment::new(tx_bob.pub_key(), 30),
+        Payment::new(tx_carol.pub_key(), 20),
+    ];
+    let tx = TxBatchTransfer::new(tx_alice.pub_key(), payments, 1, 0, &key_alice);
+    api.batch_transfer(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 49);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 130);
+    assert_eq!(api.get_wallet(tx_carol.pub_key()).balance(), 120);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).history_len(), 2);
+
+    // The sender cannot pay for the second payment, so neither is made.
+    let payments = vec![
+        Payment::new(tx_bob.pub_key(), 30),
+        Payment::new(tx_carol.pub_key(), 30),
+    ];
+    let tx = TxBatchTransfer::new(tx_alice.pub_key(), payments, 0, 1, &key_alice);
+    api.batch_transfer(&tx);
+    testkit.create_block();
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()),
+        Some(Err(Error::InsufficientFunds))
+    );
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 49);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 130);
+}
+
+/// Check that transfer fees are collected and paid to the validator.
+#[test]
+fn test_validator_rewards() {
+    let config = CurrencyConfig {
+        validator_rewards: true,
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+
+    let (validator, validator_key) = {
+        let (pubkey, key) = testkit.network().validators()[0].service_keypair();
+        (*pubkey, key.clone())
+    };
+    api.create_wallet_with_keypair("Validator", validator, validator_key);
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let tx = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        10,
+        3, // fee
+        &Hash::zero(),
+        &Hash::zero(),
+        "",
+        0,
+        &key_alice,
+    );
+    api.transfer(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 87);
+    assert_eq!(CurrencySchema::new(testkit.snapshot()).fee_pool().get(), Some(3));
+
+    // The pool is distributed in the next block.
+    testkit.create_block();
+    assert_eq!(api.get_wallet(&validator).balance(), 103);
+    assert_eq!(CurrencySchema::new(testkit.snapshot()).fee_pool().get(), Some(0));
+}
+
+/// Check that grants from the treasury are capped per epoch.
+#[test]
+fn test_epoch_caps() {
+    let (issuer_pubkey, issuer_key) = crypto::gen_keypair();
+    let (treasury_pubkey, treasury_key) = crypto::gen_keypair();
+    let config = CurrencyConfig {
+        issuer: Some(issuer_pubkey),
+        treasury: Some(treasury_pubkey),
+        caps: Some(EpochCaps {
+            epoch_length: 5,
+            max_treasury_outflow: 40,
+            max_issuance: None,
+        }),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+
+    api.create_wallet_with_keypair("Treasury", treasury_pubkey, treasury_key);
+    let (tx_alice, _) = api.create_wallet("Alice");
+    testkit.create_block();
+
+    let expires_at = testkit.height().0 + 100;
+    let first = TxGrantPromotion::new(
+        &issuer_pubkey,
+        tx_alice.pub_key(),
+        30,
+        expires_at,
+        0,
+        &issuer_key,
+    );
+    let second = TxGrantPromotion::new(
+        &issuer_pubkey,
+        tx_alice.pub_key(),
+        20,
+        expires_at,
+        1,
+        &issuer_key,
+    );
+    // Grants in one block are executed in the order of their hashes.
+    api.grant_promotion(&first);
+    testkit.create_block();
+    api.grant_promotion(&second);
+    testkit.create_block();
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&first.hash()), Some(Ok(())));
+    assert_eq!(schema.tx_result(&second.hash()), Some(Err(Error::EpochCapExceeded)));
+    assert_eq!(api.get_wallet(&treasury_pubkey).balance(), 70);
+
+    // The cap applies anew in the next epoch.
+    while testkit.height().0 % 5 != 0 {
+        testkit.create_block();
+    }
+    let third = TxGrantPromotion::new(
+        &issuer_pubkey,
+        tx_alice.pub_key(),
+        20,
+        expires_at,
+        2,
+        &issuer_key,
+    );
+    api.grant_promotion(&third);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(&treasury_pubkey).balance(), 50);
+}
+
+/// Check that new coins are issued only by the issuer and within the cap.
+#[test]
+fn test_issue() {
+    let (issuer_pubkey, issuer_key) = crypto::gen_keypair();
+    let config = CurrencyConfig {
+        issuer: Some(issuer_pubkey),
+        caps: Some(EpochCaps {
+            epoch_length: 1000,
+            max_treasury_outflow: 0,
+            max_issuance: Some(100),
+        }),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    testkit.create_block();
+
+    let issue = TxIssue::new(&issuer_pubkey, tx_alice.pub_key(), 60, 0, &issuer_key);
+    let forged = TxIssue::new(tx_alice.pub_key(), tx_alice.pub_key(), 60, 1, &key_alice);
+    api.issue(&issue);
+    api.issue(&forged);
+    testkit.create_block();
+    let over_cap = TxIssue::new(&issuer_pubkey, tx_alice.pub_key(), 50, 2, &issuer_key);
+    api.issue(&over_cap);
+    testkit.create_block();
+
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&issue.hash()), Some(Ok(())));
+    assert_eq!(schema.tx_result(&forged.hash()), Some(Err(Error::Unauthorized)));
+    assert_eq!(schema.tx_result(&over_cap.hash()), Some(Err(Error::EpochCapExceeded)));
+    assert_eq!(schema.issuance(), 60);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 160);
+}
+
+/// Check that the total of minted coins bounds the issuance across epochs, so that
+/// moving all coins into one wallet does not overflow its balance.
+#[test]
+fn test_minted_supply() {
+    let (issuer_pubkey, issuer_key) = crypto::gen_keypair();
+    let config = CurrencyConfig {
+        issuer: Some(issuer_pubkey),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+    let (tx_alice, _) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block();
+    assert_eq!(api.get_supply().minted, 200);
+
+    let max = u64::max_value();
+    let to_alice = TxIssue::new(&issuer_pubkey, tx_alice.pub_key(), max - 200, 0, &issuer_key);
+    let to_bob = TxIssue::new(&issuer_pubkey, tx_bob.pub_key(), max - 200, 1, &issuer_key);
+    api.issue(&to_alice);
+    testkit.create_block();
+    api.issue(&to_bob);
+    testkit.create_block();
+    let (tx_carol, _) = api.create_wallet("Carol");
+    testkit.create_block();
+
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&to_alice.hash()), Some(Ok(())));
+    assert_eq!(schema.tx_result(&to_bob.hash()), Some(Err(Error::BalanceOverflow)));
+    assert_eq!(schema.tx_result(&tx_carol.hash()), Some(Err(Error::BalanceOverflow)));
+    assert_eq!(api.get_supply().minted, max);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), max - 100);
+
+    let tx = TxTransfer::new(
+        tx_bob.pub_key(),
+        tx_alice.pub_key(),
+        100, // transfer amount
+        0, // fee
+        &Hash::zero(), // no dependency
+        &Hash::zero(), // no reference
+        "", // no memo
+        0, // seed
+        &key_bob,
+    );
+    api.transfer(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), max);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 0);
+}
+
+/// Check that wallet metadata is set on creation and replaced by its owner.
+#[test]
+fn test_wallet_metadata() {
+    let (mut testkit, api) = create_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    let avatar = crypto::hash(b"avatar");
+    let tx = TxCreateWallet::new(
+        &pubkey,
+        "Alice",
+        &Hash::zero(),
+        "Alice A.",
+        "mailto:alice@example.com",
+        &avatar,
+        &key,
+    );
+    api.create_wallet_tx(&tx);
+    testkit.create_block();
+    let wallet = api.get_wallet(&pubkey);
+    assert_eq!(wallet.display_name(), "Alice A.");
+    assert_eq!(wallet.contact_uri(), "mailto:alice@example.com");
+    assert_eq!(wallet.avatar(), &avatar);
+
+    let update = TxUpdateWallet::new(&pubkey, "Alice", "", &Hash::zero(), 0, &key);
+    let invalid = TxUpdateWallet::new(&pubkey, "Cafe\u{301}", "", &Hash::zero(), 1, &key);
+    api.update_wallet(&update);
+    testkit.create_block();
+    api.update_wallet(&invalid);
+    testkit.create_block();
+
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&update.hash()), Some(Ok(())));
+    assert_eq!(schema.tx_result(&invalid.hash()), Some(Err(Error::InvalidMetadata)));
+    let wallet = api.get_wallet(&pubkey);
+    assert_eq!(wallet.name(), "Alice");
+    assert_eq!(wallet.display_name(), "Alice");
+    assert_eq!(wallet.contact_uri(), "");
+    assert_eq!(wallet.avatar(), &Hash::zero());
+    assert_eq!(wallet.history_len(), 2);
+}
+
+/// Check that a wallet can be renamed by its owner and is found by the new name only.
+#[test]
+fn test_rename_wallet() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let rename = TxRenameWallet::new(tx_alice.pub_key(), "Alicia", 0, &key_alice);
+    let forged = TxRenameWallet::new(tx_alice.pub_key(), "Mallory", 0, &key_bob);
+    let invalid = TxRenameWallet::new(tx_bob.pub_key(), "Bob\u{200b}", 0, &key_bob);
+    api.rename_wallet(&rename);
+    api.rename_wallet(&invalid);
+    testkit.create_block();
+    assert!(!forged.verify());
+
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&rename.hash()), Some(Ok(())));
+    assert_eq!(schema.tx_result(&invalid.hash()), Some(Err(Error::InvalidName)));
+    let wallet = api.get_wallet(tx_alice.pub_key());
+    assert_eq!(wallet.name(), "Alicia");
+    assert_eq!(wallet.history_len(), 2);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).name(), "Bob");
+
+    let check = api.get_name_check("Alicia");
+    assert_eq!(check.matches.len(), 1);
+    assert_eq!(check.matches[0].kind, NameMatchKind::Exact);
+    let check = api.get_name_check("Alice");
+    assert!(check.matches.iter().all(|m| m.name != "Alice"));
+}
+
+/// Check that closing a wallet sweeps its balance and blocks transfers to and from it.
+#[test]
+fn test_close_wallet() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let close = TxCloseWallet::new(tx_alice.pub_key(), tx_bob.pub_key(), 0, &key_alice);
+    let to_self = TxCloseWallet::new(tx_bob.pub_key(), tx_bob.pub_key(), 0, &key_bob);
+    assert!(!to_self.verify());
+    api.close_wallet(&close);
+    testkit.create_block();
+
+    let alice = api.get_wallet(tx_alice.pub_key());
+    assert!(alice.closed());
+    assert_eq!(alice.balance(), 0);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 200);
+
+    let transfer = |from: &PublicKey, to: &PublicKey, key: &SecretKey| {
+        TxTransfer::new(from, to, 10, 0, &Hash::zero(), &Hash::zero(), "", 0, key)
+    };
+    let incoming = transfer(tx_bob.pub_key(), tx_alice.pub_key(), &key_bob);
+    let outgoing = transfer(tx_alice.pub_key(), tx_bob.pub_key(), &key_alice);
+    let reopen = TxCloseWallet::new(tx_alice.pub_key(), tx_bob.pub_key(), 1, &key_alice);
+    api.transfer(&incoming);
+    api.transfer(&outgoing);
+    api.close_wallet(&reopen);
+    testkit.create_block();
+
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&close.hash()), Some(Ok(())));
+    for tx_hash in &[incoming.hash(), outgoing.hash(), reopen.hash()] {
+        assert_eq!(schema.tx_result(tx_hash), Some(Err(Error::WalletClosed)));
+    }
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 200);
+}
+
+/// Check that coins paid out to a closed wallet return to the sender and coins
+/// refunded to a closed wallet go to its beneficiary.
+#[test]
+fn test_closed_wallet_payouts() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    let (tx_carol, key_carol) = api.create_wallet("Carol");
+    testkit.create_block();
+
+    let (alice, bob, carol) = (tx_alice.pub_key(), tx_bob.pub_key(), tx_carol.pub_key());
+    let target_height = testkit.height().0 + 5;
+    let incoming = TxDeferredTransfer::new(carol, alice, 30, target_height, 0, &key_carol);
+    let outgoing = TxDeferredTransfer::new(alice, carol, 20, target_height, 0, &key_alice);
+    api.deferred_transfer(&incoming);
+    api.deferred_transfer(&outgoing);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(alice).balance(), 80);
+    assert_eq!(api.get_wallet(carol).balance(), 70);
+
+    api.close_wallet(&TxCloseWallet::new(alice, bob, 0, &key_alice));
+    testkit.create_block();
+    assert_eq!(api.get_wallet(bob).balance(), 180);
+
+    let cancel = TxCancelDeferredTransfer::new(alice, &outgoing.hash(), &key_alice);
+    api.cancel_deferred_transfer(&cancel);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(alice).balance(), 0);
+    assert_eq!(api.get_wallet(bob).balance(), 200);
+
+    // The commit hook issues the tick transaction processed at the target height.
+    while testkit.height().0 < target_height + 1 {
+        testkit.create_block();
+    }
+    assert!(api.get_deferred_transfers().is_empty());
+    assert_eq!(api.get_wallet(alice).balance(), 0);
+    assert_eq!(api.get_wallet(carol).balance(), 100);
+}
+
+/// Check that assets are issued, transferred and swept on closing apart from the coins.
+#[test]
+fn test_assets() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    let (tx_carol, _) = api.create_wallet("Carol");
+    testkit.create_block();
+
+    let (alice, bob, carol) = (tx_alice.pub_key(), tx_bob.pub_key(), tx_carol.pub_key());
+    let gold = TxCreateAsset::new(alice, "Gold", 1000, 0, &key_alice);
+    api.create_asset(&gold);
+    testkit.create_block();
+    let asset = api.get_asset(&gold.hash());
+    assert_eq!(asset.name(), "Gold");
+    assert_eq!(asset.issuer(), alice);
+
+    let overdraft = TxAssetTransfer::new(alice, bob, &gold.hash(), 1001, 0, &key_alice);
+    let unknown = TxAssetTransfer::new(alice, bob, &Hash::zero(), 1, 1, &key_alice);
+    api.asset_transfer(&overdraft);
+    api.asset_transfer(&unknown);
+    api.asset_transfer(&TxAssetTransfer::new(alice, bob, &gold.hash(), 400, 2, &key_alice));
+    testkit.create_block();
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&overdraft.hash()), Some(Err(Error::InsufficientFunds)));
+    assert_eq!(schema.tx_result(&unknown.hash()), Some(Err(Error::AssetNotFound)));
+    assert_eq!(schema.asset_balance(alice, &gold.hash()), 600);
+
+    // Asset transfers do not touch the coins.
+    assert_eq!(api.get_wallet(alice).balance(), 100);
+    assert_eq!(api.get_wallet(bob).balance(), 100);
+    let balances = api.get_asset_balances(bob);
+    assert_eq!(balances.len(), 1);
+    assert_eq!(balances[0].asset, gold.hash());
+    assert_eq!(balances[0].balance, 400);
+
+    api.close_wallet(&TxCloseWallet::new(bob, carol, 0, &key_bob));
+    testkit.create_block();
+    assert!(api.get_asset_balances(bob).is_empty());
+    assert_eq!(api.get_asset_balances(carol)[0].balance, 400);
+}
+
+/// Check that a spender can transfer coins from the owner's wallet within the allowance.
+#[test]
+fn test_allowance() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    let (tx_carol, key_carol) = api.create_wallet("Carol");
+    testkit.create_block();
+
+    api.approve(&TxApprove::new(tx_alice.pub_key(), tx_bob.pub_key(), 30, 0, &key_alice));
+    testkit.create_block();
+    let allowances = api.get_allowances(tx_alice.pub_key());
+    assert_eq!(allowances.len(), 1);
+    assert_eq!(&allowances[0].spender, tx_bob.pub_key());
+    assert_eq!(allowances[0].amount, 30);
+
+    let transfer_from = |spender: &PublicKey, amount, key: &SecretKey| {
+        TxTransferFrom::new(spender, tx_alice.pub_key(), tx_carol.pub_key(), amount, 0, 0, key)
+    };
+    let spend = transfer_from(tx_bob.pub_key(), 20, &key_bob);
+    api.transfer_from(&spend);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 80);
+    assert_eq!(api.get_wallet(tx_carol.pub_key()).balance(), 120);
+    assert_eq!(api.get_allowances(tx_alice.pub_key())[0].amount, 10);
+
+    let overspend = transfer_from(tx_bob.pub_key(), 11, &key_bob);
+    let stranger = transfer_from(tx_carol.pub_key(), 5, &key_carol);
+    api.transfer_from(&overspend);
+    api.transfer_from(&stranger);
+    testkit.create_block();
+
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&spend.hash()), Some(Ok(())));
+    assert_eq!(schema.tx_result(&overspend.hash()), Some(Err(Error::AllowanceExceeded)));
+    assert_eq!(schema.tx_result(&stranger.hash()), Some(Err(Error::AllowanceExceeded)));
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 80);
+
+    api.approve(&TxApprove::new(tx_alice.pub_key(), tx_bob.pub_key(), 0, 1, &key_alice));
+    testkit.create_block();
+    assert!(api.get_allowances(tx_alice.pub_key()).is_empty());
+}
+
+/// Check that the balance over the threshold of a sweep rule moves to the cold wallet.
+#[test]
+fn test_sweep_rule() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_hot, key_hot) = api.create_wallet("Hot");
+    let (tx_cold, _) = api.create_wallet("Cold");
+    let (tx_carol, key_carol) = api.create_wallet("Carol");
+    testkit.create_block();
+
+    let rule = TxSetSweepRule::new(tx_hot.pub_key(), tx_cold.pub_key(), 60, 0, &key_hot);
+    api.set_sweep_rule(&rule);
+    testkit.create_block();
+    assert_eq!(api.get_sweep_rule(tx_hot.pub_key()).threshold(), 60);
+
+    // The commit hook issues the tick transaction making the sweep.
+    testkit.create_block();
+    let hot = api.get_wallet(tx_hot.pub_key());
+    assert_eq!(hot.balance(), 60);
+    assert_eq!(hot.history_len(), 2);
+    assert_eq!(api.get_wallet(tx_cold.pub_key()).balance(), 140);
+    // Only wallets whose balance has exceeded the threshold are checked for sweeps.
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert!(schema.sweep_candidates().iter().next().is_none());
+    assert!(!schema.has_scheduled(testkit.height().0));
+
+    let tx = TxTransfer::new(
+        tx_carol.pub_key(),
+        tx_hot.pub_key(),
+        30,
+        0,
+        &Hash::zero(),
+        &Hash::zero(),
+        "",
+        0,
+        &key_carol,
+    );
+    api.transfer(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_hot.pub_key()).balance(), 90);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_hot.pub_key()).balance(), 60);
+    assert_eq!(api.get_wallet(tx_cold.pub_key()).balance(), 170);
+
+    let clear = TxClearSweepRule::new(tx_hot.pub_key(), 0, &key_hot);
+    api.clear_sweep_rule(&clear);
+    testkit.create_block();
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&clear.hash()), Some(Ok(())));
+    assert!(schema.sweep_rules().get(tx_hot.pub_key()).is_none());
+}
+
+/// Check that the arbiter of an escrow releases it to the recipient or refunds it to the sender.
+#[test]
+fn test_escrow() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    let (tx_carol, key_carol) = api.create_wallet("Carol");
+    testkit.create_block();
+
+    let (alice, bob, carol) = (tx_alice.pub_key(), tx_bob.pub_key(), tx_carol.pub_key());
+    let released = TxCreateEscrow::new(alice, bob, carol, 40, 0, &key_alice);
+    let refunded = TxCreateEscrow::new(alice, bob, carol, 30, 1, &key_alice);
+    api.create_escrow(&released);
+    api.create_escrow(&refunded);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(alice).balance(), 30);
+    let escrows = api.get_escrows();
+    assert_eq!(escrows.len(), 2);
+    assert!(escrows.iter().all(|info| info.escrow.arbiter() == carol));
+
+    let forged = TxReleaseEscrow::new(bob, &released.hash(), &key_bob);
+    api.release_escrow(&forged);
+    api.release_escrow(&TxReleaseEscrow::new(carol, &released.hash(), &key_carol));
+    api.refund_escrow(&TxRefundEscrow::new(carol, &refunded.hash(), &key_carol));
+    testkit.create_block();
+    assert_eq!(api.get_wallet(alice).balance(), 60);
+    assert_eq!(api.get_wallet(bob).balance(), 140);
+    assert_eq!(api.get_wallet(carol).balance(), 100);
+    assert!(api.get_escrows().is_empty());
+
+    let again = TxRefundEscrow::new(carol, &released.hash(), &key_carol);
+    api.refund_escrow(&again);
+    testkit.create_block();
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&forged.hash()), Some(Err(Error::Unauthorized)));
+    assert_eq!(schema.tx_result(&again.hash()), Some(Err(Error::EscrowNotFound)));
+    assert_eq!(api.get_wallet(alice).balance(), 60);
+}
+
+/// Check that an escrow settled under the velocity rules is reported only once
+/// its payment is made.
+#[test]
+fn test_escrow_velocity_rules() {
+    let (compliance_pubkey, compliance_key) = crypto::gen_keypair();
+    let config = CurrencyConfig {
+        velocity: Some(VelocityRules {
+            compliance: compliance_pubkey,
+            max_amount: Some(50),
+            max_transfers: None,
+            max_counterparties: None,
+        }),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    let (tx_carol, key_carol) = api.create_wallet("Carol");
+    testkit.create_block();
+
+    let (alice, bob, carol) = (tx_alice.pub_key(), tx_bob.pub_key(), tx_carol.pub_key());
+    let released = TxCreateEscrow::new(alice, bob, carol, 60, 0, &key_alice);
+    let refunded = TxCreateEscrow::new(carol, bob, alice, 60, 0, &key_carol);
+    api.create_escrow(&released);
+    api.create_escrow(&refunded);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(alice).balance(), 40);
+    assert_eq!(api.get_wallet(carol).balance(), 40);
+
+    // A release above the limit is held; a refund returns the coins to the sender.
+    let release = TxReleaseEscrow::new(carol, &released.hash(), &key_carol);
+    let refund = TxRefundEscrow::new(alice, &refunded.hash(), &key_alice);
+    api.release_escrow(&release);
+    api.refund_escrow(&refund);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(carol).balance(), 100);
+    assert_eq!(api.get_wallet(bob).balance(), 100);
+    let held = api.get_held_transfers();
+    assert_eq!(held.len(), 1);
+    assert_eq!(held[0].tx_hash, release.hash());
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&release.hash()), None);
+    assert_eq!(schema.tx_result(&refund.hash()), Some(Ok(())));
+
+    let review =
+        TxReviewHeldTransfer::new(&compliance_pubkey, &release.hash(), true, &compliance_key);
+    api.review_held_transfer(&review);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(bob).balance(), 160);
+    assert!(api.get_escrows().is_empty());
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&release.hash()), Some(Ok(())));
+}
+
+/// Check that a hashed time-lock contract is claimed with the preimage before
+/// the timeout and refunded after it.
+#[test]
+fn test_htlc() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let (alice, bob) = (tx_alice.pub_key(), tx_bob.pub_key());
+    let preimage = crypto::hash(b"swap secret");
+    let hash_lock = crypto::hash(preimage.as_ref());
+    let timeout = testkit.height().0 + 5;
+    let claimed = TxCreateHtlc::new(alice, bob, &hash_lock, 40, timeout, 0, &key_alice);
+    let refunded = TxCreateHtlc::new(alice, bob, &hash_lock, 30, timeout, 1, &key_alice);
+    api.create_htlc(&claimed);
+    api.create_htlc(&refunded);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(alice).balance(), 30);
+    assert_eq!(api.get_htlc(&claimed.hash()).hash_lock(), &hash_lock);
+
+    let wrong = TxClaimHtlc::new(bob, &claimed.hash(), &hash_lock, &key_bob);
+    let early = TxRefundHtlc::new(alice, &refunded.hash(), &key_alice);
+    api.claim_htlc(&wrong);
+    api.refund_htlc(&early);
+    testkit.create_block();
+    api.claim_htlc(&TxClaimHtlc::new(bob, &claimed.hash(), &preimage, &key_bob));
+    testkit.create_block();
+    assert_eq!(api.get_wallet(alice).balance(), 30);
+    assert_eq!(api.get_wallet(bob).balance(), 140);
+    assert_eq!(api.get_htlc_preimage(&hash_lock).preimage, preimage);
+
+    while testkit.height().0 < timeout {
+        testkit.create_block();
+    }
+    let late = TxClaimHtlc::new(bob, &refunded.hash(), &preimage, &key_bob);
+    api.claim_htlc(&late);
+    testkit.create_block();
+    api.refund_htlc(&TxRefundHtlc::new(alice, &refunded.hash(), &key_alice));
+    testkit.create_block();
+    assert_eq!(api.get_wallet(alice).balance(), 60);
+    assert_eq!(api.get_wallet(bob).balance(), 140);
+
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&wrong.hash()), Some(Err(Error::InvalidPreimage)));
+    assert_eq!(schema.tx_result(&early.hash()), Some(Err(Error::InvalidHeight)));
+    assert_eq!(schema.tx_result(&late.hash()), Some(Err(Error::InvalidHeight)));
+    assert!(schema.htlcs().get(&refunded.hash()).is_none());
+}
+
+/// Check that a hashed time-lock contract settled under the velocity rules is
+/// reported only once its payment is made.
+#[test]
+fn test_htlc_velocity_rules() {
+    let (compliance_pubkey, compliance_key) = crypto::gen_keypair();
+    let config = CurrencyConfig {
+        velocity: Some(VelocityRules {
+            compliance: compliance_pubkey,
+            max_amount: Some(50),
+            max_transfers: None,
+            max_counterparties: None,
+        }),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    let (tx_carol, key_carol) = api.create_wallet("Carol");
+    testkit.create_block();
 
-/// Wrapper for the cryptocurrency service API allowing to easily use it
-/// (compared to `TestKitApi` calls).
-struct CryptocurrencyApi {
-    inner: TestKitApi,
+    let (alice, bob, carol) = (tx_alice.pub_key(), tx_bob.pub_key(), tx_carol.pub_key());
+    let preimage = crypto::hash(b"swap secret");
+    let hash_lock = crypto::hash(preimage.as_ref());
+    let timeout = testkit.height().0 + 3;
+    let claimed = TxCreateHtlc::new(alice, bob, &hash_lock, 60, timeout, 0, &key_alice);
+    let refunded = TxCreateHtlc::new(carol, bob, &hash_lock, 60, timeout, 0, &key_carol);
+    api.create_htlc(&claimed);
+    api.create_htlc(&refunded);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(alice).balance(), 40);
+    assert_eq!(api.get_wallet(carol).balance(), 40);
+
+    // A claim above the limit is held; a refund returns the coins to the sender.
+    let claim = TxClaimHtlc::new(bob, &claimed.hash(), &preimage, &key_bob);
+    api.claim_htlc(&claim);
+    while testkit.height().0 < timeout {
+        testkit.create_block();
+    }
+    let refund = TxRefundHtlc::new(carol, &refunded.hash(), &key_carol);
+    api.refund_htlc(&refund);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(carol).balance(), 100);
+    assert_eq!(api.get_wallet(bob).balance(), 100);
+    let held = api.get_held_transfers();
+    assert_eq!(held.len(), 1);
+    assert_eq!(held[0].tx_hash, claim.hash());
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&claim.hash()), None);
+    assert_eq!(schema.tx_result(&refund.hash()), Some(Ok(())));
+    assert!(schema.htlcs().get(&refunded.hash()).is_none());
+
+    let review =
+        TxReviewHeldTransfer::new(&compliance_pubkey, &claim.hash(), true, &compliance_key);
+    api.review_held_transfer(&review);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(bob).balance(), 160);
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&claim.hash()), Some(Ok(())));
 }
 
-impl CryptocurrencyApi {
-    /// Generates a wallet creation transaction with a random key pair, sends it over HTTP,
-    /// and checks the synchronous result (i.e., the hash of the transaction returned
-    /// within the `TransactionResponse` struct).
-    /// Note that the transaction is not immediately added to the blockchain, but rather is put
-    /// to the pool of unconfirmed transactions.
-    fn create_wallet(&self, name: &str) -> (TxCreateWallet, SecretKey) {
-        let (pubkey, key) = crypto::gen_keypair();
-        // Create a presigned transaction
-        let tx = TxCreateWallet::new(&pubkey, name, &key);
+/// Check that a payout manifest is checked and paid out in batches with stable seeds.
+#[test]
+fn test_payout_manifest() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    let (tx_carol, _) = api.create_wallet("Carol");
+    let (tx_dave, _) = api.create_wallet("Dave");
+    testkit.create_block();
 
-        let tx_info: TransactionResponse = self.inner.post(
-            ApiKind::Service("cryptocurrency"),
-            "v1/wallets",
-            &tx,
-        );
-        assert_eq!(tx_info.tx_hash, tx.hash());
-        (tx, key)
+    let (approver, approver_key) = crypto::gen_keypair();
+    let csv = format!(
+        "to,amount\n{},10\n# bonus\n{},20\n{},30\n",
+        tx_bob.pub_key().to_string(),
+        tx_carol.pub_key().to_string(),
+        tx_dave.pub_key().to_string()
+    );
+    let manifest = Manifest::parse(csv.as_bytes()).unwrap();
+    assert_eq!(manifest.lines.len(), 3);
+    let signature = crypto::sign(manifest.hash.as_ref(), &approver_key);
+    assert!(manifest.verify(&signature, &approver));
+    assert!(!manifest.verify(&signature, tx_alice.pub_key()));
+
+    let (alice, schema) = (tx_alice.pub_key(), CurrencySchema::new(testkit.snapshot()));
+    let cost = |fee| {
+        let schema = &schema;
+        move |amounts: &[u64]| {
+            schema.batch_transfer_cost(alice, amounts, fee).map_err(|e| e.to_string())
+        }
+    };
+    assert_eq!(manifest.check(100, 2, cost(1)), Ok(()));
+    assert_eq!(
+        manifest.check(61, 2, cost(1)),
+        Err(PayoutError::InsufficientFunds {
+            required: 62,
+            available: 61,
+        })
+    );
+    let duplicate = format!("{}\n{}", csv, csv.lines().nth(1).unwrap());
+    assert_eq!(
+        Manifest::parse(duplicate.as_bytes()).unwrap().check(100, 2, cost(0)),
+        Err(PayoutError::Duplicate(*tx_bob.pub_key()))
+    );
+    assert_eq!(Manifest::parse(b"00,10").err(), Some(PayoutError::Parse(1)));
+
+    let batches = manifest.batches(tx_alice.pub_key(), 0, 2, &key_alice);
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[1].payments().len(), 1);
+    let again = manifest.batches(tx_alice.pub_key(), 0, 2, &key_alice);
+    assert_eq!(again[0].hash(), batches[0].hash());
+    for tx in &batches {
+        api.batch_transfer(tx);
     }
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 40);
+    assert_eq!(api.get_wallet(tx_dave.pub_key()).balance(), 130);
+}
 
-    /// Sends a transfer transaction over HTTP and checks the synchronous result.
-    fn transfer(&self, tx: &TxTransfer) {
-        let tx_info: TransactionResponse = self.inner.post(
+/// Check that a payout manifest is checked against the insurance levy of every
+/// payment and the minimum fee of every batch.
+#[test]
+fn test_payout_manifest_levy() {
+    let (fund_pubkey, fund_key) = crypto::gen_keypair();
+    let (admin_pubkey, _) = crypto::gen_keypair();
+    let config = CurrencyConfig {
+        insurance: Some(InsuranceConfig {
+            fund: fund_pubkey,
+            levy_bps: 1000,
+            governors: Vec::new(),
+            threshold: 1,
+        }),
+        tiers: Some(TierConfig {
+            admin: admin_pubkey,
+            basic: TierRules {
+                max_amount: None,
+                fee_bps: 500,
+            },
+            verified: TierRules::default(),
+            institutional: TierRules::default(),
+        }),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+    api.create_wallet_with_keypair("Insurance fund", fund_pubkey, fund_key);
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    let (tx_carol, _) = api.create_wallet("Carol");
+    let (tx_dave, _) = api.create_wallet("Dave");
+    testkit.create_block();
+
+    let csv = format!(
+        "{},20\n{},20\n{},20\n",
+        tx_bob.pub_key().to_string(),
+        tx_carol.pub_key().to_string(),
+        tx_dave.pub_key().to_string()
+    );
+    let manifest = Manifest::parse(csv.as_bytes()).unwrap();
+    let alice = tx_alice.pub_key();
+
+    // A 10% levy per payment and a fee of at least 5% of the batch total.
+    let info: BatchCost = api.inner.get(
+        ApiKind::Service("cryptocurrency"),
+        &format!("v1/wallet/{}/batch-cost?fee=2&amounts=20,20", alice.to_string()),
+    );
+    assert_eq!(info.cost, 46);
+    let schema = CurrencySchema::new(testkit.snapshot());
+    let cost = |fee| {
+        let schema = &schema;
+        move |amounts: &[u64]| {
+            schema.batch_transfer_cost(alice, amounts, fee).map_err(|e| e.to_string())
+        }
+    };
+    assert_eq!(
+        manifest.check(69, 2, cost(2)),
+        Err(PayoutError::InsufficientFunds {
+            required: 70,
+            available: 69,
+        })
+    );
+    assert_eq!(
+        manifest.check(100, 2, cost(1)),
+        Err(PayoutError::Rejected(Error::FeeTooLow.to_string()))
+    );
+    assert_eq!(manifest.check(70, 2, cost(2)), Ok(()));
+
+    for tx in &manifest.batches(alice, 2, 2, &key_alice) {
+        api.batch_transfer(tx);
+    }
+    testkit.create_block();
+    assert_eq!(api.get_wallet(alice).balance(), 30);
+    assert_eq!(api.get_wallet(&fund_pubkey).balance(), 106);
+}
+
+/// Check that a key split in a ceremony is recovered from any threshold of shares
+/// and passes the verification on a testnet.
+#[test]
+fn test_key_ceremony() {
+    let (mut testkit, api) = create_testkit();
+    let seed = ceremony::random_bytes(32);
+    let (pub_key, secret_key) = ceremony::keypair_from_seed(&seed).unwrap();
+    let shares = ceremony::split(&seed, 3, 5).unwrap();
+    assert_eq!(shares.len(), 5);
+    assert_eq!(ceremony::split(&seed, 6, 5).err(), Some(CeremonyError::Threshold));
+
+    let subset = vec![shares[4].clone(), shares[0].clone(), shares[2].clone()];
+    assert_eq!(ceremony::combine(&subset).unwrap(), seed);
+    assert_eq!(ceremony::combine(&shares[1..4]).unwrap(), seed);
+    assert_eq!(ceremony::combine(&shares[..2]).err(), Some(CeremonyError::NotEnoughShares));
+    let duplicate = vec![shares[0].clone(), shares[0].clone(), shares[1].clone()];
+    assert_eq!(ceremony::combine(&duplicate).err(), Some(CeremonyError::DuplicateShare(1)));
+
+    // Shares survive the recovery sheet, including the spaces inserted into them.
+    let sheet = ceremony::recovery_sheet("issuer", &pub_key, &shares[1], 5);
+    assert!(sheet.contains(&pub_key.to_string()));
+    let start = sheet.find(ceremony::SHARE_PREFIX).unwrap();
+    let text = sheet[start..].split("\n\n").next().unwrap();
+    assert!(text.contains(' '));
+    assert_eq!(text.parse::<Share>().unwrap(), shares[1]);
+    let mut tampered = shares[1].to_string().into_bytes();
+    let last = tampered.len() - 1;
+    tampered[last] = if tampered[last] == b'0' { b'1' } else { b'0' };
+    let tampered = String::from_utf8(tampered).unwrap();
+    assert_eq!(tampered.parse::<Share>().err(), Some(CeremonyError::Checksum));
+
+    let recovered = ceremony::combine(&shares[2..]).unwrap();
+    let (recovered_key, recovered_secret) = ceremony::keypair_from_seed(&recovered).unwrap();
+    assert_eq!(recovered_key, pub_key);
+    let tx = ceremony::verification_tx("issuer", &recovered_key, &recovered_secret);
+    api.create_wallet_tx(&tx);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(&pub_key).name(), "issuer key check");
+}
+
+/// Check that a transaction not committed within its deadline raises an alert.
+#[test]
+fn test_sla_alerts() {
+    let (mut testkit, api) = create_testkit();
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, _) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let transfer = |seed| {
+        TxTransfer::new(
+            tx_alice.pub_key(),
+            tx_bob.pub_key(),
+            10, // transfer amount
+            0, // fee
+            &Hash::zero(), // no dependency
+            &Hash::zero(), // no reference
+            "", // no memo
+            seed,
+            &key_alice,
+        )
+    };
+    let on_time = transfer(1);
+    let late = transfer(2);
+    for &(tx, deadline) in &[(&on_time, 3600), (&late, 0)] {
+        let tx_info: TransactionResponse = api.inner.post(
             ApiKind::Service("cryptocurrency"),
-            "v1/wallets/transfer",
+            &format!("v1/wallets/transfer?deadline={}", deadline),
             tx,
         );
         assert_eq!(tx_info.tx_hash, tx.hash());
     }
+    testkit.create_block_with_tx_hashes(&[on_time.hash()]);
 
-    /// Gets the state of a particular wallet using an HTTP request.
-    fn get_wallet(&self, pubkey: &PublicKey) -> Wallet {
-        self.inner.get(
+    let get_alerts = || -> Vec<SlaAlert> {
+        api.inner.get_private(
             ApiKind::Service("cryptocurrency"),
-            &format!("v1/wallet/{}", pubkey.to_string()),
+            "v1/admin/sla-alerts",
         )
-    }
+    };
+    let alerts = get_alerts();
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].tx_hash, late.hash());
+    assert_eq!(alerts[0].tracked.deadline, alerts[0].tracked.submitted_at);
+    assert!(alerts[0].diagnostics.in_queue);
+    assert_eq!(alerts[0].diagnostics.pending, 1);
+    assert_eq!(alerts[0].diagnostics.blocks_since_submission, 1);
 
-    /// Asserts that a wallet with the specified public key is not known to the blockchain.
-    fn assert_no_wallet(&self, pubkey: &PublicKey) {
-        let err: String = self.inner.get_err(
-            ApiKind::Service("cryptocurrency"),
-            &format!("v1/wallet/{}", pubkey.to_string()),
-        );
-        assert_eq!(err, "Wallet not found".to_string());
-    }
-}
+    // An alert is raised once.
+    testkit.create_block();
+    assert_eq!(get_alerts().len(), 1);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 120);
 
-/// Creates a testkit together with the API wrapper defined above.
-fn create_testkit() -> (TestKit, CryptocurrencyApi) {
-    let testkit = TestKitBuilder::validator()
-        .with_service(CurrencyService)
-        .create();
-    let api = CryptocurrencyApi { inner: testkit.api() };
-    (testkit, api)
+    // Deadlines are bounded, and so is the number of tracked transactions.
+    let mut tracker = SlaTracker::default();
+    let too_long = tracker.track(late.hash(), 0, MAX_SLA_DEADLINE + 1, 0);
+    assert_eq!(too_long, Err(SlaError::DeadlineTooLong));
+    for index in 0..MAX_TRACKED_TRANSACTIONS {
+        let tx_hash = crypto::hash(index.to_string().as_bytes());
+        assert_eq!(tracker.track(tx_hash, 0, MAX_SLA_DEADLINE, 0), Ok(()));
+    }
+    assert_eq!(tracker.accepts(0), Err(SlaError::TooManyTracked));
+    assert!(tracker.tracked(&late.hash()).is_none());
 }
 
-/// Check that the wallet creation transaction works when invoked via API.
+/// Check that the counterparty graph follows transfers up to the requested depth.
 #[test]
-fn test_create_wallet() {
+fn test_counterparty_graph() {
     let (mut testkit, api) = create_testkit();
-    // Create and send a transaction via API
-    let (tx, _) = api.create_wallet("Alice");
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    let (tx_carol, key_carol) = api.create_wallet("Carol");
+    let (tx_dave, _) = api.create_wallet("Dave");
     testkit.create_block();
 
-    // Check that the user indeed is persisted by the service
-    let wallet = api.get_wallet(tx.pub_key());
-    assert_eq!(wallet.pub_key(), tx.pub_key());
-    assert_eq!(wallet.name(), tx.name());
-    assert_eq!(wallet.balance(), 100);
+    let transfer = |from: &TxCreateWallet, to: &TxCreateWallet, amount, seed, key: &SecretKey| {
+        let tx = TxTransfer::new(
+            from.pub_key(),
+            to.pub_key(),
+            amount,
+            0, // fee
+            &Hash::zero(), // no dependency
+            &Hash::zero(), // no reference
+            "", // no memo
+            seed,
+            key,
+        );
+        api.transfer(&tx);
+    };
+    transfer(&tx_alice, &tx_bob, 30, 1, &key_alice);
+    transfer(&tx_alice, &tx_bob, 10, 2, &key_alice);
+    transfer(&tx_bob, &tx_carol, 5, 3, &key_bob);
+    let payments = vec![Payment::new(tx_dave.pub_key(), 1)];
+    api.batch_transfer(&TxBatchTransfer::new(tx_carol.pub_key(), payments, 0, 4, &key_carol));
+    testkit.create_block();
+
+    let graph = api.get_counterparty_graph(tx_alice.pub_key(), "");
+    assert_eq!(graph.nodes.len(), 2);
+    assert_eq!(graph.edges.len(), 1);
+    assert_eq!(&graph.edges[0].from, tx_alice.pub_key());
+    assert_eq!((graph.edges[0].volume, graph.edges[0].transfers), (40, 2));
+    assert!(!graph.truncated);
+
+    let graph = api.get_counterparty_graph(tx_alice.pub_key(), "depth=3");
+    assert_eq!(graph.nodes.len(), 4);
+    assert_eq!(graph.edges.len(), 3);
+    assert_eq!(&graph.nodes[3].pub_key, tx_dave.pub_key());
+    assert_eq!(graph.nodes[3].distance, 3);
+
+    // Light edges are neither followed nor listed.
+    let graph = api.get_counterparty_graph(tx_alice.pub_key(), "depth=3&min_amount=10");
+    assert_eq!(graph.nodes.len(), 2);
+    assert_eq!(graph.edges.len(), 1);
 }
 
-/// Check that the transfer transaction works as intended.
+/// Check the aggregate figures served to the operator.
 #[test]
-fn test_transfer() {
-    // Create 2 wallets.
+fn test_overview() {
     let (mut testkit, api) = create_testkit();
     let (tx_alice, key_alice) = api.create_wallet("Alice");
     let (tx_bob, _) = api.create_wallet("Bob");
+    api.create_wallet("Carol");
     testkit.create_block();
 
-    // Check that the initial Alice's and Bob's balances persisted by the service.
-    let wallet = api.get_wallet(tx_alice.pub_key());
-    assert_eq!(wallet.balance(), 100);
-    let wallet = api.get_wallet(tx_bob.pub_key());
-    assert_eq!(wallet.balance(), 100);
+    let transfer = |amount, seed| {
+        TxTransfer::new(
+            tx_alice.pub_key(),
+            tx_bob.pub_key(),
+            amount,
+            0, // fee
+            &Hash::zero(), // no dependency
+            &Hash::zero(), // no reference
+            "", // no memo
+            seed,
+            &key_alice,
+        )
+    };
+    api.transfer(&transfer(10, 0));
+    api.transfer(&transfer(1000, 1));
+    testkit.create_block();
 
-    // Transfer funds by invoking the corresponding API method.
-    let tx = TxTransfer::new(
+    let overview = api.get_overview("");
+    assert_eq!(overview.window, testkit.height().0 + 1);
+    assert_eq!(overview.wallets, 3);
+    assert_eq!(overview.active_wallets, 3);
+    assert_eq!(overview.transactions, 5);
+    assert_eq!(overview.errors.len(), 1);
+    assert_eq!(overview.errors[&Error::InsufficientFunds.code()], 1);
+    assert_eq!(overview.pending, 0);
+    assert_eq!(overview.supply, 300);
+    assert_eq!(overview.treasury_balance, None);
+
+    api.transfer(&transfer(10, 2));
+    let overview = api.get_overview("blocks=1");
+    assert_eq!(overview.window, 1);
+    assert_eq!(overview.active_wallets, 2);
+    assert_eq!(overview.transactions, 2);
+    assert_eq!(overview.pending, 1);
+}
+
+/// Check that a frozen wallet can receive but not send coins until unfrozen.
+#[test]
+fn test_freeze_wallet() {
+    let (admin_pubkey, admin_key) = crypto::gen_keypair();
+    let config = CurrencyConfig {
+        admin: Some(admin_pubkey),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block();
+
+    let forged = TxFreezeWallet::new(tx_bob.pub_key(), tx_alice.pub_key(), 0, &key_bob);
+    let freeze = TxFreezeWallet::new(&admin_pubkey, tx_alice.pub_key(), 0, &admin_key);
+    api.freeze_wallet(&forged);
+    api.freeze_wallet(&freeze);
+    testkit.create_block();
+    assert!(api.get_wallet(tx_alice.pub_key()).frozen());
+
+    let outgoing = TxTransfer::new(
         tx_alice.pub_key(),
         tx_bob.pub_key(),
-        10, // transferred amount
-        0, // seed
+        10,
+        0,
+        &Hash::zero(),
+        &Hash::zero(),
+        "",
+        0,
         &key_alice,
     );
-    api.transfer(&tx);
+    let incoming = TxTransfer::new(
+        tx_bob.pub_key(),
+        tx_alice.pub_key(),
+        10,
+        0,
+        &Hash::zero(),
+        &Hash::zero(),
+        "",
+        0,
+        &key_bob,
+    );
+    api.transfer(&outgoing);
+    api.transfer(&incoming);
     testkit.create_block();
+    let schema = CurrencySchema::new(testkit.snapshot());
+    assert_eq!(schema.tx_result(&forged.hash()), Some(Err(Error::Unauthorized)));
+    assert_eq!(schema.tx_result(&outgoing.hash()), Some(Err(Error::WalletFrozen)));
+    assert_eq!(schema.tx_result(&incoming.hash()), Some(Ok(())));
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 110);
 
-    // After the transfer transaction is included into a block, we may check new wallet
-    // balances.
-    let wallet = api.get_wallet(tx_alice.pub_key());
-    assert_eq!(wallet.balance(), 90);
-    let wallet = api.get_wallet(tx_bob.pub_key());
-    assert_eq!(wallet.balance(), 110);
+    let unfreeze = TxUnfreezeWallet::new(&admin_pubkey, tx_alice.pub_key(), 1, &admin_key);
+    api.unfreeze_wallet(&unfreeze);
+    testkit.create_block();
+    assert!(!api.get_wallet(tx_alice.pub_key()).frozen());
+    let outgoing = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        10,
+        0,
+        &Hash::zero(),
+        &Hash::zero(),
+        "",
+        1,
+        &key_alice,
+    );
+    api.transfer(&outgoing);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 100);
 }
 
-/// Check that a transfer from a non-existing wallet fails as expected.
+/// Check that coins of a multisignature wallet are only spent with enough approvals.
 #[test]
-fn test_transfer_from_nonexisting_wallet() {
+fn test_multisig_wallet() {
     let (mut testkit, api) = create_testkit();
-
     let (tx_alice, key_alice) = api.create_wallet("Alice");
     let (tx_bob, _) = api.create_wallet("Bob");
-    // Do not commit Alice's transaction, so Alice's wallet does not exist
-    // when a transfer occurs.
-    testkit.create_block_with_tx_hashes(&[tx_bob.hash()]);
-
-    api.assert_no_wallet(tx_alice.pub_key());
-    let wallet = api.get_wallet(tx_bob.pub_key());
-    assert_eq!(wallet.balance(), 100);
+    let signers: Vec<_> = (0..3).map(|_| crypto::gen_keypair()).collect();
+    let setup = TxSetupMultisig::new(
+        tx_alice.pub_key(),
+        signers.iter().map(|&(pubkey, _)| pubkey).collect(),
+        2,
+        &key_alice,
+    );
+    api.setup_multisig(&setup);
+    testkit.create_block();
 
+    // The wallet key alone can no longer spend the coins.
     let tx = TxTransfer::new(
         tx_alice.pub_key(),
         tx_bob.pub_key(),
-        10, // transfer amount
-        0, // seed
+        10,
+        0,
+        &Hash::zero(),
+        &Hash::zero(),
+        "",
+        0,
         &key_alice,
     );
     api.transfer(&tx);
-    testkit.create_block_with_tx_hashes(&[tx.hash()]);
+    testkit.create_block();
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()),
+        Some(Err(Error::Unauthorized))
+    );
 
-    // Check that Bob's balance doesn't change
-    let wallet = api.get_wallet(tx_bob.pub_key());
-    assert_eq!(wallet.balance(), 100);
+    let (ref signer, ref signer_key) = signers[0];
+    let proposal =
+        TxMultisigTransfer::new(tx_alice.pub_key(), signer, tx_bob.pub_key(), 30, 0, 0, signer_key);
+    api.multisig_transfer(&proposal);
+    testkit.create_block();
+    let transfer = api.get_multisig_transfer(&proposal.hash());
+    assert_eq!(transfer.approval_count(), 1);
+    assert_eq!(transfer.executed_at(), 0);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 100);
+
+    let (ref signer, ref signer_key) = signers[2];
+    api.approve_multisig_transfer(&TxApproveMultisigTransfer::new(
+        signer,
+        &proposal.hash(),
+        signer_key,
+    ));
+    testkit.create_block();
+    assert_ne!(api.get_multisig_transfer(&proposal.hash()).executed_at(), 0);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 70);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 130);
 }
 
-/// Check that a transfer to a non-existing wallet fails as expected.
+/// Check that a clawback needs a supermajority of governors and returns what is
+/// left of the transfer to the sender after the delay.
 #[test]
-fn test_transfer_to_nonexisting_wallet() {
-    let (mut testkit, api) = create_testkit();
+fn test_clawback() {
+    let governors: Vec<_> = (0..3).map(|_| crypto::gen_keypair()).collect();
+    let config = CurrencyConfig {
+        clawback: Some(ClawbackConfig {
+            governors: governors.iter().map(|&(pubkey, _)| pubkey).collect(),
+            delay: 2,
+        }),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
 
     let (tx_alice, key_alice) = api.create_wallet("Alice");
-    let (tx_bob, _) = api.create_wallet("Bob");
-    // Do not commit Bob's transaction, so Bob's wallet does not exist
-    // when a transfer occurs.
-    testkit.create_block_with_tx_hashes(&[tx_alice.hash()]);
-
-    let wallet = api.get_wallet(tx_alice.pub_key());
-    assert_eq!(wallet.balance(), 100);
-    api.assert_no_wallet(tx_bob.pub_key());
+    let (tx_bob, key_bob) = api.create_wallet("Bob");
+    testkit.create_block();
 
-    let tx = TxTransfer::new(
+    let stolen = TxTransfer::new(
         tx_alice.pub_key(),
         tx_bob.pub_key(),
-        10, // transfer amount
-        0, // seed
+        40,
+        0,
+        &Hash::zero(),
+        &Hash::zero(),
+        "",
+        0,
         &key_alice,
     );
+    api.transfer(&stolen);
+    testkit.create_block();
+    // Bob moves most of the coins away before the incident is noticed.
+    let tx = TxTransfer::new(
+        tx_bob.pub_key(),
+        tx_alice.pub_key(),
+        120,
+        0,
+        &Hash::zero(),
+        &Hash::zero(),
+        "",
+        0,
+        &key_bob,
+    );
     api.transfer(&tx);
-    testkit.create_block_with_tx_hashes(&[tx.hash()]);
+    testkit.create_block();
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 20);
 
-    // Check that Alice's balance doesn't change
-    let wallet = api.get_wallet(tx_alice.pub_key());
-    assert_eq!(wallet.balance(), 100);
+    let reason = crypto::hash(b"incident report #1");
+    let (ref governor, ref governor_key) = governors[0];
+    api.propose_clawback(&TxClawback::new(governor, &stolen.hash(), &reason, governor_key));
+    let (ref governor, ref governor_key) = governors[1];
+    api.approve_clawback(&TxApproveClawback::new(governor, &stolen.hash(), governor_key));
+    // Only committed transfers can be clawed back.
+    let tx = TxClawback::new(governor, &tx_alice.hash(), &reason, governor_key);
+    api.propose_clawback(&tx);
+    testkit.create_block();
+    assert_eq!(
+        CurrencySchema::new(testkit.snapshot()).tx_result(&tx.hash()),
+        Some(Err(Error::TransferNotFound))
+    );
+    let clawbacks = api.get_clawbacks();
+    assert_eq!(clawbacks.len(), 1);
+    assert_eq!(clawbacks[0].transfer, stolen.hash());
+    assert_eq!(clawbacks[0].clawback.reason(), &reason);
+    assert_eq!(clawbacks[0].clawback.approval_count(), 2);
+    assert_eq!(clawbacks[0].clawback.execute_at(), 0);
+
+    let (ref governor, ref governor_key) = governors[2];
+    api.approve_clawback(&TxApproveClawback::new(governor, &stolen.hash(), governor_key));
+    testkit.create_block();
+    let execute_at = api.get_clawbacks()[0].clawback.execute_at();
+    assert_ne!(execute_at, 0);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 20);
+
+    while testkit.height().0 < execute_at + 1 {
+        testkit.create_block();
+    }
+    let clawback = api.get_clawbacks()[0].clawback.clone();
+    assert_ne!(clawback.executed_at(), 0);
+    assert_eq!(clawback.recovered(), 20);
+    assert_eq!(clawback.error(), None);
+    assert_eq!(api.get_wallet(tx_bob.pub_key()).balance(), 0);
+    assert_eq!(api.get_wallet(tx_alice.pub_key()).balance(), 200);
 }
 
-/// Check that an overcharge does not lead to changes in sender's and receiver's balances.
+/// Check that a clawback recovers nothing from a frozen or closed receiver and
+/// records the error.
 #[test]
-fn test_transfer_overcharge() {
-    let (mut testkit, api) = create_testkit();
-
+fn test_clawback_blocked_wallets() {
+    let (admin_pubkey, admin_key) = crypto::gen_keypair();
+    let (governor, governor_key) = crypto::gen_keypair();
+    let config = CurrencyConfig {
+        admin: Some(admin_pubkey),
+        clawback: Some(ClawbackConfig {
+            governors: vec![governor],
+            delay: 1,
+        }),
+        ..Default::default()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService::new().with_config(config))
+        .create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
     let (tx_alice, key_alice) = api.create_wallet("Alice");
     let (tx_bob, _) = api.create_wallet("Bob");
+    let (tx_carol, _) = api.create_wallet("Carol");
+    let (tx_dave, key_dave) = api.create_wallet("Dave");
     testkit.create_block();
 
-    // Transfer funds. The transfer amount (110) is more than Alice has (100).
-    let tx = TxTransfer::new(
-        tx_alice.pub_key(),
-        tx_bob.pub_key(),
-        110, // transfer amount
-        0, // seed
-        &key_alice,
-    );
-    api.transfer(&tx);
+    let (alice, bob, carol, dave) =
+        (tx_alice.pub_key(), tx_bob.pub_key(), tx_carol.pub_key(), tx_dave.pub_key());
+    let transfer = |to: &PublicKey, seed: u64| {
+        TxTransfer::new(
+            alice,
+            to,
+            40,
+            0,
+            &Hash::zero(),
+            &Hash::zero(),
+            "",
+            seed,
+            &key_alice,
+        )
+    };
+    let to_bob = transfer(bob, 0);
+    let to_dave = transfer(dave, 1);
+    api.transfer(&to_bob);
+    api.transfer(&to_dave);
+    testkit.create_block();
+
+    // Bob is frozen and Dave closes the wallet in favor of Carol.
+    api.freeze_wallet(&TxFreezeWallet::new(&admin_pubkey, bob, 0, &admin_key));
+    api.close_wallet(&TxCloseWallet::new(dave, carol, 0, &key_dave));
+    testkit.create_block();
+    assert_eq!(api.get_wallet(carol).balance(), 240);
+
+    let reason = crypto::hash(b"incident report #2");
+    for tx in &[&to_bob, &to_dave] {
+        api.propose_clawback(&TxClawback::new(&governor, &tx.hash(), &reason, &governor_key));
+    }
+    testkit.create_block();
+    let execute_at = api.get_clawbacks()[0].clawback.execute_at();
+    assert_ne!(execute_at, 0);
+    while testkit.height().0 < execute_at + 1 {
+        testkit.create_block();
+    }
+
+    let clawbacks = api.get_clawbacks();
+    assert_eq!(clawbacks.len(), 2);
+    for info in &clawbacks {
+        assert_ne!(info.clawback.executed_at(), 0);
+        assert_eq!(info.clawback.recovered(), 0);
+        let error = if info.transfer == to_bob.hash() {
+            Error::WalletFrozen
+        } else {
+            Error::WalletClosed
+        };
+        assert_eq!(info.clawback.error(), Some(error));
+    }
+    assert_eq!(api.get_wallet(alice).balance(), 20);
+    assert_eq!(api.get_wallet(bob).balance(), 140);
+    assert_eq!(api.get_wallet(carol).balance(), 240);
+}
+
+/// Message type of the transaction registered by the test below.
+const TX_BONUS_ID: u16 = 100;
+
+/// Transaction defined outside of the service crate, crediting a fixed bonus
+/// to an existing wallet.
+message! {
+    struct TxBonus {
+        const TYPE = SERVICE_ID;
+        const ID = TX_BONUS_ID;
+        const SIZE = 32;
+
+        field pub_key:     &PublicKey  [00 => 32]
+    }
+}
+
+impl Transaction for TxBonus {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.pub_key())
+    }
+
+    fn execute(&self, view: &mut Fork) {
+        let mut schema = CurrencySchema::new(view);
+        if let Some(wallet) = schema.wallet(self.pub_key()).and_then(|wallet| wallet.increase(5)) {
+            schema.wallets_mut().put(self.pub_key(), wallet);
+        }
+    }
+}
+
+/// Check that transactions registered by an embedder are processed by the service.
+#[test]
+fn test_registered_transaction() {
+    let service = CurrencyService::new()
+        .with_transaction(TX_BONUS_ID, |raw| Ok(Box::new(TxBonus::from_raw(raw)?)));
+    let mut testkit = TestKitBuilder::validator().with_service(service).create();
+    let api = CryptocurrencyApi { inner: testkit.api() };
+
+    let (tx_alice, key_alice) = api.create_wallet("Alice");
     testkit.create_block();
 
+    let tx = TxBonus::new(tx_alice.pub_key(), &key_alice);
+    testkit.create_block_with_transactions(txvec![tx]);
+
     let wallet = api.get_wallet(tx_alice.pub_key());
-    assert_eq!(wallet.balance(), 100);
-    let wallet = api.get_wallet(tx_bob.pub_key());
-    assert_eq!(wallet.balance(), 100);
+    assert_eq!(wallet.balance(), 105);
 }
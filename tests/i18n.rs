@@ -0,0 +1,54 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate cryptocurrency;
+
+use cryptocurrency::i18n::{ApiMessage, Language, format_amount};
+
+/// Check `Accept-Language` negotiation.
+#[test]
+fn test_negotiate_language() {
+    assert_eq!(Language::negotiate(""), Language::English);
+    assert_eq!(Language::negotiate("ru"), Language::Russian);
+    assert_eq!(Language::negotiate("uk-UA,uk;q=0.9"), Language::Ukrainian);
+    assert_eq!(Language::negotiate("fr-FR, ru;q=0.5, en;q=0.8"), Language::English);
+    assert_eq!(Language::negotiate("ru;q=0.7, uk;q=0.7"), Language::Russian);
+    assert_eq!(Language::negotiate("ru;q=0, de"), Language::English);
+}
+
+/// Check that messages are translated, falling back to English by default.
+#[test]
+fn test_message_catalog() {
+    let lang = Language::negotiate("de-DE");
+    assert_eq!(ApiMessage::WalletNotFound.text(lang), "Wallet not found");
+    assert_eq!(
+        ApiMessage::WalletNotFound.text(Language::Russian),
+        "Кошелек не найден"
+    );
+}
+
+/// Check locale-aware formatting of amounts.
+#[test]
+fn test_format_amount() {
+    assert_eq!(format_amount(0, 0, Language::English), "0");
+    assert_eq!(format_amount(1_234_567, 0, Language::English), "1,234,567");
+    assert_eq!(format_amount(123_456_789, 2, Language::English), "1,234,567.89");
+    assert_eq!(format_amount(5, 3, Language::English), "0.005");
+    assert_eq!(format_amount(100_000, 2, Language::English), "1,000.00");
+    assert_eq!(
+        format_amount(123_456_789, 2, Language::Russian),
+        "1\u{a0}234\u{a0}567,89"
+    );
+    assert_eq!(format_amount(999, 2, Language::Ukrainian), "9,99");
+}
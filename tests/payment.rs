@@ -0,0 +1,72 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate cryptocurrency;
+extern crate exonum;
+
+use exonum::crypto;
+
+use cryptocurrency::payment::{PaymentRequest, PaymentUriError};
+
+/// Check that a payment request survives serialization to a URI and back.
+#[test]
+fn test_payment_uri_roundtrip() {
+    let (pub_key, _) = crypto::gen_keypair();
+    let request = PaymentRequest {
+        to: pub_key,
+        amount: Some(25),
+        memo: Some("Invoice #42 & café".to_owned()),
+    };
+
+    let uri = request.to_uri();
+    assert!(uri.starts_with(&format!("excoin:{}?amount=25&memo=", pub_key.to_string())));
+    assert!(!uri.contains(' '));
+    assert_eq!(uri.parse::<PaymentRequest>(), Ok(request));
+
+    let bare = PaymentRequest::new(pub_key);
+    assert_eq!(bare.to_uri(), format!("excoin:{}", pub_key.to_string()));
+    assert_eq!(PaymentRequest::from_uri(&bare.to_uri()), Ok(bare));
+}
+
+/// Check that malformed payment URIs are rejected.
+#[test]
+fn test_payment_uri_errors() {
+    let (pub_key, _) = crypto::gen_keypair();
+    let key = pub_key.to_string();
+
+    assert_eq!(
+        PaymentRequest::from_uri(&format!("bitcoin:{}", key)),
+        Err(PaymentUriError::Scheme)
+    );
+    assert_eq!(
+        PaymentRequest::from_uri("excoin:deadbeef"),
+        Err(PaymentUriError::PublicKey)
+    );
+    assert_eq!(
+        PaymentRequest::from_uri(&format!("excoin:{}?amount=-1", key)),
+        Err(PaymentUriError::Amount)
+    );
+    assert_eq!(
+        PaymentRequest::from_uri(&format!("excoin:{}?memo=%E", key)),
+        Err(PaymentUriError::Encoding)
+    );
+    assert_eq!(
+        PaymentRequest::from_uri(&format!("excoin:{}?amount=1&amount=2", key)),
+        Err(PaymentUriError::DuplicateParameter("amount".to_owned()))
+    );
+    assert_eq!(
+        PaymentRequest::from_uri(&format!("excoin:{}?label=shop", key)),
+        Err(PaymentUriError::UnknownParameter("label".to_owned()))
+    );
+}
@@ -0,0 +1,62 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate cryptocurrency;
+extern crate exonum;
+
+use exonum::crypto;
+
+use cryptocurrency::risk::{HeuristicModel, RiskFlag, RiskMonitor};
+
+/// Check that a block volume much larger than the recent average is flagged.
+#[test]
+fn test_volume_spike() {
+    let (alice, _) = crypto::gen_keypair();
+    let (bob, _) = crypto::gen_keypair();
+    let mut monitor = RiskMonitor::default();
+
+    for height in 1..4 {
+        monitor.observe(height, vec![(alice, bob, 10)]);
+    }
+    assert_eq!(monitor.assessment(&alice), None);
+
+    monitor.observe(4, vec![(alice, bob, 1000)]);
+    let assessment = monitor.assessment(&alice).unwrap();
+    assert_eq!(assessment.height, 4);
+    assert_eq!(assessment.flags, vec![RiskFlag::VolumeSpike]);
+    assert_eq!(monitor.assessment(&bob), None);
+
+    // The flag is cleared by the next regular block.
+    monitor.observe(5, vec![(alice, bob, 10)]);
+    assert_eq!(monitor.assessment(&alice), None);
+}
+
+/// Check that transfers to many receivers in a block are flagged.
+#[test]
+fn test_fan_out() {
+    let model = HeuristicModel {
+        fan_out: 3,
+        ..Default::default()
+    };
+    let mut monitor = RiskMonitor::new(Box::new(model), 10);
+    let (alice, _) = crypto::gen_keypair();
+    let receivers: Vec<_> = (0..3).map(|_| crypto::gen_keypair().0).collect();
+
+    monitor.observe(1, receivers[..2].iter().map(|to| (alice, *to, 1)));
+    assert_eq!(monitor.assessment(&alice), None);
+
+    monitor.observe(2, receivers.iter().map(|to| (alice, *to, 1)));
+    assert_eq!(monitor.assessment(&alice).unwrap().flags, vec![RiskFlag::FanOut]);
+    assert_eq!(monitor.flagged().len(), 1);
+}